@@ -12,11 +12,13 @@ use letsearch::actors::model_actor::ModelManagerActor;
 use letsearch::collection::collection_utils::CollectionConfig;
 
 pub async fn import_jsonl(files: &str, collection_name: &str) -> anyhow::Result<()> {
-    let mut config = CollectionConfig::default();
-    config.name = collection_name.to_string();
+    let config = CollectionConfig {
+        name: collection_name.to_string(),
+        ..CollectionConfig::default()
+    };
 
     let model_manager = ModelManagerActor::new().start();
-    let collection_manager = CollectionManagerActor::new(None, model_manager, None).start();
+    let collection_manager = CollectionManagerActor::new(None, model_manager, None, None).start();
 
     let collection_addr = collection_manager
         .send(CreateCollection {
@@ -55,14 +57,17 @@ pub async fn embed_and_index(
     index_columns: &[String],
     hf_token: Option<String>,
 ) -> anyhow::Result<()> {
-    let mut config = CollectionConfig::default();
-    config.name = collection_name.to_string();
-    config.index_columns = index_columns.to_vec();
-    config.model_name = model.to_string();
-    config.model_variant = variant.to_string();
+    let config = CollectionConfig {
+        name: collection_name.to_string(),
+        index_columns: index_columns.to_vec(),
+        model_name: model.to_string(),
+        model_variant: variant.to_string(),
+        ..CollectionConfig::default()
+    };
 
     let model_manager = ModelManagerActor::new().start();
-    let collection_manager = CollectionManagerActor::new(hf_token, model_manager.clone(), None).start();
+    let collection_manager =
+        CollectionManagerActor::new(hf_token, model_manager.clone(), None, None).start();
 
     let collection_addr = collection_manager
         .send(CreateCollection {
@@ -0,0 +1,17 @@
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        println!("cargo:rerun-if-changed=src/lib.rs");
+        cbindgen::Builder::new()
+            .with_src("src/lib.rs")
+            .with_language(cbindgen::Language::C)
+            .with_include_guard("LETSEARCH_H")
+            .with_autogen_warning(
+                "/* Generated from src/lib.rs with `cargo build --features header`. Do not edit. */",
+            )
+            .with_documentation(true)
+            .generate()
+            .expect("Failed to generate the C header")
+            .write_to_file("include/letsearch.h");
+    }
+}
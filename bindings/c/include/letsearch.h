@@ -0,0 +1,89 @@
+#ifndef LETSEARCH_H
+#define LETSEARCH_H
+
+/* Generated from src/lib.rs with `cargo build --features header`. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A collection opened with [`letsearch_open_collection`], along with the
+ * models it uses. It may be shared between threads.
+ */
+typedef struct LetsearchCollection LetsearchCollection;
+
+/**
+ * Open the collection `name`.
+ *
+ * `options_json` may be NULL, or an object with any of `data_dir`,
+ * `hf_token`, `gemini_api_key` and `openai_api_key`. Returns NULL on
+ * failure. Close the collection with [`letsearch_close_collection`].
+ *
+ * # Safety
+ *
+ * `name` and `options_json` must be NULL or point to NUL-terminated
+ * strings.
+ */
+struct LetsearchCollection *letsearch_open_collection(const char *name, const char *options_json);
+
+/**
+ * Search `collection` and return the results as JSON, shaped like the
+ * HTTP API's search responses.
+ *
+ * `request_json` is an object with the `query` and, optionally,
+ * `column_names`, `limit`, `offset`, `score_type`, `min_score`,
+ * `max_distance`, `groups`, `filter`, `geo_filter`, `timeout_ms` and
+ * `partial_ok`. Returns NULL on failure. Free the results with
+ * [`letsearch_string_free`].
+ *
+ * # Safety
+ *
+ * `collection` must come from [`letsearch_open_collection`] and not be
+ * closed yet. `request_json` must point to a NUL-terminated string.
+ */
+char *letsearch_search(const struct LetsearchCollection *collection, const char *request_json);
+
+/**
+ * Add documents to `collection` and index them.
+ *
+ * `documents_json` is an array of objects with the collection's columns.
+ * Returns an object with the indexing report of each index column, or NULL
+ * on failure. Free it with [`letsearch_string_free`].
+ *
+ * # Safety
+ *
+ * `collection` must come from [`letsearch_open_collection`] and not be
+ * closed yet. `documents_json` must point to a NUL-terminated string.
+ */
+char *letsearch_add_documents(const struct LetsearchCollection *collection,
+                              const char *documents_json);
+
+/**
+ * Flush and close `collection`. Does nothing when it is NULL.
+ *
+ * # Safety
+ *
+ * `collection` must be NULL or come from [`letsearch_open_collection`],
+ * and not be used afterwards.
+ */
+void letsearch_close_collection(struct LetsearchCollection *collection);
+
+/**
+ * Message of the last error on the calling thread, or NULL if its last
+ * call succeeded. The string is owned by letsearch and valid until the
+ * next call on the same thread.
+ */
+const char *letsearch_last_error(void);
+
+/**
+ * Free a string returned by letsearch. Does nothing when it is NULL.
+ *
+ * # Safety
+ *
+ * `s` must be NULL or a string returned by letsearch, not freed yet.
+ */
+void letsearch_string_free(char *s);
+
+#endif  /* LETSEARCH_H */
@@ -0,0 +1,296 @@
+//! C API of letsearch, for runtimes that cannot link Rust directly. Build
+//! it as a shared library with `cargo build --release` in this directory
+//! and include `include/letsearch.h`.
+//!
+//! Requests and results are passed as JSON strings. Functions return NULL
+//! on failure, after which [`letsearch_last_error`] tells what went wrong.
+
+use letsearch::collection::collection_utils::SearchTimeout;
+use letsearch::collection::filter::{Filter, GeoFilter};
+use letsearch::collection::vector_index::{ScoreThreshold, ScoreType};
+use letsearch::{Client, ClientConfig, Collection, SearchOptions};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A collection opened with [`letsearch_open_collection`], along with the
+/// models it uses. It may be shared between threads.
+pub struct LetsearchCollection {
+    collection: Collection,
+    client: Client,
+}
+
+/// Options of [`letsearch_open_collection`].
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct OpenOptions {
+    /// Directory of the collection, instead of the letsearch home directory.
+    data_dir: Option<String>,
+    hf_token: Option<String>,
+    gemini_api_key: Option<String>,
+    openai_api_key: Option<String>,
+}
+
+/// Request of [`letsearch_search`], with the fields of the HTTP API's
+/// `POST /collections/{name}/search` that apply.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchRequest {
+    query: String,
+    #[serde(default)]
+    column_names: Vec<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    #[serde(default)]
+    score_type: ScoreType,
+    min_score: Option<f32>,
+    max_distance: Option<f32>,
+    groups: Option<Vec<String>>,
+    filter: Option<Filter>,
+    geo_filter: Option<GeoFilter>,
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    partial_ok: bool,
+}
+
+impl SearchRequest {
+    fn options(self) -> SearchOptions {
+        let defaults = SearchOptions::default();
+        SearchOptions {
+            columns: self.column_names,
+            limit: self.limit.unwrap_or(defaults.limit),
+            offset: self.offset.unwrap_or(defaults.offset),
+            score_type: self.score_type,
+            threshold: ScoreThreshold {
+                min_score: self.min_score,
+                max_distance: self.max_distance,
+            },
+            groups: self.groups,
+            filter: self.filter,
+            geo_filter: self.geo_filter,
+            timeout: self.timeout_ms.map(|ms| SearchTimeout {
+                timeout: Duration::from_millis(ms),
+                partial_ok: self.partial_ok,
+            }),
+        }
+    }
+}
+
+/// Run `f`, turning its errors and panics into `failed` and the last error.
+fn guard<T>(failed: T, f: impl FnOnce() -> anyhow::Result<T>) -> T {
+    let result = catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("letsearch panicked")));
+    match result {
+        Ok(value) => {
+            set_last_error(None);
+            value
+        }
+        Err(e) => {
+            set_last_error(Some(format!("{:#}", e)));
+            failed
+        }
+    }
+}
+
+fn set_last_error(message: Option<String>) {
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// The UTF-8 string at `s`, which must not be NULL.
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if s.is_null() {
+        return Err(anyhow::anyhow!("{} must not be NULL", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", name))
+}
+
+/// `value` as a JSON string owned by the caller.
+fn to_json<T: serde::Serialize>(value: &T) -> anyhow::Result<*mut c_char> {
+    Ok(CString::new(serde_json::to_string(value)?)?.into_raw())
+}
+
+/// Open the collection `name`.
+///
+/// `options_json` may be NULL, or an object with any of `data_dir`,
+/// `hf_token`, `gemini_api_key` and `openai_api_key`. Returns NULL on
+/// failure. Close the collection with [`letsearch_close_collection`].
+///
+/// # Safety
+///
+/// `name` and `options_json` must be NULL or point to NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn letsearch_open_collection(
+    name: *const c_char,
+    options_json: *const c_char,
+) -> *mut LetsearchCollection {
+    guard(ptr::null_mut(), || {
+        let name = read_str(name, "name")?;
+        let options: OpenOptions = if options_json.is_null() {
+            OpenOptions::default()
+        } else {
+            serde_json::from_str(read_str(options_json, "options_json")?)?
+        };
+        let client = Client::with_config(ClientConfig {
+            hf_token: options.hf_token,
+            gemini_api_key: options.gemini_api_key,
+            openai_api_key: options.openai_api_key,
+            ..Default::default()
+        })?;
+        let collection = match &options.data_dir {
+            Some(data_dir) => client.collection_at_blocking(name, data_dir)?,
+            None => client.collection_blocking(name)?,
+        };
+        Ok(Box::into_raw(Box::new(LetsearchCollection {
+            collection,
+            client,
+        })))
+    })
+}
+
+/// Search `collection` and return the results as JSON, shaped like the
+/// HTTP API's search responses.
+///
+/// `request_json` is an object with the `query` and, optionally,
+/// `column_names`, `limit`, `offset`, `score_type`, `min_score`,
+/// `max_distance`, `groups`, `filter`, `geo_filter`, `timeout_ms` and
+/// `partial_ok`. Returns NULL on failure. Free the results with
+/// [`letsearch_string_free`].
+///
+/// # Safety
+///
+/// `collection` must come from [`letsearch_open_collection`] and not be
+/// closed yet. `request_json` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn letsearch_search(
+    collection: *const LetsearchCollection,
+    request_json: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let collection = collection
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("collection must not be NULL"))?;
+        let request: SearchRequest = serde_json::from_str(read_str(request_json, "request_json")?)?;
+        let query = request.query.clone();
+        let results = collection
+            .collection
+            .search_blocking(&query, &request.options())?;
+        to_json(&results)
+    })
+}
+
+/// Add documents to `collection` and index them.
+///
+/// `documents_json` is an array of objects with the collection's columns.
+/// Returns an object with the indexing report of each index column, or NULL
+/// on failure. Free it with [`letsearch_string_free`].
+///
+/// # Safety
+///
+/// `collection` must come from [`letsearch_open_collection`] and not be
+/// closed yet. `documents_json` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn letsearch_add_documents(
+    collection: *const LetsearchCollection,
+    documents_json: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let collection = collection
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("collection must not be NULL"))?;
+        let documents: Vec<serde_json::Value> =
+            serde_json::from_str(read_str(documents_json, "documents_json")?)?;
+        let reports = collection.collection.add_documents_blocking(&documents)?;
+        let reports: serde_json::Map<String, serde_json::Value> = reports
+            .into_iter()
+            .map(|(column, report)| Ok((column, serde_json::to_value(report)?)))
+            .collect::<anyhow::Result<_>>()?;
+        to_json(&reports)
+    })
+}
+
+/// Flush and close `collection`. Does nothing when it is NULL.
+///
+/// # Safety
+///
+/// `collection` must be NULL or come from [`letsearch_open_collection`],
+/// and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn letsearch_close_collection(collection: *mut LetsearchCollection) {
+    if collection.is_null() {
+        return;
+    }
+    // Freed inside the guard, as dropping the collection may panic too.
+    guard((), || {
+        let collection = *Box::from_raw(collection);
+        Ok(collection.client.shutdown()?)
+    });
+}
+
+/// Message of the last error on the calling thread, or NULL if its last
+/// call succeeded. The string is owned by letsearch and valid until the
+/// next call on the same thread.
+#[no_mangle]
+pub extern "C" fn letsearch_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Free a string returned by letsearch. Does nothing when it is NULL.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by letsearch, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn letsearch_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        let name = CString::new("missing").unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let options =
+            CString::new(serde_json::json!({"data_dir": dir.to_string_lossy()}).to_string())
+                .unwrap();
+        unsafe {
+            let collection = letsearch_open_collection(name.as_ptr(), options.as_ptr());
+            assert!(collection.is_null());
+            assert!(!letsearch_last_error().is_null());
+
+            let request = CString::new(r#"{"query": "hi"}"#).unwrap();
+            assert!(letsearch_search(collection, request.as_ptr()).is_null());
+            let error = CStr::from_ptr(letsearch_last_error()).to_str().unwrap();
+            assert_eq!(error, "collection must not be NULL");
+
+            letsearch_close_collection(ptr::null_mut());
+            letsearch_string_free(ptr::null_mut());
+        }
+
+        let request: SearchRequest = serde_json::from_str(
+            r#"{"query": "hi", "limit": 3, "min_score": 0.5, "timeout_ms": 100}"#,
+        )
+        .unwrap();
+        let options = request.options();
+        assert_eq!(options.limit, 3);
+        assert_eq!(options.threshold.min_score, Some(0.5));
+        assert_eq!(options.timeout.unwrap().timeout, Duration::from_millis(100));
+        assert!(serde_json::from_str::<SearchRequest>(r#"{"query": "hi", "k": 3}"#).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Node.js bindings of letsearch, to load collections and search them
+//! in-process instead of over HTTP.
+
+use letsearch::collection::collection_utils::SearchTimeout;
+use letsearch::collection::filter::{Filter, GeoFilter};
+use letsearch::collection::vector_index::{ScoreThreshold, ScoreType};
+use letsearch::{Client, ClientConfig};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::Mutex;
+use std::time::Duration;
+
+fn to_napi(e: impl std::fmt::Display) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// Options of `openCollection`. The token and API keys fall back to the
+/// same environment variables as the letsearch CLI.
+#[napi(object)]
+pub struct OpenOptions {
+    /// Directory of the collection, instead of the letsearch home directory.
+    pub data_dir: Option<String>,
+    pub hf_token: Option<String>,
+    pub gemini_api_key: Option<String>,
+    pub openai_api_key: Option<String>,
+}
+
+/// Options of `Collection.search`, named as in the HTTP API.
+#[napi(object)]
+pub struct SearchOptions {
+    /// Index columns to search. Every index column when left out.
+    pub column_names: Option<Vec<String>>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// `similarity` (default) or `distance`.
+    pub score_type: Option<String>,
+    pub min_score: Option<f64>,
+    pub max_distance: Option<f64>,
+    /// Groups of the caller, for collections with an ACL column.
+    pub groups: Option<Vec<String>>,
+    /// Filter on the collection's filterable columns, e.g.
+    /// `{field: 'lang', op: 'eq', value: 'en'}`.
+    pub filter: Option<serde_json::Value>,
+    /// Only search documents within `radiusM` meters of a point, for
+    /// collections with geo columns.
+    pub geo_filter: Option<GeoFilterOptions>,
+    pub timeout_ms: Option<u32>,
+    pub partial_ok: Option<bool>,
+}
+
+/// A point and a distance in meters, as `{lat, lon, radiusM}`.
+#[napi(object)]
+pub struct GeoFilterOptions {
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_m: f64,
+}
+
+impl SearchOptions {
+    fn into_options(self) -> Result<letsearch::SearchOptions> {
+        let defaults = letsearch::SearchOptions::default();
+        let score_type = match self.score_type {
+            Some(score_type) => serde_json::from_value::<ScoreType>(score_type.into())
+                .map_err(|_| to_napi("scoreType must be 'similarity' or 'distance'"))?,
+            None => defaults.score_type,
+        };
+        let filter = match self.filter {
+            Some(filter) => Some(
+                serde_json::from_value::<Filter>(filter)
+                    .map_err(|e| to_napi(format!("Invalid filter: {}", e)))?,
+            ),
+            None => None,
+        };
+        Ok(letsearch::SearchOptions {
+            columns: self.column_names.unwrap_or_default(),
+            limit: self.limit.unwrap_or(defaults.limit),
+            offset: self.offset.unwrap_or(defaults.offset),
+            score_type,
+            threshold: ScoreThreshold {
+                min_score: self.min_score.map(|s| s as f32),
+                max_distance: self.max_distance.map(|d| d as f32),
+            },
+            groups: self.groups,
+            filter,
+            geo_filter: self.geo_filter.map(|geo| GeoFilter {
+                lat: geo.lat,
+                lon: geo.lon,
+                radius_m: geo.radius_m,
+            }),
+            timeout: self.timeout_ms.map(|ms| SearchTimeout {
+                timeout: Duration::from_millis(ms as u64),
+                partial_ok: self.partial_ok.unwrap_or(false),
+            }),
+        })
+    }
+}
+
+/// A collection loaded with `openCollection`, along with the models it uses.
+#[napi]
+pub struct Collection {
+    inner: letsearch::Collection,
+    /// Taken by `close`.
+    client: Mutex<Option<Client>>,
+}
+
+/// Load the collection `name` and its models.
+#[napi]
+pub async fn open_collection(name: String, options: Option<OpenOptions>) -> Result<Collection> {
+    let options = options.unwrap_or(OpenOptions {
+        data_dir: None,
+        hf_token: None,
+        gemini_api_key: None,
+        openai_api_key: None,
+    });
+    let client = Client::with_config(ClientConfig {
+        hf_token: options.hf_token,
+        gemini_api_key: options.gemini_api_key,
+        openai_api_key: options.openai_api_key,
+        ..Default::default()
+    })
+    .map_err(to_napi)?;
+    let inner = match &options.data_dir {
+        Some(data_dir) => client.collection_at(&name, data_dir).await,
+        None => client.collection(&name).await,
+    }
+    .map_err(to_napi)?;
+    Ok(Collection {
+        inner,
+        client: Mutex::new(Some(client)),
+    })
+}
+
+#[napi]
+impl Collection {
+    #[napi(getter)]
+    pub fn name(&self) -> String {
+        self.inner.name().to_string()
+    }
+
+    /// Search the collection. Resolves to the same results as the HTTP
+    /// API's search endpoint.
+    #[napi]
+    pub async fn search(
+        &self,
+        query: String,
+        options: Option<SearchOptions>,
+    ) -> Result<serde_json::Value> {
+        let options = match options {
+            Some(options) => options.into_options()?,
+            None => letsearch::SearchOptions::default(),
+        };
+        let results = self.inner.search(&query, &options).await.map_err(to_napi)?;
+        serde_json::to_value(results).map_err(to_napi)
+    }
+
+    /// Add documents, objects with the collection's columns, and index
+    /// them. Resolves to the indexing report of each index column.
+    #[napi]
+    pub async fn add_documents(
+        &self,
+        documents: Vec<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let reports = self
+            .inner
+            .add_documents(&documents)
+            .await
+            .map_err(to_napi)?;
+        let reports = reports
+            .into_iter()
+            .map(|(column, report)| Ok((column, serde_json::to_value(report)?)))
+            .collect::<serde_json::Result<serde_json::Map<_, _>>>()
+            .map_err(to_napi)?;
+        Ok(serde_json::Value::Object(reports))
+    }
+
+    /// Flush the collection and release its models. The collection cannot
+    /// be used afterwards. Collections that are not closed are flushed when
+    /// garbage collected.
+    #[napi]
+    pub fn close(&self) -> Result<()> {
+        let client = self.client.lock().map_err(to_napi)?.take();
+        match client {
+            Some(client) => client.shutdown().map_err(to_napi),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,30 +1,266 @@
 use actix::prelude::*;
 use anyhow::anyhow;
+use arrow::error::ArrowError;
+use arrow::ipc::reader::{FileReader, StreamReader};
+use chrono::{DateTime, Utc};
 use duckdb::arrow::array::{PrimitiveArray, StringArray};
 use duckdb::arrow::datatypes::UInt64Type;
 use duckdb::arrow::record_batch::RecordBatch;
-use log::info;
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use duckdb::vtab::arrow::ArrowVTab;
+use duckdb::vtab::arrow_recordbatch_to_query_params;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use log::{info, warn};
+use ndarray::Array2;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info_span, Instrument, Span};
 use usearch::f16 as UsearchF16;
-use usearch::{IndexOptions, MetricKind, ScalarKind};
-
-use crate::actors::model_actor::{GetModelMetadata, ModelManagerActor, Predict};
-use crate::chunker::ChunkerConfig;
-use crate::collection::collection_utils::{home_dir, CollectionConfig, SearchResult};
-use crate::collection::vector_index::VectorIndex;
+use usearch::IndexOptions;
+
+use crate::actors::model_actor::{
+    GetModelMetadata, GetModelMetric, GetModelPrompts, GetTokenizer, ModelManagerActor, Predict,
+    PredictImages,
+};
+use crate::chunker::{ChunkerConfig, MarkdownChunker};
+use crate::collection::aggregate::{sql_value_to_json, AggregateResults, Aggregation};
+use crate::collection::calibration::ScoreCalibration;
+use crate::collection::chunking::{Chunk, TextChunker, CHUNKS_TABLE};
+use crate::collection::collection_utils::{
+    is_valid_identifier, CollectionConfig, CollectionStats, ColumnModel, DetailedStats,
+    IndexReport, MatchedChunk, ModelRequest, SearchResult, SearchResults, SearchTimeout,
+    SkippedRow, EMBEDDINGS_TABLE, NULL_VALUE, SKIPPED_TABLE,
+};
+use crate::collection::extensions::load_extensions;
+use crate::collection::filter::{Filter, GeoFilter};
+use crate::collection::grouping::GroupBy;
+use crate::collection::idempotency::{
+    OperationClaim, OperationGuard, RunningOperations, OPERATIONS_TABLE,
+};
+use crate::collection::journal::JOURNAL_CHECKPOINT_BYTES;
+use crate::collection::language::{detect_language, LANG_COLUMN, UNDETERMINED};
+use crate::collection::normalization::Normalization;
+use crate::collection::prefix::{PrefixIndex, PREFIX_INDEX_FILE};
+use crate::collection::related::{merge_neighbours, reverse_candidates, Neighbour, RELATED_TABLE};
+use crate::collection::spelling::{extract_terms, Correction, SpellingDictionary, TERMS_TABLE};
+use crate::collection::sql::ReadOnlyDatabase;
+use crate::collection::suggest::{
+    extract_phrases, normalize_phrase, Suggestion, SuggestionSource, MAX_PHRASE_CHARS,
+    MIN_QUERY_COUNT, SUGGESTIONS_TABLE,
+};
+use crate::collection::vector_index::{
+    IndexOverrides, IndexPreset, Metric, ScoreThreshold, ScoreType, VectorIndex,
+};
+use crate::documents::{document_chunks, DocumentChunk, PAGE_COLUMN, SOURCE_COLUMN};
 use crate::error::ProjectError;
-use crate::model::model_utils::{Embeddings, ModelOutputDType};
+use crate::estimate::VectorDType;
+use crate::ingest::{DatabaseSource, FileFormat};
+use crate::model::model_utils::Embeddings;
+use crate::storage::ObjectStoreCredentials;
+use crate::transcribe::Transcriber;
 
 // ---- Helpers ----
 
-/// Return `true` when `name` is a safe SQL identifier (alphanumeric + `_`).
+/// Record batches of an Arrow IPC file (`.arrow` / `.feather` v2), read one
+/// at a time so that the file is never held in memory as a whole. Files
+/// written in the IPC streaming format are accepted as well.
+fn read_arrow_ipc(
+    path: &str,
+) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<RecordBatch>>>> {
+    let batches: Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>>> =
+        match FileReader::try_new(BufReader::new(File::open(path)?), None) {
+            Ok(reader) => Box::new(reader),
+            Err(_) => Box::new(
+                StreamReader::try_new(BufReader::new(File::open(path)?), None)
+                    .map_err(|e| anyhow!("'{}' is not a valid Arrow IPC file: {}", path, e))?,
+            ),
+        };
+    Ok(Box::new(
+        batches.map(|batch| batch.map_err(anyhow::Error::from)),
+    ))
+}
+
+/// Temporary table that appended files are loaded into before being merged
+/// into the collection table by [`append_staged`].
+const STAGING_TABLE: &str = "_letsearch_staging";
+/// Name a source database is attached under while rows are copied from it.
+const SOURCE_DATABASE: &str = "_letsearch_source";
+/// Table the detected languages are appended to before they are stored, see
+/// [`DbDetectLanguages`].
+const LANGUAGES_TABLE: &str = "_letsearch_languages";
+
+/// Quote an identifier coming from an imported file, e.g. a column name.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Index each filterable column of `config` in DuckDB, so that filtered
+/// searches look up the matching keys instead of scanning the table.
+/// The geo and display columns are checked to exist.
+fn create_filter_indexes(
+    tx: &duckdb::Transaction,
+    config: &CollectionConfig,
+) -> Result<(), ProjectError> {
+    for column in &config.filterable_columns {
+        if !is_valid_identifier(column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid filterable column '{}'",
+                column
+            )));
+        }
+        let exists: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?;",
+            [&config.name, column],
+            |row| row.get(0),
+        )?;
+        if exists == 0 && config.languages.is_some() && column == LANG_COLUMN {
+            // Added when the languages of the documents are first detected.
+            continue;
+        }
+        if exists == 0 {
+            return Err(ProjectError::ColumnNotFound(column.clone()));
+        }
+        tx.execute_batch(&format!(
+            "CREATE INDEX IF NOT EXISTS {table}_{column}_filter ON {table} ({column});",
+            table = config.name,
+        ))?;
+    }
+    // Geo filters compute a distance per row, which no index speeds up, so
+    // their columns are only checked.
+    if let Some(geo_columns) = &config.geo_columns {
+        for column in [&geo_columns.lat, &geo_columns.lon] {
+            let exists: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?;",
+                [&config.name, column],
+                |row| row.get(0),
+            )?;
+            if exists == 0 {
+                return Err(ProjectError::ColumnNotFound(column.clone()));
+            }
+        }
+    }
+    // Display columns are only read when results are returned, so a missing
+    // one would otherwise fail every search instead of the import.
+    for column in config.display_columns.values() {
+        if !is_valid_identifier(column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid display column '{}'",
+                column
+            )));
+        }
+        let exists: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?;",
+            [&config.name, column],
+            |row| row.get(0),
+        )?;
+        if exists == 0 {
+            return Err(ProjectError::ColumnNotFound(column.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Append the rows of [`STAGING_TABLE`] to `config`'s table by column name,
+/// then drop it.
 ///
-/// Column names and other identifiers that must be interpolated directly into
-/// SQL strings (they cannot be parameterized) are validated with this guard
-/// to prevent SQL-injection attacks.
-fn is_valid_identifier(name: &str) -> bool {
-    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+/// Columns that the table does not have yet are added first, and columns the
+/// staged file lacks are left NULL, so files with different but overlapping
+/// schemas can be imported into the same collection. `_key` is always
+/// assigned by the table's sequence.
+/// Delete what the tables kept next to the collection `table` still hold of
+/// its deleted rows.
+fn delete_orphans(tx: &duckdb::Transaction, table: &str) -> Result<(), ProjectError> {
+    ensure_related_table(tx)?;
+    tx.execute(
+        &format!(
+            "DELETE FROM {related} WHERE _key NOT IN (SELECT _key FROM {table}) \
+             OR related_key NOT IN (SELECT _key FROM {table});",
+            related = RELATED_TABLE,
+            table = table
+        ),
+        [],
+    )?;
+    ensure_skipped_table(tx)?;
+    ensure_chunks_table(tx)?;
+    ensure_embeddings_table(tx)?;
+    for side_table in [SKIPPED_TABLE, CHUNKS_TABLE, EMBEDDINGS_TABLE] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE _key NOT IN (SELECT _key FROM {});",
+                side_table, table
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn append_staged(tx: &duckdb::Transaction, config: &CollectionConfig) -> Result<(), ProjectError> {
+    let table = config.name.as_str();
+    let staged: Vec<(String, String)> = {
+        let mut stmt = tx.prepare(&format!("DESCRIBE {};", STAGING_TABLE))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let existing: HashSet<String> = {
+        let mut stmt =
+            tx.prepare("SELECT column_name FROM information_schema.columns WHERE table_name = ?;")?;
+        let rows = stmt.query_map([table], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    // DuckDB cannot alter a table that has indexes, so the filter indexes
+    // are rebuilt around new columns.
+    let added = staged
+        .iter()
+        .any(|(name, _)| name != "_key" && !existing.contains(name));
+    if added {
+        for column in &config.filterable_columns {
+            tx.execute_batch(&format!(
+                "DROP INDEX IF EXISTS {}_{}_filter;",
+                table, column
+            ))?;
+        }
+    }
+
+    let mut columns = Vec::new();
+    for (name, data_type) in staged {
+        if name == "_key" {
+            continue;
+        }
+        let quoted = quote_identifier(&name);
+        if !existing.contains(&name) {
+            info!("Adding column '{}' ({}) to '{}'", name, data_type, table);
+            tx.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                table, quoted, data_type
+            ))?;
+        }
+        columns.push(quoted);
+    }
+
+    if columns.is_empty() {
+        return Err(ProjectError::Anyhow(anyhow!(
+            "No columns to append to table '{}'",
+            table
+        )));
+    }
+    let col_list = columns.join(", ");
+    tx.execute_batch(&format!(
+        "INSERT INTO {table} ({cols}) SELECT {cols} FROM {staging}; DROP TABLE {staging};",
+        table = table,
+        cols = col_list,
+        staging = STAGING_TABLE
+    ))?;
+    if added {
+        create_filter_indexes(tx, config)?;
+    }
+    Ok(())
 }
 
 // ---- Db Messages ----
@@ -41,6 +277,25 @@ pub struct DbImportParquet {
     pub path: String,
 }
 
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbSetHfToken {
+    pub token: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbSetObjectStoreCredentials {
+    pub credentials: ObjectStoreCredentials,
+}
+
+/// Create the collection table from an Arrow IPC file.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbImportArrow {
+    pub path: String,
+}
+
 /// Append rows from a JSONL file to an existing table.
 #[derive(Message)]
 #[rtype(result = "Result<(), ProjectError>")]
@@ -55,12 +310,50 @@ pub struct DbAppendParquet {
     pub path: String,
 }
 
-/// Insert a list of text chunks into the named column of the collection table.
-/// Creates the table and/or column if they do not yet exist.
+/// Append rows from an Arrow IPC file to an existing table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbAppendArrow {
+    pub path: String,
+}
+
+/// Create the collection table from a table or query of a Postgres or
+/// MySQL database.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbImportDatabase {
+    pub source: DatabaseSource,
+}
+
+/// Append rows from a table or query of a Postgres or MySQL database to an
+/// existing table, only those past the table's largest `cursor_column`
+/// when set. Rows sharing their `source_key` with an appended row are
+/// replaced. Returns the number of rows appended.
+#[derive(Message)]
+#[rtype(result = "Result<usize, ProjectError>")]
+pub struct DbAppendDatabase {
+    pub source: DatabaseSource,
+    pub cursor_column: Option<String>,
+    pub source_key: Option<String>,
+}
+
+/// Detect the language of the rows of `column` that have none yet and store
+/// it in [`LANG_COLUMN`], adding the column if needed. Returns the number of
+/// rows whose language was stored.
+#[derive(Message)]
+#[rtype(result = "Result<u64, ProjectError>")]
+pub struct DbDetectLanguages {
+    pub column: String,
+}
+
+/// Insert chunks of documents into the named column of the collection table,
+/// with their source path and page in the `source` and `page` columns and
+/// their metadata in VARCHAR columns of their own.
+/// Creates the table and/or columns if they do not yet exist.
 #[derive(Message)]
 #[rtype(result = "Result<(), ProjectError>")]
-pub struct DbImportMarkdownChunks {
-    pub chunks: Vec<String>,
+pub struct DbImportDocumentChunks {
+    pub chunks: Vec<DocumentChunk>,
     pub column: String,
 }
 
@@ -72,11 +365,11 @@ pub struct DbGetIndexedCount {
     pub column: String,
 }
 
+/// Number of rows in the collection table, which indexing walks in `_key`
+/// order whatever their values.
 #[derive(Message)]
 #[rtype(result = "Result<u64, ProjectError>")]
-pub struct DbGetRowCount {
-    pub column: String,
-}
+pub struct DbGetRowCount;
 
 #[derive(Message)]
 #[rtype(result = "Result<bool, ProjectError>")]
@@ -89,23 +382,140 @@ pub struct DbCheckIndex {
 pub struct DbInitIndex {
     pub column: String,
     pub dimensions: usize,
-    pub quantization: ScalarKind,
+    pub dtype: VectorDType,
+    pub metric: Metric,
+    /// Number of rows to be indexed, used to pick the graph parameters and
+    /// the initial capacity.
+    pub rows: u64,
+    /// Graph connectivity instead of the preset's.
+    pub connectivity: Option<usize>,
+    /// Expansion factor while building instead of the preset's.
+    pub expansion_add: Option<usize>,
+}
+
+/// Rows of a column read by [`DbGetBatch`], in `_key` order.
+pub struct ColumnBatch {
+    /// Values as text, non-string columns being cast.
+    pub texts: Vec<String>,
+    pub keys: Vec<u64>,
+    /// Keys of the rows whose value is NULL, which have nothing to embed.
+    pub null_keys: Vec<u64>,
+    /// Chunk each text was cut from, when the column is chunked. A document
+    /// then has one text per chunk, all under its key.
+    pub chunks: Vec<Chunk>,
+}
+
+impl ColumnBatch {
+    /// Split the texts into chunks, each embedded on its own under the key
+    /// of its document.
+    fn into_chunks(self, chunker: &TextChunker) -> Self {
+        let mut batch = ColumnBatch {
+            texts: Vec::new(),
+            keys: Vec::new(),
+            null_keys: self.null_keys,
+            chunks: Vec::new(),
+        };
+        for (text, key) in self.texts.iter().zip(self.keys) {
+            for chunk in chunker.split(text) {
+                batch.texts.push(chunk.text.clone());
+                batch.keys.push(key);
+                batch.chunks.push(chunk);
+            }
+        }
+        batch
+    }
+}
+
+/// Number of distinct documents among `keys`, in which the chunks of a
+/// document are adjacent.
+fn document_count(keys: &[u64]) -> u64 {
+    let mut keys = keys.to_vec();
+    keys.dedup();
+    keys.len() as u64
 }
 
 #[derive(Message)]
-#[rtype(result = "Result<(Vec<String>, Vec<u64>), ProjectError>")]
+#[rtype(result = "Result<ColumnBatch, ProjectError>")]
 pub struct DbGetBatch {
     pub column: String,
     pub batch_size: u64,
     pub offset: u64,
 }
 
+/// Record rows of `column` that were left out of its index.
 #[derive(Message)]
 #[rtype(result = "Result<(), ProjectError>")]
-pub struct DbAddEmbeddings {
+pub struct DbRecordSkipped {
+    pub column: String,
+    pub rows: Vec<SkippedRow>,
+}
+
+/// Record the chunks embedded for documents of `column`, with their
+/// embeddings so that searches can tell which chunk matched.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbStoreChunks {
+    pub column: String,
+    pub chunks: Vec<(u64, Chunk, Vec<f32>)>,
+}
+
+/// Keep the embeddings of documents of `column` added to its index.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbStoreEmbeddings {
+    pub column: String,
+    pub embeddings: Vec<(u64, Vec<f32>)>,
+}
+
+/// Build the index `staged` for `column` from the embeddings stored for it,
+/// without the model. Returns the number of vectors added.
+#[derive(Message)]
+#[rtype(result = "Result<u64, ProjectError>")]
+pub struct DbRebuildIndex {
+    pub column: String,
+    pub staged: String,
+    pub overrides: IndexOverrides,
+}
+
+/// Replace the index of `column` with the index `staged` built for it, on
+/// disk and in memory. With `move_rows`, the chunks, embeddings and skipped
+/// rows recorded for `staged` replace those of `column`, and the related
+/// documents of `column` are dropped.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbSwapIndex {
+    pub column: String,
+    pub staged: String,
+    pub move_rows: bool,
+}
+
+/// Chunk of each hit on `column` that the hit's distance came from. `None`
+/// for documents without chunks.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Option<MatchedChunk>>, ProjectError>")]
+pub struct DbMatchChunks {
+    pub column: String,
+    pub query_embedding: Embeddings,
+    pub hits: Vec<Neighbour>,
+    /// Whether the hits were grouped by document, in which case the chunk
+    /// closest to the query is returned.
+    pub grouped: bool,
+}
+
+/// Get a shared handle to the index of `column`, so that vectors can be added
+/// to it without blocking the database thread.
+#[derive(Message)]
+#[rtype(result = "Result<Arc<VectorIndex>, ProjectError>")]
+pub struct DbGetIndex {
+    pub column: String,
+}
+
+/// Record that vectors were added to the index of `column` so it is saved on
+/// flush and shutdown.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DbMarkIndexDirty {
     pub column: String,
-    pub keys: Vec<u64>,
-    pub embeddings: Embeddings,
 }
 
 #[derive(Message)]
@@ -114,94 +524,605 @@ pub struct DbSaveIndex {
     pub column: String,
 }
 
+/// Nearest neighbours of a query in the index of `column`, closest first.
 #[derive(Message)]
-#[rtype(result = "Result<Vec<SearchResult>, ProjectError>")]
-pub struct DbSearchAndFetch {
+#[rtype(result = "Result<Vec<Neighbour>, ProjectError>")]
+pub struct DbSearchIndex {
     pub column: String,
     pub query_embedding: Embeddings,
     pub limit: usize,
+    /// Number of top results to skip, for pagination.
+    pub offset: usize,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+    /// See [`Search::filter`].
+    pub filter: Option<Filter>,
+    /// See [`Search::geo_filter`].
+    pub geo_filter: Option<GeoFilter>,
+    /// Combine the hits of each document into one. See [`Search::group_by`].
+    pub group_by: Option<GroupBy>,
+    /// See [`Search::threshold`].
+    pub threshold: ScoreThreshold,
+}
+
+/// Content of the documents `keys`, read from the display column of
+/// `column`, in one query. Keys whose row no longer exists are left out,
+/// and rows whose display column is NULL get empty content.
+#[derive(Message)]
+#[rtype(result = "Result<HashMap<u64, String>, ProjectError>")]
+pub struct DbFetchContents {
+    pub column: String,
+    pub keys: Vec<u64>,
+}
+
+/// Delete the rows matching `filter` and remove their keys from every column
+/// index. With `dry_run` nothing is changed. A filter matching every row is
+/// refused unless `all` is set. Returns the number of matching rows.
+#[derive(Message)]
+#[rtype(result = "Result<u64, ProjectError>")]
+pub struct DbDeleteByFilter {
+    pub filter: Filter,
+    pub dry_run: bool,
+    pub all: bool,
+}
+
+/// Run an aggregation over the documents visible to `groups`, see
+/// [`Search::groups`].
+#[derive(Message)]
+#[rtype(result = "Result<AggregateResults, ProjectError>")]
+pub struct DbAggregate {
+    pub aggregation: Aggregation,
+    pub groups: Option<Vec<String>>,
+}
+
+/// Connect to the collection's database for read-only queries, in a
+/// separate, locked down DuckDB instance, see [`ReadOnlyDatabase::open`].
+/// The instance is opened again once the collection was written since, so
+/// that queries see the documents as of their last write.
+#[derive(Message)]
+#[rtype(result = "Result<ReadOnlyDatabase, ProjectError>")]
+pub struct DbConnection;
+
+/// Return document and vector counts along with the last write time.
+#[derive(Message)]
+#[rtype(result = "Result<CollectionStats, ProjectError>")]
+pub struct DbGetStats;
+
+/// Return the sizes, shapes and modification times of the collection.
+#[derive(Message)]
+#[rtype(result = "Result<DetailedStats, ProjectError>")]
+pub struct DbGetDetailedStats;
+
+/// Save every index with unsaved changes, checkpoint the database and stop
+/// the actor so the DuckDB connection is closed cleanly.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbShutdown;
+
+/// Reopen the database and vector indexes from disk, replacing everything
+/// held in memory.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbReload {
+    pub config: CollectionConfig,
+}
+
+/// Drop the related documents precomputed on `column`.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbClearRelated {
+    pub column: String,
+}
+
+/// Keys of the documents without precomputed related documents on `column`.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<u64>, ProjectError>")]
+pub struct DbGetKeysWithoutRelated {
+    pub column: String,
+}
+
+/// Store the related documents of newly processed documents, and merge the
+/// processed documents into the lists of their neighbours.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbStoreRelated {
+    pub column: String,
+    /// Number of related documents kept per document.
+    pub count: usize,
+    pub neighbours: Vec<(u64, Vec<Neighbour>)>,
+}
+
+/// Fetch the precomputed related documents of the document `key`.
+#[derive(Message)]
+#[rtype(result = "Result<SearchResults, ProjectError>")]
+pub struct DbGetRelated {
+    pub column: String,
+    pub key: u64,
+    pub limit: usize,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Check that the document `key` is visible to the caller, failing with
+/// [`ProjectError::DocumentNotFound`] when it is not.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbCheckVisible {
+    pub key: u64,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Look up a completed write operation by id, after forgetting those older
+/// than `retention`. Returns its request fingerprint and JSON response.
+#[derive(Message)]
+#[rtype(result = "Result<Option<(String, String)>, ProjectError>")]
+pub struct DbGetOperation {
+    pub op_id: String,
+    pub retention: Duration,
+}
+
+/// Record a completed write operation and its JSON response.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbStoreOperation {
+    pub op_id: String,
+    pub fingerprint: String,
+    pub response: String,
+}
+
+/// Count a searched query towards the suggestions of the collection.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct DbRecordQuery {
+    pub query: String,
+}
+
+/// Replace the phrases and the words extracted from the indexed text with
+/// those of `column` that occur in at least `min_documents` documents.
+/// Returns the number of phrases and of words stored.
+#[derive(Message)]
+#[rtype(result = "Result<(usize, usize), ProjectError>")]
+pub struct DbBuildSuggestions {
+    pub column: String,
+    pub max_words: usize,
+    pub min_documents: u64,
+}
+
+/// Suggestions starting with `prefix`, the most popular first. Phrases of
+/// the text are left out for callers restricted to `groups` on a collection
+/// with an ACL column, as they may come from documents hidden from them.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Suggestion>, ProjectError>")]
+pub struct DbSuggest {
+    pub prefix: String,
+    pub limit: usize,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Rebuild the prefix index from its column when documents were added
+/// since it was built. Returns the number of values indexed, or `None` when
+/// it is up to date or the collection has none.
+#[derive(Message)]
+#[rtype(result = "Result<Option<usize>, ProjectError>")]
+pub struct DbBuildPrefixIndex;
+
+/// Values of the prefix column starting with `prefix`, the most common first.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Suggestion>, ProjectError>")]
+pub struct DbCompletePrefix {
+    pub prefix: String,
+    pub limit: usize,
+}
+
+/// Corrections of the misspelled words of `query`, the closest first.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Correction>, ProjectError>")]
+pub struct DbCorrectSpelling {
+    pub query: String,
+    pub limit: usize,
 }
 
 // ---- CollectionDbActor (SyncActor) ----
 
 pub struct CollectionDbActor {
     conn: duckdb::Connection,
-    vector_indices: HashMap<String, VectorIndex>,
+    vector_indices: HashMap<String, Arc<VectorIndex>>,
+    /// Columns whose index received vectors since it was last saved.
+    dirty_indices: HashSet<String>,
+    /// Time of the last successful import, embedding or delete. Starts out as
+    /// the modification time of the database file for existing collections.
+    last_write: Option<DateTime<Utc>>,
     config: CollectionConfig,
+    /// Whether saved indexes are memory-mapped read-only instead of loaded.
+    mmap_indexes: bool,
+    /// Dictionary of the words of the indexed text, loaded by the first
+    /// spelling correction after it was built.
+    spelling: Option<SpellingDictionary>,
+    /// Prefix index of the collection's prefix column, if it was built.
+    prefix_index: Option<PrefixIndex>,
+    /// Rows of the collection table when the prefix index was last built in
+    /// this process.
+    prefix_rows: Option<u64>,
+    /// Instance queried by the SQL endpoint and when it was opened, see
+    /// [`DbConnection`].
+    sql_database: Option<(ReadOnlyDatabase, DateTime<Utc>)>,
 }
 
 impl CollectionDbActor {
-    pub fn new(config: CollectionConfig) -> Self {
-        let collection_dir = home_dir().join("collections").join(config.name.as_str());
+    /// Open the database and vector indexes of a collection from disk.
+    fn open(config: CollectionConfig, mmap_indexes: bool) -> Result<Self, ProjectError> {
+        let collection_dir = config.dir();
 
         // ensure dir exists
-        std::fs::create_dir_all(&collection_dir).unwrap();
+        std::fs::create_dir_all(&collection_dir).map_err(|e| ProjectError::Anyhow(e.into()))?;
 
         let db_path = collection_dir.join(config.db_path.as_str());
-        let conn = duckdb::Connection::open(&db_path).expect("Failed to open DuckDB connection");
+        let last_write = std::fs::metadata(&db_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        let conn = duckdb::Connection::open(&db_path)?;
+        conn.register_table_function::<ArrowVTab>("arrow")?;
+        load_extensions(
+            &conn,
+            &config.duckdb_extensions,
+            config.duckdb_extension_dir.as_deref(),
+        )?;
 
         let mut vector_indices = HashMap::new();
         let index_dir = collection_dir.join(config.index_dir.as_str());
         if index_dir.exists() && !config.index_columns.is_empty() {
             for index_column in config.index_columns.iter() {
                 let index_path = index_dir.join(index_column.as_str());
-                if let Ok(vector_index) = VectorIndex::from(index_path.to_path_buf()) {
-                    vector_indices.insert(index_column.clone(), vector_index);
+                // Indexes built before their metric was recorded used the
+                // collection's metric.
+                let default_metric = config.metric.unwrap_or_default();
+                let vector_index = if mmap_indexes {
+                    VectorIndex::view(index_path.to_path_buf(), default_metric)
+                } else {
+                    VectorIndex::from(index_path.to_path_buf(), default_metric)
+                };
+                if let Ok(vector_index) = vector_index {
+                    vector_indices.insert(index_column.clone(), Arc::new(vector_index));
                 }
             }
         }
 
-        Self {
+        let prefix_index = match config.prefix_column {
+            Some(_) => PrefixIndex::load(&index_dir.join(PREFIX_INDEX_FILE)).ok(),
+            None => None,
+        };
+
+        Ok(Self {
             conn,
             vector_indices,
+            dirty_indices: HashSet::new(),
+            last_write,
             config,
-        }
+            mmap_indexes,
+            spelling: None,
+            prefix_index,
+            prefix_rows: None,
+            sql_database: None,
+        })
     }
 }
 
-impl Actor for CollectionDbActor {
-    type Context = SyncContext<Self>;
-}
+impl CollectionDbActor {
+    /// Rebuild the prefix index from the values of `column`. Returns the
+    /// number of distinct values indexed.
+    fn build_prefix_index(&mut self, column: &str) -> Result<usize, ProjectError> {
+        let values = {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT CAST({} AS VARCHAR) FROM {} WHERE {} IS NOT NULL;",
+                column, self.config.name, column
+            ))?;
+            let values = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            values
+        };
+        let index = PrefixIndex::build(values)?;
+        let index_dir = self.config.dir().join(self.config.index_dir.as_str());
+        std::fs::create_dir_all(&index_dir).map_err(|e| ProjectError::Anyhow(e.into()))?;
+        index.save(&index_dir.join(PREFIX_INDEX_FILE))?;
+
+        let values = index.len();
+        self.prefix_index = Some(index);
+        self.prefix_rows = Some(self.documents()?);
+        Ok(values)
+    }
 
-impl Handler<DbImportJsonl> for CollectionDbActor {
-    type Result = Result<(), ProjectError>;
+    /// Number of rows of the collection table, 0 before anything was imported.
+    fn documents(&self) -> Result<u64, ProjectError> {
+        let table_exists: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = ?;",
+            duckdb::params![self.config.name],
+            |row| row.get(0),
+        )?;
+        if table_exists == 0 {
+            return Ok(0);
+        }
+        let documents: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {};", self.config.name),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(documents as u64)
+    }
 
-    fn handle(&mut self, msg: DbImportJsonl, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let tx = self.conn.transaction()?;
-        tx.execute_batch(&format!(
-            "CREATE TABLE {} AS SELECT * FROM read_json_auto('{}');",
-            self.config.name, msg.path
+    /// Keys of the documents visible to members of `groups`, or `None` when
+    /// every document is visible because the collection has no ACL column
+    /// or the caller is unrestricted.
+    fn visible_keys(
+        &self,
+        groups: Option<&Vec<String>>,
+    ) -> Result<Option<HashSet<u64>>, ProjectError> {
+        let Some((clause, params)) = self.acl_clause(groups)? else {
+            return Ok(None);
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT _key FROM {} WHERE {};",
+            self.config.name, clause
         ))?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| row.get(0))?;
+        Ok(Some(rows.collect::<Result<_, _>>()?))
+    }
 
-        let query = format!(
-            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = '{}' AND column_name = '_key';",
-            self.config.name
-        );
-        let mut stmt = tx.prepare(&query)?;
-        let count: i64 = stmt.query_row([], |row| row.get(0))?;
-        if count == 0 {
-            tx.execute_batch(&format!(
-                r"CREATE SEQUENCE keys_seq;
-    ALTER TABLE {} ADD COLUMN _key UBIGINT DEFAULT NEXTVAL('keys_seq');",
-                self.config.name,
-            ))?;
+    /// Fail with [`ProjectError::DocumentNotFound`] when the document `key`
+    /// is not visible to members of `groups`, so that restricted callers
+    /// cannot tell it from a missing one.
+    fn check_visible(&self, key: u64, groups: Option<&Vec<String>>) -> Result<(), ProjectError> {
+        let Some((clause, mut params)) = self.acl_clause(groups)? else {
+            return Ok(());
+        };
+        params.insert(0, duckdb::types::Value::UBigInt(key));
+        let visible: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE _key = ? AND {};",
+                self.config.name, clause
+            ),
+            duckdb::params_from_iter(params.iter()),
+            |row| row.get(0),
+        )?;
+        if visible == 0 {
+            return Err(ProjectError::DocumentNotFound(key));
         }
-        tx.commit()?;
         Ok(())
     }
-}
 
-impl Handler<DbImportParquet> for CollectionDbActor {
-    type Result = Result<(), ProjectError>;
+    /// SQL condition and bind parameters of the documents visible to
+    /// `groups`, or `None` when every document is.
+    fn acl_clause(
+        &self,
+        groups: Option<&Vec<String>>,
+    ) -> Result<Option<(String, Vec<duckdb::types::Value>)>, ProjectError> {
+        let (acl_column, groups) = match (self.config.acl_column.as_deref(), groups) {
+            (Some(acl_column), Some(groups)) => (acl_column, groups),
+            _ => return Ok(None),
+        };
+        if groups.is_empty() {
+            return Ok(Some((String::from("FALSE"), Vec::new())));
+        }
+        if !is_valid_identifier(acl_column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid ACL column '{}'",
+                acl_column
+            )));
+        }
 
-    fn handle(&mut self, msg: DbImportParquet, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let tx = self.conn.transaction()?;
-        tx.execute_batch(&format!(
-            "CREATE TABLE {} AS SELECT * FROM read_parquet('{}');",
-            self.config.name, msg.path
-        ))?;
+        let placeholders = vec!["?"; groups.len()].join(", ");
+        let params = groups
+            .iter()
+            .map(|group| duckdb::types::Value::Text(group.clone()))
+            .collect();
+        Ok(Some((
+            format!("list_has_any({}, list_value({}))", acl_column, placeholders),
+            params,
+        )))
+    }
 
-        let query = format!(
+    /// Keys of the documents matching both `filter` and `geo_filter`, or
+    /// `None` without either.
+    fn filtered_keys(
+        &self,
+        filter: Option<&Filter>,
+        geo_filter: Option<&GeoFilter>,
+    ) -> Result<Option<HashSet<u64>>, ProjectError> {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+        if let Some(filter) = filter {
+            let (clause, filter_params) = filter.to_sql().map_err(ProjectError::InvalidFilter)?;
+            clauses.push(format!("({})", clause));
+            params.extend(filter_params);
+        }
+        if let Some(geo_filter) = geo_filter {
+            let columns = self
+                .config
+                .geo_columns
+                .as_ref()
+                .ok_or_else(|| ProjectError::NoGeoColumns(self.config.name.clone()))?;
+            let (clause, geo_params) = geo_filter.to_sql(&columns.lat, &columns.lon)?;
+            clauses.push(format!("({})", clause));
+            params.extend(geo_params);
+        }
+        if clauses.is_empty() {
+            return Ok(None);
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT _key FROM {} WHERE {};",
+            self.config.name,
+            clauses.join(" AND ")
+        ))?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| row.get(0))?;
+        Ok(Some(rows.collect::<Result<_, _>>()?))
+    }
+
+    /// Replace the distances of `neighbours` found in the index of `column`
+    /// with their distances to `query` in the stored embeddings, closest
+    /// first, see [`CollectionConfig::rescore`]. Of chunked documents, the
+    /// closest chunk counts. Neighbours without stored embeddings keep
+    /// their distance.
+    fn rescore(
+        &self,
+        column: &str,
+        query: &[f32],
+        metric: Metric,
+        neighbours: &mut [Neighbour],
+    ) -> Result<(), ProjectError> {
+        if neighbours.is_empty() {
+            return Ok(());
+        }
+        let table = if self.config.chunking.is_some() {
+            ensure_chunks_table(&self.conn)?;
+            CHUNKS_TABLE
+        } else {
+            ensure_embeddings_table(&self.conn)?;
+            EMBEDDINGS_TABLE
+        };
+        let keys_str = neighbours
+            .iter()
+            .map(|neighbour| neighbour.key.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT _key, embedding FROM {} WHERE column_name = ? AND _key IN ({});",
+            table, keys_str
+        ))?;
+        let rows = stmt.query_map(duckdb::params![column], |row| {
+            Ok((row.get::<_, u64>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+        let mut distances: HashMap<u64, f32> = HashMap::new();
+        for row in rows {
+            let (key, embedding) = row?;
+            let embedding: Vec<f32> = embedding
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect();
+            let distance = metric.distance(query, &embedding);
+            distances
+                .entry(key)
+                .and_modify(|best| *best = best.min(distance))
+                .or_insert(distance);
+        }
+        for neighbour in neighbours.iter_mut() {
+            if let Some(distance) = distances.get(&neighbour.key) {
+                neighbour.distance = *distance;
+            }
+        }
+        neighbours.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.key.cmp(&b.key)));
+        Ok(())
+    }
+}
+
+fn ensure_related_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (_key UBIGINT, column_name VARCHAR, rank INTEGER, related_key UBIGINT, distance FLOAT);",
+        RELATED_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_skipped_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (column_name VARCHAR, _key UBIGINT, error VARCHAR);",
+        SKIPPED_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_chunks_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (column_name VARCHAR, _key UBIGINT, chunk UINTEGER, start_offset UBIGINT, end_offset UBIGINT, text VARCHAR, embedding BLOB);",
+        CHUNKS_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_embeddings_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (column_name VARCHAR, _key UBIGINT, embedding BLOB);",
+        EMBEDDINGS_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_suggestions_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (phrase VARCHAR, source VARCHAR, count UBIGINT, PRIMARY KEY (phrase, source));",
+        SUGGESTIONS_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_terms_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (term VARCHAR PRIMARY KEY, count UBIGINT);",
+        TERMS_TABLE
+    ))?;
+    Ok(())
+}
+
+fn ensure_operations_table(conn: &duckdb::Connection) -> Result<(), ProjectError> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (op_id VARCHAR PRIMARY KEY, fingerprint VARCHAR, response VARCHAR, created_at BIGINT);",
+        OPERATIONS_TABLE
+    ))?;
+    Ok(())
+}
+
+/// Run `fut` until `deadline`, when there is one. Returns `None` when the
+/// deadline passed first.
+async fn with_deadline<T>(
+    deadline: Option<tokio::time::Instant>,
+    fut: impl std::future::Future<Output = T>,
+) -> Option<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// Reject filters on columns that are not filterable in `config`.
+fn check_filterable(config: &CollectionConfig, filter: &Filter) -> Result<(), ProjectError> {
+    match filter
+        .fields()
+        .into_iter()
+        .find(|field| !config.filterable_columns.iter().any(|c| c == field))
+    {
+        Some(field) if config.filterable_columns.is_empty() => Err(ProjectError::NotFilterable(
+            field.to_string(),
+            String::from("none"),
+        )),
+        Some(field) => Err(ProjectError::NotFilterable(
+            field.to_string(),
+            config.filterable_columns.join(", "),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn keys_list(keys: impl Iterator<Item = u64>) -> String {
+    keys.map(|k| k.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Actor for CollectionDbActor {
+    type Context = SyncContext<Self>;
+}
+
+impl Handler<DbImportJsonl> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbImportJsonl, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(&format!(
+            "CREATE TABLE {} AS SELECT * FROM read_json_auto('{}');",
+            self.config.name, msg.path
+        ))?;
+
+        let query = format!(
             "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = '{}' AND column_name = '_key';",
             self.config.name
         );
@@ -214,43 +1135,134 @@ impl Handler<DbImportParquet> for CollectionDbActor {
                 self.config.name,
             ))?;
         }
+        create_filter_indexes(&tx, &self.config)?;
         tx.commit()?;
+        self.last_write = Some(Utc::now());
         Ok(())
     }
 }
 
-impl Handler<DbAppendJsonl> for CollectionDbActor {
+impl Handler<DbSetHfToken> for CollectionDbActor {
     type Result = Result<(), ProjectError>;
 
-    fn handle(&mut self, msg: DbAppendJsonl, _ctx: &mut SyncContext<Self>) -> Self::Result {
+    fn handle(&mut self, msg: DbSetHfToken, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        // Temporary, so that the token is not persisted with the database.
+        self.conn.execute_batch(&format!(
+            "CREATE OR REPLACE TEMPORARY SECRET letsearch_hf (TYPE HUGGINGFACE, TOKEN '{}');",
+            msg.token.replace('\'', "''")
+        ))?;
+        Ok(())
+    }
+}
+
+impl Handler<DbSetObjectStoreCredentials> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(
+        &mut self,
+        msg: DbSetObjectStoreCredentials,
+        _ctx: &mut SyncContext<Self>,
+    ) -> Self::Result {
+        for secret in msg.credentials.duckdb_secrets() {
+            self.conn.execute_batch(&secret)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler<DbImportParquet> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbImportParquet, _ctx: &mut SyncContext<Self>) -> Self::Result {
         let tx = self.conn.transaction()?;
+        tx.execute_batch(&format!(
+            "CREATE TABLE {} AS SELECT * FROM read_parquet('{}');",
+            self.config.name, msg.path
+        ))?;
 
-        // Discover all columns except _key so the DEFAULT on _key is used.
-        let cols_query = format!(
-            "SELECT column_name FROM information_schema.columns \
-             WHERE table_name = '{}' AND column_name != '_key' \
-             ORDER BY ordinal_position;",
+        let query = format!(
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = '{}' AND column_name = '_key';",
             self.config.name
         );
-        let mut stmt = tx.prepare(&cols_query)?;
-        let cols: Vec<String> = stmt
-            .query_map([], |row| row.get(0))?
-            .filter_map(|r| r.ok())
-            .collect();
+        let mut stmt = tx.prepare(&query)?;
+        let count: i64 = stmt.query_row([], |row| row.get(0))?;
+        if count == 0 {
+            tx.execute_batch(&format!(
+                r"CREATE SEQUENCE keys_seq;
+    ALTER TABLE {} ADD COLUMN _key UBIGINT DEFAULT NEXTVAL('keys_seq');",
+                self.config.name,
+            ))?;
+        }
+        create_filter_indexes(&tx, &self.config)?;
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+        Ok(())
+    }
+}
+
+impl Handler<DbImportArrow> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
 
-        if cols.is_empty() {
+    fn handle(&mut self, msg: DbImportArrow, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let tx = self.conn.transaction()?;
+        let mut imported = false;
+        for (i, batch) in read_arrow_ipc(&msg.path)?.enumerate() {
+            imported = true;
+            let sql = if i == 0 {
+                format!(
+                    "CREATE TABLE {} AS SELECT * FROM arrow(?, ?);",
+                    self.config.name
+                )
+            } else {
+                format!(
+                    "INSERT INTO {} SELECT * FROM arrow(?, ?);",
+                    self.config.name
+                )
+            };
+            tx.execute(&sql, arrow_recordbatch_to_query_params(batch?))?;
+        }
+        if !imported {
             return Err(ProjectError::Anyhow(anyhow!(
-                "Table '{}' has no columns to append to",
-                self.config.name
+                "Arrow file '{}' contains no record batches",
+                msg.path
             )));
         }
-        let col_list = cols.join(", ");
-        let sql = format!(
-            "INSERT INTO {} ({}) SELECT {} FROM read_json_auto(?);",
-            self.config.name, col_list, col_list
+
+        let query = format!(
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = '{}' AND column_name = '_key';",
+            self.config.name
         );
-        tx.execute(&sql, duckdb::params![msg.path])?;
+        let mut stmt = tx.prepare(&query)?;
+        let count: i64 = stmt.query_row([], |row| row.get(0))?;
+        if count == 0 {
+            tx.execute_batch(&format!(
+                r"CREATE SEQUENCE keys_seq;
+    ALTER TABLE {} ADD COLUMN _key UBIGINT DEFAULT NEXTVAL('keys_seq');",
+                self.config.name,
+            ))?;
+        }
+        create_filter_indexes(&tx, &self.config)?;
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+        Ok(())
+    }
+}
+
+impl Handler<DbAppendJsonl> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbAppendJsonl, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            &format!(
+                "CREATE TEMP TABLE {} AS SELECT * FROM read_json_auto(?);",
+                STAGING_TABLE
+            ),
+            duckdb::params![msg.path],
+        )?;
+        append_staged(&tx, &self.config)?;
         tx.commit()?;
+        self.last_write = Some(Utc::now());
         Ok(())
     }
 }
@@ -260,42 +1272,250 @@ impl Handler<DbAppendParquet> for CollectionDbActor {
 
     fn handle(&mut self, msg: DbAppendParquet, _ctx: &mut SyncContext<Self>) -> Self::Result {
         let tx = self.conn.transaction()?;
+        tx.execute(
+            &format!(
+                "CREATE TEMP TABLE {} AS SELECT * FROM read_parquet(?);",
+                STAGING_TABLE
+            ),
+            duckdb::params![msg.path],
+        )?;
+        append_staged(&tx, &self.config)?;
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+        Ok(())
+    }
+}
 
-        let cols_query = format!(
-            "SELECT column_name FROM information_schema.columns \
-             WHERE table_name = '{}' AND column_name != '_key' \
-             ORDER BY ordinal_position;",
-            self.config.name
-        );
-        let mut stmt = tx.prepare(&cols_query)?;
-        let cols: Vec<String> = stmt
-            .query_map([], |row| row.get(0))?
-            .filter_map(|r| r.ok())
-            .collect();
+impl Handler<DbAppendArrow> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
 
-        if cols.is_empty() {
-            return Err(ProjectError::Anyhow(anyhow!(
-                "Table '{}' has no columns to append to",
-                self.config.name
-            )));
+    fn handle(&mut self, msg: DbAppendArrow, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let tx = self.conn.transaction()?;
+        let mut staged = false;
+        for (i, batch) in read_arrow_ipc(&msg.path)?.enumerate() {
+            staged = true;
+            let sql = if i == 0 {
+                format!(
+                    "CREATE TEMP TABLE {} AS SELECT * FROM arrow(?, ?);",
+                    STAGING_TABLE
+                )
+            } else {
+                format!("INSERT INTO {} SELECT * FROM arrow(?, ?);", STAGING_TABLE)
+            };
+            tx.execute(&sql, arrow_recordbatch_to_query_params(batch?))?;
         }
-        let col_list = cols.join(", ");
-        let sql = format!(
-            "INSERT INTO {} ({}) SELECT {} FROM read_parquet(?);",
-            self.config.name, col_list, col_list
-        );
-        tx.execute(&sql, duckdb::params![msg.path])?;
+        if !staged {
+            return Ok(());
+        }
+        append_staged(&tx, &self.config)?;
         tx.commit()?;
+        self.last_write = Some(Utc::now());
+        Ok(())
+    }
+}
+
+impl CollectionDbActor {
+    /// Refuse changes that drop vectors when an index is memory-mapped, as
+    /// they could only fail once the rows are gone.
+    fn check_writable(&self) -> Result<(), ProjectError> {
+        if self.vector_indices.values().any(|index| index.read_only()) {
+            return Err(ProjectError::ReadOnlyIndexes(self.config.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Drop the vectors of the deleted rows under `keys` from every index.
+    /// Removals are journaled before they touch an index, so one that fails
+    /// to save is replayed when the index is loaded again.
+    fn remove_vectors(&mut self, keys: &[u64]) -> Result<(), ProjectError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        for (column, index) in self.vector_indices.iter() {
+            index.remove(keys)?;
+            index.save()?;
+            self.dirty_indices.remove(column);
+        }
         Ok(())
     }
+
+    /// Attach `source` read-only while `copy` runs, loading the extension
+    /// that reads it first.
+    fn with_source<T>(
+        &mut self,
+        source: &DatabaseSource,
+        copy: impl FnOnce(&mut Self) -> Result<T, ProjectError>,
+    ) -> Result<T, ProjectError> {
+        load_extensions(
+            &self.conn,
+            &[source.extension().to_string()],
+            self.config.duckdb_extension_dir.as_deref(),
+        )?;
+        self.conn
+            .execute_batch(&source.attach_sql(SOURCE_DATABASE)?)
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to connect to '{}': {}",
+                    DatabaseSource::redact(&source.url),
+                    e
+                )
+            })?;
+        let result = copy(self);
+        self.conn
+            .execute_batch(&format!("DETACH {};", SOURCE_DATABASE))?;
+        result
+    }
+
+    /// The collection table qualified with its catalog, which stays visible
+    /// while a source's search path is set.
+    fn qualified_table(&self) -> Result<String, ProjectError> {
+        let catalog: String = self
+            .conn
+            .query_row("SELECT current_database();", [], |row| row.get(0))?;
+        Ok(format!(
+            "{}.main.{}",
+            quote_identifier(&catalog),
+            self.config.name
+        ))
+    }
+}
+
+/// `statement` copying rows of `source`, run with the source's search path
+/// set, if any. See [`DatabaseSource::search_path`].
+fn copy_statement(source: &DatabaseSource, statement: String) -> String {
+    match source.search_path(SOURCE_DATABASE) {
+        Some(path) => format!(
+            "SET search_path = '{}'; {} RESET search_path;",
+            path, statement
+        ),
+        None => statement,
+    }
+}
+
+impl Handler<DbImportDatabase> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbImportDatabase, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let table = self.qualified_table()?;
+        self.with_source(&msg.source, |db| {
+            let tx = db.conn.transaction()?;
+            tx.execute_batch(&copy_statement(
+                &msg.source,
+                format!(
+                    "CREATE TABLE {} AS {};",
+                    table,
+                    msg.source.select_sql(SOURCE_DATABASE, None)
+                ),
+            ))?;
+
+            let query = format!(
+                "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = '{}' AND column_name = '_key';",
+                db.config.name
+            );
+            let mut stmt = tx.prepare(&query)?;
+            let count: i64 = stmt.query_row([], |row| row.get(0))?;
+            if count == 0 {
+                tx.execute_batch(&format!(
+                    r"CREATE SEQUENCE keys_seq;
+    ALTER TABLE {} ADD COLUMN _key UBIGINT DEFAULT NEXTVAL('keys_seq');",
+                    db.config.name,
+                ))?;
+            }
+            create_filter_indexes(&tx, &db.config)?;
+            tx.commit()?;
+            db.last_write = Some(Utc::now());
+            Ok(())
+        })
+    }
+}
+
+impl Handler<DbAppendDatabase> for CollectionDbActor {
+    type Result = Result<usize, ProjectError>;
+
+    fn handle(&mut self, msg: DbAppendDatabase, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let table = self.qualified_table()?;
+        for column in msg.cursor_column.iter().chain(&msg.source_key) {
+            let exists: i64 = self.conn.query_row(
+                "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?;",
+                [&self.config.name, column],
+                |row| row.get(0),
+            )?;
+            if !is_valid_identifier(column) || exists == 0 {
+                return Err(ProjectError::ColumnNotFound(column.clone()));
+            }
+        }
+        if msg.source_key.is_some() {
+            self.check_writable()?;
+        }
+        let cursor = msg
+            .cursor_column
+            .as_deref()
+            .map(|column| (column, table.as_str()));
+        self.with_source(&msg.source, |db| {
+            let tx = db.conn.transaction()?;
+            tx.execute_batch(&copy_statement(
+                &msg.source,
+                format!(
+                    "CREATE TEMP TABLE {} AS {};",
+                    STAGING_TABLE,
+                    msg.source.select_sql(SOURCE_DATABASE, cursor)
+                ),
+            ))?;
+            let rows: i64 = tx.query_row(
+                &format!("SELECT COUNT(*) FROM {};", STAGING_TABLE),
+                [],
+                |row| row.get(0),
+            )?;
+            // Updated rows replace their previous version, which is
+            // deleted with its vectors rather than indexed twice.
+            let replaced: Vec<u64> = match &msg.source_key {
+                Some(column) => {
+                    let condition = format!(
+                        "{column} IN (SELECT {column} FROM {staging})",
+                        column = quote_identifier(column),
+                        staging = STAGING_TABLE
+                    );
+                    let keys = {
+                        let mut stmt = tx.prepare(&format!(
+                            "SELECT _key FROM {} WHERE {};",
+                            db.config.name, condition
+                        ))?;
+                        let rows = stmt.query_map([], |row| row.get(0))?;
+                        rows.collect::<Result<Vec<u64>, _>>()?
+                    };
+                    if !keys.is_empty() {
+                        tx.execute(
+                            &format!("DELETE FROM {} WHERE {};", db.config.name, condition),
+                            [],
+                        )?;
+                        delete_orphans(&tx, &db.config.name)?;
+                    }
+                    keys
+                }
+                None => Vec::new(),
+            };
+            append_staged(&tx, &db.config)?;
+            tx.commit()?;
+            db.last_write = Some(Utc::now());
+            db.remove_vectors(&replaced)?;
+            if !replaced.is_empty() {
+                info!(
+                    "Replaced {} rows of collection '{}'",
+                    replaced.len(),
+                    db.config.name
+                );
+            }
+            Ok(rows as usize)
+        })
+    }
 }
 
-impl Handler<DbImportMarkdownChunks> for CollectionDbActor {
+impl Handler<DbImportDocumentChunks> for CollectionDbActor {
     type Result = Result<(), ProjectError>;
 
     fn handle(
         &mut self,
-        msg: DbImportMarkdownChunks,
+        msg: DbImportDocumentChunks,
         _ctx: &mut SyncContext<Self>,
     ) -> Self::Result {
         if msg.chunks.is_empty() {
@@ -310,6 +1530,26 @@ impl Handler<DbImportMarkdownChunks> for CollectionDbActor {
                 msg.column
             )));
         }
+        if msg.column == SOURCE_COLUMN || msg.column == PAGE_COLUMN {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Column '{}' holds document metadata, choose another column for the text",
+                msg.column
+            )));
+        }
+
+        // Metadata columns of the documents, in order of appearance.
+        let mut metadata_columns: Vec<&str> = Vec::new();
+        for (name, _) in msg.chunks.iter().flat_map(|chunk| &chunk.metadata) {
+            if !is_valid_identifier(name) {
+                return Err(ProjectError::Anyhow(anyhow!(
+                    "Invalid metadata column name '{}'",
+                    name
+                )));
+            }
+            if *name != msg.column && !metadata_columns.contains(&name.as_str()) {
+                metadata_columns.push(name);
+            }
+        }
 
         let tx = self.conn.transaction()?;
 
@@ -322,66 +1562,218 @@ impl Handler<DbImportMarkdownChunks> for CollectionDbActor {
             stmt.query_row([], |row| row.get(0))?
         };
 
+        let mut columns = Vec::new();
         if table_exists == 0 {
-            // First import — create table with just the text column plus _key.
+            // First import — create table with the text and metadata columns plus _key.
             tx.execute_batch(&format!(
-                "CREATE TABLE {table} ({col} VARCHAR); \
+                "CREATE TABLE {table} ({col} VARCHAR, {source} VARCHAR, {page} INTEGER); \
                  CREATE SEQUENCE keys_seq; \
                  ALTER TABLE {table} ADD COLUMN _key UBIGINT DEFAULT NEXTVAL('keys_seq');",
                 table = self.config.name,
                 col = msg.column,
+                source = SOURCE_COLUMN,
+                page = PAGE_COLUMN,
             ))?;
         } else {
-            // Table exists — ensure the target column is present.
+            // Table exists — ensure the target and document columns are present.
+            columns.extend([
+                (msg.column.as_str(), "VARCHAR"),
+                (SOURCE_COLUMN, "VARCHAR"),
+                (PAGE_COLUMN, "INTEGER"),
+            ]);
+        }
+        columns.extend(metadata_columns.iter().map(|column| (*column, "VARCHAR")));
+        let mut missing = Vec::new();
+        for (column, data_type) in columns {
             let col_exists: i64 = {
                 let mut stmt = tx.prepare(&format!(
                     "SELECT COUNT(*) FROM information_schema.columns \
                      WHERE table_name = '{}' AND column_name = '{}';",
-                    self.config.name, msg.column
+                    self.config.name, column
                 ))?;
                 stmt.query_row([], |row| row.get(0))?
             };
             if col_exists == 0 {
+                missing.push((column, data_type));
+            }
+        }
+        // DuckDB cannot alter a table that has indexes, so the filter indexes
+        // are rebuilt around new columns.
+        if !missing.is_empty() {
+            for column in &self.config.filterable_columns {
                 tx.execute_batch(&format!(
-                    "ALTER TABLE {} ADD COLUMN {} VARCHAR;",
-                    self.config.name, msg.column
+                    "DROP INDEX IF EXISTS {}_{}_filter;",
+                    self.config.name, column
                 ))?;
             }
         }
+        for (column, data_type) in missing {
+            tx.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                self.config.name, column, data_type
+            ))?;
+        }
 
         // Insert each chunk using a parameterised statement.
         let insert_sql = format!(
-            "INSERT INTO {} ({}) VALUES (?);",
-            self.config.name, msg.column
+            "INSERT INTO {} ({}, {}, {}{}) VALUES (?, ?, ?{});",
+            self.config.name,
+            msg.column,
+            SOURCE_COLUMN,
+            PAGE_COLUMN,
+            metadata_columns
+                .iter()
+                .map(|column| format!(", {}", column))
+                .collect::<String>(),
+            ", ?".repeat(metadata_columns.len()),
         );
         let mut stmt = tx.prepare(&insert_sql)?;
         for chunk in &msg.chunks {
-            stmt.execute(duckdb::params![chunk.as_str()])?;
+            let mut values = vec![
+                duckdb::types::Value::Text(chunk.text.clone()),
+                duckdb::types::Value::Text(chunk.source.clone()),
+                chunk
+                    .page
+                    .map_or(duckdb::types::Value::Null, duckdb::types::Value::Int),
+            ];
+            values.extend(metadata_columns.iter().map(|column| {
+                chunk
+                    .metadata
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map_or(duckdb::types::Value::Null, |(_, value)| {
+                        duckdb::types::Value::Text(value.clone())
+                    })
+            }));
+            stmt.execute(duckdb::params_from_iter(values))?;
         }
+        create_filter_indexes(&tx, &self.config)?;
 
         tx.commit()?;
+        self.last_write = Some(Utc::now());
         Ok(())
     }
 }
 
+impl Handler<DbDetectLanguages> for CollectionDbActor {
+    type Result = Result<u64, ProjectError>;
+
+    fn handle(&mut self, msg: DbDetectLanguages, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if !is_valid_identifier(&msg.column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid language column '{}'",
+                msg.column
+            )));
+        }
+        let table = self.config.name.clone();
+        let tx = self.conn.transaction()?;
+        let has_lang: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?;",
+            [&table, LANG_COLUMN],
+            |row| row.get(0),
+        )?;
+        let pending = if has_lang == 0 {
+            String::new()
+        } else {
+            format!(" AND {} IS NULL", LANG_COLUMN)
+        };
+        let rows: Vec<(u64, String)> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT _key, CAST({column} AS VARCHAR) FROM {table} WHERE {column} IS NOT NULL{pending};",
+                column = msg.column,
+            ))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        if rows.is_empty() && has_lang != 0 {
+            return Ok(0);
+        }
+
+        // DuckDB cannot alter a table that has indexes, nor update indexed
+        // columns in place, so the filter indexes are rebuilt afterwards.
+        for column in &self.config.filterable_columns {
+            tx.execute_batch(&format!(
+                "DROP INDEX IF EXISTS {}_{}_filter;",
+                table, column
+            ))?;
+        }
+        if has_lang == 0 {
+            tx.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} VARCHAR;",
+                table, LANG_COLUMN
+            ))?;
+        }
+        tx.execute_batch(&format!(
+            "CREATE TABLE {LANGUAGES_TABLE} (_key UBIGINT, {LANG_COLUMN} VARCHAR);"
+        ))?;
+        {
+            let mut appender = tx.appender(LANGUAGES_TABLE)?;
+            for (key, text) in &rows {
+                let lang = detect_language(text).unwrap_or(UNDETERMINED);
+                appender.append_row(duckdb::params![key, lang])?;
+            }
+            appender.flush()?;
+        }
+        tx.execute_batch(&format!(
+            "UPDATE {table} SET {LANG_COLUMN} = detected.{LANG_COLUMN} \
+             FROM {LANGUAGES_TABLE} detected WHERE {table}._key = detected._key; \
+             DROP TABLE {LANGUAGES_TABLE};"
+        ))?;
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+
+        // Indexes cannot be created in the transaction that updated the table.
+        let tx = self.conn.transaction()?;
+        create_filter_indexes(&tx, &self.config)?;
+        tx.commit()?;
+        Ok(rows.len() as u64)
+    }
+}
+
 impl Handler<DbGetIndexedCount> for CollectionDbActor {
     type Result = Result<u64, ProjectError>;
 
     fn handle(&mut self, msg: DbGetIndexedCount, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        if let Some(index) = self.vector_indices.get(&msg.column) {
-            if let Some(idx) = &index.index {
-                return Ok(idx.size() as u64);
-            }
+        let vectors = match self
+            .vector_indices
+            .get(&msg.column)
+            .and_then(|index| index.index.as_ref())
+        {
+            Some(idx) => idx.size() as u64,
+            None => return Ok(0),
+        };
+        ensure_skipped_table(&self.conn)?;
+        if self.config.chunking.is_some() {
+            // Documents have a vector per chunk, so they are counted by key.
+            ensure_chunks_table(&self.conn)?;
+            let documents: i64 = self.conn.query_row(
+                &format!(
+                    "SELECT COUNT(DISTINCT _key) FROM (SELECT _key FROM {} WHERE column_name = ? \
+                     UNION ALL SELECT _key FROM {} WHERE column_name = ?);",
+                    CHUNKS_TABLE, SKIPPED_TABLE
+                ),
+                duckdb::params![msg.column, msg.column],
+                |row| row.get(0),
+            )?;
+            return Ok(documents as u64);
         }
-        Ok(0)
+        let skipped: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE column_name = ?;",
+                SKIPPED_TABLE
+            ),
+            duckdb::params![msg.column],
+            |row| row.get(0),
+        )?;
+        Ok(vectors + skipped as u64)
     }
 }
 
 impl Handler<DbGetRowCount> for CollectionDbActor {
     type Result = Result<u64, ProjectError>;
 
-    fn handle(&mut self, msg: DbGetRowCount, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let query = format!("SELECT COUNT('{}') FROM {};", msg.column, self.config.name);
+    fn handle(&mut self, _msg: DbGetRowCount, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let query = format!("SELECT COUNT(*) FROM {};", self.config.name);
         let mut stmt = self.conn.prepare(&query)?;
         let count: i64 = stmt.query_row([], |row| row.get(0))?;
         Ok(count as u64)
@@ -400,514 +1792,3677 @@ impl Handler<DbInitIndex> for CollectionDbActor {
     type Result = Result<(), ProjectError>;
 
     fn handle(&mut self, msg: DbInitIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let index_path = home_dir()
-            .join("collections")
-            .join(self.config.name.as_str())
+        let index_path = self
+            .config
+            .dir()
             .join(self.config.index_dir.as_str())
             .join(&msg.column);
 
+        if msg.dtype == VectorDType::B1 && !msg.dimensions.is_multiple_of(8) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Binary indexes need a multiple of 8 dimensions, column '{}' has {}",
+                msg.column,
+                msg.dimensions
+            )));
+        }
+        let preset = IndexPreset::for_collection(msg.rows, msg.dimensions);
+        info!(
+            "Using {} index preset and {} metric for column '{}' ({} rows, {} dimensions)",
+            preset, msg.metric, msg.column, msg.rows, msg.dimensions
+        );
         let options = IndexOptions {
             dimensions: msg.dimensions,
-            metric: MetricKind::Cos,
-            quantization: msg.quantization,
-            connectivity: 0,
-            expansion_add: 0,
-            expansion_search: 0,
+            metric: msg.metric.kind_for(msg.dtype),
+            quantization: msg.dtype.scalar_kind(),
+            connectivity: msg.connectivity.unwrap_or(preset.connectivity()),
+            expansion_add: msg.expansion_add.unwrap_or(preset.expansion_add()),
+            expansion_search: preset.expansion_search(),
             multi: true,
         };
 
-        let mut index = VectorIndex::new(index_path, true)?;
-        index.with_options(&options, 20000)?;
-        self.vector_indices.insert(msg.column, index);
+        let mut index = VectorIndex::new(index_path, true, msg.metric)?;
+        index.with_options(&options, (msg.rows as usize).max(20000))?;
+        // Saved while still empty, so that the journal of the vectors added
+        // next can be replayed onto it after a crash.
+        index.save()?;
+        ensure_skipped_table(&self.conn)?;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE column_name = ?;", SKIPPED_TABLE),
+            duckdb::params![msg.column],
+        )?;
+        ensure_chunks_table(&self.conn)?;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE column_name = ?;", CHUNKS_TABLE),
+            duckdb::params![msg.column],
+        )?;
+        ensure_embeddings_table(&self.conn)?;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE column_name = ?;", EMBEDDINGS_TABLE),
+            duckdb::params![msg.column],
+        )?;
+        self.vector_indices.insert(msg.column, Arc::new(index));
         Ok(())
     }
 }
 
 impl Handler<DbGetBatch> for CollectionDbActor {
-    type Result = Result<(Vec<String>, Vec<u64>), ProjectError>;
+    type Result = Result<ColumnBatch, ProjectError>;
 
     fn handle(&mut self, msg: DbGetBatch, _ctx: &mut SyncContext<Self>) -> Self::Result {
         let mut stmt = self.conn.prepare(&format!(
-            "SELECT {}, _key FROM {} LIMIT {} OFFSET {};",
-            msg.column, self.config.name, msg.batch_size, msg.offset
+            "SELECT CAST({col} AS VARCHAR) AS {col}, _key FROM {table} ORDER BY _key LIMIT {limit} OFFSET {offset};",
+            col = msg.column,
+            table = self.config.name,
+            limit = msg.batch_size,
+            offset = msg.offset
         ))?;
-        let result: Vec<RecordBatch> = stmt.query_arrow([])?.collect();
-        if result.is_empty() {
-            return Ok((vec![], vec![]));
-        }
-        let batch = &result[0];
-
-        let col_array = batch
-            .column_by_name(&msg.column)
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("Column '{}' not found", msg.column)))?
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("Column is not of type String")))?;
-        let col_values: Vec<String> = col_array
-            .iter()
-            .map(|s| s.unwrap_or_default().to_string())
-            .collect();
-
-        let key_array = batch
-            .column_by_name("_key")
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("_key column not found")))?
-            .as_any()
-            .downcast_ref::<PrimitiveArray<UInt64Type>>()
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("_key is not of type UInt64")))?;
-        let keys: Vec<u64> = key_array.iter().map(|key| key.unwrap_or(0)).collect();
-
-        Ok((col_values, keys))
-    }
+        let mut batch = ColumnBatch {
+            texts: Vec::new(),
+            keys: Vec::new(),
+            null_keys: Vec::new(),
+            chunks: Vec::new(),
+        };
+        for record_batch in stmt.query_arrow([])? {
+            let col_array = record_batch
+                .column_by_name(&msg.column)
+                .ok_or_else(|| ProjectError::ColumnNotFound(msg.column.clone()))?
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| ProjectError::ColumnType {
+                    column: msg.column.clone(),
+                    expected: "String",
+                })?;
+            let key_array = record_batch
+                .column_by_name("_key")
+                .ok_or_else(|| ProjectError::ColumnNotFound(String::from("_key")))?
+                .as_any()
+                .downcast_ref::<PrimitiveArray<UInt64Type>>()
+                .ok_or_else(|| ProjectError::ColumnType {
+                    column: String::from("_key"),
+                    expected: "UInt64",
+                })?;
+
+            for (value, key) in col_array.iter().zip(key_array.values().iter()) {
+                match value {
+                    Some(value) => {
+                        batch.texts.push(value.to_string());
+                        batch.keys.push(*key);
+                    }
+                    None => batch.null_keys.push(*key),
+                }
+            }
+        }
+
+        Ok(batch)
+    }
+}
+
+impl Handler<DbRecordSkipped> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbRecordSkipped, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_skipped_table(&self.conn)?;
+        let mut appender = self.conn.appender(SKIPPED_TABLE)?;
+        for row in &msg.rows {
+            appender.append_row(duckdb::params![msg.column, row.key, row.error])?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
+}
+
+impl Handler<DbStoreChunks> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbStoreChunks, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_chunks_table(&self.conn)?;
+        let mut appender = self.conn.appender(CHUNKS_TABLE)?;
+        for (key, chunk, embedding) in &msg.chunks {
+            let embedding: Vec<u8> = embedding.iter().flat_map(|v| v.to_le_bytes()).collect();
+            appender.append_row(duckdb::params![
+                msg.column,
+                key,
+                chunk.index as u32,
+                chunk.span.start as u64,
+                chunk.span.end as u64,
+                chunk.text,
+                embedding
+            ])?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
 }
 
-impl Handler<DbAddEmbeddings> for CollectionDbActor {
+impl Handler<DbStoreEmbeddings> for CollectionDbActor {
     type Result = Result<(), ProjectError>;
 
-    fn handle(&mut self, msg: DbAddEmbeddings, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let index = self.vector_indices.get_mut(&msg.column).ok_or_else(|| {
-            ProjectError::Anyhow(anyhow!(
-                "Vector index for column '{}' not found",
+    fn handle(&mut self, msg: DbStoreEmbeddings, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_embeddings_table(&self.conn)?;
+        let mut appender = self.conn.appender(EMBEDDINGS_TABLE)?;
+        for (key, embedding) in &msg.embeddings {
+            let embedding: Vec<u8> = embedding.iter().flat_map(|v| v.to_le_bytes()).collect();
+            appender.append_row(duckdb::params![msg.column, key, embedding])?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
+}
+
+impl Handler<DbRebuildIndex> for CollectionDbActor {
+    type Result = Result<u64, ProjectError>;
+
+    fn handle(&mut self, msg: DbRebuildIndex, ctx: &mut SyncContext<Self>) -> Self::Result {
+        let current = self
+            .vector_indices
+            .get(&msg.column)
+            .cloned()
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.column.clone()))?;
+        let table = if self.config.chunking.is_some() {
+            ensure_chunks_table(&self.conn)?;
+            CHUNKS_TABLE
+        } else if self.config.store_embeddings {
+            ensure_embeddings_table(&self.conn)?;
+            EMBEDDINGS_TABLE
+        } else {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Collection '{}' does not store embeddings, embed the documents again instead",
+                self.config.name
+            )));
+        };
+
+        let dimensions = current.dimensions();
+        let mut keys = Vec::new();
+        let mut vectors = Vec::new();
+        {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT _key, embedding FROM {} WHERE column_name = ? ORDER BY _key;",
+                table
+            ))?;
+            let rows = stmt.query_map(duckdb::params![msg.column], |row| {
+                Ok((row.get::<_, u64>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?;
+            for row in rows {
+                let (key, embedding) = row?;
+                if embedding.len() != dimensions * 4 {
+                    return Err(ProjectError::Anyhow(anyhow!(
+                        "Stored embedding of _key {} has {} dimensions but the index of column '{}' has {}",
+                        key,
+                        embedding.len() / 4,
+                        msg.column,
+                        dimensions
+                    )));
+                }
+                keys.push(key);
+                vectors.extend(
+                    embedding
+                        .chunks_exact(4)
+                        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+                );
+            }
+        }
+        let indexed = current.stats().vectors;
+        if (keys.len() as u64) < indexed {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Only {} of the {} vectors of column '{}' have stored embeddings, embed the documents again instead",
+                keys.len(),
+                indexed,
                 msg.column
-            ))
-        })?;
+            )));
+        }
 
-        match msg.embeddings {
-            Embeddings::F16(emb) => {
-                let (_, vector_dim) = emb.dim();
-                index.add::<UsearchF16>(
-                    &msg.keys,
-                    emb.as_ptr() as *const UsearchF16,
-                    vector_dim,
+        let dtype = msg.overrides.dtype.or(current.dtype()).unwrap_or_else(|| {
+            warn!(
+                "The scalar type of the index of column '{}' was not recorded, rebuilding it as f32",
+                msg.column
+            );
+            VectorDType::F32
+        });
+        self.handle(
+            DbInitIndex {
+                column: msg.staged.clone(),
+                dimensions,
+                dtype,
+                metric: msg.overrides.metric.unwrap_or(current.metric()),
+                rows: keys.len() as u64,
+                connectivity: msg.overrides.connectivity,
+                expansion_add: msg.overrides.expansion_add,
+            },
+            ctx,
+        )?;
+        let index = self
+            .vector_indices
+            .get(&msg.staged)
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.staged.clone()))?;
+        if !keys.is_empty() {
+            index.add::<f32>(&keys, vectors.as_ptr(), dimensions)?;
+        }
+        index.save()?;
+        Ok(keys.len() as u64)
+    }
+}
+
+impl Handler<DbSwapIndex> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbSwapIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if !self.vector_indices.contains_key(&msg.staged) {
+            return Err(ProjectError::IndexNotFound(msg.staged));
+        }
+
+        let tx = self.conn.transaction()?;
+        if msg.move_rows {
+            ensure_chunks_table(&tx)?;
+            ensure_skipped_table(&tx)?;
+            ensure_embeddings_table(&tx)?;
+            for table in [CHUNKS_TABLE, SKIPPED_TABLE, EMBEDDINGS_TABLE] {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE column_name = ?;", table),
+                    duckdb::params![msg.column],
+                )?;
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET column_name = ? WHERE column_name = ?;",
+                        table
+                    ),
+                    duckdb::params![msg.column, msg.staged],
                 )?;
             }
-            Embeddings::F32(emb) => {
-                let (_, vector_dim) = emb.dim();
-                index.add::<f32>(&msg.keys, emb.as_ptr(), vector_dim)?;
+            ensure_related_table(&tx)?;
+            tx.execute(
+                &format!("DELETE FROM {} WHERE column_name = ?;", RELATED_TABLE),
+                duckdb::params![msg.column],
+            )?;
+        }
+
+        // The previous index is moved aside rather than deleted until the
+        // new one is in place, so that a failed rename can be undone.
+        let index_dir = self.config.dir().join(self.config.index_dir.as_str());
+        let path = index_dir.join(&msg.column);
+        let staged_path = index_dir.join(&msg.staged);
+        let previous_path = index_dir.join(format!("{}.previous", msg.column));
+        let io = |e: std::io::Error| ProjectError::Anyhow(e.into());
+        if previous_path.exists() {
+            std::fs::remove_dir_all(&previous_path).map_err(io)?;
+        }
+        if path.exists() {
+            std::fs::rename(&path, &previous_path).map_err(io)?;
+        }
+        if let Err(e) = std::fs::rename(&staged_path, &path) {
+            if previous_path.exists() {
+                std::fs::rename(&previous_path, &path).map_err(io)?;
             }
+            return Err(io(e));
+        }
+        tx.commit()?;
+
+        self.vector_indices.remove(&msg.staged);
+        self.dirty_indices.remove(&msg.staged);
+        let default_metric = self.config.metric.unwrap_or_default();
+        let index = if self.mmap_indexes {
+            VectorIndex::view(path, default_metric)?
+        } else {
+            VectorIndex::from(path, default_metric)?
+        };
+        self.vector_indices.insert(msg.column, Arc::new(index));
+        if previous_path.exists() {
+            std::fs::remove_dir_all(&previous_path).map_err(io)?;
         }
+        self.last_write = Some(Utc::now());
         Ok(())
     }
 }
 
+impl Handler<DbGetIndex> for CollectionDbActor {
+    type Result = Result<Arc<VectorIndex>, ProjectError>;
+
+    fn handle(&mut self, msg: DbGetIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        self.vector_indices
+            .get(&msg.column)
+            .cloned()
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.column.clone()))
+    }
+}
+
+impl Handler<DbMarkIndexDirty> for CollectionDbActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: DbMarkIndexDirty, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        self.dirty_indices.insert(msg.column);
+        self.last_write = Some(Utc::now());
+    }
+}
+
+/// Add `embeddings` to `index` under `keys`, one row per key.
+fn add_embeddings(
+    index: &VectorIndex,
+    keys: &[u64],
+    embeddings: &Embeddings,
+) -> Result<(), ProjectError> {
+    let (rows, vector_dim) = match embeddings {
+        Embeddings::F16(emb) => emb.dim(),
+        Embeddings::F32(emb) => emb.dim(),
+    };
+    if rows != keys.len() {
+        return Err(ProjectError::Embedding(anyhow!(
+            "Got {} embeddings for {} texts",
+            rows,
+            keys.len()
+        )));
+    }
+    // The index reads `rows * vector_dim` values from the start of the
+    // array, so its rows have to be laid out one after the other.
+    let not_contiguous = || ProjectError::Embedding(anyhow!("Embeddings are not contiguous"));
+    match embeddings {
+        Embeddings::F16(emb) => {
+            let values = emb.as_slice().ok_or_else(not_contiguous)?;
+            // SAFETY: `half::f16` and `usearch::f16` are both 16-bit
+            // transparent wrappers with identical layout.
+            index.add::<UsearchF16>(keys, values.as_ptr() as *const UsearchF16, vector_dim)
+        }
+        Embeddings::F32(emb) => {
+            let values = emb.as_slice().ok_or_else(not_contiguous)?;
+            index.add::<f32>(keys, values.as_ptr(), vector_dim)
+        }
+    }
+    .map_err(ProjectError::VectorIndex)
+}
+
+/// Print indexing progress on a single line, with the ETA extrapolated from
+/// the rows processed so far.
+fn print_progress(rows: u64, total_rows: u64, batches: u64, total_batches: u64, elapsed: Duration) {
+    let eta = if rows > 0 {
+        elapsed.mul_f64(total_rows.saturating_sub(rows) as f64 / rows as f64)
+    } else {
+        Duration::ZERO
+    };
+    print!(
+        "\r{} / {} rows ({} / {} batches) - elapsed: {:.1?} - ETA: {:.1?}",
+        rows, total_rows, batches, total_batches, elapsed, eta
+    );
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
+/// Truncate `embeddings` to the `embedding_dim` of a collection, if any,
+/// see [`CollectionConfig::embedding_dim`].
+fn truncated(
+    embeddings: Embeddings,
+    embedding_dim: Option<usize>,
+) -> Result<Embeddings, ProjectError> {
+    match embedding_dim {
+        Some(dim) => embeddings.truncate(dim).map_err(ProjectError::Anyhow),
+        None => Ok(embeddings),
+    }
+}
+
+/// Largest image read from a file or a URL for an image column.
+const MAX_IMAGE_BYTES: u64 = 32 << 20;
+/// How long fetching an image URL may take.
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether `ip` is reachable on the internet, as opposed to private,
+/// loopback, link-local, e.g. cloud metadata endpoints, or reserved ranges.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Shared address space of carrier-grade NAT.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local and link-local addresses.
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves host names to their public addresses only, so that an allowed
+/// host cannot lead image requests to internal services.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(anyhow!("'{}' has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Reads the images located in an image column, see
+/// [`CollectionConfig::image_columns`].
+#[derive(Clone)]
+struct ImageReader {
+    http: reqwest::Client,
+    /// See [`CollectionConfig::image_root`].
+    root: Option<PathBuf>,
+    /// See [`CollectionConfig::image_url_hosts`].
+    hosts: Arc<[String]>,
+}
+
+/// Why an image URL may not be fetched, `None` when it may.
+fn refuse_image_url(url: &reqwest::Url, hosts: &[String]) -> Option<String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(format!("'{}' is not an http(s) URL", url));
+    }
+    let host = url.host_str().unwrap_or_default();
+    if !hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    {
+        return Some(format!(
+            "host '{}' is not one of the collection's image URL hosts",
+            host
+        ));
+    }
+    // Addresses given as such are not resolved, so they are checked here.
+    match host
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) if !is_public_ip(ip) => Some(format!("'{}' is not a public address", ip)),
+        _ => None,
+    }
+}
+
+impl ImageReader {
+    fn new(root: Option<&str>, hosts: &[String]) -> anyhow::Result<Self> {
+        let root = root
+            .map(|root| {
+                std::fs::canonicalize(root)
+                    .map_err(|e| anyhow!("Cannot open the image root '{}': {}", root, e))
+            })
+            .transpose()?;
+        let hosts: Arc<[String]> = hosts.into();
+        let redirect_hosts = hosts.clone();
+        let http = reqwest::Client::builder()
+            .timeout(IMAGE_TIMEOUT)
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 5 {
+                    return attempt.error("too many redirects");
+                }
+                match refuse_image_url(attempt.url(), &redirect_hosts) {
+                    Some(reason) => attempt.error(reason),
+                    None => attempt.follow(),
+                }
+            }))
+            .build()?;
+        Ok(Self { http, root, hosts })
+    }
+
+    /// Read the image at `location`, an http(s) URL on one of the allowed
+    /// hosts or a path within the image root.
+    async fn read(&self, location: &str) -> anyhow::Result<Vec<u8>> {
+        use tokio::io::AsyncReadExt;
+
+        let too_large = || {
+            anyhow!(
+                "Cannot read image '{}': it is larger than {} bytes",
+                location,
+                MAX_IMAGE_BYTES
+            )
+        };
+        if location.starts_with("http://") || location.starts_with("https://") {
+            let url = reqwest::Url::parse(location)
+                .map_err(|e| anyhow!("Cannot read image '{}': {}", location, e))?;
+            if let Some(reason) = refuse_image_url(&url, &self.hosts) {
+                return Err(anyhow!("Cannot read image '{}': {}", location, reason));
+            }
+            let mut response = self.http.get(url).send().await?.error_for_status()?;
+            if response
+                .content_length()
+                .is_some_and(|len| len > MAX_IMAGE_BYTES)
+            {
+                return Err(too_large());
+            }
+            let mut bytes = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                if (bytes.len() + chunk.len()) as u64 > MAX_IMAGE_BYTES {
+                    return Err(too_large());
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            return Ok(bytes);
+        }
+
+        let root = self.root.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Cannot read image '{}': the collection has no image root to read local paths from",
+                location
+            )
+        })?;
+        // Symbolic links are resolved before checking, so that none leads
+        // outside of the root either.
+        let path = tokio::fs::canonicalize(root.join(location))
+            .await
+            .map_err(|e| anyhow!("Cannot read image '{}': {}", location, e))?;
+        if !path.starts_with(root) {
+            return Err(anyhow!(
+                "Cannot read image '{}': it is outside of the image root {:?}",
+                location,
+                root
+            ));
+        }
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| anyhow!("Cannot read image '{}': {}", location, e))?;
+        let mut bytes = Vec::new();
+        // One byte more than allowed tells a file that is too large, even
+        // one still growing.
+        file.take(MAX_IMAGE_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| anyhow!("Cannot read image '{}': {}", location, e))?;
+        if bytes.len() as u64 > MAX_IMAGE_BYTES {
+            return Err(too_large());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Embed `texts` with the model, or the images they locate when `images`
+/// is given, see [`CollectionConfig::image_columns`].
+async fn embed_batch(
+    model_manager: &Addr<ModelManagerActor>,
+    model_id: u32,
+    embedding_dim: Option<usize>,
+    texts: Vec<String>,
+    images: Option<&ImageReader>,
+) -> Result<Embeddings, ProjectError> {
+    let embeddings = if let Some(reader) = images {
+        let images = futures::future::try_join_all(texts.iter().map(|t| reader.read(t))).await?;
+        model_manager
+            .send(PredictImages {
+                id: model_id,
+                images,
+                span: Span::current(),
+            })
+            .await??
+    } else {
+        model_manager
+            .send(Predict {
+                id: model_id,
+                texts,
+                span: Span::current(),
+            })
+            .await??
+    };
+    truncated(embeddings, embedding_dim)
+}
+
+/// Embed `texts` with the model and add them to `index` under `keys`.
+/// Returns the embeddings that were added.
+async fn index_batch(
+    model_manager: &Addr<ModelManagerActor>,
+    index: &Arc<VectorIndex>,
+    model_id: u32,
+    embedding_dim: Option<usize>,
+    texts: Vec<String>,
+    keys: Vec<u64>,
+    images: Option<&ImageReader>,
+) -> Result<Embeddings, ProjectError> {
+    let embeddings = embed_batch(model_manager, model_id, embedding_dim, texts, images).await?;
+    add_batch(index, keys, embeddings.clone()).await?;
+    Ok(embeddings)
+}
+
+/// Pair the `chunks` embedded under `keys` with their `embeddings`, as
+/// stored by [`DbStoreChunks`].
+fn chunk_rows(
+    keys: &[u64],
+    chunks: Vec<Chunk>,
+    embeddings: &Embeddings,
+) -> Vec<(u64, Chunk, Vec<f32>)> {
+    keys.iter()
+        .copied()
+        .zip(chunks)
+        .zip(embeddings.to_f32_rows())
+        .map(|((key, chunk), embedding)| (key, chunk, embedding))
+        .collect()
+}
+
+/// Add `embeddings` to `index` off the database thread, so that searches keep
+/// being served while the index grows.
+async fn add_batch(
+    index: &Arc<VectorIndex>,
+    keys: Vec<u64>,
+    embeddings: Embeddings,
+) -> Result<(), ProjectError> {
+    let index = index.clone();
+    tokio::task::spawn_blocking(move || add_embeddings(&index, &keys, &embeddings)).await?
+}
+
 impl Handler<DbSaveIndex> for CollectionDbActor {
     type Result = Result<(), ProjectError>;
 
     fn handle(&mut self, msg: DbSaveIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let index = self.vector_indices.get(&msg.column).ok_or_else(|| {
-            ProjectError::Anyhow(anyhow!(
-                "Vector index for column '{}' not found",
-                msg.column
-            ))
-        })?;
+        let index = self
+            .vector_indices
+            .get(&msg.column)
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.column.clone()))?;
         index.save()?;
+        self.dirty_indices.remove(&msg.column);
         Ok(())
     }
 }
 
-impl Handler<DbSearchAndFetch> for CollectionDbActor {
-    type Result = Result<Vec<SearchResult>, ProjectError>;
+impl Handler<DbDeleteByFilter> for CollectionDbActor {
+    type Result = Result<u64, ProjectError>;
 
-    fn handle(&mut self, msg: DbSearchAndFetch, _ctx: &mut SyncContext<Self>) -> Self::Result {
-        let index = self.vector_indices.get(&msg.column).ok_or_else(|| {
-            ProjectError::Anyhow(anyhow!(
-                "Vector index for column '{}' not found",
-                msg.column
-            ))
-        })?;
+    fn handle(&mut self, msg: DbDeleteByFilter, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if !msg.dry_run && !msg.all && msg.filter.matches_all() {
+            return Err(ProjectError::DeleteAll(self.config.name.clone()));
+        }
+        // Checked up front, as the vectors can only be dropped once the
+        // rows are gone.
+        if !msg.dry_run {
+            self.check_writable()?;
+        }
+        let (clause, params) = msg.filter.to_sql().map_err(ProjectError::InvalidFilter)?;
+        let tx = self.conn.transaction()?;
 
-        let similarity_results = match msg.query_embedding {
-            Embeddings::F16(emb) => index.search::<UsearchF16>(
-                emb.as_ptr() as *const UsearchF16,
-                emb.dim().1,
-                msg.limit,
-            )?,
-            Embeddings::F32(emb) => index.search::<f32>(emb.as_ptr(), emb.dim().1, msg.limit)?,
+        let keys: Vec<u64> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT _key FROM {} WHERE {};",
+                self.config.name, clause
+            ))?;
+            let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
         };
 
-        let keys: Vec<u64> = similarity_results.iter().map(|r| r.key).collect();
-        if keys.is_empty() {
-            return Ok(Vec::new());
+        // Dropping the transaction without committing rolls it back.
+        if msg.dry_run || keys.is_empty() {
+            return Ok(keys.len() as u64);
         }
 
-        let keys_str = keys
-            .iter()
-            .map(|k| k.to_string())
-            .collect::<Vec<_>>()
-            .join(", ");
-        let query = format!(
-            "SELECT _key, {} FROM {} WHERE _key IN ({});",
-            msg.column, self.config.name, keys_str
+        tx.execute(
+            &format!("DELETE FROM {} WHERE {};", self.config.name, clause),
+            duckdb::params_from_iter(params.iter()),
+        )?;
+        delete_orphans(&tx, &self.config.name)?;
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+        self.remove_vectors(&keys)?;
+        if let Some(column) = self.config.prefix_column.clone() {
+            self.build_prefix_index(&column)?;
+        }
+
+        info!(
+            "Deleted {} rows from collection '{}'",
+            keys.len(),
+            self.config.name
         );
-        let mut stmt = self.conn.prepare(&query)?;
+        Ok(keys.len() as u64)
+    }
+}
 
-        let rbs: Vec<RecordBatch> = stmt.query_arrow([])?.collect();
-        let rb = rbs
-            .first()
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("No records found")))?;
-
-        let key_array = rb
-            .column_by_name("_key")
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("Column '_key' not found")))?
-            .as_any()
-            .downcast_ref::<PrimitiveArray<UInt64Type>>()
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("_key is not of type UInt64")))?;
-
-        let text_array = rb
-            .column_by_name(&msg.column)
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("Column '{}' not found", msg.column)))?
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .ok_or_else(|| ProjectError::Anyhow(anyhow!("Column is not of type String")))?;
-
-        let mut content_map = key_array
-            .iter()
-            .zip(text_array.iter())
-            .filter_map(|(k, v)| k.map(|k_val| (k_val, v.map(|v_val| v_val.to_string()))))
-            .filter_map(|(k, v)| v.map(|v_val| (k, v_val)))
-            .collect::<HashMap<_, _>>();
+impl Handler<DbAggregate> for CollectionDbActor {
+    type Result = Result<AggregateResults, ProjectError>;
+
+    fn handle(&mut self, msg: DbAggregate, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let visibility = self.acl_clause(msg.groups.as_ref())?;
+        let (sql, params) = msg.aggregation.to_sql(&self.config.name, visibility)?;
+        let columns = msg.aggregation.columns();
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| Ok((column.clone(), sql_value_to_json(row.get(i)?))))
+                .collect()
+        })?;
+        Ok(AggregateResults {
+            groups: rows.collect::<Result<_, _>>()?,
+        })
+    }
+}
 
-        let ordered_contents: Vec<String> =
-            keys.iter().filter_map(|k| content_map.remove(k)).collect();
+impl Handler<DbConnection> for CollectionDbActor {
+    type Result = Result<ReadOnlyDatabase, ProjectError>;
 
-        let search_results = similarity_results
-            .into_iter()
-            .zip(ordered_contents.into_iter())
-            .map(|(sim, content)| SearchResult {
-                content,
-                key: sim.key,
-                score: sim.score,
+    fn handle(&mut self, _msg: DbConnection, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if let Some((database, opened)) = &self.sql_database {
+            if self.last_write.is_none_or(|w| w < *opened) {
+                return Ok(database.connect()?);
+            }
+        }
+        // The instance reads the file as of its last checkpoint. Queries
+        // still running on the previous instance keep it open until they end.
+        self.conn.execute_batch("CHECKPOINT;")?;
+        let opened = Utc::now();
+        let path = self.config.dir().join(self.config.db_path.as_str());
+        let database = ReadOnlyDatabase::open(&path)?;
+        let connection = database.connect()?;
+        self.sql_database = Some((database, opened));
+        Ok(connection)
+    }
+}
+
+impl Handler<DbGetStats> for CollectionDbActor {
+    type Result = Result<CollectionStats, ProjectError>;
+
+    fn handle(&mut self, _msg: DbGetStats, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let documents = self.documents()?;
+        let vectors = self
+            .vector_indices
+            .iter()
+            .map(|(column, index)| {
+                let size = index.index.as_ref().map_or(0, |idx| idx.size());
+                (column.clone(), size as u64)
             })
             .collect();
 
-        Ok(search_results)
+        Ok(CollectionStats {
+            name: self.config.qualified_name(),
+            documents,
+            vectors,
+            last_write: self.last_write.map(|t| t.to_rfc3339()),
+        })
     }
 }
 
-// ---- CollectionActor ----
-
-pub struct CollectionActor {
-    config: CollectionConfig,
-    model_manager: Addr<ModelManagerActor>,
-    db_actor: Addr<CollectionDbActor>,
+impl Handler<DbGetDetailedStats> for CollectionDbActor {
+    type Result = Result<DetailedStats, ProjectError>;
+
+    fn handle(&mut self, _msg: DbGetDetailedStats, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let db_path = self.config.dir().join(self.config.db_path.as_str());
+        let db_file = std::fs::metadata(&db_path).ok();
+        let mut wal_path = db_path.into_os_string();
+        wal_path.push(".wal");
+        let wal_bytes = std::fs::metadata(wal_path).map_or(0, |wal| wal.len());
+
+        Ok(DetailedStats {
+            name: self.config.name.clone(),
+            documents: self.documents()?,
+            model_name: self.config.model_name.clone(),
+            model_variant: self.config.model_variant.clone(),
+            index_columns: self.config.index_columns.clone(),
+            indexes: self
+                .vector_indices
+                .iter()
+                .map(|(column, index)| (column.clone(), index.stats()))
+                .collect(),
+            db_bytes: db_file.as_ref().map_or(0, |file| file.len()) + wal_bytes,
+            db_modified: db_file
+                .and_then(|file| file.modified().ok())
+                .map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+            last_write: self.last_write.map(|t| t.to_rfc3339()),
+        })
+    }
 }
 
-impl CollectionActor {
-    pub fn new(config: CollectionConfig, model_manager: Addr<ModelManagerActor>) -> Self {
-        let config_clone = config.clone();
-        let db_actor = SyncArbiter::start(1, move || CollectionDbActor::new(config_clone.clone()));
+impl Handler<DbGetOperation> for CollectionDbActor {
+    type Result = Result<Option<(String, String)>, ProjectError>;
 
-        Self {
-            config,
-            model_manager,
-            db_actor,
-        }
+    fn handle(&mut self, msg: DbGetOperation, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_operations_table(&self.conn)?;
+        let cutoff = Utc::now().timestamp() - msg.retention.as_secs() as i64;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE created_at < ?;", OPERATIONS_TABLE),
+            duckdb::params![cutoff],
+        )?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT fingerprint, response FROM {} WHERE op_id = ?;",
+            OPERATIONS_TABLE
+        ))?;
+        let mut rows = stmt.query_map(duckdb::params![msg.op_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        Ok(rows.next().transpose()?)
     }
 }
 
-impl Actor for CollectionActor {
-    type Context = Context<Self>;
+impl Handler<DbStoreOperation> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbStoreOperation, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_operations_table(&self.conn)?;
+        self.conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} VALUES (?, ?, ?, ?);",
+                OPERATIONS_TABLE
+            ),
+            duckdb::params![
+                msg.op_id,
+                msg.fingerprint,
+                msg.response,
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
 }
 
-// ---- External Messages ----
+impl Handler<DbShutdown> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
 
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct ImportJsonl {
-    pub path: String,
+    fn handle(&mut self, _msg: DbShutdown, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        for column in self.dirty_indices.drain() {
+            if let Some(index) = self.vector_indices.get(&column) {
+                info!("Saving index for column '{}'", column);
+                index.save()?;
+            }
+        }
+        self.conn.execute_batch("CHECKPOINT;")?;
+        self.sql_database = None;
+        // Close the file, so that it can be opened again in the process.
+        // The actor is not stopped, as a stopped actor is restarted.
+        self.conn = duckdb::Connection::open_in_memory()?;
+        Ok(())
+    }
 }
 
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct ImportParquet {
-    pub path: String,
-}
+impl Handler<DbReload> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
 
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct EmbedColumn {
-    pub name: String,
-    pub batch_size: u64,
-    pub model_id: u32,
+    fn handle(&mut self, msg: DbReload, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if !self.dirty_indices.is_empty() {
+            return Err(ProjectError::CollectionBusy(self.config.name.clone()));
+        }
+        // Move the write-ahead log into the database file being replaced, so
+        // that it is not replayed onto its replacement.
+        self.conn.execute_batch("CHECKPOINT;")?;
+        // DuckDB does not open a file that is already open in the process,
+        // so the current connection is closed first.
+        self.conn = duckdb::Connection::open_in_memory()?;
+        *self = Self::open(msg.config, self.mmap_indexes)?;
+        Ok(())
+    }
 }
 
-#[derive(Message)]
-#[rtype(result = "Result<Vec<SearchResult>, ProjectError>")]
-pub struct Search {
-    pub column: String,
-    pub query: String,
-    pub limit: u32,
-    pub model_id: u32,
+impl Handler<DbSearchIndex> for CollectionDbActor {
+    type Result = Result<Vec<Neighbour>, ProjectError>;
+
+    fn handle(&mut self, msg: DbSearchIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let index = self
+            .vector_indices
+            .get(&msg.column)
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.column.clone()))?;
+
+        let visible = match (
+            self.visible_keys(msg.groups.as_ref())?,
+            self.filtered_keys(msg.filter.as_ref(), msg.geo_filter.as_ref())?,
+        ) {
+            (Some(visible), Some(matching)) => Some(&visible & &matching),
+            (visible, matching) => visible.or(matching),
+        };
+        if visible.as_ref().is_some_and(HashSet::is_empty) {
+            return Ok(Vec::new());
+        }
+
+        let candidates = |count: usize| -> Result<Vec<Neighbour>, ProjectError> {
+            let similarity_results = match &msg.query_embedding {
+                Embeddings::F16(emb) => {
+                    let row = emb.row(0);
+                    let query = row.as_slice().ok_or_else(|| {
+                        ProjectError::Anyhow(anyhow!("Query embedding is not contiguous"))
+                    })?;
+                    // SAFETY: `half::f16` and `usearch::f16` are both 16-bit
+                    // transparent wrappers with identical layout.
+                    let query: &[UsearchF16] = unsafe {
+                        std::slice::from_raw_parts(query.as_ptr() as *const UsearchF16, query.len())
+                    };
+                    match visible.as_ref() {
+                        Some(keys) => {
+                            index.filtered_search::<UsearchF16, _>(query, count, |key| {
+                                keys.contains(&key)
+                            })?
+                        }
+                        None => index.search::<UsearchF16>(query, count)?,
+                    }
+                }
+                Embeddings::F32(emb) => {
+                    let row = emb.row(0);
+                    let query = row.as_slice().ok_or_else(|| {
+                        ProjectError::Anyhow(anyhow!("Query embedding is not contiguous"))
+                    })?;
+                    match visible.as_ref() {
+                        Some(keys) => index
+                            .filtered_search::<f32, _>(query, count, |key| keys.contains(&key))?,
+                        None => index.search::<f32>(query, count)?,
+                    }
+                }
+            };
+            Ok(similarity_results
+                .into_iter()
+                .map(|r| Neighbour {
+                    key: r.key,
+                    distance: r.distance,
+                })
+                .collect())
+        };
+        let metric = index.metric();
+        // Searches never ask for more neighbours than the index holds, which
+        // usearch would allocate room for whatever the page requested.
+        let size = index.index.as_ref().map_or(0, |idx| idx.size());
+        let rescore = self.config.rescore.unwrap_or(1).max(1);
+        let query = match rescore {
+            1 => Vec::new(),
+            _ => msg.query_embedding.to_f32_rows().swap_remove(0),
+        };
+        let search = |count: usize| -> Result<Vec<Neighbour>, ProjectError> {
+            if rescore == 1 {
+                return candidates(count);
+            }
+            let mut neighbours = candidates(count.saturating_mul(rescore).min(size))?;
+            self.rescore(&msg.column, &query, metric, &mut neighbours)?;
+            neighbours.truncate(count);
+            Ok(neighbours)
+        };
+
+        // Fetch enough neighbours to cover the requested page.
+        let count = msg.offset.saturating_add(msg.limit).min(size);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let accepted = |neighbour: &Neighbour| msg.threshold.accepts(metric, neighbour.distance);
+        let neighbours = match msg.group_by {
+            None => {
+                // Order by distance, then by key so that results with equal
+                // scores always land on the same page.
+                let mut neighbours = search(count)?;
+                neighbours.retain(accepted);
+                neighbours
+                    .sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.key.cmp(&b.key)));
+                neighbours
+            }
+            Some(group_by) => {
+                // Documents may have several vectors among the nearest ones,
+                // so the search widens until the page is filled with distinct
+                // documents or the whole index was searched. Once the
+                // threshold drops a neighbour, wider searches only add
+                // weaker ones.
+                let mut fetch = count;
+                loop {
+                    let mut neighbours = search(fetch)?;
+                    let found = neighbours.len();
+                    neighbours.retain(accepted);
+                    let cut = neighbours.len() < found;
+                    let grouped = group_by.group(&neighbours);
+                    if grouped.len() >= count || fetch >= size || cut {
+                        break grouped;
+                    }
+                    fetch = (fetch * 2).min(size);
+                }
+            }
+        };
+        Ok(neighbours
+            .into_iter()
+            .skip(msg.offset)
+            .take(msg.limit)
+            .collect())
+    }
 }
 
-#[derive(Message)]
-#[rtype(result = "Result<CollectionConfig, ProjectError>")]
-pub struct GetConfig;
+impl Handler<DbMatchChunks> for CollectionDbActor {
+    type Result = Result<Vec<Option<MatchedChunk>>, ProjectError>;
+
+    fn handle(&mut self, msg: DbMatchChunks, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let query = msg.query_embedding.to_f32_rows().into_iter().next();
+        let (Some(query), false) = (query, msg.hits.is_empty()) else {
+            return Ok(vec![None; msg.hits.len()]);
+        };
+        let metric = self
+            .vector_indices
+            .get(&msg.column)
+            .map(|index| index.metric())
+            .unwrap_or_default();
+
+        let keys_str = msg
+            .hits
+            .iter()
+            .map(|hit| hit.key.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        ensure_chunks_table(&self.conn)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT _key, chunk, start_offset, end_offset, text, embedding FROM {} \
+             WHERE column_name = ? AND _key IN ({});",
+            CHUNKS_TABLE, keys_str
+        ))?;
+        let rows = stmt.query_map(duckdb::params![msg.column], |row| {
+            let embedding: Vec<u8> = row.get(5)?;
+            Ok((
+                row.get::<_, u64>(0)?,
+                MatchedChunk {
+                    index: row.get::<_, u32>(1)? as usize,
+                    start: row.get::<_, u64>(2)? as usize,
+                    end: row.get::<_, u64>(3)? as usize,
+                    text: row.get(4)?,
+                },
+                embedding
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect::<Vec<f32>>(),
+            ))
+        })?;
+        let mut chunks: HashMap<u64, Vec<(MatchedChunk, Vec<f32>)>> = HashMap::new();
+        for row in rows {
+            let (key, chunk, embedding) = row?;
+            chunks.entry(key).or_default().push((chunk, embedding));
+        }
+
+        // The index only returns the key and distance of a hit, so the chunk
+        // is the one whose distance to the query is closest to it. A grouped
+        // hit stands for its document's best chunk.
+        Ok(msg
+            .hits
+            .iter()
+            .map(|hit| {
+                let closeness = |embedding: &Vec<f32>| {
+                    let distance = metric.distance(&query, embedding);
+                    if msg.grouped {
+                        distance
+                    } else {
+                        (distance - hit.distance).abs()
+                    }
+                };
+                chunks
+                    .get(&hit.key)?
+                    .iter()
+                    .min_by(|(_, a), (_, b)| closeness(a).total_cmp(&closeness(b)))
+                    .map(|(chunk, _)| chunk.clone())
+            })
+            .collect())
+    }
+}
+
+impl Handler<DbFetchContents> for CollectionDbActor {
+    type Result = Result<HashMap<u64, String>, ProjectError>;
+
+    fn handle(&mut self, msg: DbFetchContents, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let keys = msg.keys;
+        if keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let keys_str = keys
+            .iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let content_column = self.config.display_column(&msg.column);
+        if !is_valid_identifier(content_column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid display column '{}' for '{}'",
+                content_column,
+                msg.column
+            )));
+        }
+        let query = format!(
+            "SELECT _key, CAST({} AS VARCHAR) AS content FROM {} WHERE _key IN ({});",
+            content_column, self.config.name, keys_str
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let mut content_map = HashMap::with_capacity(keys.len());
+        for rb in stmt.query_arrow([])? {
+            let key_array = rb
+                .column_by_name("_key")
+                .ok_or_else(|| ProjectError::ColumnNotFound(String::from("_key")))?
+                .as_any()
+                .downcast_ref::<PrimitiveArray<UInt64Type>>()
+                .ok_or_else(|| ProjectError::ColumnType {
+                    column: String::from("_key"),
+                    expected: "UInt64",
+                })?;
+
+            let text_array = rb
+                .column_by_name("content")
+                .ok_or_else(|| ProjectError::ColumnNotFound(content_column.to_string()))?
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| ProjectError::ColumnType {
+                    column: content_column.to_string(),
+                    expected: "String",
+                })?;
+
+            content_map.extend(key_array.iter().zip(text_array.iter()).filter_map(
+                |(key, content)| Some((key?, content.unwrap_or_default().to_string())),
+            ));
+        }
+
+        Ok(content_map)
+    }
+}
+
+impl Handler<DbClearRelated> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbClearRelated, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_related_table(&self.conn)?;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE column_name = ?;", RELATED_TABLE),
+            duckdb::params![msg.column],
+        )?;
+        Ok(())
+    }
+}
+
+impl Handler<DbGetKeysWithoutRelated> for CollectionDbActor {
+    type Result = Result<Vec<u64>, ProjectError>;
+
+    fn handle(
+        &mut self,
+        msg: DbGetKeysWithoutRelated,
+        _ctx: &mut SyncContext<Self>,
+    ) -> Self::Result {
+        ensure_related_table(&self.conn)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT _key FROM {} WHERE _key NOT IN (SELECT _key FROM {} WHERE column_name = ?) ORDER BY _key;",
+            self.config.name, RELATED_TABLE
+        ))?;
+        let rows = stmt.query_map(duckdb::params![msg.column], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+impl Handler<DbStoreRelated> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbStoreRelated, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        ensure_related_table(&self.conn)?;
+        let tx = self.conn.transaction()?;
+
+        // Documents that already have a list may now have closer neighbours
+        // among the processed ones. Documents without a list are left to
+        // their own run, which searches the whole index anyway.
+        let candidates = reverse_candidates(&msg.neighbours);
+        let mut existing: HashMap<u64, Vec<Neighbour>> = HashMap::new();
+        if !candidates.is_empty() {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT _key, related_key, distance FROM {} WHERE column_name = ? AND _key IN ({}) ORDER BY _key, rank;",
+                RELATED_TABLE,
+                keys_list(candidates.keys().copied())
+            ))?;
+            let rows = stmt.query_map(duckdb::params![msg.column], |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    Neighbour {
+                        key: row.get(1)?,
+                        distance: row.get(2)?,
+                    },
+                ))
+            })?;
+            for row in rows {
+                let (key, neighbour) = row?;
+                existing.entry(key).or_default().push(neighbour);
+            }
+        }
+
+        let mut lists = msg.neighbours;
+        for (key, current) in existing {
+            let merged = merge_neighbours(&current, &candidates[&key], msg.count);
+            if merged != current {
+                lists.push((key, merged));
+            }
+        }
+        if lists.is_empty() {
+            return Ok(());
+        }
+
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE column_name = ? AND _key IN ({});",
+                RELATED_TABLE,
+                keys_list(lists.iter().map(|(key, _)| *key))
+            ),
+            duckdb::params![msg.column],
+        )?;
+        {
+            let mut appender = tx.appender(RELATED_TABLE)?;
+            for (key, neighbours) in &lists {
+                for (rank, neighbour) in neighbours.iter().enumerate() {
+                    appender.append_row(duckdb::params![
+                        key,
+                        msg.column,
+                        rank as i32,
+                        neighbour.key,
+                        neighbour.distance
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        self.last_write = Some(Utc::now());
+        Ok(())
+    }
+}
+
+impl Handler<DbGetRelated> for CollectionDbActor {
+    type Result = Result<SearchResults, ProjectError>;
+
+    fn handle(&mut self, msg: DbGetRelated, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let metric = self
+            .vector_indices
+            .get(&msg.column)
+            .ok_or_else(|| ProjectError::IndexNotFound(msg.column.clone()))?
+            .metric();
+        let content_column = self.config.display_column(&msg.column);
+        if !is_valid_identifier(content_column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid display column '{}' for '{}'",
+                content_column,
+                msg.column
+            )));
+        }
+        self.check_visible(msg.key, msg.groups.as_ref())?;
+        let (acl_clause, acl_params) = self
+            .acl_clause(msg.groups.as_ref())?
+            .unwrap_or_else(|| (String::from("TRUE"), Vec::new()));
+
+        ensure_related_table(&self.conn)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT r.related_key, r.distance, CAST(t.{} AS VARCHAR) FROM {} r JOIN {} t ON t._key = r.related_key \
+             WHERE r._key = ? AND r.column_name = ? AND {} ORDER BY r.rank LIMIT ?;",
+            content_column, RELATED_TABLE, self.config.name, acl_clause
+        ))?;
+        let mut params = vec![
+            duckdb::types::Value::UBigInt(msg.key),
+            duckdb::types::Value::Text(msg.column.clone()),
+        ];
+        params.extend(acl_params);
+        params.push(duckdb::types::Value::UBigInt(msg.limit as u64));
+        let rows = stmt.query_map(duckdb::params_from_iter(params.iter()), |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, f32>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let (key, distance, content) = row?;
+            results.push(SearchResult {
+                content: content.unwrap_or_default(),
+                key,
+                score: ScoreType::Similarity.score(metric, distance),
+                column: msg.column.clone(),
+                chunk: None,
+                snippet: None,
+            });
+        }
+        Ok(SearchResults {
+            results,
+            metric,
+            score_type: ScoreType::Similarity,
+            indexing: false,
+            partial: false,
+            query_language: None,
+        })
+    }
+}
+
+impl Handler<DbCheckVisible> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbCheckVisible, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        self.check_visible(msg.key, msg.groups.as_ref())
+    }
+}
+
+impl Handler<DbRecordQuery> for CollectionDbActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: DbRecordQuery, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let phrase = match normalize_phrase(&msg.query) {
+            Some(phrase) => phrase,
+            None => return Ok(()),
+        };
+        ensure_suggestions_table(&self.conn)?;
+        self.conn.execute(
+            &format!(
+                "INSERT INTO {} VALUES (?, ?, 1) ON CONFLICT (phrase, source) DO UPDATE SET count = count + 1;",
+                SUGGESTIONS_TABLE
+            ),
+            duckdb::params![phrase, SuggestionSource::Query.to_string()],
+        )?;
+        Ok(())
+    }
+}
+
+impl Handler<DbBuildSuggestions> for CollectionDbActor {
+    type Result = Result<(usize, usize), ProjectError>;
+
+    fn handle(&mut self, msg: DbBuildSuggestions, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if !is_valid_identifier(&msg.column) {
+            return Err(ProjectError::Anyhow(anyhow!(
+                "Invalid column name '{}'",
+                msg.column
+            )));
+        }
+
+        // Number of documents each phrase and each word occurs in.
+        let mut documents: HashMap<String, u64> = HashMap::new();
+        let mut terms: HashMap<String, u64> = HashMap::new();
+        {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT CAST({} AS VARCHAR) FROM {} WHERE {} IS NOT NULL;",
+                msg.column, self.config.name, msg.column
+            ))?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let text: String = row.get(0)?;
+                for phrase in extract_phrases(&text, msg.max_words) {
+                    *documents.entry(phrase).or_insert(0) += 1;
+                }
+                for term in extract_terms(&text) {
+                    *terms.entry(term).or_insert(0) += 1;
+                }
+            }
+        }
+
+        ensure_suggestions_table(&self.conn)?;
+        ensure_terms_table(&self.conn)?;
+        let source = SuggestionSource::Text.to_string();
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            &format!("DELETE FROM {} WHERE source = ?;", SUGGESTIONS_TABLE),
+            duckdb::params![source],
+        )?;
+        let mut stored = 0;
+        {
+            let mut appender = tx.appender(SUGGESTIONS_TABLE)?;
+            for (phrase, count) in documents {
+                if count >= msg.min_documents && phrase.chars().count() <= MAX_PHRASE_CHARS {
+                    appender.append_row(duckdb::params![phrase, source, count])?;
+                    stored += 1;
+                }
+            }
+        }
+        tx.execute(&format!("DELETE FROM {};", TERMS_TABLE), [])?;
+        let mut stored_terms = 0;
+        {
+            let mut appender = tx.appender(TERMS_TABLE)?;
+            for (term, count) in terms {
+                if count >= msg.min_documents {
+                    appender.append_row(duckdb::params![term, count])?;
+                    stored_terms += 1;
+                }
+            }
+        }
+        tx.commit()?;
+        self.spelling = None;
+        Ok((stored, stored_terms))
+    }
+}
+
+impl Handler<DbSuggest> for CollectionDbActor {
+    type Result = Result<Vec<Suggestion>, ProjectError>;
+
+    fn handle(&mut self, msg: DbSuggest, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let prefix = match normalize_phrase(&msg.prefix) {
+            Some(prefix) => prefix,
+            None => return Ok(Vec::new()),
+        };
+        // Keep a trailing space so that "rust " only completes whole words.
+        let prefix = if msg.prefix.ends_with(char::is_whitespace) {
+            format!("{} ", prefix)
+        } else {
+            prefix
+        };
+
+        let text_visible = msg.groups.is_none() || self.config.acl_column.is_none();
+
+        ensure_suggestions_table(&self.conn)?;
+        // A phrase both searched and found in the text adds up its counts.
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT phrase, CAST(SUM(count) AS UBIGINT) AS total FROM {} WHERE starts_with(phrase, ?) \
+             AND ((source = ? AND count >= ?) OR (source = ? AND ?)) \
+             GROUP BY phrase ORDER BY total DESC, phrase LIMIT ?;",
+            SUGGESTIONS_TABLE
+        ))?;
+        let params = duckdb::params![
+            prefix,
+            SuggestionSource::Query.to_string(),
+            MIN_QUERY_COUNT,
+            SuggestionSource::Text.to_string(),
+            text_visible,
+            msg.limit as u64
+        ];
+        let rows = stmt.query_map(params, |row| {
+            Ok(Suggestion {
+                text: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+impl Handler<DbBuildPrefixIndex> for CollectionDbActor {
+    type Result = Result<Option<usize>, ProjectError>;
+
+    fn handle(&mut self, _msg: DbBuildPrefixIndex, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let column = match self.config.prefix_column.clone() {
+            Some(column) => column,
+            None => return Ok(None),
+        };
+        // Imports only add rows, and deletes rebuild the index themselves.
+        if self.prefix_index.is_some() && self.prefix_rows == Some(self.documents()?) {
+            return Ok(None);
+        }
+        self.build_prefix_index(&column).map(Some)
+    }
+}
+
+impl Handler<DbCompletePrefix> for CollectionDbActor {
+    type Result = Result<Vec<Suggestion>, ProjectError>;
+
+    fn handle(&mut self, msg: DbCompletePrefix, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if self.config.prefix_column.is_none() {
+            return Err(ProjectError::NoPrefixIndex(self.config.name.clone()));
+        }
+        Ok(self
+            .prefix_index
+            .as_ref()
+            .map(|index| index.complete(&msg.prefix, msg.limit))
+            .unwrap_or_default())
+    }
+}
+
+impl Handler<DbCorrectSpelling> for CollectionDbActor {
+    type Result = Result<Vec<Correction>, ProjectError>;
+
+    fn handle(&mut self, msg: DbCorrectSpelling, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        if self.spelling.is_none() {
+            ensure_terms_table(&self.conn)?;
+            let mut stmt = self
+                .conn
+                .prepare(&format!("SELECT term, count FROM {};", TERMS_TABLE))?;
+            let terms = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<(String, u64)>, _>>()?;
+            self.spelling = Some(SpellingDictionary::new(terms));
+        }
+        let spelling = self.spelling.as_ref().expect("the dictionary was loaded");
+        Ok(spelling.correct(&msg.query, msg.limit))
+    }
+}
+
+// ---- CollectionActor ----
+
+pub struct CollectionActor {
+    config: CollectionConfig,
+    model_manager: Addr<ModelManagerActor>,
+    db_actor: Addr<CollectionDbActor>,
+    /// Columns whose index is currently receiving new vectors.
+    indexing: Arc<Mutex<HashSet<String>>>,
+    /// Ids of write operations claimed but not completed yet.
+    operations: RunningOperations,
+    /// Latest modification time of the collection's files when they were
+    /// last loaded, to tell when they are replaced on disk. Only tracked
+    /// while watching them.
+    loaded_files: Option<SystemTime>,
+}
+
+/// Marks a column as being indexed until dropped, including on errors.
+struct IndexingGuard {
+    indexing: Arc<Mutex<HashSet<String>>>,
+    column: String,
+}
+
+impl IndexingGuard {
+    fn new(indexing: &Arc<Mutex<HashSet<String>>>, column: &str) -> Self {
+        indexing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(column.to_string());
+        Self {
+            indexing: indexing.clone(),
+            column: column.to_string(),
+        }
+    }
+}
+
+impl Drop for IndexingGuard {
+    fn drop(&mut self) {
+        self.indexing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.column);
+    }
+}
+
+impl CollectionActor {
+    pub fn new(
+        config: CollectionConfig,
+        model_manager: Addr<ModelManagerActor>,
+    ) -> Result<Self, ProjectError> {
+        Self::with_mmap_indexes(config, model_manager, false)
+    }
+
+    /// Open the collection with its saved indexes memory-mapped read-only
+    /// when `mmap_indexes` is set, see [`VectorIndex::view`].
+    pub fn with_mmap_indexes(
+        config: CollectionConfig,
+        model_manager: Addr<ModelManagerActor>,
+        mmap_indexes: bool,
+    ) -> Result<Self, ProjectError> {
+        // Opened here, so that a database that cannot be opened fails the
+        // caller rather than the arbiter's thread.
+        let db = Mutex::new(Some(CollectionDbActor::open(config.clone(), mmap_indexes)?));
+        let db_actor = SyncArbiter::start(1, move || {
+            db.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take()
+                .expect("The collection database actor is never restarted")
+        });
+
+        Ok(Self {
+            loaded_files: None,
+            config,
+            model_manager,
+            db_actor,
+            indexing: Arc::new(Mutex::new(HashSet::new())),
+            operations: RunningOperations::default(),
+        })
+    }
+}
+
+impl Actor for CollectionActor {
+    type Context = Context<Self>;
+}
+
+// ---- External Messages ----
+
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ImportJsonl {
+    pub path: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ImportParquet {
+    pub path: String,
+}
+
+/// Authenticate the `hf://` paths of later imports with a HuggingFace
+/// token, e.g. to import a private dataset.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct SetHfToken {
+    pub token: String,
+}
+
+/// Authenticate the `s3://` and `gs://` paths of later imports.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct SetObjectStoreCredentials {
+    pub credentials: ObjectStoreCredentials,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<IndexReport, ProjectError>")]
+pub struct EmbedColumn {
+    pub name: String,
+    pub batch_size: u64,
+    pub model_id: u32,
+    /// Skip and record rows that cannot be embedded or added to the index
+    /// instead of aborting the run.
+    pub skip_errors: bool,
+    /// Number of batches embedded at the same time, overlapping their
+    /// tokenization and inference with reading and inserting other batches.
+    pub concurrency: usize,
+}
+
+/// Suffix of the name of an index being rebuilt by [`Reindex`], which is
+/// not a valid column name.
+const STAGED_SUFFIX: &str = ".reindex";
+
+/// Rebuild the indexes of `columns` in a staging directory, each replacing
+/// the current one once it is complete. Without `embed`, the indexes are
+/// built from the stored embeddings. Returns a report per column.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<(String, IndexReport)>, ProjectError>")]
+pub struct Reindex {
+    pub columns: Vec<String>,
+    pub embed: Option<Reembed>,
+    pub overrides: IndexOverrides,
+    /// Candidates to rescore per result from now on, recorded in the
+    /// config, see [`CollectionConfig::rescore`].
+    pub rescore: Option<usize>,
+}
+
+/// How [`Reindex`] embeds the documents again.
+#[derive(Clone)]
+pub struct Reembed {
+    /// Id, name and variant of the model embedding each rebuilt column,
+    /// recorded in the config once every index is rebuilt.
+    pub models: HashMap<String, (u32, ColumnModel)>,
+    pub batch_size: u64,
+    pub skip_errors: bool,
+    pub concurrency: usize,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<SearchResults, ProjectError>")]
+pub struct Search {
+    /// Columns to search. Results from several columns are merged by
+    /// document, keeping each document's best score.
+    pub columns: Vec<String>,
+    pub query: String,
+    /// Precomputed query vector, searched instead of embedding `query`,
+    /// e.g. for clients that embed queries themselves.
+    pub vector: Option<Vec<f32>>,
+    pub limit: u32,
+    pub offset: u32,
+    pub score_type: ScoreType,
+    /// Model of the columns without a model of their own.
+    pub model_id: u32,
+    /// Model of each column that has its own, see
+    /// [`CollectionConfig::column_models`].
+    pub column_model_ids: HashMap<String, u32>,
+    /// Groups the caller belongs to. `None` searches every document, `Some`
+    /// only documents whose ACL column shares a group with it. Ignored when
+    /// the collection has no ACL column.
+    pub groups: Option<Vec<String>>,
+    /// Only search documents matching this filter, on columns of
+    /// [`CollectionConfig::filterable_columns`].
+    pub filter: Option<Filter>,
+    /// Only search documents within a distance of a point, by the
+    /// collection's [`CollectionConfig::geo_columns`].
+    pub geo_filter: Option<GeoFilter>,
+    /// Return one result per document, combining the scores of documents
+    /// with several matching vectors, e.g. chunks. Without it such a
+    /// document may show up several times.
+    pub group_by: Option<GroupBy>,
+    pub timeout: Option<SearchTimeout>,
+    /// Leave out results weaker than this, before paging.
+    pub threshold: ScoreThreshold,
+    /// Return the results with empty `content` without reading the table,
+    /// for callers that only need keys and scores. Results of rows deleted
+    /// since they were indexed are then not left out.
+    pub skip_content: bool,
+    /// Span of the caller, parent of the spans of the search.
+    pub span: Span,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<CollectionConfig, ProjectError>")]
+pub struct GetConfig;
+
+/// Store the score calibration used when merging results with other
+/// collections.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct SetCalibration {
+    pub calibration: ScoreCalibration,
+}
+
+/// Precompute the related documents of every document on `column` that does
+/// not have them yet, and refresh the lists of their neighbours. With
+/// `count`, the column is set up to keep that many related documents per
+/// document; without it, columns that were never set up are skipped.
+/// Returns the number of documents processed.
+#[derive(Message)]
+#[rtype(result = "Result<usize, ProjectError>")]
+pub struct UpdateRelated {
+    pub column: String,
+    pub count: Option<usize>,
+}
+
+/// Precomputed related documents of the document `key` on `column`.
+#[derive(Message)]
+#[rtype(result = "Result<SearchResults, ProjectError>")]
+pub struct GetRelated {
+    pub column: String,
+    pub key: u64,
+    pub limit: u32,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Documents nearest to the document `key` on `column`, excluding itself,
+/// searched live with its stored vector instead of embedding its text.
+#[derive(Message)]
+#[rtype(result = "Result<SearchResults, ProjectError>")]
+pub struct Similar {
+    pub column: String,
+    pub key: u64,
+    pub limit: u32,
+    pub offset: u32,
+    pub score_type: ScoreType,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Count a searched query towards the suggestions of the collection.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct RecordQuery {
+    pub query: String,
+}
+
+/// Extract the phrases suggested from the text of `column`, and the words
+/// its spelling corrections are picked from, replacing those extracted
+/// before. Returns the number of phrases and of words stored.
+#[derive(Message)]
+#[rtype(result = "Result<(usize, usize), ProjectError>")]
+pub struct BuildSuggestions {
+    pub column: String,
+    /// Longest phrase extracted, in words.
+    pub max_words: usize,
+    /// Documents a phrase or word must occur in to be suggested.
+    pub min_documents: u64,
+}
+
+/// Past queries and phrases of the indexed text that start with `prefix`,
+/// the most popular first. See [`DbSuggest`] for what `groups` hides.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Suggestion>, ProjectError>")]
+pub struct Suggest {
+    pub prefix: String,
+    pub limit: u32,
+    /// Groups of the caller. See [`Search::groups`].
+    pub groups: Option<Vec<String>>,
+}
+
+/// Values of the collection's prefix column starting with `prefix`, those of
+/// the most documents first.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Suggestion>, ProjectError>")]
+pub struct CompletePrefix {
+    pub prefix: String,
+    pub limit: u32,
+}
+
+/// Did-you-mean corrections of `query` from the words of the indexed text.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Correction>, ProjectError>")]
+pub struct CorrectSpelling {
+    pub query: String,
+    pub limit: u32,
+}
+
+/// Import an Arrow IPC (`.arrow` / `.feather`) file into a new collection table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ImportArrow {
+    pub path: String,
+}
+
+/// Append rows from a JSONL file to an existing collection table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct AppendJsonl {
+    pub path: String,
+}
+
+/// Append rows from a Parquet file to an existing collection table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct AppendParquet {
+    pub path: String,
+}
+
+/// Delete all documents matching `filter` from the table and every column
+/// index. With `dry_run` only the number of matching documents is returned.
+/// A filter matching every document is refused unless `all` is set.
+#[derive(Message)]
+#[rtype(result = "Result<u64, ProjectError>")]
+pub struct DeleteByQuery {
+    pub filter: Filter,
+    pub dry_run: bool,
+    pub all: bool,
+}
+
+/// Open the collection's database for read-only queries run by the caller,
+/// in a DuckDB instance that cannot read files or URLs. See
+/// [`crate::collection::sql`].
+#[derive(Message)]
+#[rtype(result = "Result<ReadOnlyDatabase, ProjectError>")]
+pub struct SqlConnection;
+
+/// Count, sum or average columns of the documents per group, e.g. for
+/// dashboards. Only documents visible to `groups` are aggregated, see
+/// [`Search::groups`].
+#[derive(Message)]
+#[rtype(result = "Result<AggregateResults, ProjectError>")]
+pub struct Aggregate {
+    pub aggregation: Aggregation,
+    pub groups: Option<Vec<String>>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<CollectionStats, ProjectError>")]
+pub struct GetStats;
+
+/// Sizes, shapes and modification times of the collection.
+#[derive(Message)]
+#[rtype(result = "Result<DetailedStats, ProjectError>")]
+pub struct GetDetailedStats;
+
+/// Append rows from an Arrow IPC file to an existing collection table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct AppendArrow {
+    pub path: String,
+}
+
+/// Import a table or query of a Postgres or MySQL database into a new
+/// collection table.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ImportDatabase {
+    pub source: DatabaseSource,
+}
+
+/// Append rows from a table or query of a Postgres or MySQL database to an
+/// existing collection table. With a `cursor_column`, e.g. an increasing
+/// `id`, only rows past the largest value already imported are appended, so
+/// that repeated runs sync new rows. With a `source_key`, e.g. the primary
+/// key `id` of the source, rows already imported with the key of an
+/// appended row are replaced, vectors included, so that a cursor such as a
+/// version bumped on every update syncs updated rows too. Returns the number
+/// of rows appended.
+#[derive(Message)]
+#[rtype(result = "Result<usize, ProjectError>")]
+pub struct AppendDatabase {
+    pub source: DatabaseSource,
+    pub cursor_column: Option<String>,
+    pub source_key: Option<String>,
+}
+
+/// Flush pending index changes to disk and stop the collection.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct Shutdown;
+
+/// Reopen the collection's config, database and vector indexes from disk,
+/// e.g. after an external job rebuilt them. Returns the reloaded stats.
+#[derive(Message)]
+#[rtype(result = "Result<CollectionStats, ProjectError>")]
+pub struct Reload;
+
+/// Reload the collection whenever its files on disk change, checking every
+/// `interval`.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WatchFiles {
+    pub interval: Duration,
+}
+
+/// Import a PDF, DOCX or text document, or the transcript of an audio file:
+/// extract its text, optionally chunk it, and insert the resulting chunks into the named column of the
+/// collection table, with the document's path and page.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ImportDocument {
+    /// Path to the document.
+    pub path: String,
+    /// Format of the document, one for which [`FileFormat::is_document`] holds.
+    pub format: FileFormat,
+    /// Target column name (e.g. `"text"`).
+    pub column: String,
+    /// Optional chunker configuration.  When `None` each page of a PDF, or
+    /// the whole text of other documents, is inserted as a single row.
+    pub chunker_config: Option<ChunkerConfig>,
+    /// Command transcribing audio files, see [`Transcriber`]. Audio is
+    /// rejected without one.
+    pub transcribe_command: Option<String>,
+}
+
+/// Claim the id of a write operation before running it, so that retries of
+/// the same request are not applied twice. A `New` claim must be followed by
+/// [`CompleteOperation`].
+#[derive(Message)]
+#[rtype(result = "Result<OperationClaim, ProjectError>")]
+pub struct ClaimOperation {
+    pub op_id: String,
+    /// See [`crate::collection::idempotency::fingerprint`].
+    pub fingerprint: String,
+    /// How long completed operations are remembered.
+    pub retention: Duration,
+}
+
+/// Release a claimed operation id. With a `response`, the operation is
+/// recorded as completed and later claims replay it; without one, e.g. when
+/// the write failed, the id can be claimed again.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct CompleteOperation {
+    pub op_id: String,
+    pub fingerprint: String,
+    pub response: Option<String>,
+    /// Claim returned with [`OperationClaim::New`], released once the
+    /// response is recorded.
+    pub guard: OperationGuard,
+}
+
+// ---- Message Handlers ----
+
+impl Handler<ImportJsonl> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: ImportJsonl, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbImportJsonl { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<ImportParquet> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: ImportParquet, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbImportParquet { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<SetHfToken> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: SetHfToken, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbSetHfToken { token: msg.token }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<SetObjectStoreCredentials> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: SetObjectStoreCredentials, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbSetObjectStoreCredentials {
+                    credentials: msg.credentials,
+                })
+                .await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<ImportArrow> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: ImportArrow, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbImportArrow { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<AppendJsonl> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: AppendJsonl, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbAppendJsonl { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<AppendParquet> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: AppendParquet, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbAppendParquet { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<AppendArrow> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: AppendArrow, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor.send(DbAppendArrow { path: msg.path }).await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<ImportDatabase> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: ImportDatabase, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbImportDatabase { source: msg.source })
+                .await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<AppendDatabase> for CollectionActor {
+    type Result = ResponseFuture<Result<usize, ProjectError>>;
+
+    fn handle(&mut self, msg: AppendDatabase, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbAppendDatabase {
+                    source: msg.source,
+                    cursor_column: msg.cursor_column,
+                    source_key: msg.source_key,
+                })
+                .await?
+        })
+    }
+}
+
+impl Handler<ImportDocument> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: ImportDocument, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+
+        Box::pin(async move {
+            let path = msg.path.clone();
+            let column = msg.column.clone();
+            let cfg = msg.chunker_config.clone();
+            let transcriber = msg
+                .transcribe_command
+                .as_deref()
+                .map(Transcriber::new)
+                .transpose()?;
+
+            // Text extraction is CPU/IO-bound — run it on a blocking thread.
+            let chunks = tokio::task::spawn_blocking(move || {
+                let chunker = cfg.map(MarkdownChunker::new).transpose()?;
+                document_chunks(&path, msg.format, chunker.as_ref(), transcriber.as_ref())
+            })
+            .await
+            .map_err(ProjectError::JoinError)??;
+
+            db_actor
+                .send(DbImportDocumentChunks { chunks, column })
+                .await??;
+            Ok(())
+        })
+    }
+}
+
+impl Handler<DeleteByQuery> for CollectionActor {
+    type Result = ResponseFuture<Result<u64, ProjectError>>;
+
+    fn handle(&mut self, msg: DeleteByQuery, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            let count = db_actor
+                .send(DbDeleteByFilter {
+                    filter: msg.filter,
+                    dry_run: msg.dry_run,
+                    all: msg.all,
+                })
+                .await??;
+            Ok(count)
+        })
+    }
+}
+
+impl Handler<Aggregate> for CollectionActor {
+    type Result = ResponseFuture<Result<AggregateResults, ProjectError>>;
+
+    fn handle(&mut self, msg: Aggregate, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbAggregate {
+                    aggregation: msg.aggregation,
+                    groups: msg.groups,
+                })
+                .await?
+        })
+    }
+}
+
+impl Handler<SqlConnection> for CollectionActor {
+    type Result = ResponseFuture<Result<ReadOnlyDatabase, ProjectError>>;
+
+    fn handle(&mut self, _msg: SqlConnection, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move { db_actor.send(DbConnection).await? })
+    }
+}
+
+impl Handler<ClaimOperation> for CollectionActor {
+    type Result = ResponseFuture<Result<OperationClaim, ProjectError>>;
+
+    fn handle(&mut self, msg: ClaimOperation, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        let guard = self.operations.claim(&msg.op_id);
+        Box::pin(async move {
+            let Some(guard) = guard else {
+                return Ok(OperationClaim::InProgress);
+            };
+            let stored = db_actor
+                .send(DbGetOperation {
+                    op_id: msg.op_id.clone(),
+                    retention: msg.retention,
+                })
+                .await
+                .map_err(ProjectError::from)
+                .and_then(|result| result);
+            match stored? {
+                None => Ok(OperationClaim::New(guard)),
+                Some((fingerprint, response)) if fingerprint == msg.fingerprint => {
+                    Ok(OperationClaim::Replay(response))
+                }
+                Some(_) => Ok(OperationClaim::Mismatch),
+            }
+        })
+    }
+}
+
+impl Handler<CompleteOperation> for CollectionActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: CompleteOperation, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            let result = match msg.response {
+                Some(response) => db_actor
+                    .send(DbStoreOperation {
+                        op_id: msg.op_id.clone(),
+                        fingerprint: msg.fingerprint,
+                        response,
+                    })
+                    .await
+                    .map_err(ProjectError::from)
+                    .and_then(|result| result),
+                None => Ok(()),
+            };
+            // Released only once recorded, so that a concurrent retry either
+            // waits for this one or replays it.
+            drop(msg.guard);
+            result
+        })
+    }
+}
+
+impl Handler<GetStats> for CollectionActor {
+    type Result = ResponseFuture<Result<CollectionStats, ProjectError>>;
+
+    fn handle(&mut self, _msg: GetStats, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move { db_actor.send(DbGetStats).await? })
+    }
+}
+
+impl Handler<GetDetailedStats> for CollectionActor {
+    type Result = ResponseFuture<Result<DetailedStats, ProjectError>>;
+
+    fn handle(&mut self, _msg: GetDetailedStats, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move { db_actor.send(DbGetDetailedStats).await? })
+    }
+}
+
+impl Handler<Shutdown> for CollectionActor {
+    type Result = ResponseActFuture<Self, Result<(), ProjectError>>;
+
+    fn handle(&mut self, _msg: Shutdown, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        let name = self.config.name.clone();
+
+        let fut = async move {
+            db_actor.send(DbShutdown).await??;
+            info!("Collection '{}' flushed and closed", name);
+            Ok(())
+        };
+
+        Box::pin(
+            actix::fut::wrap_future::<_, Self>(fut).map(|result, _act, ctx| {
+                ctx.stop();
+                result
+            }),
+        )
+    }
+}
+
+impl Handler<Reload> for CollectionActor {
+    // Atomic, so that other messages and file checks wait for the reload.
+    type Result = AtomicResponse<Self, Result<CollectionStats, ProjectError>>;
+
+    fn handle(&mut self, _msg: Reload, _ctx: &mut Context<Self>) -> Self::Result {
+        let busy = !self
+            .indexing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty();
+        if busy {
+            let name = self.config.name.clone();
+            return AtomicResponse::new(Box::pin(actix::fut::ready(Err(
+                ProjectError::CollectionBusy(name),
+            ))));
+        }
+
+        let db_actor = self.db_actor.clone();
+        let current = self.config.clone();
+        let fut = async move {
+            let mut config = CollectionConfig::from_dir(&current.dir())?;
+            config.data_dir = current.data_dir.clone();
+            if config.name != current.name {
+                return Err(ProjectError::Anyhow(anyhow!(
+                    "The collection on disk is named '{}' instead of '{}'",
+                    config.name,
+                    current.name
+                )));
+            }
+            // Queries are embedded with the models loaded for the collection.
+            if config.model() != current.model() || config.column_models != current.column_models {
+                let describe = |config: &CollectionConfig| {
+                    config
+                        .requested_models()
+                        .iter()
+                        .map(ModelRequest::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                return Err(ProjectError::Anyhow(anyhow!(
+                    "The collection on disk uses models {} instead of {}, restart the server to switch models",
+                    describe(&config),
+                    describe(&current)
+                )));
+            }
+
+            db_actor
+                .send(DbReload {
+                    config: config.clone(),
+                })
+                .await??;
+            let stats = db_actor.send(DbGetStats).await??;
+            Ok((config, stats))
+        };
+
+        AtomicResponse::new(Box::pin(actix::fut::wrap_future::<_, Self>(fut).map(
+            |result, act, _ctx| {
+                let (config, stats) = result?;
+                info!(
+                    "Collection '{}' reloaded from {}",
+                    config.name,
+                    config.dir().display()
+                );
+                act.loaded_files = files_modified(&config);
+                act.config = config;
+                Ok(stats)
+            },
+        )))
+    }
+}
+
+/// Latest modification time of the files a collection is loaded from.
+fn files_modified(config: &CollectionConfig) -> Option<SystemTime> {
+    let dir = config.dir();
+    let mut paths = vec![dir.join("config.json"), dir.join(config.db_path.as_str())];
+    for column in &config.index_columns {
+        let column_dir = dir.join(config.index_dir.as_str()).join(column);
+        if let Ok(entries) = std::fs::read_dir(column_dir) {
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path()),
+            );
+        }
+    }
+    paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+impl Handler<WatchFiles> for CollectionActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchFiles, ctx: &mut Context<Self>) -> Self::Result {
+        // Opening the database replays its write-ahead log into the file, so
+        // the files are only looked at once it is open.
+        let db_actor = self.db_actor.clone();
+        ctx.wait(
+            actix::fut::wrap_future::<_, Self>(async move { db_actor.send(DbGetRowCount).await })
+                .map(|_, act, _ctx| act.loaded_files = files_modified(&act.config)),
+        );
+        ctx.run_interval(msg.interval, |act, ctx| {
+            // Files change while documents are being indexed; the reload
+            // waits until that is done.
+            if !act
+                .indexing
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_empty()
+            {
+                return;
+            }
+            let modified = files_modified(&act.config);
+            if modified == act.loaded_files {
+                return;
+            }
+            // Not retried until the files change again, e.g. when a failed
+            // reload caught them half-copied.
+            act.loaded_files = modified;
+
+            let name = act.config.name.clone();
+            let addr = ctx.address();
+            actix::spawn(async move {
+                info!("Files of collection '{}' changed, reloading", name);
+                match addr.send(Reload).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("Failed to reload collection '{}': {}", name, e),
+                    Err(e) => warn!("Failed to reload collection '{}': {}", name, e),
+                }
+            });
+        });
+    }
+}
+
+impl Handler<GetConfig> for CollectionActor {
+    type Result = Result<CollectionConfig, ProjectError>;
+
+    fn handle(&mut self, _msg: GetConfig, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.config.clone())
+    }
+}
+
+impl Handler<SetCalibration> for CollectionActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: SetCalibration, _ctx: &mut Context<Self>) -> Self::Result {
+        self.config.calibration = Some(msg.calibration);
+        self.config.save()?;
+        Ok(())
+    }
+}
+
+/// Documents processed per batch when precomputing related documents.
+const RELATED_BATCH_SIZE: usize = 1024;
+
+impl Handler<UpdateRelated> for CollectionActor {
+    type Result = ResponseFuture<Result<usize, ProjectError>>;
+
+    fn handle(&mut self, msg: UpdateRelated, _ctx: &mut Context<Self>) -> Self::Result {
+        let column = msg.column;
+        if !self.config.index_columns.contains(&column) {
+            let name = self.config.name.clone();
+            return Box::pin(async move {
+                Err(ProjectError::Anyhow(anyhow!(
+                    "Column '{}' is not indexed in collection '{}'",
+                    column,
+                    name
+                )))
+            });
+        }
+
+        let previous = self.config.related_counts.get(&column).copied();
+        let count = match msg.count.or(previous) {
+            Some(count) => count,
+            None => return Box::pin(async { Ok(0) }),
+        };
+        // Lists of another length are recomputed from scratch.
+        let reset = previous.is_some_and(|previous| previous != count);
+        if previous != Some(count) {
+            self.config.related_counts.insert(column.clone(), count);
+            if let Err(e) = self.config.save() {
+                return Box::pin(async move { Err(ProjectError::Anyhow(e)) });
+            }
+        }
+
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            if reset {
+                db_actor
+                    .send(DbClearRelated {
+                        column: column.clone(),
+                    })
+                    .await??;
+            }
+
+            let keys = db_actor
+                .send(DbGetKeysWithoutRelated {
+                    column: column.clone(),
+                })
+                .await??;
+            if keys.is_empty() {
+                info!("Related documents on column '{}' are up to date", column);
+                return Ok(0);
+            }
+
+            info!(
+                "Computing {} related documents for {} documents on column '{}'",
+                count,
+                keys.len(),
+                column
+            );
+            let index = db_actor
+                .send(DbGetIndex {
+                    column: column.clone(),
+                })
+                .await??;
+            let start = Instant::now();
+            for batch in keys.chunks(RELATED_BATCH_SIZE) {
+                let batch = batch.to_vec();
+                let batch_index = index.clone();
+                let neighbours =
+                    tokio::task::spawn_blocking(move || batch_index.neighbours(&batch, count))
+                        .await??;
+                db_actor
+                    .send(DbStoreRelated {
+                        column: column.clone(),
+                        count,
+                        neighbours,
+                    })
+                    .await??;
+            }
+            info!(
+                "Related documents on column '{}' computed in {:?}",
+                column,
+                start.elapsed()
+            );
 
-/// Append rows from a JSONL file to an existing collection table.
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct AppendJsonl {
-    pub path: String,
+            Ok(keys.len())
+        })
+    }
 }
 
-/// Append rows from a Parquet file to an existing collection table.
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct AppendParquet {
-    pub path: String,
-}
+impl Handler<GetRelated> for CollectionActor {
+    type Result = ResponseFuture<Result<SearchResults, ProjectError>>;
+
+    fn handle(&mut self, msg: GetRelated, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.config.related_counts.contains_key(&msg.column) {
+            return Box::pin(async move {
+                Err(ProjectError::Anyhow(anyhow!(
+                    "Related documents are not computed for column '{}'",
+                    msg.column
+                )))
+            });
+        }
 
-/// Import a PDF document: convert to Markdown, optionally chunk it, and
-/// insert the resulting chunks into the named column of the collection table.
-#[derive(Message)]
-#[rtype(result = "Result<(), ProjectError>")]
-pub struct ImportPdf {
-    /// Path to the PDF file.
-    pub path: String,
-    /// Target column name (e.g. `"text"`).
-    pub column: String,
-    /// Optional chunker configuration.  When `None` the full Markdown string
-    /// is inserted as a single row.
-    pub chunker_config: Option<ChunkerConfig>,
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbGetRelated {
+                    column: msg.column,
+                    key: msg.key,
+                    limit: msg.limit as usize,
+                    groups: msg.groups,
+                })
+                .await?
+        })
+    }
 }
 
-// ---- Message Handlers ----
+impl Handler<Similar> for CollectionActor {
+    type Result = ResponseFuture<Result<SearchResults, ProjectError>>;
 
-impl Handler<ImportJsonl> for CollectionActor {
-    type Result = ResponseFuture<Result<(), ProjectError>>;
+    fn handle(&mut self, msg: Similar, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.config.index_columns.contains(&msg.column) {
+            return Box::pin(async move { Err(ProjectError::IndexNotFound(msg.column)) });
+        }
 
-    fn handle(&mut self, msg: ImportJsonl, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
+        let addr = ctx.address();
+        // Chunks of the document would otherwise fill the results with itself.
+        let group_by = self.config.chunking.as_ref().map(|_| GroupBy::Max);
         Box::pin(async move {
-            db_actor.send(DbImportJsonl { path: msg.path }).await??;
-            Ok(())
+            // Neighbours of a document tell about it, so they are only for
+            // callers who may see it.
+            db_actor
+                .send(DbCheckVisible {
+                    key: msg.key,
+                    groups: msg.groups.clone(),
+                })
+                .await??;
+            let index = db_actor
+                .send(DbGetIndex {
+                    column: msg.column.clone(),
+                })
+                .await??;
+            let vector = index.get(msg.key)?.ok_or_else(|| {
+                ProjectError::Anyhow(anyhow!(
+                    "Document with key {} has no vector in column '{}'",
+                    msg.key,
+                    msg.column
+                ))
+            })?;
+
+            // The document is its own nearest neighbour, so one more result
+            // is searched and the page is cut after removing it.
+            let mut results = addr
+                .send(Search {
+                    columns: vec![msg.column],
+                    query: String::new(),
+                    vector: Some(vector),
+                    limit: msg.offset.saturating_add(msg.limit).saturating_add(1),
+                    offset: 0,
+                    score_type: msg.score_type,
+                    // Not used, as the query vector is given.
+                    model_id: 0,
+                    column_model_ids: HashMap::new(),
+                    groups: msg.groups,
+                    filter: None,
+                    geo_filter: None,
+                    group_by,
+                    timeout: None,
+                    threshold: ScoreThreshold::default(),
+                    skip_content: false,
+                    span: Span::current(),
+                })
+                .await??;
+            results.results = results
+                .results
+                .into_iter()
+                .filter(|result| result.key != msg.key)
+                .skip(msg.offset as usize)
+                .take(msg.limit as usize)
+                .collect();
+            Ok(results)
         })
     }
 }
 
-impl Handler<ImportParquet> for CollectionActor {
+impl Handler<RecordQuery> for CollectionActor {
     type Result = ResponseFuture<Result<(), ProjectError>>;
 
-    fn handle(&mut self, msg: ImportParquet, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: RecordQuery, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
-        Box::pin(async move {
-            db_actor.send(DbImportParquet { path: msg.path }).await??;
-            Ok(())
-        })
+        Box::pin(async move { db_actor.send(DbRecordQuery { query: msg.query }).await? })
     }
 }
 
-impl Handler<AppendJsonl> for CollectionActor {
-    type Result = ResponseFuture<Result<(), ProjectError>>;
+impl Handler<BuildSuggestions> for CollectionActor {
+    type Result = ResponseFuture<Result<(usize, usize), ProjectError>>;
 
-    fn handle(&mut self, msg: AppendJsonl, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: BuildSuggestions, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
         Box::pin(async move {
-            db_actor.send(DbAppendJsonl { path: msg.path }).await??;
-            Ok(())
+            let start = Instant::now();
+            let (phrases, terms) = db_actor
+                .send(DbBuildSuggestions {
+                    column: msg.column.clone(),
+                    max_words: msg.max_words,
+                    min_documents: msg.min_documents,
+                })
+                .await??;
+            info!(
+                "Extracted {} suggested phrases and {} words from column '{}' in {:?}",
+                phrases,
+                terms,
+                msg.column,
+                start.elapsed()
+            );
+            Ok((phrases, terms))
         })
     }
 }
 
-impl Handler<AppendParquet> for CollectionActor {
-    type Result = ResponseFuture<Result<(), ProjectError>>;
+impl Handler<Suggest> for CollectionActor {
+    type Result = ResponseFuture<Result<Vec<Suggestion>, ProjectError>>;
 
-    fn handle(&mut self, msg: AppendParquet, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: Suggest, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
         Box::pin(async move {
-            db_actor.send(DbAppendParquet { path: msg.path }).await??;
-            Ok(())
+            db_actor
+                .send(DbSuggest {
+                    prefix: msg.prefix,
+                    limit: msg.limit as usize,
+                    groups: msg.groups,
+                })
+                .await?
         })
     }
 }
 
-impl Handler<ImportPdf> for CollectionActor {
-    type Result = ResponseFuture<Result<(), ProjectError>>;
+impl Handler<CompletePrefix> for CollectionActor {
+    type Result = ResponseFuture<Result<Vec<Suggestion>, ProjectError>>;
 
-    fn handle(&mut self, msg: ImportPdf, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: CompletePrefix, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
-
         Box::pin(async move {
-            let path = msg.path.clone();
-            let column = msg.column.clone();
-            let cfg = msg.chunker_config.clone();
-
-            // PDF conversion is CPU/IO-bound — run it on a blocking thread.
-            let chunks: Vec<String> = tokio::task::spawn_blocking(move || {
-                let markdown = crate::pdf::pdf_to_markdown(&path)?;
-                if let Some(chunker_cfg) = cfg {
-                    let chunker = crate::chunker::MarkdownChunker::new(chunker_cfg)?;
-                    anyhow::Ok(chunker.chunk(&markdown))
-                } else {
-                    anyhow::Ok(vec![markdown])
-                }
-            })
-            .await
-            .map_err(ProjectError::JoinError)??;
-
             db_actor
-                .send(DbImportMarkdownChunks { chunks, column })
-                .await??;
-            Ok(())
+                .send(DbCompletePrefix {
+                    prefix: msg.prefix,
+                    limit: msg.limit as usize,
+                })
+                .await?
         })
     }
 }
 
-impl Handler<GetConfig> for CollectionActor {
-    type Result = Result<CollectionConfig, ProjectError>;
+impl Handler<CorrectSpelling> for CollectionActor {
+    type Result = ResponseFuture<Result<Vec<Correction>, ProjectError>>;
 
-    fn handle(&mut self, _msg: GetConfig, _ctx: &mut Context<Self>) -> Self::Result {
-        Ok(self.config.clone())
+    fn handle(&mut self, msg: CorrectSpelling, _ctx: &mut Context<Self>) -> Self::Result {
+        let db_actor = self.db_actor.clone();
+        Box::pin(async move {
+            db_actor
+                .send(DbCorrectSpelling {
+                    query: msg.query,
+                    limit: msg.limit as usize,
+                })
+                .await?
+        })
     }
 }
 
 impl Handler<EmbedColumn> for CollectionActor {
-    type Result = ResponseFuture<Result<(), ProjectError>>;
+    type Result = ResponseFuture<Result<IndexReport, ProjectError>>;
 
     fn handle(&mut self, msg: EmbedColumn, _ctx: &mut Context<Self>) -> Self::Result {
-        let db_actor = self.db_actor.clone();
-        let model_manager = self.model_manager.clone();
-
-        Box::pin(async move {
-            let column_name = msg.name;
-            let batch_size = msg.batch_size;
-            let model_id = msg.model_id;
-
-            let count = db_actor
-                .send(DbGetRowCount {
-                    column: column_name.clone(),
-                })
-                .await??;
+        let target = msg.name.clone();
+        Box::pin(embed_column(
+            self.db_actor.clone(),
+            self.model_manager.clone(),
+            self.indexing.clone(),
+            self.config.clone(),
+            msg,
+            target,
+            IndexOverrides::default(),
+        ))
+    }
+}
 
-            let has_index = db_actor
-                .send(DbCheckIndex {
-                    column: column_name.clone(),
-                })
-                .await??;
+impl Handler<Reindex> for CollectionActor {
+    // Atomic, so that documents are not added while the indexes are rebuilt.
+    type Result = AtomicResponse<Self, Result<Vec<(String, IndexReport)>, ProjectError>>;
 
-            if !has_index {
-                let (vector_dim, output_dtype) = model_manager
-                    .send(GetModelMetadata { id: model_id })
-                    .await??;
+    fn handle(&mut self, msg: Reindex, _ctx: &mut Context<Self>) -> Self::Result {
+        let fail = |e: ProjectError| AtomicResponse::new(Box::pin(actix::fut::ready(Err(e))));
+        if let Some(column) = msg
+            .columns
+            .iter()
+            .find(|column| !self.config.index_columns.contains(column))
+        {
+            return fail(ProjectError::Anyhow(anyhow!(
+                "Column '{}' is not indexed in collection '{}'",
+                column,
+                self.config.name
+            )));
+        }
+        if let Some(column) = msg.embed.as_ref().and_then(|embed| {
+            msg.columns
+                .iter()
+                .find(|column| !embed.models.contains_key(*column))
+        }) {
+            return fail(ProjectError::Anyhow(anyhow!(
+                "No model given to embed column '{}'",
+                column
+            )));
+        }
 
-                let scalar_kind = match output_dtype {
-                    ModelOutputDType::F32 => ScalarKind::F32,
-                    ModelOutputDType::F16 => ScalarKind::F16,
-                    ModelOutputDType::Int8 => ScalarKind::I8,
+        let db_actor = self.db_actor.clone();
+        let model_manager = self.model_manager.clone();
+        let indexing = self.indexing.clone();
+        let config = self.config.clone();
+        let columns = msg.columns.clone();
+        let embed = msg.embed.clone();
+        let overrides = msg.overrides;
+        let fut = async move {
+            let mut reports = Vec::new();
+            for column in columns {
+                let staged = format!("{}{}", column, STAGED_SUFFIX);
+                let start = Instant::now();
+                let report = match &embed {
+                    Some(embed) => {
+                        embed_column(
+                            db_actor.clone(),
+                            model_manager.clone(),
+                            indexing.clone(),
+                            config.clone(),
+                            EmbedColumn {
+                                name: column.clone(),
+                                batch_size: embed.batch_size,
+                                model_id: embed.models[&column].0,
+                                skip_errors: embed.skip_errors,
+                                concurrency: embed.concurrency,
+                            },
+                            staged.clone(),
+                            overrides,
+                        )
+                        .await?
+                    }
+                    None => IndexReport {
+                        indexed: db_actor
+                            .send(DbRebuildIndex {
+                                column: column.clone(),
+                                staged: staged.clone(),
+                                overrides,
+                            })
+                            .await??,
+                        ..IndexReport::default()
+                    },
                 };
-
                 db_actor
-                    .send(DbInitIndex {
-                        column: column_name.clone(),
-                        dimensions: vector_dim as usize,
-                        quantization: scalar_kind,
+                    .send(DbSwapIndex {
+                        column: column.clone(),
+                        staged,
+                        move_rows: embed.is_some(),
                     })
                     .await??;
+                info!(
+                    "Rebuilt the index of column '{}' in {:?}",
+                    column,
+                    start.elapsed()
+                );
+                reports.push((column, report));
             }
+            Ok::<_, ProjectError>(reports)
+        };
 
-            // For incremental indexing: skip rows that are already indexed.
-            let already_indexed = db_actor
-                .send(DbGetIndexedCount {
-                    column: column_name.clone(),
-                })
-                .await??;
-            let start_offset = already_indexed;
-            let remaining = count.saturating_sub(start_offset);
-            let num_batches = (remaining + batch_size - 1) / batch_size;
-
-            info!(
-                "Starting to index {} new records from column '{}' in batches of {} (skipping {} already indexed)",
-                remaining, column_name, batch_size, start_offset
-            );
-
-            if remaining == 0 {
-                info!("Column '{}' is already fully indexed", column_name);
-                return Ok(());
-            }
+        AtomicResponse::new(Box::pin(actix::fut::wrap_future::<_, Self>(fut).map(
+            move |result, act, _ctx| {
+                let reports = result?;
+                if let Some(embed) = msg.embed {
+                    let models = embed
+                        .models
+                        .into_iter()
+                        .map(|(column, (_, model))| (column, model))
+                        .collect();
+                    act.config.set_column_models(&models);
+                }
+                // Recorded so that indexes built later use the same metric.
+                if let Some(metric) = msg.overrides.metric {
+                    for (column, _) in &reports {
+                        act.config.column_metrics.insert(column.clone(), metric);
+                    }
+                }
+                if msg.rescore.is_some() {
+                    act.config.rescore = msg.rescore;
+                }
+                act.config.save()?;
+                Ok(reports)
+            },
+        )))
+    }
+}
 
-            let start = Instant::now();
+/// Embed the rows of the column `msg.name` that are not indexed yet into the
+/// index named `target`, which is the column's own index except when it is
+/// rebuilt. A missing index is created with `overrides` applied.
+async fn embed_column(
+    db_actor: Addr<CollectionDbActor>,
+    model_manager: Addr<ModelManagerActor>,
+    indexing: Arc<Mutex<HashSet<String>>>,
+    config: CollectionConfig,
+    msg: EmbedColumn,
+    target: String,
+    overrides: IndexOverrides,
+) -> Result<IndexReport, ProjectError> {
+    let column_name = target;
+    let source_column = msg.name;
+    let batch_size = msg.batch_size;
+    let model_id = msg.model_id;
+
+    // Languages are detected before embedding, which every import ends with.
+    if let Some(languages) = &config.languages {
+        let detected = db_actor
+            .send(DbDetectLanguages {
+                column: languages.column.clone(),
+            })
+            .await??;
+        if detected > 0 {
+            info!("Detected the language of {} documents", detected);
+        }
+    }
+    // So is the prefix index, which completes the values of the documents
+    // just imported.
+    if let Some(values) = db_actor.send(DbBuildPrefixIndex).await?? {
+        info!("Rebuilt the prefix index with {} values", values);
+    }
 
-            for batch in 0..num_batches {
-                let elapsed = start.elapsed();
-                let steps_completed = batch as f64;
-                let total_steps = num_batches as f64;
-                let eta = if steps_completed > 0.0 {
-                    elapsed.mul_f64((total_steps - steps_completed) / steps_completed)
-                } else {
-                    Duration::ZERO
-                };
+    let count = db_actor.send(DbGetRowCount).await??;
 
-                print!("\r{} / {} batches - ETA: {:?}", batch, total_steps, eta);
-                let _ = std::io::Write::flush(&mut std::io::stdout());
+    let has_index = db_actor
+        .send(DbCheckIndex {
+            column: column_name.clone(),
+        })
+        .await??;
+
+    let embedding_dim = config.embedding_dim;
+    if !has_index {
+        let (vector_dim, output_dtype) = model_manager
+            .send(GetModelMetadata { id: model_id })
+            .await??;
+        let vector_dim = match embedding_dim {
+            Some(dim) if dim == 0 || dim as i64 > vector_dim => {
+                return Err(ProjectError::Anyhow(anyhow!(
+                    "Cannot truncate the {} dimensions of the model of column '{}' to {}",
+                    vector_dim,
+                    source_column,
+                    dim
+                )));
+            }
+            Some(dim) => dim as i64,
+            None => vector_dim,
+        };
 
-                let offset = start_offset + batch * batch_size;
+        let model_metric = model_manager
+            .send(GetModelMetric { id: model_id })
+            .await??;
+
+        db_actor
+            .send(DbInitIndex {
+                column: column_name.clone(),
+                dimensions: vector_dim as usize,
+                dtype: overrides
+                    .dtype
+                    .unwrap_or_else(|| VectorDType::from(output_dtype)),
+                metric: overrides
+                    .metric
+                    .unwrap_or_else(|| config.index_metric(&source_column, model_metric)),
+                rows: count,
+                connectivity: overrides.connectivity,
+                expansion_add: overrides.expansion_add,
+            })
+            .await??;
+    }
 
-                let (texts, keys) = db_actor
-                    .send(DbGetBatch {
-                        column: column_name.clone(),
-                        batch_size,
-                        offset,
-                    })
-                    .await??;
+    // For incremental indexing: skip rows that are already indexed.
+    let already_indexed = db_actor
+        .send(DbGetIndexedCount {
+            column: column_name.clone(),
+        })
+        .await??;
+    let start_offset = already_indexed;
+    let remaining = count.saturating_sub(start_offset);
+    let num_batches = remaining.div_ceil(batch_size);
+
+    info!(
+        "Starting to index {} new records from column '{}' in batches of {} (skipping {} already indexed)",
+        remaining, source_column, batch_size, start_offset
+    );
+
+    let mut report = IndexReport::default();
+    if remaining == 0 {
+        info!("Column '{}' is already fully indexed", source_column);
+        return Ok(report);
+    }
 
-                if texts.is_empty() {
-                    break;
+    let index = db_actor
+        .send(DbGetIndex {
+            column: column_name.clone(),
+        })
+        .await??;
+    let _indexing = IndexingGuard::new(&indexing, &column_name);
+    let start = Instant::now();
+
+    // Indexing is pipelined: a reader task fetches batches ahead,
+    // up to `concurrency` batches are embedded at the same time, and
+    // their vectors are added in order, so that an interrupted run
+    // resumes at the right offset. Rows added while indexing are
+    // left to the next run, so the totals stay those counted up front.
+    let concurrency = msg.concurrency.max(1);
+    let (mut batch_tx, batch_rx) = mpsc::channel(concurrency);
+    let reader_db = db_actor.clone();
+    // Image columns hold the locations of images, embedded as they are.
+    let images = config
+        .image_columns
+        .contains(&source_column)
+        .then(|| ImageReader::new(config.image_root.as_deref(), &config.image_url_hosts))
+        .transpose()?;
+    let reader_column = source_column;
+    let normalization = if images.is_some() {
+        Normalization::default()
+    } else {
+        config.normalization.clone().unwrap_or_default()
+    };
+    let mut prompts = config.prompts.or(&model_manager
+        .send(GetModelPrompts { id: model_id })
+        .await??);
+    if images.is_some() {
+        prompts.document = None;
+        info!("Embedding the images located in column '{}'", reader_column);
+    }
+    if let Some(prompt) = &prompts.document {
+        info!("Documents are embedded with the prompt '{}'", prompt);
+    }
+    let chunker = match config.chunking.clone().filter(|_| images.is_none()) {
+        Some(chunking) => {
+            let tokenizer = model_manager.send(GetTokenizer { id: model_id }).await??;
+            if tokenizer.is_none() {
+                warn!("The model has no local tokenizer, chunks are counted in words");
+            }
+            info!("Splitting documents into chunks of {}", chunking);
+            Some(TextChunker::new(chunking, tokenizer))
+        }
+        None => None,
+    };
+    let chunked = chunker.is_some();
+    // Chunk embeddings are kept with the chunks already.
+    let store_embeddings = config.store_embeddings && !chunked;
+    actix::spawn(async move {
+        for batch in 0..num_batches {
+            let offset = start_offset + batch * batch_size;
+            let result = match reader_db
+                .send(DbGetBatch {
+                    column: reader_column.clone(),
+                    batch_size,
+                    offset,
+                })
+                .await
+            {
+                Ok(result) => result.map(|mut batch| {
+                    // Chunks are cut from the original text, so that
+                    // their offsets point into the stored document.
+                    if let Some(chunker) = &chunker {
+                        batch = batch.into_chunks(chunker);
+                    }
+                    batch.texts = normalization.apply_all(batch.texts);
+                    if prompts.document.is_some() {
+                        batch.texts = batch.texts.iter().map(|t| prompts.document(t)).collect();
+                    }
+                    batch
+                }),
+                Err(e) => Err(e.into()),
+            };
+            let done = match &result {
+                Ok(batch) => batch.texts.is_empty() && batch.null_keys.is_empty(),
+                Err(_) => true,
+            };
+            if batch_tx.send((offset, result)).await.is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let mut embedded = batch_rx
+        .map(
+            |(offset, batch): (u64, Result<ColumnBatch, ProjectError>)| {
+                let model_manager = model_manager.clone();
+                let images = images.clone();
+                async move {
+                    let batch = batch?;
+                    let embeddings = if batch.texts.is_empty() {
+                        None
+                    } else {
+                        Some(
+                            embed_batch(
+                                &model_manager,
+                                model_id,
+                                embedding_dim,
+                                batch.texts.clone(),
+                                images.as_ref(),
+                            )
+                            .await,
+                        )
+                    };
+                    Ok::<_, ProjectError>((offset, batch, embeddings))
                 }
+            },
+        )
+        .buffered(concurrency);
+
+    let mut rows_read = 0;
+    let mut batches_done = 0;
+    while let Some(item) = embedded.next().await {
+        print_progress(
+            rows_read,
+            remaining,
+            batches_done,
+            num_batches,
+            start.elapsed(),
+        );
+        let (offset, batch, embeddings) = item?;
+        let ColumnBatch {
+            texts,
+            keys,
+            null_keys,
+            chunks,
+        } = batch;
+
+        if texts.is_empty() && null_keys.is_empty() {
+            break;
+        }
+        rows_read += document_count(&keys) + null_keys.len() as u64;
+        batches_done += 1;
+        if !null_keys.is_empty() {
+            // Recorded like skipped rows, so that incremental
+            // indexing still resumes at the right offset.
+            report.nulls += null_keys.len() as u64;
+            db_actor
+                .send(DbRecordSkipped {
+                    column: column_name.clone(),
+                    rows: null_keys
+                        .into_iter()
+                        .map(|key| SkippedRow {
+                            key,
+                            error: String::from(NULL_VALUE),
+                        })
+                        .collect(),
+                })
+                .await??;
+        }
+        let embeddings = match embeddings {
+            Some(embeddings) => embeddings,
+            None => continue,
+        };
 
-                let embeddings = model_manager
-                    .send(Predict {
-                        id: model_id,
-                        texts,
-                    })
-                    .await??;
-
+        let result = match embeddings {
+            Ok(embeddings) => add_batch(&index, keys.clone(), embeddings.clone())
+                .await
+                .map(|()| embeddings),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(embeddings) => {
+                report.indexed += document_count(&keys);
+                if chunked {
+                    db_actor
+                        .send(DbStoreChunks {
+                            column: column_name.clone(),
+                            chunks: chunk_rows(&keys, chunks, &embeddings),
+                        })
+                        .await??;
+                }
+                if store_embeddings {
+                    db_actor
+                        .send(DbStoreEmbeddings {
+                            column: column_name.clone(),
+                            embeddings: keys
+                                .iter()
+                                .copied()
+                                .zip(embeddings.to_f32_rows())
+                                .collect(),
+                        })
+                        .await??;
+                }
+            }
+            Err(e) if !msg.skip_errors => return Err(e),
+            Err(e) => {
+                warn!(
+                    "Batch at offset {} failed, retrying row by row: {}",
+                    offset, e
+                );
+                // Drop whatever part of the batch made it into the index.
+                index.remove(&keys).map_err(ProjectError::VectorIndex)?;
+
+                let batch_len = keys.len();
+                let mut skipped = Vec::new();
+                let mut indexed_keys = Vec::new();
+                let mut indexed_chunks = Vec::new();
+                let mut indexed_embeddings = Vec::new();
+                let mut chunks = chunks.into_iter();
+                for (text, key) in texts.into_iter().zip(keys) {
+                    let chunk = chunks.next();
+                    match index_batch(
+                        &model_manager,
+                        &index,
+                        model_id,
+                        embedding_dim,
+                        vec![text],
+                        vec![key],
+                        images.as_ref(),
+                    )
+                    .await
+                    {
+                        Ok(embeddings) => {
+                            indexed_keys.push(key);
+                            if store_embeddings {
+                                indexed_embeddings
+                                    .extend(embeddings.to_f32_rows().into_iter().map(|e| (key, e)));
+                            }
+                            indexed_chunks.extend(chunk.map(|chunk| (key, chunk, embeddings)));
+                        }
+                        Err(e) => skipped.push(SkippedRow {
+                            key,
+                            error: e.to_string(),
+                        }),
+                    }
+                }
+                report.indexed += document_count(&indexed_keys);
+                if !indexed_chunks.is_empty() {
+                    db_actor
+                        .send(DbStoreChunks {
+                            column: column_name.clone(),
+                            chunks: indexed_chunks
+                                .into_iter()
+                                .flat_map(|(key, chunk, embeddings)| {
+                                    chunk_rows(&[key], vec![chunk], &embeddings)
+                                })
+                                .collect(),
+                        })
+                        .await??;
+                }
+                if !indexed_embeddings.is_empty() {
+                    db_actor
+                        .send(DbStoreEmbeddings {
+                            column: column_name.clone(),
+                            embeddings: indexed_embeddings,
+                        })
+                        .await??;
+                }
+                // A failure of every row points at the model or the
+                // embedding service rather than at the data.
+                if batch_len > 1 && skipped.len() == batch_len {
+                    return Err(e);
+                }
+                for row in &skipped {
+                    warn!("Skipping row with _key {}: {}", row.key, row.error);
+                }
                 db_actor
-                    .send(DbAddEmbeddings {
+                    .send(DbRecordSkipped {
                         column: column_name.clone(),
-                        keys,
-                        embeddings,
+                        rows: skipped.clone(),
                     })
                     .await??;
+                report.skipped.extend(skipped);
             }
-
+        }
+        db_actor
+            .send(DbMarkIndexDirty {
+                column: column_name.clone(),
+            })
+            .await?;
+        // Saving empties the journal, which would otherwise grow to hold
+        // every vector of a large column.
+        if index.journal_bytes() > JOURNAL_CHECKPOINT_BYTES {
             db_actor
                 .send(DbSaveIndex {
                     column: column_name.clone(),
                 })
                 .await??;
+        }
+    }
 
-            println!("");
-            info!("Total duration: {:?}", start.elapsed());
-
-            Ok(())
+    print_progress(
+        rows_read,
+        remaining,
+        batches_done,
+        num_batches,
+        start.elapsed(),
+    );
+    println!();
+
+    db_actor
+        .send(DbSaveIndex {
+            column: column_name.clone(),
         })
-    }
+        .await??;
+
+    info!("Total duration: {:?}", start.elapsed());
+
+    Ok(report)
 }
 
 impl Handler<Search> for CollectionActor {
-    type Result = ResponseFuture<Result<Vec<SearchResult>, ProjectError>>;
+    type Result = ResponseFuture<Result<SearchResults, ProjectError>>;
 
     fn handle(&mut self, msg: Search, _ctx: &mut Context<Self>) -> Self::Result {
         let db_actor = self.db_actor.clone();
         let model_manager = self.model_manager.clone();
+        let indexing = self.indexing.clone();
+        let normalization = self.config.normalization.clone();
+        let embedding_dim = self.config.embedding_dim;
+        let mut prompts = self.config.prompts.clone();
+        let chunked = self.config.chunking.is_some();
+        // The query's language picks its prompt, and is reported so that
+        // clients can filter on it.
+        let query_language = match &self.config.languages {
+            Some(languages) if msg.vector.is_none() => {
+                let language = detect_language(&msg.query);
+                if let Some(prompt) = language.and_then(|lang| languages.query_prompts.get(lang)) {
+                    prompts.query = Some(prompt.clone());
+                }
+                language.map(String::from)
+            }
+            _ => None,
+        };
+        if let Some(filter) = &msg.filter {
+            if let Err(e) = check_filterable(&self.config, filter) {
+                return Box::pin(async move { Err(e) });
+            }
+        }
+        if msg.geo_filter.is_some() && self.config.geo_columns.is_none() {
+            let name = self.config.name.clone();
+            return Box::pin(async move { Err(ProjectError::NoGeoColumns(name)) });
+        }
 
-        Box::pin(async move {
-            let query_embedding = model_manager
-                .send(Predict {
-                    id: msg.model_id,
-                    texts: vec![msg.query],
-                })
-                .await??;
+        let span = info_span!(parent: &msg.span, "collection_search", columns = msg.columns.len());
 
-            let search_results = db_actor
-                .send(DbSearchAndFetch {
-                    column: msg.column,
-                    query_embedding,
-                    limit: msg.limit as usize,
+        let search = async move {
+            let timeout = msg.timeout;
+            let grouped = msg.group_by.is_some();
+            let score_type = msg.score_type;
+            let skip_content = msg.skip_content;
+            let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout.timeout);
+
+            // Checked up front: an index that finishes growing mid-search may
+            // still have served results from before the last batches.
+            let growing = {
+                let indexing = indexing.lock().unwrap_or_else(|e| e.into_inner());
+                msg.columns.iter().any(|column| indexing.contains(column))
+            };
+
+            // Ranking, from embedding the query to the vector search, has to
+            // finish in time; reading the contents may be skipped.
+            let ranking_db = db_actor.clone();
+            let ranked = async move {
+                let db_actor = ranking_db;
+                // Scores of different metrics are not comparable, so merged
+                // columns must share one.
+                let mut metric = None;
+                for column in &msg.columns {
+                    let index = db_actor
+                        .send(DbGetIndex {
+                            column: column.clone(),
+                        })
+                        .await??;
+                    if let Some(vector) = &msg.vector {
+                        if vector.len() != index.dimensions() {
+                            return Err(ProjectError::InvalidVector {
+                                column: column.clone(),
+                                given: vector.len(),
+                                expected: index.dimensions(),
+                            });
+                        }
+                    }
+                    let column_metric = index.metric();
+                    match metric {
+                        Some(metric) if metric != column_metric => {
+                            return Err(ProjectError::MixedMetrics(metric, column_metric));
+                        }
+                        _ => metric = Some(column_metric),
+                    }
+                }
+
+                // The query is embedded once per model and reused for every
+                // column embedded with it.
+                let mut query_embeddings: HashMap<String, Embeddings> = HashMap::new();
+                match msg.vector {
+                    Some(vector) => {
+                        let vector = Array2::from_shape_vec((1, vector.len()), vector)
+                            .map_err(|e| ProjectError::Anyhow(e.into()))?;
+                        let embedding = Embeddings::F32(Arc::new(vector));
+                        for column in &msg.columns {
+                            query_embeddings.insert(column.clone(), embedding.clone());
+                        }
+                    }
+                    None => {
+                        let query = match &normalization {
+                            Some(normalization) => normalization.apply(&msg.query),
+                            None => msg.query,
+                        };
+                        let mut by_model: HashMap<u32, Embeddings> = HashMap::new();
+                        for column in &msg.columns {
+                            let model_id = msg
+                                .column_model_ids
+                                .get(column)
+                                .copied()
+                                .unwrap_or(msg.model_id);
+                            let embedding = match by_model.get(&model_id) {
+                                Some(embedding) => embedding.clone(),
+                                None => {
+                                    let model_prompts = model_manager
+                                        .send(GetModelPrompts { id: model_id })
+                                        .await??;
+                                    let query = prompts.or(&model_prompts).query(&query);
+                                    let embedding = model_manager
+                                        .send(Predict {
+                                            id: model_id,
+                                            texts: vec![query],
+                                            span: Span::current(),
+                                        })
+                                        .instrument(info_span!("embed_query", model = model_id))
+                                        .await??;
+                                    let embedding = truncated(embedding, embedding_dim)?;
+                                    by_model.insert(model_id, embedding.clone());
+                                    embedding
+                                }
+                            };
+                            query_embeddings.insert(column.clone(), embedding);
+                        }
+                    }
+                }
+
+                let limit = msg.limit as usize;
+                let offset = msg.offset as usize;
+                let metric = metric.unwrap_or_default();
+                let hits = if let [column] = msg.columns.as_slice() {
+                    db_actor
+                        .send(DbSearchIndex {
+                            column: column.clone(),
+                            query_embedding: query_embeddings[column].clone(),
+                            limit,
+                            offset,
+                            groups: msg.groups,
+                            filter: msg.filter,
+                            geo_filter: msg.geo_filter,
+                            group_by: msg.group_by,
+                            threshold: msg.threshold,
+                        })
+                        .instrument(info_span!("vector_search", column = %column))
+                        .await??
+                        .into_iter()
+                        .map(|neighbour| (column.clone(), neighbour))
+                        .collect::<Vec<_>>()
+                } else {
+                    // Every column contributes its top `offset + limit` hits,
+                    // which is enough to fill the requested page after merging.
+                    let mut best: HashMap<u64, (String, Neighbour)> = HashMap::new();
+                    for column in msg.columns {
+                        let neighbours = db_actor
+                            .send(DbSearchIndex {
+                                column: column.clone(),
+                                query_embedding: query_embeddings[&column].clone(),
+                                limit: offset + limit,
+                                offset: 0,
+                                groups: msg.groups.clone(),
+                                filter: msg.filter.clone(),
+                                geo_filter: msg.geo_filter,
+                                group_by: msg.group_by,
+                                threshold: msg.threshold,
+                            })
+                            .instrument(info_span!("vector_search", column = %column))
+                            .await??;
+                        for neighbour in neighbours {
+                            match best.get(&neighbour.key) {
+                                Some((_, existing)) if existing.distance <= neighbour.distance => {}
+                                _ => {
+                                    best.insert(neighbour.key, (column.clone(), neighbour));
+                                }
+                            }
+                        }
+                    }
+
+                    let mut merged: Vec<(String, Neighbour)> = best.into_values().collect();
+                    merged.sort_by(|(_, a), (_, b)| {
+                        a.distance.total_cmp(&b.distance).then(a.key.cmp(&b.key))
+                    });
+                    merged.into_iter().skip(offset).take(limit).collect()
+                };
+                Ok((metric, hits, query_embeddings))
+            };
+            let (metric, hits, query_embeddings) = match with_deadline(deadline, ranked).await {
+                Some(result) => result?,
+                None => return Err(ProjectError::Timeout(timeout.unwrap_or_default().timeout)),
+            };
+
+            // Read the contents of the page, one query per column.
+            let fetched = with_deadline(deadline, async {
+                let mut contents: HashMap<(String, u64), String> = HashMap::new();
+                let mut columns: Vec<&String> = hits.iter().map(|(column, _)| column).collect();
+                columns.sort();
+                columns.dedup();
+                if skip_content {
+                    columns.clear();
+                }
+                for column in columns {
+                    let keys = hits
+                        .iter()
+                        .filter(|(hit_column, _)| hit_column == column)
+                        .map(|(_, neighbour)| neighbour.key)
+                        .collect();
+                    let column_contents = db_actor
+                        .send(DbFetchContents {
+                            column: column.clone(),
+                            keys,
+                        })
+                        .await??;
+                    contents.extend(
+                        column_contents
+                            .into_iter()
+                            .map(|(key, content)| ((column.clone(), key), content)),
+                    );
+                }
+
+                // The chunk of each hit, matched per column as well.
+                let mut chunks = vec![None; hits.len()];
+                if chunked {
+                    let mut columns: Vec<&String> = hits.iter().map(|(column, _)| column).collect();
+                    columns.sort();
+                    columns.dedup();
+                    for column in columns {
+                        let positions: Vec<usize> =
+                            (0..hits.len()).filter(|&i| &hits[i].0 == column).collect();
+                        let matched = db_actor
+                            .send(DbMatchChunks {
+                                column: column.clone(),
+                                query_embedding: query_embeddings[column].clone(),
+                                hits: positions.iter().map(|&i| hits[i].1).collect(),
+                                grouped,
+                            })
+                            .await??;
+                        for (i, chunk) in positions.into_iter().zip(matched) {
+                            chunks[i] = chunk;
+                        }
+                    }
+                }
+                Ok::<_, ProjectError>((contents, chunks))
+            })
+            .instrument(info_span!("fetch_contents", hits = hits.len()))
+            .await;
+            let (contents, chunks) = match fetched {
+                Some(fetched) => {
+                    let (contents, chunks) = fetched?;
+                    (Some(contents), chunks)
+                }
+                None if timeout.is_some_and(|timeout| timeout.partial_ok) => {
+                    (None, vec![None; hits.len()])
+                }
+                None => return Err(ProjectError::Timeout(timeout.unwrap_or_default().timeout)),
+            };
+
+            let partial = contents.is_none();
+            let results = hits
+                .into_iter()
+                .zip(chunks)
+                .filter_map(|((column, neighbour), chunk)| {
+                    // A vector whose row no longer exists is skipped instead of
+                    // shifting every following result.
+                    let content = match &contents {
+                        Some(contents) if !skip_content => {
+                            contents.get(&(column.clone(), neighbour.key))?.clone()
+                        }
+                        _ => String::new(),
+                    };
+                    Some(SearchResult {
+                        content,
+                        key: neighbour.key,
+                        score: score_type.score(metric, neighbour.distance),
+                        column,
+                        chunk,
+                        snippet: None,
+                    })
                 })
-                .await??;
+                .collect();
+
+            Ok(SearchResults {
+                results,
+                metric,
+                score_type,
+                indexing: growing,
+                partial,
+                query_language,
+            })
+        };
+        Box::pin(search.instrument(span))
+    }
+}
 
-            Ok(search_results)
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray as ArrowStringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::ipc::writer::{FileWriter, StreamWriter};
+    use std::sync::Arc;
+
+    fn batch(schema: &Arc<Schema>, ids: Vec<i64>, texts: Vec<&str>) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(ArrowStringArray::from(texts)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read_arrow_ipc() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("text", DataType::Utf8, false),
+        ]));
+        let first = batch(&schema, vec![1, 2], vec!["a", "b"]);
+        let second = batch(&schema, vec![3], vec!["c"]);
+
+        let file_path = dir.join("docs.arrow");
+        let mut writer = FileWriter::try_new(File::create(&file_path).unwrap(), &schema).unwrap();
+        writer.write(&first).unwrap();
+        writer.write(&second).unwrap();
+        writer.finish().unwrap();
+        let stream_path = dir.join("docs.ipc");
+        let mut writer =
+            StreamWriter::try_new(File::create(&stream_path).unwrap(), &schema).unwrap();
+        writer.write(&first).unwrap();
+        writer.write(&second).unwrap();
+        writer.finish().unwrap();
+
+        // Both formats are read batch by batch.
+        for path in [&file_path, &stream_path] {
+            let batches = read_arrow_ipc(path.to_str().unwrap())
+                .unwrap()
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap();
+            let rows: Vec<usize> = batches.iter().map(RecordBatch::num_rows).collect();
+            assert_eq!(rows, vec![2, 1], "{:?}", path);
+            assert_eq!(batches[1], second);
+        }
+
+        let invalid = dir.join("invalid.arrow");
+        std::fs::write(&invalid, "not arrow").unwrap();
+        let error = read_arrow_ipc(invalid.to_str().unwrap()).err().unwrap();
+        assert!(error.to_string().contains("not a valid Arrow IPC file"));
+    }
+
+    #[tokio::test]
+    async fn test_image_reader_stays_in_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let root = dir.join("images");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("cat.png"), b"cat").unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+
+        let reader = ImageReader::new(root.to_str(), &[]).unwrap();
+        assert_eq!(reader.read("cat.png").await.unwrap(), b"cat");
+        let absolute = root.join("cat.png");
+        assert_eq!(
+            reader.read(absolute.to_str().unwrap()).await.unwrap(),
+            b"cat"
+        );
+        let error = reader.read("../secret.txt").await.unwrap_err();
+        assert!(error.to_string().contains("outside of the image root"));
+        let secret = dir.join("secret.txt");
+        assert!(reader.read(secret.to_str().unwrap()).await.is_err());
+
+        std::fs::write(
+            root.join("huge.png"),
+            vec![0u8; MAX_IMAGE_BYTES as usize + 1],
+        )
+        .unwrap();
+        let error = reader.read("huge.png").await.unwrap_err();
+        assert!(error.to_string().contains("larger than"));
+
+        let without_root = ImageReader::new(None, &[]).unwrap();
+        assert!(without_root.read(absolute.to_str().unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_image_reader_refuses_internal_urls() {
+        let hosts = [
+            String::from("images.example.com"),
+            String::from("127.0.0.1"),
+        ];
+        let reader = ImageReader::new(None, &hosts).unwrap();
+        let refused = [
+            "http://169.254.169.254/latest/meta-data/",
+            "http://other.example.com/cat.png",
+            "http://127.0.0.1:8080/cat.png",
+        ];
+        for url in refused {
+            assert!(reader.read(url).await.is_err(), "{}", url);
+        }
+        // Without allowed hosts, no URL is fetched.
+        let reader = ImageReader::new(None, &[]).unwrap();
+        let error = reader.read("https://images.example.com/cat.png").await;
+        assert!(error.unwrap_err().to_string().contains("image URL hosts"));
+
+        for ip in [
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_display_columns_must_exist() {
+        let mut conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE docs (text VARCHAR, title VARCHAR);")
+            .unwrap();
+        let config = |display_column: &str| CollectionConfig {
+            name: String::from("docs"),
+            display_columns: HashMap::from([(String::from("text"), display_column.to_string())]),
+            ..CollectionConfig::default()
+        };
+
+        let tx = conn.transaction().unwrap();
+        assert!(create_filter_indexes(&tx, &config("title")).is_ok());
+        assert!(matches!(
+            create_filter_indexes(&tx, &config("headline")),
+            Err(ProjectError::ColumnNotFound(column)) if column == "headline"
+        ));
+        assert!(create_filter_indexes(&tx, &config("title; DROP TABLE docs")).is_err());
     }
 }
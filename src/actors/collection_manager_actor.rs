@@ -1,17 +1,34 @@
-use crate::actors::collection_actor::{CollectionActor, GetConfig, Search as SearchMsg};
-use crate::actors::model_actor::{LoadModel, ModelManagerActor};
-use crate::collection::collection_utils::{CollectionConfig, SearchResult};
+use crate::actors::collection_actor::{
+    CollectionActor, GetConfig, GetStats, RecordQuery as RecordQueryMsg, Search as SearchMsg,
+    Shutdown,
+};
+use crate::actors::model_actor::{GetModelVariant, LoadModel, ModelManagerActor, ReleaseModel};
+use crate::collection::collection_utils::{
+    collection_dir, qualified_name, split_name, CollectionConfig, CollectionStats, ColumnModel,
+    FederatedResult, FederatedResults, ModelRequest, SearchResults, SearchTimeout,
+};
+use crate::collection::filter::{Filter, GeoFilter};
+use crate::collection::grouping::GroupBy;
+use crate::collection::vector_index::{ScoreThreshold, ScoreType};
 use crate::error::ProjectError;
 use actix::prelude::*;
-use std::collections::HashMap;
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use tracing::{info_span, Instrument, Span};
 
 // ---- Actor Definition ----
 pub struct CollectionManagerActor {
     collections: HashMap<String, Addr<CollectionActor>>,
     model_manager: Addr<ModelManagerActor>,
     model_lookup: HashMap<(String, String), u32>,
+    /// Ids of the models loaded for each collection, released when the
+    /// collection is unloaded or replaced.
+    collection_models: HashMap<String, Vec<u32>>,
     hf_token: Option<String>,
     gemini_api_key: Option<String>,
+    openai_api_key: Option<String>,
+    /// Memory-map the saved indexes of loaded collections read-only.
+    mmap_indexes: bool,
 }
 
 impl CollectionManagerActor {
@@ -19,15 +36,58 @@ impl CollectionManagerActor {
         hf_token: Option<String>,
         model_manager: Addr<ModelManagerActor>,
         gemini_api_key: Option<String>,
+        openai_api_key: Option<String>,
     ) -> Self {
         Self {
             collections: HashMap::new(),
             model_manager,
             model_lookup: HashMap::new(),
+            collection_models: HashMap::new(),
             hf_token,
             gemini_api_key,
+            openai_api_key,
+            mmap_indexes: false,
         }
     }
+
+    /// Memory-map the saved indexes of the collections it loads instead of
+    /// reading them into memory. Their documents can then be searched but
+    /// not added or deleted.
+    pub fn with_mmap_indexes(mut self, mmap_indexes: bool) -> Self {
+        self.mmap_indexes = mmap_indexes;
+        self
+    }
+
+    /// Release the models loaded for collection `name`, forgetting those
+    /// no other collection uses.
+    fn release_models(&mut self, name: &str) {
+        let Some(ids) = self.collection_models.remove(name) else {
+            return;
+        };
+        for id in ids {
+            self.model_manager.do_send(ReleaseModel { id });
+        }
+        let held: HashSet<u32> = self.collection_models.values().flatten().copied().collect();
+        self.model_lookup.retain(|_, id| held.contains(id));
+    }
+
+    /// Loaded collections of `namespace` by their name within it, sorted.
+    /// Collections of other namespaces are never part of it.
+    fn namespace_collections(
+        &self,
+        namespace: Option<&str>,
+    ) -> Vec<(String, Addr<CollectionActor>)> {
+        let mut collections: Vec<_> = self
+            .collections
+            .iter()
+            .filter_map(|(qualified, addr)| match split_name(qualified) {
+                (ns, name) if ns == namespace => Some((name.to_string(), addr.clone())),
+                _ => None,
+            })
+            .collect();
+        collections.sort_by(|(a, _), (b, _)| a.cmp(b));
+        collections
+    }
 }
 
 impl Actor for CollectionManagerActor {
@@ -46,6 +106,9 @@ pub struct CreateCollection {
 #[rtype(result = "Result<Addr<CollectionActor>, ProjectError>")]
 pub struct LoadCollection {
     pub name: String,
+    /// Load the collection from this directory instead of looking it up by
+    /// name under the letsearch home directory.
+    pub data_dir: Option<String>,
 }
 
 #[derive(Message)]
@@ -58,28 +121,145 @@ pub struct GetCollectionAddr {
 #[rtype(result = "Result<Vec<CollectionConfig>, ProjectError>")]
 pub struct GetAllCollectionConfigs;
 
+#[derive(Message)]
+#[rtype(result = "Result<Vec<CollectionStats>, ProjectError>")]
+pub struct GetAllCollectionStats;
+
+/// Configs of the loaded collections of a namespace, the default namespace
+/// when `None`.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<CollectionConfig>, ProjectError>")]
+pub struct GetNamespaceConfigs {
+    pub namespace: Option<String>,
+}
+
+/// Stats of the loaded collections of a namespace, the default namespace
+/// when `None`.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<CollectionStats>, ProjectError>")]
+pub struct GetNamespaceStats {
+    pub namespace: Option<String>,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 struct UpdateCollection {
     name: String,
     addr: Addr<CollectionActor>,
-    model_key: (String, String),
-    model_id: u32,
+    /// Id of each model loaded for the collection.
+    models: Vec<(ModelRequest, u32)>,
+}
+
+/// Flush and close a loaded collection and release its models, which are
+/// unloaded unless another collection uses them.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct UnloadCollection {
+    pub name: String,
 }
 
+/// Flush and close every loaded collection. Sent once the server has stopped.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ShutdownCollections;
+
 #[derive(Message)]
 #[rtype(result = "Result<u32, ProjectError>")]
 pub struct GetModelIdForCollection {
     pub name: String,
 }
 
+/// Id of the model embedding an index column of a collection: the column's
+/// own model, or the collection's.
+#[derive(Message)]
+#[rtype(result = "Result<u32, ProjectError>")]
+pub struct GetModelIdForColumn {
+    pub name: String,
+    pub column: String,
+}
+
+/// Find a model loaded for one of the collections by name, e.g.
+/// `hf://mys/minilm`, and variant. The variant may be left out when a
+/// single variant of the model is loaded. Returns the model's name, variant
+/// and id.
+#[derive(Message)]
+#[rtype(result = "Result<(String, String, u32), ProjectError>")]
+pub struct FindModel {
+    pub model: String,
+    pub variant: Option<String>,
+    /// Only look at the models of the collections of this namespace when
+    /// set, so that callers limited to it cannot use the others.
+    pub namespace: Option<String>,
+}
+
 #[derive(Message)]
-#[rtype(result = "Result<Vec<SearchResult>, ProjectError>")]
+#[rtype(result = "Result<SearchResults, ProjectError>")]
 pub struct SearchCollection {
     pub collection_name: String,
-    pub column: String,
+    /// Index columns to search. Every index column of the collection when
+    /// empty.
+    pub columns: Vec<String>,
     pub query: String,
+    /// See [`crate::actors::collection_actor::Search::vector`].
+    pub vector: Option<Vec<f32>>,
     pub limit: u32,
+    /// Number of top results to skip. Results are ordered by score, then by
+    /// key, so consecutive pages never overlap.
+    pub offset: u32,
+    pub score_type: ScoreType,
+    /// Groups the caller belongs to, see
+    /// [`crate::actors::collection_actor::Search::groups`].
+    pub groups: Option<Vec<String>>,
+    /// See [`crate::actors::collection_actor::Search::filter`].
+    pub filter: Option<Filter>,
+    /// See [`crate::actors::collection_actor::Search::geo_filter`].
+    pub geo_filter: Option<GeoFilter>,
+    /// See [`crate::actors::collection_actor::Search::group_by`].
+    pub group_by: Option<GroupBy>,
+    pub timeout: Option<SearchTimeout>,
+    /// See [`crate::actors::collection_actor::Search::threshold`].
+    pub threshold: ScoreThreshold,
+    /// See [`crate::actors::collection_actor::Search::skip_content`].
+    pub skip_content: bool,
+    /// Span of the caller, parent of the spans of the search.
+    pub span: Span,
+}
+
+/// Search several collections with one query and merge their results by
+/// calibrated similarity score.
+#[derive(Message)]
+#[rtype(result = "Result<FederatedResults, ProjectError>")]
+pub struct SearchCollections {
+    /// Collections to search. Every loaded collection when empty.
+    pub collection_names: Vec<String>,
+    /// Index columns to search in every collection. Each collection's own
+    /// index columns when `None`.
+    pub columns: Option<Vec<String>>,
+    pub query: String,
+    pub limit: u32,
+    pub offset: u32,
+    pub groups: Option<Vec<String>>,
+    pub group_by: Option<GroupBy>,
+    /// Time limit of the search in each collection.
+    pub timeout: Option<SearchTimeout>,
+    /// Namespace of the collections. Only collections of this namespace
+    /// are searched, and `collection_names` are names within it.
+    pub namespace: Option<String>,
+    /// Applied to each collection's own scores, before calibration.
+    pub threshold: ScoreThreshold,
+    /// See [`crate::actors::collection_actor::Search::skip_content`].
+    pub skip_content: bool,
+    /// Span of the caller, parent of the spans of the search.
+    pub span: Span,
+}
+
+/// Count a searched query towards the suggestions of a collection, without
+/// waiting for it to be stored.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordQuery {
+    pub collection_name: String,
+    pub query: String,
 }
 
 // ---- Message Handlers ----
@@ -87,8 +267,46 @@ impl Handler<UpdateCollection> for CollectionManagerActor {
     type Result = ();
 
     fn handle(&mut self, msg: UpdateCollection, _ctx: &mut Context<Self>) -> Self::Result {
+        // Models of the collection this one replaces, e.g. when it was
+        // created again, are released after taking those of the new one.
+        let replaced = self.collection_models.insert(
+            msg.name.clone(),
+            msg.models.iter().map(|(_, model_id)| *model_id).collect(),
+        );
         self.collections.insert(msg.name, msg.addr);
-        self.model_lookup.insert(msg.model_key, msg.model_id);
+        self.model_lookup.extend(
+            msg.models
+                .into_iter()
+                .map(|(request, model_id)| (request.key(), model_id)),
+        );
+        for id in replaced.into_iter().flatten() {
+            self.model_manager.do_send(ReleaseModel { id });
+        }
+    }
+}
+
+impl Handler<UnloadCollection> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: UnloadCollection, _ctx: &mut Context<Self>) -> Self::Result {
+        let addr = match self.collections.remove(&msg.name) {
+            Some(addr) => addr,
+            None => {
+                return Box::pin(async move { Err(ProjectError::CollectionNotFound(msg.name)) })
+            }
+        };
+        self.release_models(&msg.name);
+        Box::pin(async move { addr.send(Shutdown).await? })
+    }
+}
+
+impl Handler<RecordQuery> for CollectionManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordQuery, _ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(addr) = self.collections.get(&msg.collection_name) {
+            addr.do_send(RecordQueryMsg { query: msg.query });
+        }
     }
 }
 
@@ -128,47 +346,159 @@ impl Handler<GetAllCollectionConfigs> for CollectionManagerActor {
     }
 }
 
+impl Handler<GetNamespaceConfigs> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<Vec<CollectionConfig>, ProjectError>>;
+
+    fn handle(&mut self, msg: GetNamespaceConfigs, _ctx: &mut Context<Self>) -> Self::Result {
+        let futures: Vec<_> = self
+            .namespace_collections(msg.namespace.as_deref())
+            .into_iter()
+            .map(|(_, addr)| addr.send(GetConfig))
+            .collect();
+
+        Box::pin(async move {
+            let mut configs = Vec::with_capacity(futures.len());
+            for result in futures::future::join_all(futures).await {
+                configs.push(result??);
+            }
+            Ok(configs)
+        })
+    }
+}
+
+impl Handler<GetAllCollectionStats> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<Vec<CollectionStats>, ProjectError>>;
+
+    fn handle(&mut self, _msg: GetAllCollectionStats, _ctx: &mut Context<Self>) -> Self::Result {
+        let futures: Vec<_> = self
+            .collections
+            .values()
+            .map(|addr| addr.send(GetStats))
+            .collect();
+
+        Box::pin(async move {
+            let results = futures::future::join_all(futures).await;
+            results
+                .into_iter()
+                .map(|res| match res {
+                    Ok(Ok(stats)) => Ok(stats),
+                    Ok(Err(e)) => Err(e),
+                    Err(e) => Err(ProjectError::Mailbox(e)),
+                })
+                .collect()
+        })
+    }
+}
+
+impl Handler<GetNamespaceStats> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<Vec<CollectionStats>, ProjectError>>;
+
+    fn handle(&mut self, msg: GetNamespaceStats, _ctx: &mut Context<Self>) -> Self::Result {
+        let futures: Vec<_> = self
+            .namespace_collections(msg.namespace.as_deref())
+            .into_iter()
+            .map(|(_, addr)| addr.send(GetStats))
+            .collect();
+
+        Box::pin(async move {
+            let mut stats = Vec::with_capacity(futures.len());
+            for result in futures::future::join_all(futures).await {
+                stats.push(result??);
+            }
+            Ok(stats)
+        })
+    }
+}
+
+/// Config of the collection `config` would replace: the loaded collection
+/// of the same name, or one stored in its directory or under its name.
+async fn existing_config(
+    loaded: Option<Addr<CollectionActor>>,
+    config: &CollectionConfig,
+) -> Result<Option<CollectionConfig>, ProjectError> {
+    if let Some(addr) = loaded {
+        return Ok(Some(addr.send(GetConfig).await??));
+    }
+    let dir = config.dir();
+    if dir.join("config.json").exists() {
+        return Ok(Some(CollectionConfig::from_dir(&dir)?));
+    }
+    let name = config.qualified_name();
+    if collection_dir(&name).join("config.json").exists() {
+        return Ok(Some(CollectionConfig::from_file(&name)?));
+    }
+    Ok(None)
+}
+
 impl Handler<CreateCollection> for CollectionManagerActor {
     type Result = ResponseFuture<Result<Addr<CollectionActor>, ProjectError>>;
 
     fn handle(&mut self, msg: CreateCollection, ctx: &mut Context<Self>) -> Self::Result {
-        let collection_name = msg.config.name.clone();
-        if self.collections.contains_key(&collection_name) && !msg.overwrite {
-            return Box::pin(async move {
-                Err(ProjectError::Anyhow(anyhow::anyhow!(
-                    "Collection '{}' already exists.",
-                    collection_name
-                )))
-            });
-        }
+        let collection_name = msg.config.qualified_name();
+        let loaded = self.collections.get(&collection_name).cloned();
 
         let model_manager = self.model_manager.clone();
-        let model_key = (
-            msg.config.model_name.clone(),
-            msg.config.model_variant.clone(),
-        );
         let hf_token = self.hf_token.clone();
         let gemini_api_key = self.gemini_api_key.clone();
+        let openai_api_key = self.openai_api_key.clone();
         let self_addr = ctx.address();
 
         Box::pin(async move {
-            let model_id = model_manager
-                .send(LoadModel {
-                    path: model_key.0.clone(),
-                    variant: model_key.1.clone(),
-                    token: hf_token,
-                    gemini_api_key,
-                })
-                .await??;
+            let mut config = msg.config;
+            if !msg.overwrite {
+                if let Some(existing) = existing_config(loaded, &config).await? {
+                    return Err(ProjectError::AlreadyExists {
+                        name: collection_name,
+                        summary: existing.summary(),
+                        differences: existing.differences(&config),
+                    });
+                }
+            }
+            if msg.overwrite {
+                config
+                    .remove_files()
+                    .map_err(|e| ProjectError::Anyhow(e.into()))?;
+            }
+
+            let loaded = load_models(
+                &model_manager,
+                &config,
+                hf_token,
+                gemini_api_key,
+                openai_api_key,
+            )
+            .await?;
+            // Store the variant chosen for `auto`, so that the collection is
+            // served with the models it was indexed with.
+            let mut models = Vec::new();
+            for (mut request, model_id) in loaded {
+                let variant = model_manager
+                    .send(GetModelVariant { id: model_id })
+                    .await??;
+                let requested = ColumnModel {
+                    model_name: request.name.clone(),
+                    model_variant: request.variant.clone(),
+                };
+                if config.model() == requested {
+                    config.model_variant = variant.clone();
+                }
+                for model in config.column_models.values_mut() {
+                    if *model == requested {
+                        model.model_variant = variant.clone();
+                    }
+                }
+                request.variant = variant;
+                models.push((request, model_id));
+            }
 
-            let collection_actor = CollectionActor::new(msg.config, model_manager);
+            config.save()?;
+            let collection_actor = CollectionActor::new(config, model_manager)?;
             let collection_addr = collection_actor.start();
 
             self_addr.do_send(UpdateCollection {
                 name: collection_name,
                 addr: collection_addr.clone(),
-                model_key,
-                model_id,
+                models,
             });
 
             Ok(collection_addr)
@@ -188,28 +518,31 @@ impl Handler<LoadCollection> for CollectionManagerActor {
         let name = msg.name.clone();
         let hf_token = self.hf_token.clone();
         let gemini_api_key = self.gemini_api_key.clone();
+        let openai_api_key = self.openai_api_key.clone();
+        let mmap_indexes = self.mmap_indexes;
         let self_addr = ctx.address();
 
         Box::pin(async move {
-            let config = CollectionConfig::from_file(&name)?;
-            let model_key = (config.model_name.clone(), config.model_variant.clone());
-            let model_id = model_manager
-                .send(LoadModel {
-                    path: model_key.0.clone(),
-                    variant: model_key.1.clone(),
-                    token: hf_token,
-                    gemini_api_key,
-                })
-                .await??;
-
-            let actor = CollectionActor::new(config, model_manager);
+            let config = match msg.data_dir {
+                Some(dir) => CollectionConfig::from_dir(std::path::Path::new(&dir))?,
+                None => CollectionConfig::from_file(&name)?,
+            };
+            let models = load_models(
+                &model_manager,
+                &config,
+                hf_token,
+                gemini_api_key,
+                openai_api_key,
+            )
+            .await?;
+
+            let actor = CollectionActor::with_mmap_indexes(config, model_manager, mmap_indexes)?;
             let collection_addr = actor.start();
 
             self_addr.do_send(UpdateCollection {
                 name,
                 addr: collection_addr.clone(),
-                model_key,
-                model_id,
+                models,
             });
 
             Ok(collection_addr)
@@ -217,6 +550,25 @@ impl Handler<LoadCollection> for CollectionManagerActor {
     }
 }
 
+impl Handler<ShutdownCollections> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<(), ProjectError>>;
+
+    fn handle(&mut self, _msg: ShutdownCollections, _ctx: &mut Context<Self>) -> Self::Result {
+        let futures: Vec<_> = self
+            .collections
+            .drain()
+            .map(|(_, addr)| addr.send(Shutdown))
+            .collect();
+
+        Box::pin(async move {
+            for result in futures::future::join_all(futures).await {
+                result??;
+            }
+            Ok(())
+        })
+    }
+}
+
 impl Handler<GetModelIdForCollection> for CollectionManagerActor {
     type Result = ResponseFuture<Result<u32, ProjectError>>;
 
@@ -241,8 +593,76 @@ impl Handler<GetModelIdForCollection> for CollectionManagerActor {
     }
 }
 
+impl Handler<GetModelIdForColumn> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<u32, ProjectError>>;
+
+    fn handle(&mut self, msg: GetModelIdForColumn, _ctx: &mut Context<Self>) -> Self::Result {
+        let collection_addr = match self.collections.get(&msg.name) {
+            Some(addr) => addr.clone(),
+            None => {
+                return Box::pin(async move { Err(ProjectError::CollectionNotFound(msg.name)) });
+            }
+        };
+
+        let model_lookup = self.model_lookup.clone();
+
+        Box::pin(async move {
+            let config = collection_addr.send(GetConfig).await??;
+            model_id(&model_lookup, config.column_model(&msg.column))
+        })
+    }
+}
+
+impl Handler<FindModel> for CollectionManagerActor {
+    type Result = Result<(String, String, u32), ProjectError>;
+
+    fn handle(&mut self, msg: FindModel, _ctx: &mut Context<Self>) -> Self::Result {
+        let visible: Option<HashSet<u32>> = msg.namespace.as_deref().map(|namespace| {
+            self.collection_models
+                .iter()
+                .filter(|(name, _)| split_name(name).0 == Some(namespace))
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect()
+        });
+        let models: Vec<_> = self
+            .model_lookup
+            .iter()
+            .filter(|(_, id)| visible.as_ref().is_none_or(|ids| ids.contains(id)))
+            .collect();
+        let mut matches: Vec<_> = models
+            .iter()
+            .filter(|((model, variant), _)| {
+                *model == msg.model && msg.variant.as_ref().is_none_or(|v| v == variant)
+            })
+            .collect();
+        matches.sort();
+        match matches.as_slice() {
+            [((model, variant), id)] => Ok((model.clone(), variant.clone(), **id)),
+            [] => {
+                let mut loaded: Vec<String> = models
+                    .iter()
+                    .map(|((model, variant), _)| format!("{}:{}", model, variant))
+                    .collect();
+                loaded.sort();
+                let model = match msg.variant {
+                    Some(variant) => format!("{}:{}", msg.model, variant),
+                    None => msg.model,
+                };
+                Err(ProjectError::ModelNotLoaded(model, loaded.join(", ")))
+            }
+            _ => {
+                let variants: Vec<&str> = matches
+                    .iter()
+                    .map(|((_, variant), _)| variant.as_str())
+                    .collect();
+                Err(ProjectError::AmbiguousModel(msg.model, variants.join(", ")))
+            }
+        }
+    }
+}
+
 impl Handler<SearchCollection> for CollectionManagerActor {
-    type Result = ResponseFuture<Result<Vec<SearchResult>, ProjectError>>;
+    type Result = ResponseFuture<Result<SearchResults, ProjectError>>;
 
     fn handle(&mut self, msg: SearchCollection, _ctx: &mut Context<Self>) -> Self::Result {
         let collection_addr = match self.collections.get(&msg.collection_name) {
@@ -255,25 +675,203 @@ impl Handler<SearchCollection> for CollectionManagerActor {
         };
 
         let model_lookup = self.model_lookup.clone();
+        let span = info_span!(parent: &msg.span, "search", collection = %msg.collection_name);
 
-        Box::pin(async move {
+        let search = async move {
             let config = collection_addr.send(GetConfig).await??;
-            let model_key = (config.model_name, config.model_variant);
-            let model_id = model_lookup
-                .get(&model_key)
-                .copied()
-                .ok_or_else(|| ProjectError::ModelNotFound(0))?;
-
-            let search_results = collection_addr
+            let model_id = model_id(&model_lookup, config.model())?;
+            let column_model_ids = column_model_ids(&model_lookup, &config)?;
+            let columns = if msg.columns.is_empty() {
+                config.index_columns
+            } else {
+                msg.columns
+            };
+
+            collection_addr
                 .send(SearchMsg {
-                    column: msg.column,
+                    columns,
                     query: msg.query,
+                    vector: msg.vector,
                     limit: msg.limit,
+                    offset: msg.offset,
+                    score_type: msg.score_type,
                     model_id,
+                    column_model_ids,
+                    groups: msg.groups,
+                    filter: msg.filter,
+                    geo_filter: msg.geo_filter,
+                    group_by: msg.group_by,
+                    timeout: msg.timeout,
+                    threshold: msg.threshold,
+                    skip_content: msg.skip_content,
+                    span: Span::current(),
                 })
-                .await??;
+                .await?
+        };
+        Box::pin(search.instrument(span))
+    }
+}
 
-            Ok(search_results)
-        })
+impl Handler<SearchCollections> for CollectionManagerActor {
+    type Result = ResponseFuture<Result<FederatedResults, ProjectError>>;
+
+    fn handle(&mut self, msg: SearchCollections, _ctx: &mut Context<Self>) -> Self::Result {
+        let namespace = msg.namespace.as_deref();
+        let mut targets = Vec::new();
+        if msg.collection_names.is_empty() {
+            targets = self.namespace_collections(namespace);
+        }
+        for name in &msg.collection_names {
+            match self.collections.get(&qualified_name(namespace, name)) {
+                Some(addr) => targets.push((name.clone(), addr.clone())),
+                None => {
+                    let name = name.clone();
+                    return Box::pin(async move { Err(ProjectError::CollectionNotFound(name)) });
+                }
+            }
+        }
+
+        let model_lookup = self.model_lookup.clone();
+        let span = info_span!(parent: &msg.span, "federated_search", collections = targets.len());
+
+        let search = async move {
+            // Every collection returns its own top results, so that any of
+            // them can end up on the requested page after merging.
+            let fetch = msg.limit + msg.offset;
+            let searches = targets.into_iter().map(|(name, addr)| {
+                let model_lookup = &model_lookup;
+                let columns = msg.columns.clone();
+                let query = msg.query.clone();
+                let groups = msg.groups.clone();
+                let timeout = msg.timeout;
+                let group_by = msg.group_by;
+                let threshold = msg.threshold;
+                let skip_content = msg.skip_content;
+                let span = info_span!("search", collection = %name);
+                async move {
+                    let config = addr.send(GetConfig).await??;
+                    let model_id = model_id(model_lookup, config.model())?;
+                    let column_model_ids = column_model_ids(model_lookup, &config)?;
+
+                    let results = addr
+                        .send(SearchMsg {
+                            columns: columns.unwrap_or(config.index_columns),
+                            query,
+                            vector: None,
+                            limit: fetch,
+                            offset: 0,
+                            score_type: ScoreType::Similarity,
+                            model_id,
+                            column_model_ids,
+                            groups,
+                            filter: None,
+                            geo_filter: None,
+                            group_by,
+                            timeout,
+                            threshold,
+                            skip_content,
+                            span: Span::current(),
+                        })
+                        .await??;
+                    Ok::<_, ProjectError>((name, config.calibration, results))
+                }
+                .instrument(span)
+            });
+
+            let mut merged = Vec::new();
+            let mut calibrated = true;
+            let mut indexing = false;
+            let mut partial = false;
+            for (name, calibration, results) in futures::future::try_join_all(searches).await? {
+                indexing |= results.indexing;
+                partial |= results.partial;
+                if calibration.is_none() {
+                    warn!(
+                        "Collection '{}' has no score calibration, merging its raw scores",
+                        name
+                    );
+                    calibrated = false;
+                }
+                for mut result in results.results {
+                    if let Some(calibration) = calibration {
+                        result.score = calibration.apply(result.score);
+                    }
+                    merged.push(FederatedResult {
+                        collection: name.clone(),
+                        result,
+                    });
+                }
+            }
+
+            merged.sort_by(|a, b| {
+                b.result
+                    .score
+                    .total_cmp(&a.result.score)
+                    .then_with(|| a.collection.cmp(&b.collection))
+                    .then(a.result.key.cmp(&b.result.key))
+            });
+            let results = merged
+                .into_iter()
+                .skip(msg.offset as usize)
+                .take(msg.limit as usize)
+                .collect();
+
+            Ok(FederatedResults {
+                results,
+                calibrated,
+                indexing,
+                partial,
+            })
+        };
+        Box::pin(search.instrument(span))
     }
 }
+
+/// Load the model of `config` and those of its columns, returning the id
+/// of each.
+async fn load_models(
+    model_manager: &Addr<ModelManagerActor>,
+    config: &CollectionConfig,
+    hf_token: Option<String>,
+    gemini_api_key: Option<String>,
+    openai_api_key: Option<String>,
+) -> Result<Vec<(ModelRequest, u32)>, ProjectError> {
+    let mut models = Vec::new();
+    for request in config.requested_models() {
+        let model_id = model_manager
+            .send(LoadModel {
+                path: request.name.clone(),
+                variant: request.variant.clone(),
+                token: hf_token.clone(),
+                gemini_api_key: gemini_api_key.clone(),
+                openai_base_url: config.openai_base_url.clone(),
+                openai_api_key: openai_api_key.clone(),
+                remote_api_key: None,
+            })
+            .await??;
+        models.push((request, model_id));
+    }
+    Ok(models)
+}
+
+fn model_id(
+    model_lookup: &HashMap<(String, String), u32>,
+    model: ColumnModel,
+) -> Result<u32, ProjectError> {
+    model_lookup
+        .get(&(model.model_name, model.model_variant))
+        .copied()
+        .ok_or(ProjectError::ModelNotFound(0)) // 0 is a placeholder
+}
+
+/// Ids of the models of the columns that have their own.
+fn column_model_ids(
+    model_lookup: &HashMap<(String, String), u32>,
+    config: &CollectionConfig,
+) -> Result<HashMap<String, u32>, ProjectError> {
+    config
+        .column_models
+        .iter()
+        .map(|(column, model)| Ok((column.clone(), model_id(model_lookup, model.clone())?)))
+        .collect()
+}
@@ -1,27 +1,279 @@
 use actix::prelude::*;
-use log::info;
-use std::collections::HashMap;
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
+use tokenizers::Tokenizer;
+use tracing::{info_span, Instrument, Span};
 
+use crate::collection::vector_index::Metric;
 use crate::error::ProjectError;
-use crate::hf_ops::download_model;
+use crate::hf_ops::{download_model, resolve_variant};
 use crate::model::backends::gemini::gemini_embedder::GeminiEmbedder;
-use crate::model::backends::onnx::encoder_onnx::EncoderONNX;
-use crate::model::model_utils::{Embedder, Embeddings, ModelOutputDType, ModelTrait};
+use crate::model::backends::letsearch::letsearch_embedder::LetsearchEmbedder;
+use crate::model::backends::onnx::encoder_onnx::{EncoderONNX, OnnxSessionOptions};
+use crate::model::backends::openai::openai_embedder::{OpenAIEmbedder, DEFAULT_OPENAI_BASE_URL};
+use crate::model::batching::{BatchingEmbedder, DEFAULT_BATCH_WINDOW, DEFAULT_MAX_BATCH_SIZE};
+use crate::model::metadata::{ModelMetadata, Prompts};
+use crate::model::model_utils::{Backend, Embedder, Embeddings, ModelOutputDType};
 
 // ---- Actor Definition ----
+
+/// Instances of a loaded model, e.g. one per ONNX session. Predict calls
+/// use them in turn.
+#[derive(Clone)]
+struct LoadedModel {
+    instances: Vec<Arc<dyn Embedder>>,
+    next: usize,
+}
+
+impl LoadedModel {
+    fn first(&self) -> &Arc<dyn Embedder> {
+        &self.instances[0]
+    }
+
+    fn next_instance(&mut self) -> Arc<dyn Embedder> {
+        let instance = self.instances[self.next % self.instances.len()].clone();
+        self.next = self.next.wrapping_add(1);
+        instance
+    }
+}
+
+/// What a model is, kept while it is evicted so that it can be described
+/// without loading it again.
+#[derive(Clone)]
+struct ModelSummary {
+    backend: Backend,
+    dim: i64,
+    dtype: ModelOutputDType,
+    tokenizer: Option<Arc<Tokenizer>>,
+    instances: usize,
+}
+
+impl ModelSummary {
+    fn of(model: &LoadedModel) -> anyhow::Result<Self> {
+        let first = model.first();
+        Ok(Self {
+            backend: first.backend(),
+            dim: first.output_dim()?,
+            dtype: first.output_dtype()?,
+            tokenizer: first.tokenizer(),
+            instances: model.instances.len(),
+        })
+    }
+}
+
+/// A reload of an evicted model, shared by the predictions waiting for it.
+type Reload = Shared<BoxFuture<'static, Result<LoadedModel, String>>>;
+
 #[derive(Clone)]
 pub struct ModelManagerActor {
-    models: HashMap<u32, Arc<dyn Embedder>>,
+    /// Models in memory. Evicted models are missing here but keep their id
+    /// and everything else.
+    models: HashMap<u32, LoadedModel>,
+    /// Request each model was loaded with, to load it again after eviction.
+    requests: HashMap<u32, LoadModel>,
+    summaries: HashMap<u32, ModelSummary>,
+    /// Similarity metric each model was trained for, when its metadata says.
+    metrics: HashMap<u32, Metric>,
+    /// Query and document prompts of each model that declares them.
+    prompts: HashMap<u32, Prompts>,
+    /// Variant each model was loaded with, after resolving `auto`.
+    variants: HashMap<u32, String>,
+    /// Path each model was loaded from, e.g. `hf://mys/minilm`.
+    paths: HashMap<u32, String>,
+    /// Number of [`LoadModel`] requests of each model not released yet.
+    references: HashMap<u32, usize>,
+    /// When each model last embedded something, to evict the least recently
+    /// used one first.
+    last_used: HashMap<u32, Instant>,
+    reloads: HashMap<u32, Reload>,
+    /// Models whose instances all ran once, see [`WarmUp`].
+    warmed: HashSet<u32>,
     next_id: u32,
+    onnx_options: OnnxSessionOptions,
+    /// Most local models kept in memory at once, see
+    /// [`ModelManagerActor::with_max_loaded_models`].
+    max_loaded_models: Option<usize>,
 }
 
 impl ModelManagerActor {
     pub fn new() -> Self {
+        Self::with_onnx_options(OnnxSessionOptions::default())
+    }
+
+    /// Create a manager loading ONNX models with the given session options.
+    pub fn with_onnx_options(onnx_options: OnnxSessionOptions) -> Self {
         Self {
             models: HashMap::new(),
+            requests: HashMap::new(),
+            summaries: HashMap::new(),
+            metrics: HashMap::new(),
+            prompts: HashMap::new(),
+            variants: HashMap::new(),
+            paths: HashMap::new(),
+            references: HashMap::new(),
+            last_used: HashMap::new(),
+            reloads: HashMap::new(),
+            warmed: HashSet::new(),
             next_id: 1,
+            onnx_options,
+            max_loaded_models: None,
+        }
+    }
+
+    /// Keep at most `max` local models in memory. Beyond it, the least
+    /// recently used one is evicted and loaded again on its next prediction,
+    /// e.g. on servers with many collections of different models. Remote
+    /// models hold no memory to speak of and are never evicted.
+    pub fn with_max_loaded_models(mut self, max: Option<usize>) -> Self {
+        self.max_loaded_models = max.map(|max| max.max(1));
+        self
+    }
+}
+
+impl ModelManagerActor {
+    fn summary(&self, id: u32) -> Result<&ModelSummary, ProjectError> {
+        self.summaries
+            .get(&id)
+            .ok_or(ProjectError::ModelNotFound(id))
+    }
+
+    fn info(&self, id: u32) -> Result<ModelInfo, ProjectError> {
+        let summary = self.summary(id)?;
+        Ok(ModelInfo {
+            id,
+            path: self.paths.get(&id).cloned().unwrap_or_default(),
+            variant: self.variants.get(&id).cloned().unwrap_or_default(),
+            backend: summary.backend.clone(),
+            dim: summary.dim,
+            dtype: summary.dtype.clone(),
+            metric: self.metrics.get(&id).copied(),
+            prompts: self.prompts.get(&id).cloned().unwrap_or_default(),
+            instances: summary.instances,
+            tokenizer: summary.tokenizer.is_some(),
+            warmed: self.warmed.contains(&id),
+            loaded: self.models.contains_key(&id),
+            references: self.references.get(&id).copied().unwrap_or_default(),
+        })
+    }
+
+    /// Id of a model loaded, or evicted, for the same model as `msg`.
+    fn find(&self, msg: &LoadModel) -> Option<u32> {
+        self.requests
+            .iter()
+            .filter(|(id, request)| {
+                request.path == msg.path
+                    && request.openai_base_url == msg.openai_base_url
+                    && (request.variant == msg.variant
+                        || self.variants.get(id) == Some(&msg.variant))
+            })
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    /// Forget model `id`, freeing its instances once running predictions
+    /// are done with them.
+    fn unload(&mut self, id: u32) {
+        self.models.remove(&id);
+        self.requests.remove(&id);
+        self.summaries.remove(&id);
+        self.metrics.remove(&id);
+        self.prompts.remove(&id);
+        self.variants.remove(&id);
+        self.references.remove(&id);
+        self.last_used.remove(&id);
+        self.reloads.remove(&id);
+        self.warmed.remove(&id);
+        if let Some(path) = self.paths.remove(&id) {
+            info!("Unloaded model {} ({})", id, path);
+        }
+    }
+
+    /// Evict the least recently used local models beyond
+    /// `max_loaded_models`, except `keep`, which was just used.
+    fn evict(&mut self, keep: u32) {
+        let Some(max) = self.max_loaded_models else {
+            return;
+        };
+        let mut local: Vec<u32> = self
+            .models
+            .keys()
+            .filter(|id| {
+                self.summaries
+                    .get(id)
+                    .is_some_and(|summary| summary.backend == Backend::ONNX)
+            })
+            .copied()
+            .collect();
+        local.sort_by_key(|id| (*id == keep, self.last_used.get(id).copied()));
+        let excess = local.len().saturating_sub(max);
+        for id in local.into_iter().take(excess) {
+            self.models.remove(&id);
+            self.warmed.remove(&id);
+            info!(
+                "Evicted model {} ({}), it is loaded again on its next prediction",
+                id,
+                self.paths.get(&id).map_or("", String::as_str)
+            );
+        }
+    }
+
+    /// Load evicted model `id` again, or join the reload already running.
+    fn reload(&mut self, id: u32) -> Result<Reload, ProjectError> {
+        let request = self
+            .requests
+            .get(&id)
+            .cloned()
+            .ok_or(ProjectError::ModelNotFound(id))?;
+        let onnx_options = self.onnx_options.clone();
+        let reload = self.reloads.entry(id).or_insert_with(|| {
+            info!("Loading evicted model {} ({}) again", id, request.path);
+            async move {
+                load_instances(request, onnx_options)
+                    .await
+                    .map(|loaded| loaded.model)
+                    .map_err(|e| e.to_string())
+            }
+            .boxed()
+            .shared()
+        });
+        Ok(reload.clone())
+    }
+
+    /// Store the instances of reloaded model `id` and pick one to predict
+    /// with. Of several predictions waiting for the reload, the first one
+    /// stores it.
+    fn reloaded(
+        &mut self,
+        id: u32,
+        result: Result<LoadedModel, String>,
+    ) -> Result<Arc<dyn Embedder>, ProjectError> {
+        self.reloads.remove(&id);
+        // Unloaded while it was loading again.
+        if !self.requests.contains_key(&id) {
+            return Err(ProjectError::ModelNotFound(id));
+        }
+        if let Entry::Vacant(entry) = self.models.entry(id) {
+            entry.insert(result.map_err(|e| ProjectError::Anyhow(anyhow::anyhow!(e)))?);
         }
+        self.last_used.insert(id, Instant::now());
+        self.evict(id);
+        let model = self
+            .models
+            .get_mut(&id)
+            .ok_or(ProjectError::ModelNotFound(id))?;
+        Ok(model.next_instance())
+    }
+}
+
+impl Default for ModelManagerActor {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -29,23 +281,106 @@ impl Actor for ModelManagerActor {
     type Context = Context<Self>;
 }
 
+/// What a loaded model is and how it runs.
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelInfo {
+    pub id: u32,
+    pub path: String,
+    /// Variant with `auto` resolved. For remote models, the requested one.
+    pub variant: String,
+    pub backend: Backend,
+    /// Dimension of the embeddings.
+    pub dim: i64,
+    pub dtype: ModelOutputDType,
+    /// Similarity metric the model was trained for, if declared.
+    pub metric: Option<Metric>,
+    /// Query and document prompts from the model's metadata.
+    #[serde(skip_serializing_if = "Prompts::is_empty")]
+    pub prompts: Prompts,
+    /// Instances queries are spread over, e.g. ONNX sessions.
+    pub instances: usize,
+    /// Whether texts are tokenized locally.
+    pub tokenizer: bool,
+    /// Whether every instance already ran once.
+    pub warmed: bool,
+    /// Whether the model is in memory, rather than evicted to be loaded
+    /// again on its next prediction.
+    pub loaded: bool,
+    /// Holders of the model, e.g. collections, see [`ReleaseModel`].
+    pub references: usize,
+}
+
 // ---- Message Definitions ----
-#[derive(Message)]
+/// Load a model and return its id. A model that is already loaded for the
+/// same path and variant is shared instead of being loaded again, and
+/// every such request has to be released with [`ReleaseModel`] before the
+/// model is unloaded.
+#[derive(Message, Clone)]
 #[rtype(result = "Result<u32, ProjectError>")]
 pub struct LoadModel {
     pub path: String,
+    /// Variant to load, e.g. `i8`, or `auto` to pick the one fastest on
+    /// this machine. For local models, the name of the ONNX file.
     pub variant: String,
     pub token: Option<String>,
     /// Gemini API key. Required when `path` starts with `gemini://`.
     /// Falls back to the `GEMINI_API_KEY` environment variable when `None`.
     pub gemini_api_key: Option<String>,
+    /// Base URL of an OpenAI-compatible embeddings API. Used when `path`
+    /// starts with `openai://`. Falls back to the `OPENAI_BASE_URL`
+    /// environment variable and then to the official OpenAI endpoint.
+    pub openai_base_url: Option<String>,
+    /// API key for the OpenAI-compatible endpoint. Falls back to the
+    /// `OPENAI_API_KEY` environment variable. May be absent for local servers.
+    pub openai_api_key: Option<String>,
+    /// API key of the remote letsearch instance used when `path` is an
+    /// `http://` or `https://` URL. Falls back to the
+    /// `LETSEARCH_REMOTE_API_KEY` environment variable.
+    pub remote_api_key: Option<String>,
+}
+
+/// Release a model loaded with [`LoadModel`], unloading it when it was the
+/// last request holding it.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct ReleaseModel {
+    pub id: u32,
+}
+
+/// Unload a model right away, however many requests hold it. Its id is
+/// unknown afterwards.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct UnloadModel {
+    pub id: u32,
 }
 
+/// Embed `texts`, loading the model again first when it was evicted.
 #[derive(Message)]
 #[rtype(result = "Result<Embeddings, ProjectError>")]
 pub struct Predict {
     pub id: u32,
     pub texts: Vec<String>,
+    /// Span of the caller, parent of the inference span.
+    pub span: Span,
+}
+
+/// Embed encoded `images` with a model that supports images, see
+/// [`Embedder::predict_image`].
+#[derive(Message)]
+#[rtype(result = "Result<Embeddings, ProjectError>")]
+pub struct PredictImages {
+    pub id: u32,
+    pub images: Vec<Vec<u8>>,
+    pub span: Span,
+}
+
+/// Embed a short text with every instance of a model, so that the lazy
+/// initialization of ONNX Runtime happens before the first query.
+#[derive(Message)]
+#[rtype(result = "Result<(), ProjectError>")]
+pub struct WarmUp {
+    pub id: u32,
 }
 
 #[derive(Message)]
@@ -54,94 +389,390 @@ pub struct GetModelMetadata {
     pub id: u32,
 }
 
+/// Similarity metric the model was trained for, if its metadata declares one.
+#[derive(Message)]
+#[rtype(result = "Result<Option<Metric>, ProjectError>")]
+pub struct GetModelMetric {
+    pub id: u32,
+}
+
+/// Query and document prompts the model's metadata declares, empty for
+/// models without any.
+#[derive(Message)]
+#[rtype(result = "Result<Prompts, ProjectError>")]
+pub struct GetModelPrompts {
+    pub id: u32,
+}
+
+/// Variant the model was loaded with, with `auto` resolved.
+#[derive(Message)]
+#[rtype(result = "Result<String, ProjectError>")]
+pub struct GetModelVariant {
+    pub id: u32,
+}
+
+/// Description of a loaded model.
+#[derive(Message)]
+#[rtype(result = "Result<ModelInfo, ProjectError>")]
+pub struct GetModelInfo {
+    pub id: u32,
+}
+
+/// Tokenizer of the model, or `None` for models tokenized remotely.
+#[derive(Message)]
+#[rtype(result = "Result<Option<Arc<Tokenizer>>, ProjectError>")]
+pub struct GetTokenizer {
+    pub id: u32,
+}
+
+/// Instances of a model, with what its metadata declares and the variant
+/// they were loaded with.
+struct Loaded {
+    model: LoadedModel,
+    metric: Option<Metric>,
+    prompts: Prompts,
+    variant: String,
+}
+
+/// Load the instances of the model `msg` asks for.
+async fn load_instances(
+    msg: LoadModel,
+    onnx_options: OnnxSessionOptions,
+) -> Result<Loaded, ProjectError> {
+    let mut metric = None;
+    let mut prompts = Prompts::default();
+    let mut variant = msg.variant.clone();
+    let models: Vec<Arc<dyn Embedder>> = if msg.path.starts_with("gemini://") {
+        let model_name = msg.path.strip_prefix("gemini://").unwrap();
+
+        let api_key = msg
+            .gemini_api_key
+            .or_else(|| std::env::var("GEMINI_API_KEY").ok())
+            .ok_or_else(|| {
+                ProjectError::Anyhow(anyhow::anyhow!(
+                    "Gemini API key not provided. \
+                     Pass --gemini-api-key or set the GEMINI_API_KEY environment variable."
+                ))
+            })?;
+
+        vec![Arc::new(GeminiEmbedder::new(model_name, &api_key, None))]
+    } else if let Some(model_name) = msg.path.strip_prefix("openai://") {
+        let base_url = msg
+            .openai_base_url
+            .or_else(|| std::env::var("OPENAI_BASE_URL").ok())
+            .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string());
+        let api_key = msg
+            .openai_api_key
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok());
+
+        vec![Arc::new(
+            OpenAIEmbedder::connect(&base_url, model_name, api_key)
+                .await
+                .map_err(ProjectError::Anyhow)?,
+        )]
+    } else if msg.path.starts_with("http://") || msg.path.starts_with("https://") {
+        let api_key = msg
+            .remote_api_key
+            .or_else(|| std::env::var("LETSEARCH_REMOTE_API_KEY").ok());
+
+        let embedder = LetsearchEmbedder::connect(&msg.path, api_key)
+            .await
+            .map_err(ProjectError::Anyhow)?;
+        info!(
+            "Remote letsearch at {} serves model {}",
+            msg.path,
+            embedder.model_name()
+        );
+        vec![Arc::new(embedder)]
+    } else {
+        variant = resolve_variant(&msg.path, &msg.variant, msg.token.clone())
+            .await
+            .map_err(ProjectError::Anyhow)?;
+        let (model_dir, model_file) = if msg.path.starts_with("hf://") {
+            download_model(msg.path, variant.clone(), msg.token)
+                .await
+                .map_err(ProjectError::Anyhow)?
+        } else {
+            (msg.path, variant.clone())
+        };
+        if let Some(metadata) =
+            ModelMetadata::read(Path::new(&model_dir)).map_err(ProjectError::Anyhow)?
+        {
+            metric = metadata.metric;
+            prompts = metadata.prompts;
+        }
+
+        let sessions = onnx_options.sessions.max(1);
+        let mut models: Vec<Arc<dyn Embedder>> = Vec::with_capacity(sessions);
+        for _ in 0..sessions {
+            models.push(Arc::new(
+                EncoderONNX::with_options(model_dir.as_str(), model_file.as_str(), &onnx_options)
+                    .map_err(ProjectError::Anyhow)?,
+            ));
+        }
+        if sessions > 1 {
+            info!("Created {} ONNX sessions", sessions);
+        }
+        models
+    };
+
+    let mut instances: Vec<Arc<dyn Embedder>> = Vec::with_capacity(models.len());
+    for model in models {
+        // Calls to remote APIs are sent as they are, as merged calls could
+        // exceed the APIs' limits on texts per request.
+        if model.backend() != Backend::ONNX {
+            instances.push(model);
+            continue;
+        }
+        // Pay the session initialization cost at load time instead of on the
+        // first query.
+        let start = Instant::now();
+        match model.embed(vec![String::from("warmup")]).await {
+            Ok(_) => info!("Model warmed up in {:?}", start.elapsed()),
+            Err(e) => warn!("Model warmup failed: {}", e),
+        }
+
+        instances.push(Arc::new(BatchingEmbedder::new(
+            model,
+            DEFAULT_BATCH_WINDOW,
+            DEFAULT_MAX_BATCH_SIZE,
+        )));
+    }
+    Ok(Loaded {
+        model: LoadedModel { instances, next: 0 },
+        metric,
+        prompts,
+        variant,
+    })
+}
+
 // ---- Message Handlers ----
 impl Handler<LoadModel> for ModelManagerActor {
     type Result = ResponseActFuture<Self, Result<u32, ProjectError>>;
 
     fn handle(&mut self, msg: LoadModel, _ctx: &mut Context<Self>) -> Self::Result {
-        let model_path = msg.path.clone();
+        if let Some(id) = self.find(&msg) {
+            *self.references.entry(id).or_default() += 1;
+            return Box::pin(actix::fut::ready(Ok(id)));
+        }
 
-        let fut = async move {
-            let model: Arc<dyn Embedder> = if msg.path.starts_with("gemini://") {
-                let model_name = msg.path
-                    .strip_prefix("gemini://")
-                    .unwrap();
-
-                let api_key = msg
-                    .gemini_api_key
-                    .or_else(|| std::env::var("GEMINI_API_KEY").ok())
-                    .ok_or_else(|| {
-                        ProjectError::Anyhow(anyhow::anyhow!(
-                            "Gemini API key not provided. \
-                             Pass --gemini-api-key or set the GEMINI_API_KEY environment variable."
-                        ))
-                    })?;
-
-                Arc::new(GeminiEmbedder::new(&model_name, &api_key, None))
-            } else {
-                let (model_dir, model_file) = if msg.path.starts_with("hf://") {
-                    download_model(msg.path, msg.variant, msg.token)
-                        .await
-                        .map_err(|e| ProjectError::Anyhow(e))?
-                } else {
-                    (msg.path, msg.variant)
-                };
-
-                Arc::new(
-                    EncoderONNX::new(model_dir.as_str(), model_file.as_str())
-                        .map_err(|e| ProjectError::Anyhow(e))?,
-                )
-            };
-
-            Ok(model)
-        };
+        let request = msg.clone();
+        let fut = load_instances(msg, self.onnx_options.clone());
+        Box::pin(
+            actix::fut::wrap_future::<_, Self>(fut).map(move |result, act, _ctx| {
+                let Loaded {
+                    model,
+                    metric,
+                    prompts,
+                    variant,
+                } = result?;
+                // Another request for the same model may have loaded it first.
+                if let Some(id) = act.find(&request) {
+                    *act.references.entry(id).or_default() += 1;
+                    return Ok(id);
+                }
 
-        Box::pin(actix::fut::wrap_future::<_, Self>(fut).map(
-            move |result, act, _ctx| match result {
-                Ok(model) => {
-                    let id = act.next_id;
-                    act.next_id += 1;
-                    act.models.insert(id, model);
-                    info!("Model loaded from {}", model_path);
-                    Ok(id)
+                let summary = ModelSummary::of(&model).map_err(ProjectError::Anyhow)?;
+                let id = act.next_id;
+                act.next_id += 1;
+                act.models.insert(id, model);
+                act.summaries.insert(id, summary);
+                if let Some(metric) = metric {
+                    act.metrics.insert(id, metric);
                 }
-                Err(e) => Err(e),
-            },
-        ))
+                if !prompts.is_empty() {
+                    act.prompts.insert(id, prompts);
+                }
+                act.variants.insert(id, variant);
+                act.paths.insert(id, request.path.clone());
+                act.references.insert(id, 1);
+                act.last_used.insert(id, Instant::now());
+                info!("Model loaded from {}", request.path);
+                act.requests.insert(id, request);
+                act.evict(id);
+                Ok(id)
+            }),
+        )
+    }
+}
+
+impl Handler<ReleaseModel> for ModelManagerActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: ReleaseModel, _ctx: &mut Context<Self>) -> Self::Result {
+        let references = self
+            .references
+            .get_mut(&msg.id)
+            .ok_or(ProjectError::ModelNotFound(msg.id))?;
+        *references = references.saturating_sub(1);
+        if *references == 0 {
+            self.unload(msg.id);
+        }
+        Ok(())
+    }
+}
+
+impl Handler<UnloadModel> for ModelManagerActor {
+    type Result = Result<(), ProjectError>;
+
+    fn handle(&mut self, msg: UnloadModel, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.requests.contains_key(&msg.id) {
+            return Err(ProjectError::ModelNotFound(msg.id));
+        }
+        self.unload(msg.id);
+        Ok(())
     }
 }
 
 impl Handler<Predict> for ModelManagerActor {
-    type Result = ResponseFuture<Result<Embeddings, ProjectError>>;
+    type Result = ResponseActFuture<Self, Result<Embeddings, ProjectError>>;
 
     fn handle(&mut self, msg: Predict, _ctx: &mut Context<Self>) -> Self::Result {
-        let model = match self.models.get(&msg.id) {
-            Some(m) => m.clone(),
-            None => return Box::pin(async move { Err(ProjectError::ModelNotFound(msg.id)) }),
-        };
+        let id = msg.id;
+        let span = info_span!(parent: &msg.span, "inference", model = id, texts = msg.texts.len());
+        let texts = msg.texts;
+        self.with_instance(id, move |model: Arc<dyn Embedder>| {
+            async move { model.embed(texts).await.map_err(ProjectError::Embedding) }
+                .instrument(span)
+        })
+    }
+}
 
-        Box::pin(async move {
-            model
-                .embed(msg.texts)
-                .await
-                .map_err(|e| ProjectError::Anyhow(e))
+impl Handler<PredictImages> for ModelManagerActor {
+    type Result = ResponseActFuture<Self, Result<Embeddings, ProjectError>>;
+
+    fn handle(&mut self, msg: PredictImages, _ctx: &mut Context<Self>) -> Self::Result {
+        let id = msg.id;
+        let span =
+            info_span!(parent: &msg.span, "inference", model = id, images = msg.images.len());
+        let images = msg.images;
+        self.with_instance(id, move |model: Arc<dyn Embedder>| {
+            async move {
+                model
+                    .predict_image(images)
+                    .await
+                    .map_err(ProjectError::Embedding)
+            }
+            .instrument(span)
         })
     }
 }
 
+impl ModelManagerActor {
+    /// Run `embed` with the next instance of model `id`, loading the model
+    /// again first when it was evicted.
+    fn with_instance<F, Fut>(
+        &mut self,
+        id: u32,
+        embed: F,
+    ) -> ResponseActFuture<Self, Result<Embeddings, ProjectError>>
+    where
+        F: FnOnce(Arc<dyn Embedder>) -> Fut + 'static,
+        Fut: std::future::Future<Output = Result<Embeddings, ProjectError>> + 'static,
+    {
+        if let Some(model) = self.models.get_mut(&id) {
+            let instance = model.next_instance();
+            self.last_used.insert(id, Instant::now());
+            return Box::pin(actix::fut::wrap_future(embed(instance)));
+        }
+        let reload = match self.reload(id) {
+            Ok(reload) => reload,
+            Err(e) => return Box::pin(actix::fut::ready(Err(e))),
+        };
+        Box::pin(
+            actix::fut::wrap_future::<_, Self>(reload)
+                .map(move |result, act, _ctx| act.reloaded(id, result))
+                .then(move |instance, _act, _ctx| {
+                    actix::fut::wrap_future(async move { embed(instance?).await })
+                }),
+        )
+    }
+}
+
+impl Handler<WarmUp> for ModelManagerActor {
+    type Result = ResponseActFuture<Self, Result<(), ProjectError>>;
+
+    fn handle(&mut self, msg: WarmUp, _ctx: &mut Context<Self>) -> Self::Result {
+        let id = msg.id;
+        let instances = match self.models.get(&id) {
+            Some(model) => model.instances.clone(),
+            // Evicted models are warmed up when they are loaded again.
+            None if self.requests.contains_key(&id) => Vec::new(),
+            None => {
+                return Box::pin(actix::fut::ready(Err(ProjectError::ModelNotFound(id))));
+            }
+        };
+
+        let fut = async move {
+            for instance in instances {
+                instance
+                    .embed(vec![String::from("warm up")])
+                    .await
+                    .map_err(ProjectError::Embedding)?;
+            }
+            Ok(())
+        };
+        Box::pin(
+            actix::fut::wrap_future::<_, Self>(fut).map(move |result, act, _ctx| {
+                if result.is_ok() {
+                    act.warmed.insert(id);
+                }
+                result
+            }),
+        )
+    }
+}
+
 impl Handler<GetModelMetadata> for ModelManagerActor {
     type Result = Result<(i64, ModelOutputDType), ProjectError>;
 
     fn handle(&mut self, msg: GetModelMetadata, _ctx: &mut Context<Self>) -> Self::Result {
-        let model = self
-            .models
+        let summary = self.summary(msg.id)?;
+        Ok((summary.dim, summary.dtype.clone()))
+    }
+}
+
+impl Handler<GetModelMetric> for ModelManagerActor {
+    type Result = Result<Option<Metric>, ProjectError>;
+
+    fn handle(&mut self, msg: GetModelMetric, _ctx: &mut Context<Self>) -> Self::Result {
+        self.summary(msg.id)?;
+        Ok(self.metrics.get(&msg.id).copied())
+    }
+}
+
+impl Handler<GetModelPrompts> for ModelManagerActor {
+    type Result = Result<Prompts, ProjectError>;
+
+    fn handle(&mut self, msg: GetModelPrompts, _ctx: &mut Context<Self>) -> Self::Result {
+        self.summary(msg.id)?;
+        Ok(self.prompts.get(&msg.id).cloned().unwrap_or_default())
+    }
+}
+
+impl Handler<GetModelVariant> for ModelManagerActor {
+    type Result = Result<String, ProjectError>;
+
+    fn handle(&mut self, msg: GetModelVariant, _ctx: &mut Context<Self>) -> Self::Result {
+        self.variants
             .get(&msg.id)
-            .ok_or_else(|| ProjectError::ModelNotFound(msg.id))?;
+            .cloned()
+            .ok_or(ProjectError::ModelNotFound(msg.id))
+    }
+}
 
-        let dim = model.output_dim().map_err(|e| ProjectError::Anyhow(e))?;
-        let dtype = model.output_dtype().map_err(|e| ProjectError::Anyhow(e))?;
+impl Handler<GetTokenizer> for ModelManagerActor {
+    type Result = Result<Option<Arc<Tokenizer>>, ProjectError>;
 
-        Ok((dim, dtype))
+    fn handle(&mut self, msg: GetTokenizer, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.summary(msg.id)?.tokenizer.clone())
     }
 }
 
+impl Handler<GetModelInfo> for ModelManagerActor {
+    type Result = Result<ModelInfo, ProjectError>;
+
+    fn handle(&mut self, msg: GetModelInfo, _ctx: &mut Context<Self>) -> Self::Result {
+        self.info(msg.id)
+    }
+}
@@ -199,7 +199,7 @@ impl MarkdownChunker {
                 // Token-split the oversized paragraph; all resulting sub-chunks
                 // are complete — do NOT carry any of them into `current`.
                 let sub = self.split_by_tokens(para);
-                chunks.extend(sub.into_iter());
+                chunks.extend(sub);
                 continue;
             }
 
@@ -0,0 +1,347 @@
+use crate::actors::collection_actor::{
+    AppendJsonl, CollectionActor, EmbedColumn, GetConfig, UpdateRelated,
+};
+use crate::actors::collection_manager_actor::{
+    CollectionManagerActor, GetModelIdForColumn, LoadCollection, SearchCollection,
+    ShutdownCollections,
+};
+use crate::actors::model_actor::ModelManagerActor;
+use crate::collection::collection_utils::{
+    check_page, documents_file_path, CollectionConfig, IndexReport, SearchResults, SearchTimeout,
+};
+use crate::collection::filter::{Filter, GeoFilter};
+use crate::collection::vector_index::{ScoreThreshold, ScoreType};
+use crate::error::ProjectError;
+use crate::model::backends::onnx::encoder_onnx::OnnxSessionOptions;
+use actix::{Actor, Addr, System};
+use std::future::Future;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use tracing::Span;
+
+/// Rows embedded per batch when indexing documents added with
+/// [`Collection::add_documents`].
+const DOCUMENTS_BATCH_SIZE: u64 = 32;
+
+/// Options of a [`Client`].
+#[derive(Clone, Debug, Default)]
+pub struct ClientConfig {
+    /// HuggingFace token, to download private models. Falls back to the
+    /// `HF_TOKEN` environment variable.
+    pub hf_token: Option<String>,
+    /// Falls back to the `GEMINI_API_KEY` environment variable.
+    pub gemini_api_key: Option<String>,
+    /// Falls back to the `OPENAI_API_KEY` environment variable.
+    pub openai_api_key: Option<String>,
+    /// Sessions and threads of ONNX models.
+    pub onnx_options: OnnxSessionOptions,
+    /// Most local models kept in memory at once. Unlimited when `None`.
+    pub max_loaded_models: Option<usize>,
+}
+
+/// Collections and models of an embedded letsearch, without the HTTP layer.
+///
+/// The actors doing the work run on a thread of their own, so a client can
+/// be used from synchronous code through the `_blocking` methods, as well as
+/// from any async runtime. Dropping the client flushes its collections.
+pub struct Client {
+    collection_manager: Addr<CollectionManagerActor>,
+    system: System,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Client {
+    /// Start a client with the default [`ClientConfig`].
+    pub fn new() -> Result<Self, ProjectError> {
+        Self::with_config(ClientConfig::default())
+    }
+
+    pub fn with_config(config: ClientConfig) -> Result<Self, ProjectError> {
+        let (tx, rx) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name(String::from("letsearch"))
+            .spawn(move || {
+                let system = System::new();
+                let collection_manager = system.block_on(async move {
+                    let model_manager = ModelManagerActor::with_onnx_options(config.onnx_options)
+                        .with_max_loaded_models(config.max_loaded_models)
+                        .start();
+                    CollectionManagerActor::new(
+                        config.hf_token.or_else(|| std::env::var("HF_TOKEN").ok()),
+                        model_manager,
+                        config
+                            .gemini_api_key
+                            .or_else(|| std::env::var("GEMINI_API_KEY").ok()),
+                        config
+                            .openai_api_key
+                            .or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+                    )
+                    .start()
+                });
+                let _ = tx.send((collection_manager, System::current()));
+                let _ = system.run();
+            })
+            .map_err(|e| ProjectError::Anyhow(e.into()))?;
+        let (collection_manager, system) = rx.recv().map_err(|_| {
+            ProjectError::Anyhow(anyhow::anyhow!("The letsearch thread failed to start"))
+        })?;
+        Ok(Self {
+            collection_manager,
+            system,
+            thread: Some(thread),
+        })
+    }
+
+    /// Load a collection by name from the letsearch home directory.
+    pub async fn collection(&self, name: &str) -> Result<Collection, ProjectError> {
+        self.load(name, None).await
+    }
+
+    /// Load a collection stored in `data_dir`, e.g. one indexed with
+    /// `letsearch index --data-dir`.
+    pub async fn collection_at(
+        &self,
+        name: &str,
+        data_dir: &str,
+    ) -> Result<Collection, ProjectError> {
+        self.load(name, Some(data_dir.to_string())).await
+    }
+
+    pub fn collection_blocking(&self, name: &str) -> Result<Collection, ProjectError> {
+        block_on(self.collection(name))
+    }
+
+    pub fn collection_at_blocking(
+        &self,
+        name: &str,
+        data_dir: &str,
+    ) -> Result<Collection, ProjectError> {
+        block_on(self.collection_at(name, data_dir))
+    }
+
+    async fn load(&self, name: &str, data_dir: Option<String>) -> Result<Collection, ProjectError> {
+        let addr = self
+            .collection_manager
+            .send(LoadCollection {
+                name: name.to_string(),
+                data_dir,
+            })
+            .await??;
+        Ok(Collection {
+            name: name.to_string(),
+            addr,
+            collection_manager: self.collection_manager.clone(),
+        })
+    }
+
+    /// Flush every loaded collection and stop the client. Dropping it does
+    /// the same, but cannot report errors.
+    pub fn shutdown(mut self) -> Result<(), ProjectError> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), ProjectError> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        let flushed = block_on(self.collection_manager.send(ShutdownCollections));
+        self.system.stop();
+        let _ = thread.join();
+        flushed?
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            log::warn!("Failed to flush the collections: {}", e);
+        }
+    }
+}
+
+/// Options of [`Collection::search`].
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    /// Index columns to search. Every index column when empty.
+    pub columns: Vec<String>,
+    /// Number of results, between 1 and [`MAX_SEARCH_LIMIT`].
+    ///
+    /// [`MAX_SEARCH_LIMIT`]: crate::collection::collection_utils::MAX_SEARCH_LIMIT
+    pub limit: u32,
+    /// Number of top results to skip, to page through them, at most
+    /// [`MAX_SEARCH_OFFSET`].
+    ///
+    /// [`MAX_SEARCH_OFFSET`]: crate::collection::collection_utils::MAX_SEARCH_OFFSET
+    pub offset: u32,
+    pub score_type: ScoreType,
+    /// Leave out results weaker than this.
+    pub threshold: ScoreThreshold,
+    /// Groups of the caller, for collections with an ACL column. Every
+    /// document is visible when `None`.
+    pub groups: Option<Vec<String>>,
+    /// Only search documents matching this filter, on filterable columns.
+    pub filter: Option<Filter>,
+    /// Only search documents within a distance of a point, for collections
+    /// with geo columns.
+    pub geo_filter: Option<GeoFilter>,
+    pub timeout: Option<SearchTimeout>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            limit: 10,
+            offset: 0,
+            score_type: ScoreType::Similarity,
+            threshold: ScoreThreshold::default(),
+            groups: None,
+            filter: None,
+            geo_filter: None,
+            timeout: None,
+        }
+    }
+}
+
+/// A collection loaded by a [`Client`]. Cheap to clone.
+#[derive(Clone)]
+pub struct Collection {
+    name: String,
+    addr: Addr<CollectionActor>,
+    collection_manager: Addr<CollectionManagerActor>,
+}
+
+impl Collection {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn config(&self) -> Result<CollectionConfig, ProjectError> {
+        self.addr.send(GetConfig).await?
+    }
+
+    /// Embed `query` with the collection's models and return its nearest
+    /// documents.
+    pub async fn search(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchResults, ProjectError> {
+        check_page(options.limit, options.offset)?;
+        self.collection_manager
+            .send(SearchCollection {
+                collection_name: self.name.clone(),
+                columns: options.columns.clone(),
+                query: query.to_string(),
+                vector: None,
+                limit: options.limit,
+                offset: options.offset,
+                score_type: options.score_type,
+                groups: options.groups.clone(),
+                filter: options.filter.clone(),
+                geo_filter: options.geo_filter,
+                group_by: None,
+                timeout: options.timeout,
+                threshold: options.threshold,
+                skip_content: false,
+                span: Span::current(),
+            })
+            .await?
+    }
+
+    pub fn search_blocking(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchResults, ProjectError> {
+        block_on(self.search(query, options))
+    }
+
+    /// Add documents, JSON objects with the collection's columns, and index
+    /// them. Returns the report of each index column.
+    pub async fn add_documents(
+        &self,
+        documents: &[serde_json::Value],
+    ) -> Result<Vec<(String, IndexReport)>, ProjectError> {
+        let path = documents_file_path();
+        let appended = async {
+            let mut content = String::new();
+            for document in documents {
+                content.push_str(
+                    &serde_json::to_string(document).map_err(|e| ProjectError::Anyhow(e.into()))?,
+                );
+                content.push('\n');
+            }
+            std::fs::write(&path, content).map_err(|e| ProjectError::Anyhow(e.into()))?;
+            self.addr
+                .send(AppendJsonl {
+                    path: path.to_string_lossy().to_string(),
+                })
+                .await?
+        }
+        .await;
+        let _ = std::fs::remove_file(&path);
+        appended?;
+
+        let config = self.config().await?;
+        let mut reports = Vec::new();
+        for column in &config.index_columns {
+            let model_id = self
+                .collection_manager
+                .send(GetModelIdForColumn {
+                    name: self.name.clone(),
+                    column: column.clone(),
+                })
+                .await??;
+            let report = self
+                .addr
+                .send(EmbedColumn {
+                    name: column.clone(),
+                    batch_size: DOCUMENTS_BATCH_SIZE,
+                    model_id,
+                    skip_errors: false,
+                    concurrency: 2,
+                })
+                .await??;
+            reports.push((column.clone(), report));
+        }
+        for column in config.related_counts.keys() {
+            self.addr
+                .send(UpdateRelated {
+                    column: column.clone(),
+                    count: None,
+                })
+                .await??;
+        }
+        Ok(reports)
+    }
+
+    pub fn add_documents_blocking(
+        &self,
+        documents: &[serde_json::Value],
+    ) -> Result<Vec<(String, IndexReport)>, ProjectError> {
+        block_on(self.add_documents(documents))
+    }
+}
+
+/// Wait for `future` on the calling thread. The actors it talks to run on
+/// the client's thread, so this works outside of any async runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    futures::executor::block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client() {
+        let client = Client::new().unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let missing = client.collection_at_blocking("missing", &dir.to_string_lossy());
+        assert!(missing.is_err());
+        assert_eq!(SearchOptions::default().limit, 10);
+        client.shutdown().unwrap();
+    }
+}
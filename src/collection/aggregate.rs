@@ -0,0 +1,299 @@
+use crate::collection::collection_utils::is_valid_identifier;
+use crate::collection::filter::Filter;
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, SecondsFormat};
+use duckdb::types::Value;
+use serde::{Deserialize, Serialize};
+
+/// Groups returned by an aggregation when it does not set a limit.
+const DEFAULT_GROUPS: u32 = 100;
+/// Most groups an aggregation may return.
+const MAX_GROUPS: u32 = 10_000;
+
+/// An aggregation over the rows of a collection table, e.g. the number of
+/// documents and their mean year per language:
+///
+/// ```json
+/// {"group_by": ["lang"],
+///  "metrics": [{"op": "count"}, {"op": "avg", "field": "year"}],
+///  "filter": {"field": "draft", "op": "eq", "value": false}}
+/// ```
+///
+/// Groups are ordered by their first metric, largest first, then by their
+/// values. Compiled to SQL with [`Aggregation::to_sql`], with validated
+/// column names and the filter's values as bind parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Aggregation {
+    /// Columns to group rows by. A single group of every row when empty.
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// Computed for every group. A count of its rows when empty.
+    #[serde(default)]
+    pub metrics: Vec<AggregateMetric>,
+    /// Only aggregate rows matching this filter.
+    pub filter: Option<Filter>,
+    /// Most groups to return, 100 by default.
+    pub limit: Option<u32>,
+}
+
+/// A value computed for each group, named `count` for a count of rows and
+/// `{op}_{field}` otherwise, e.g. `avg_year`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AggregateMetric {
+    pub op: AggregateOp,
+    /// Column the metric is computed on. Counts without one count rows,
+    /// with one the rows where it is not NULL.
+    pub field: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateOp {
+    Count,
+    Sum,
+    Avg,
+}
+
+impl AggregateMetric {
+    pub fn name(&self) -> String {
+        match (&self.op, &self.field) {
+            (AggregateOp::Count, None) => String::from("count"),
+            (op, Some(field)) => format!("{}_{}", op.sql().to_lowercase(), field),
+            (op, None) => op.sql().to_lowercase(),
+        }
+    }
+
+    fn to_sql(&self) -> anyhow::Result<String> {
+        match (&self.op, &self.field) {
+            (AggregateOp::Count, None) => Ok(String::from("COUNT(*)")),
+            (op, Some(field)) if is_valid_identifier(field) => {
+                Ok(format!("{}({})", op.sql(), field))
+            }
+            (_, Some(field)) => Err(anyhow!("Invalid metric field '{}'", field)),
+            (op, None) => Err(anyhow!(
+                "Metric '{}' needs a field",
+                op.sql().to_lowercase()
+            )),
+        }
+    }
+}
+
+impl AggregateOp {
+    fn sql(&self) -> &'static str {
+        match self {
+            AggregateOp::Count => "COUNT",
+            AggregateOp::Sum => "SUM",
+            AggregateOp::Avg => "AVG",
+        }
+    }
+}
+
+impl Aggregation {
+    /// The metrics computed for every group, defaulting to a count.
+    pub fn metrics(&self) -> Vec<AggregateMetric> {
+        if self.metrics.is_empty() {
+            vec![AggregateMetric {
+                op: AggregateOp::Count,
+                field: None,
+            }]
+        } else {
+            self.metrics.clone()
+        }
+    }
+
+    /// Names of the columns of each result row: the group columns, then
+    /// the metrics.
+    pub fn columns(&self) -> Vec<String> {
+        let metrics = self.metrics().into_iter().map(|metric| metric.name());
+        self.group_by.iter().cloned().chain(metrics).collect()
+    }
+
+    /// Compile the aggregation over `table` to a SQL query and its bind
+    /// parameters. `visibility` is an extra condition rows must meet, with
+    /// its own parameters, e.g. to apply the collection's ACL.
+    pub fn to_sql(
+        &self,
+        table: &str,
+        visibility: Option<(String, Vec<Value>)>,
+    ) -> anyhow::Result<(String, Vec<Value>)> {
+        let limit = self.limit.unwrap_or(DEFAULT_GROUPS);
+        if !(1..=MAX_GROUPS).contains(&limit) {
+            return Err(anyhow!("limit must be between 1 and {}", MAX_GROUPS));
+        }
+        if let Some(column) = self.group_by.iter().find(|c| !is_valid_identifier(c)) {
+            return Err(anyhow!("Invalid group by column '{}'", column));
+        }
+        let metrics = self.metrics();
+        let mut names = self.columns();
+        names.sort();
+        if names.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(anyhow!(
+                "Group columns and metrics must have distinct names"
+            ));
+        }
+
+        let mut select = self.group_by.clone();
+        for metric in &metrics {
+            select.push(format!("{} AS {}", metric.to_sql()?, metric.name()));
+        }
+
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+        if let Some(filter) = &self.filter {
+            let (clause, filter_params) = filter.to_sql()?;
+            clauses.push(format!("({})", clause));
+            params.extend(filter_params);
+        }
+        if let Some((clause, visibility_params)) = visibility {
+            clauses.push(format!("({})", clause));
+            params.extend(visibility_params);
+        }
+
+        let mut sql = format!("SELECT {} FROM {}", select.join(", "), table);
+        if !clauses.is_empty() {
+            sql.push_str(&format!(" WHERE {}", clauses.join(" AND ")));
+        }
+        if !self.group_by.is_empty() {
+            // Positions, as the first metric's name may shadow a column.
+            let first_metric = self.group_by.len() + 1;
+            let groups = (1..first_metric).map(|i| i.to_string()).collect::<Vec<_>>();
+            sql.push_str(&format!(
+                " GROUP BY {groups} ORDER BY {first_metric} DESC NULLS LAST, {groups}",
+                groups = groups.join(", ")
+            ));
+        }
+        sql.push_str(&format!(" LIMIT {};", limit));
+        Ok((sql, params))
+    }
+}
+
+/// Rows of an [`Aggregation`], one object per group with the group's values
+/// and metrics by name.
+#[derive(Serialize, Debug)]
+pub struct AggregateResults {
+    pub groups: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// `value` read from DuckDB as JSON. Dates and timestamps become ISO 8601
+/// strings and types without a JSON counterpart their debug representation.
+pub fn sql_value_to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Boolean(b) => b.into(),
+        Value::TinyInt(i) => i.into(),
+        Value::SmallInt(i) => i.into(),
+        Value::Int(i) => i.into(),
+        Value::BigInt(i) => i.into(),
+        Value::HugeInt(i) => match i64::try_from(i) {
+            Ok(i) => i.into(),
+            Err(_) => (i as f64).into(),
+        },
+        Value::UTinyInt(u) => u.into(),
+        Value::USmallInt(u) => u.into(),
+        Value::UInt(u) => u.into(),
+        Value::UBigInt(u) => u.into(),
+        Value::Float(f) => f.into(),
+        Value::Double(f) => f.into(),
+        Value::Decimal(d) => d
+            .to_string()
+            .parse::<f64>()
+            .map_or(serde_json::Value::Null, Into::into),
+        Value::Text(s) | Value::Enum(s) => s.into(),
+        Value::Timestamp(unit, t) => DateTime::from_timestamp_micros(unit.to_micros(t))
+            .map_or(serde_json::Value::Null, |t| {
+                t.to_rfc3339_opts(SecondsFormat::AutoSi, true).into()
+            }),
+        Value::Date32(days) => NaiveDate::default()
+            .checked_add_signed(chrono::Duration::days(days as i64))
+            .map_or(serde_json::Value::Null, |d| d.to_string().into()),
+        Value::List(values) | Value::Array(values) => {
+            values.into_iter().map(sql_value_to_json).collect()
+        }
+        other => format!("{:?}", other).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(json: &str) -> Aggregation {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_aggregation_sql() {
+        let aggregation = parse(
+            r#"{"group_by": ["lang"],
+                "metrics": [{"op": "count"}, {"op": "avg", "field": "year"}],
+                "filter": {"field": "draft", "op": "eq", "value": false}}"#,
+        );
+        assert_eq!(aggregation.columns(), vec!["lang", "count", "avg_year"]);
+        let (sql, params) = aggregation.to_sql("docs", None).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT lang, COUNT(*) AS count, AVG(year) AS avg_year FROM docs \
+             WHERE (draft = ?) GROUP BY 1 ORDER BY 2 DESC NULLS LAST, 1 LIMIT 100;"
+        );
+        assert_eq!(params, vec![Value::Boolean(false)]);
+
+        let (sql, _) = parse("{}").to_sql("docs", None).unwrap();
+        assert_eq!(sql, "SELECT COUNT(*) AS count FROM docs LIMIT 100;");
+    }
+
+    #[test]
+    fn test_aggregation_on_duckdb() {
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE docs (lang TEXT, year INTEGER, published DATE);
+             INSERT INTO docs VALUES ('en', 2020, '2020-05-01'), ('en', 2022, '2022-01-01'),
+                 ('de', 2021, '2021-03-01');",
+        )
+        .unwrap();
+        let aggregation = parse(
+            r#"{"group_by": ["lang"],
+                "metrics": [{"op": "count"}, {"op": "sum", "field": "year"}]}"#,
+        );
+        let (sql, params) = aggregation.to_sql("docs", None).unwrap();
+        let mut stmt = conn.prepare(&sql).unwrap();
+        let rows: Vec<Vec<serde_json::Value>> = stmt
+            .query_map(duckdb::params_from_iter(params.iter()), |row| {
+                (0..3)
+                    .map(|i| row.get::<_, Value>(i).map(sql_value_to_json))
+                    .collect()
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![json!("en"), json!(2), json!(4042)],
+                vec![json!("de"), json!(1), json!(2021)],
+            ]
+        );
+
+        let published: Value = conn
+            .query_row("SELECT MIN(published) FROM docs;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sql_value_to_json(published), "2020-05-01");
+    }
+
+    #[test]
+    fn test_rejects_invalid_aggregations() {
+        let invalid = [
+            r#"{"group_by": ["lang; DROP TABLE docs"]}"#,
+            r#"{"metrics": [{"op": "sum"}]}"#,
+            r#"{"metrics": [{"op": "avg", "field": "year)"}]}"#,
+            r#"{"group_by": ["count"]}"#,
+            r#"{"limit": 0}"#,
+        ];
+        for json in invalid {
+            assert!(parse(json).to_sql("docs", None).is_err(), "{}", json);
+        }
+        assert!(serde_json::from_str::<Aggregation>(r#"{"metrics": [{"op": "median"}]}"#).is_err());
+    }
+}
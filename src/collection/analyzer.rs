@@ -0,0 +1,190 @@
+use crate::collection::normalization::Normalization;
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Language of a stemmer or a stopword list.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyzerLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Turkish,
+}
+
+/// Languages with their names and ISO 639-1 codes.
+const LANGUAGES: &[(AnalyzerLanguage, &str, &str)] = &[
+    (AnalyzerLanguage::Arabic, "arabic", "ar"),
+    (AnalyzerLanguage::Danish, "danish", "da"),
+    (AnalyzerLanguage::Dutch, "dutch", "nl"),
+    (AnalyzerLanguage::English, "english", "en"),
+    (AnalyzerLanguage::Finnish, "finnish", "fi"),
+    (AnalyzerLanguage::French, "french", "fr"),
+    (AnalyzerLanguage::German, "german", "de"),
+    (AnalyzerLanguage::Greek, "greek", "el"),
+    (AnalyzerLanguage::Hungarian, "hungarian", "hu"),
+    (AnalyzerLanguage::Italian, "italian", "it"),
+    (AnalyzerLanguage::Norwegian, "norwegian", "no"),
+    (AnalyzerLanguage::Portuguese, "portuguese", "pt"),
+    (AnalyzerLanguage::Romanian, "romanian", "ro"),
+    (AnalyzerLanguage::Russian, "russian", "ru"),
+    (AnalyzerLanguage::Spanish, "spanish", "es"),
+    (AnalyzerLanguage::Swedish, "swedish", "sv"),
+    (AnalyzerLanguage::Turkish, "turkish", "tr"),
+];
+
+impl AnalyzerLanguage {
+    fn entry(&self) -> &'static (AnalyzerLanguage, &'static str, &'static str) {
+        LANGUAGES
+            .iter()
+            .find(|(language, _, _)| language == self)
+            .expect("every language is listed")
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            AnalyzerLanguage::Arabic => Algorithm::Arabic,
+            AnalyzerLanguage::Danish => Algorithm::Danish,
+            AnalyzerLanguage::Dutch => Algorithm::Dutch,
+            AnalyzerLanguage::English => Algorithm::English,
+            AnalyzerLanguage::Finnish => Algorithm::Finnish,
+            AnalyzerLanguage::French => Algorithm::French,
+            AnalyzerLanguage::German => Algorithm::German,
+            AnalyzerLanguage::Greek => Algorithm::Greek,
+            AnalyzerLanguage::Hungarian => Algorithm::Hungarian,
+            AnalyzerLanguage::Italian => Algorithm::Italian,
+            AnalyzerLanguage::Norwegian => Algorithm::Norwegian,
+            AnalyzerLanguage::Portuguese => Algorithm::Portuguese,
+            AnalyzerLanguage::Romanian => Algorithm::Romanian,
+            AnalyzerLanguage::Russian => Algorithm::Russian,
+            AnalyzerLanguage::Spanish => Algorithm::Spanish,
+            AnalyzerLanguage::Swedish => Algorithm::Swedish,
+            AnalyzerLanguage::Turkish => Algorithm::Turkish,
+        }
+    }
+
+    /// NLTK stopwords of the language.
+    fn stopwords(&self) -> &'static [&'static str] {
+        stop_words::get(self.entry().2)
+    }
+}
+
+impl fmt::Display for AnalyzerLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.entry().1)
+    }
+}
+
+/// Parse a language name, e.g. `english`, or its ISO 639-1 code, e.g. `en`.
+impl FromStr for AnalyzerLanguage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|(_, language, code)| *language == name || *code == name)
+            .map(|(language, _, _)| *language)
+            .ok_or_else(|| {
+                let names: Vec<&str> = LANGUAGES.iter().map(|(_, name, _)| *name).collect();
+                anyhow::anyhow!(
+                    "Unknown language '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Analysis of texts into the terms that keyword matching compares, such as
+/// the highlighting of query words in snippets: each word is normalized and
+/// lowercased, stopwords are dropped and the rest reduced to their stem, so
+/// that `Running` in a query matches `runs` in a document. Documents and
+/// queries go through the same steps.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Analyzer {
+    /// Normalization of each word before it is lowercased.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<Normalization>,
+    /// Language of the Snowball stemmer words are reduced with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stemmer: Option<AnalyzerLanguage>,
+    /// Languages whose stopwords are left out of the terms.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stopwords: Vec<AnalyzerLanguage>,
+}
+
+impl Analyzer {
+    /// Whether words are only lowercased.
+    pub fn is_default(&self) -> bool {
+        *self == Analyzer::default()
+    }
+
+    /// Term of `word`, or `None` for stopwords.
+    pub fn term(&self, word: &str) -> Option<String> {
+        let word = match &self.normalization {
+            Some(normalization) => normalization.apply(word).to_lowercase(),
+            None => word.to_lowercase(),
+        };
+        if self
+            .stopwords
+            .iter()
+            .any(|language| language.stopwords().contains(&word.as_str()))
+        {
+            return None;
+        }
+        Some(match self.stemmer {
+            Some(language) => Stemmer::create(language.algorithm())
+                .stem(&word)
+                .into_owned(),
+            None => word,
+        })
+    }
+
+    /// Terms of the words of `text`, in order, stopwords left out.
+    pub fn terms(&self, text: &str) -> Vec<String> {
+        text.unicode_words()
+            .filter_map(|word| self.term(word))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyzer() {
+        let default = Analyzer::default();
+        assert!(default.is_default());
+        assert_eq!(default.terms("The Runners"), vec!["the", "runners"]);
+
+        let english = Analyzer {
+            normalization: Some("latin".parse().unwrap()),
+            stemmer: Some("english".parse().unwrap()),
+            stopwords: vec!["en".parse().unwrap()],
+        };
+        assert_eq!(
+            english.terms("The runners were running to the Café"),
+            vec!["runner", "run", "cafe"]
+        );
+        assert_eq!(english.term("Running"), english.term("runs"));
+        assert!("klingon".parse::<AnalyzerLanguage>().is_err());
+    }
+}
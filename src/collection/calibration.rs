@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How similarity scores of a collection are normalized before being merged
+/// with those of other collections.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CalibrationMethod {
+    /// Map the observed scores to `[0, 1]`.
+    MinMax,
+    /// Subtract the mean of the observed scores and divide by their standard
+    /// deviation.
+    #[default]
+    ZScore,
+}
+
+impl fmt::Display for CalibrationMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CalibrationMethod::MinMax => "minmax",
+            CalibrationMethod::ZScore => "zscore",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for CalibrationMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "minmax" => Ok(CalibrationMethod::MinMax),
+            "zscore" => Ok(CalibrationMethod::ZScore),
+            _ => Err(anyhow::anyhow!(
+                "Unknown calibration method '{}', expected minmax or zscore",
+                s
+            )),
+        }
+    }
+}
+
+/// Affine map from a collection's similarity scores to calibrated scores,
+/// `(score - center) / scale`, learned from the scores of sample queries.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ScoreCalibration {
+    pub method: CalibrationMethod,
+    pub center: f32,
+    pub scale: f32,
+    /// Number of scores the calibration was learned from.
+    pub samples: usize,
+}
+
+impl ScoreCalibration {
+    /// Learn a calibration from observed similarity scores. Returns `None`
+    /// when there are no scores to learn from.
+    pub fn fit(method: CalibrationMethod, scores: &[f32]) -> Option<Self> {
+        if scores.is_empty() {
+            return None;
+        }
+        let (center, spread) = match method {
+            CalibrationMethod::MinMax => {
+                let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                (min, max - min)
+            }
+            CalibrationMethod::ZScore => {
+                let n = scores.len() as f64;
+                let mean = scores.iter().map(|s| *s as f64).sum::<f64>() / n;
+                let variance = scores
+                    .iter()
+                    .map(|s| (*s as f64 - mean).powi(2))
+                    .sum::<f64>()
+                    / n;
+                (mean as f32, variance.sqrt() as f32)
+            }
+        };
+        // All scores being equal tells nothing about their spread.
+        let scale = if spread > f32::EPSILON { spread } else { 1.0 };
+        Some(Self {
+            method,
+            center,
+            scale,
+            samples: scores.len(),
+        })
+    }
+
+    pub fn apply(&self, score: f32) -> f32 {
+        (score - self.center) / self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_and_apply() {
+        let scores = [0.2, 0.4, 0.6, 0.8];
+
+        let minmax = ScoreCalibration::fit(CalibrationMethod::MinMax, &scores).unwrap();
+        assert!((minmax.apply(0.2) - 0.0).abs() < 1e-6);
+        assert!((minmax.apply(0.8) - 1.0).abs() < 1e-6);
+
+        let zscore = ScoreCalibration::fit(CalibrationMethod::ZScore, &scores).unwrap();
+        assert!(zscore.apply(0.5).abs() < 1e-6);
+        assert!((zscore.apply(0.8) - 1.341_640_8).abs() < 1e-5);
+        assert_eq!(zscore.samples, 4);
+
+        let constant = ScoreCalibration::fit(CalibrationMethod::ZScore, &[0.5, 0.5]).unwrap();
+        assert_eq!(constant.apply(0.7), 0.7 - 0.5);
+        assert!(ScoreCalibration::fit(CalibrationMethod::MinMax, &[]).is_none());
+    }
+}
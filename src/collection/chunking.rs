@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokenizers::Tokenizer;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Table mapping the chunks of chunked columns back to their documents, one
+/// row per `(column_name, _key, chunk)` with the chunk's text and embedding.
+pub const CHUNKS_TABLE: &str = "_letsearch_chunks";
+
+/// What the size and overlap of chunks are counted in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkUnit {
+    /// Fixed windows of tokens of the model's tokenizer.
+    #[default]
+    Tokens,
+    /// Whole sentences packed up to the chunk size in tokens. The overlap
+    /// repeats trailing sentences of the previous chunk.
+    Sentences,
+}
+
+impl fmt::Display for ChunkUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChunkUnit::Tokens => "tokens",
+            ChunkUnit::Sentences => "sentences",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ChunkUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tokens" => Ok(ChunkUnit::Tokens),
+            "sentences" => Ok(ChunkUnit::Sentences),
+            _ => Err(anyhow::anyhow!(
+                "Unknown chunk unit '{}', expected one of tokens, sentences",
+                s
+            )),
+        }
+    }
+}
+
+/// How documents longer than the model's input are split before embedding.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Chunking {
+    /// Maximum number of tokens per chunk.
+    pub size: usize,
+    /// Number of tokens shared by consecutive chunks.
+    #[serde(default)]
+    pub overlap: usize,
+    #[serde(default)]
+    pub unit: ChunkUnit,
+}
+
+impl Chunking {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.size == 0 {
+            return Err(anyhow::anyhow!("Chunk size must be greater than 0"));
+        }
+        if self.overlap >= self.size {
+            return Err(anyhow::anyhow!(
+                "Chunk overlap ({}) must be smaller than the chunk size ({})",
+                self.overlap,
+                self.size
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Chunking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tokens with an overlap of {}, by {}",
+            self.size, self.overlap, self.unit
+        )
+    }
+}
+
+/// Byte range of a chunk within its document.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A chunk of a document, numbered from 0 in document order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub index: usize,
+    pub span: Span,
+    pub text: String,
+}
+
+/// Splits documents into chunks counted in tokens of the model's tokenizer,
+/// or in words for models without one.
+pub struct TextChunker {
+    chunking: Chunking,
+    tokenizer: Option<Tokenizer>,
+}
+
+impl TextChunker {
+    pub fn new(chunking: Chunking, tokenizer: Option<Arc<Tokenizer>>) -> Self {
+        // Chunks are cut from the full token sequence, so it must be neither
+        // truncated nor padded.
+        let tokenizer = tokenizer.and_then(|tokenizer| {
+            let mut tokenizer = (*tokenizer).clone();
+            tokenizer.with_padding(None);
+            tokenizer.with_truncation(None).ok()?;
+            Some(tokenizer)
+        });
+        Self {
+            chunking,
+            tokenizer,
+        }
+    }
+
+    /// Byte ranges of the tokens of `text`.
+    fn tokens(&self, text: &str) -> Vec<Span> {
+        let encoding = self
+            .tokenizer
+            .as_ref()
+            .and_then(|tokenizer| tokenizer.encode(text, false).ok());
+        match encoding {
+            Some(encoding) => encoding
+                .get_offsets()
+                .iter()
+                .filter(|(start, end)| start < end)
+                .map(|&(start, end)| Span { start, end })
+                .collect(),
+            None => text
+                .split_word_bound_indices()
+                .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+                .map(|(start, word)| Span {
+                    start,
+                    end: start + word.len(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Split `text` into chunks. Texts within the chunk size stay whole.
+    pub fn split(&self, text: &str) -> Vec<Chunk> {
+        let tokens = self.tokens(text);
+        let spans = if tokens.len() <= self.chunking.size {
+            vec![Span {
+                start: 0,
+                end: text.len(),
+            }]
+        } else {
+            match self.chunking.unit {
+                ChunkUnit::Tokens => self.token_windows(&tokens),
+                ChunkUnit::Sentences => self.sentence_chunks(text, &tokens),
+            }
+        };
+        spans
+            .into_iter()
+            .enumerate()
+            .map(|(index, span)| {
+                let span = char_boundaries(text, span);
+                Chunk {
+                    index,
+                    span,
+                    text: text[span.start..span.end].to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Windows of `size` tokens, each starting `size - overlap` tokens after
+    /// the previous one.
+    fn token_windows(&self, tokens: &[Span]) -> Vec<Span> {
+        let Chunking { size, overlap, .. } = self.chunking;
+        let stride = size.saturating_sub(overlap).max(1);
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while start < tokens.len() {
+            let end = (start + size).min(tokens.len());
+            spans.push(Span {
+                start: tokens[start].start,
+                end: tokens[end - 1].end,
+            });
+            if end == tokens.len() {
+                break;
+            }
+            start += stride;
+        }
+        spans
+    }
+
+    /// Whole sentences packed up to `size` tokens. Sentences longer than
+    /// that are split into token windows.
+    fn sentence_chunks(&self, text: &str, tokens: &[Span]) -> Vec<Span> {
+        let Chunking { size, overlap, .. } = self.chunking;
+        // Each sentence with the range of its tokens.
+        let sentences: Vec<(Span, std::ops::Range<usize>)> = text
+            .split_sentence_bound_indices()
+            .filter_map(|(start, sentence)| {
+                let trimmed = sentence.trim_end();
+                let start = start + (trimmed.len() - trimmed.trim_start().len());
+                let span = Span {
+                    start,
+                    end: start + trimmed.trim_start().len(),
+                };
+                let first = tokens.partition_point(|token| token.start < span.start);
+                let last = tokens.partition_point(|token| token.start < span.end);
+                (first < last).then_some((span, first..last))
+            })
+            .collect();
+
+        let mut spans = Vec::new();
+        let mut current: Vec<(Span, std::ops::Range<usize>)> = Vec::new();
+        let mut current_tokens = 0;
+        for (span, range) in sentences {
+            let len = range.len();
+            if !current.is_empty() && current_tokens + len > size {
+                spans.push(Span {
+                    start: current[0].0.start,
+                    end: current[current.len() - 1].0.end,
+                });
+                // Carry over the trailing sentences that fit in the overlap.
+                let mut carried = 0;
+                let mut keep = current.len();
+                while keep > 0 && carried + current[keep - 1].1.len() <= overlap {
+                    keep -= 1;
+                    carried += current[keep].1.len();
+                }
+                current.drain(..keep);
+                current_tokens = carried;
+                if current_tokens + len > size {
+                    current.clear();
+                    current_tokens = 0;
+                }
+            }
+            if len > size {
+                spans.extend(self.token_windows(&tokens[range]));
+                continue;
+            }
+            current.push((span, range));
+            current_tokens += len;
+        }
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            spans.push(Span {
+                start: first.0.start,
+                end: last.0.end,
+            });
+        }
+        spans
+    }
+}
+
+/// Widen `span` to the nearest character boundaries, as byte-level
+/// tokenizers may cut through multi-byte characters.
+fn char_boundaries(text: &str, span: Span) -> Span {
+    let mut start = span.start.min(text.len());
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = span.end.clamp(start, text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+    Span { start, end }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunker(size: usize, overlap: usize, unit: ChunkUnit) -> TextChunker {
+        TextChunker::new(
+            Chunking {
+                size,
+                overlap,
+                unit,
+            },
+            None,
+        )
+    }
+
+    fn texts(chunks: Vec<Chunk>) -> Vec<String> {
+        chunks.into_iter().map(|chunk| chunk.text).collect()
+    }
+
+    #[test]
+    fn test_short_text_is_one_chunk() {
+        let chunks = chunker(8, 2, ChunkUnit::Tokens).split("a short text");
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "a short text");
+        assert_eq!(chunks[0].span, Span { start: 0, end: 12 });
+    }
+
+    #[test]
+    fn test_token_windows() {
+        let chunks = chunker(3, 1, ChunkUnit::Tokens).split("one two three four five six");
+        assert_eq!(
+            texts(chunks),
+            vec!["one two three", "three four five", "five six"]
+        );
+    }
+
+    #[test]
+    fn test_sentence_chunks() {
+        let text = "One two three. Four five. Six seven eight. Nine.";
+        let chunks = chunker(5, 3, ChunkUnit::Sentences).split(text);
+        assert_eq!(
+            texts(chunks.clone()),
+            vec![
+                "One two three. Four five.",
+                "Four five. Six seven eight.",
+                "Six seven eight. Nine."
+            ]
+        );
+        for chunk in chunks {
+            assert_eq!(&text[chunk.span.start..chunk.span.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_long_sentence_is_split() {
+        let chunks = chunker(2, 0, ChunkUnit::Sentences).split("Hi. One two three four.");
+        assert_eq!(texts(chunks), vec!["Hi.", "One two", "three four"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Chunking {
+            size: 4,
+            overlap: 4,
+            unit: ChunkUnit::Tokens
+        }
+        .validate()
+        .is_err());
+        assert!("paragraphs".parse::<ChunkUnit>().is_err());
+        assert_eq!(
+            "Sentences".parse::<ChunkUnit>().unwrap(),
+            ChunkUnit::Sentences
+        );
+    }
+}
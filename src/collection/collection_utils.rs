@@ -1,31 +1,263 @@
+use crate::collection::analyzer::Analyzer;
+use crate::collection::calibration::ScoreCalibration;
+use crate::collection::chunking::Chunking;
+use crate::collection::language::Languages;
+use crate::collection::normalization::Normalization;
+use crate::collection::vector_index::{IndexStats, Metric, ScoreType};
+use crate::config::{default_home_dir, settings, DEFAULT_MODEL};
+use crate::error::ProjectError;
+use crate::model::metadata::Prompts;
+use crate::model::model_utils::Backend;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::path::PathBuf;
-
-const DEFAULT_HOME_DIR: &str = ".letsearch";
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
+/// Directory holding collections and downloaded models: `LETSEARCH_HOME`,
+/// then `data_dir` in `letsearch.toml`, then a per-user default.
 pub fn home_dir() -> PathBuf {
-    std::env::var("LETSEARCH_HOME")
-        .unwrap_or_else(|_| DEFAULT_HOME_DIR.to_string())
-        .into()
+    if let Ok(home) = std::env::var("LETSEARCH_HOME") {
+        return home.into();
+    }
+    match settings().data_dir.as_ref() {
+        Some(dir) => dir.into(),
+        None => default_home_dir(),
+    }
+}
+
+/// Default directory holding the database, index files and `config.json` of a
+/// collection. Collections created with a custom `data_dir` keep a
+/// [`ConfigPointer`] to it here so they can still be loaded by name. Collections of a
+/// namespace, named `{namespace}/{name}`, live under the namespace's own
+/// directory.
+pub fn collection_dir(name: &str) -> PathBuf {
+    match split_name(name) {
+        (Some(namespace), name) => home_dir()
+            .join("namespaces")
+            .join(namespace)
+            .join("collections")
+            .join(name),
+        (None, name) => home_dir().join("collections").join(name),
+    }
+}
+
+/// Separates the namespace from the collection name in qualified names.
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// Name of the collection `name` of `namespace`, unique across namespaces.
+/// Collections of the default namespace keep their plain name.
+pub fn qualified_name(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, name),
+        None => name.to_string(),
+    }
+}
+
+/// Split a qualified collection name into its namespace, if any, and name.
+pub fn split_name(qualified: &str) -> (Option<&str>, &str) {
+    match qualified.split_once(NAMESPACE_SEPARATOR) {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, qualified),
+    }
+}
+
+/// `config.json` left in the default directory of a collection stored in a
+/// custom `data_dir`, pointing to the config there, so that the collection
+/// can be loaded by name while its config is written in one place.
+#[derive(Serialize, Deserialize)]
+struct ConfigPointer {
+    data_dir: String,
+}
+
+/// Return `true` when `name` is a safe SQL identifier (alphanumeric + `_`).
+///
+/// Column names and other identifiers that must be interpolated directly into
+/// SQL strings (they cannot be parameterized) are validated with this guard
+/// to prevent SQL-injection attacks.
+pub fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct CollectionConfig {
     #[serde(default = "default_collection_name")]
     pub name: String,
+    /// Namespace of the collection, which scopes its name and directory so
+    /// that several applications can use the same letsearch home. The
+    /// default namespace when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
     #[serde(default = "default_index_columns")]
     pub index_columns: Vec<String>,
     #[serde(default = "default_model_name")]
     pub model_name: String,
     #[serde(default = "default_model_variant")]
     pub model_variant: String,
+    /// Model of the index columns that are not embedded with the
+    /// collection's model, e.g. a code model for a `source` column next to
+    /// a text model for `description`.
+    #[serde(default)]
+    pub column_models: HashMap<String, ColumnModel>,
     #[serde(default = "default_db_path")]
     pub db_path: String,
     #[serde(default = "default_index_dir")]
     pub index_dir: String,
     #[serde(default = "default_serialization_version")]
     pub serialization_version: u32,
+    /// Base URL of the OpenAI-compatible embeddings API for `openai://` models.
+    /// Persisted so that `serve` talks to the same endpoint used at index time.
+    #[serde(default)]
+    pub openai_base_url: Option<String>,
+    /// Directory to store this collection in instead of
+    /// `$LETSEARCH_HOME/collections/{name}`, e.g. a faster disk for the index.
+    #[serde(default)]
+    pub data_dir: Option<String>,
+    /// Distance metric of new vector indexes. When unset, the metric in the
+    /// model's metadata is used, falling back to cosine. Existing indexes
+    /// keep the metric they were built with.
+    #[serde(default)]
+    pub metric: Option<Metric>,
+    /// Per-column metric of new vector indexes, overriding `metric`.
+    #[serde(default)]
+    pub column_metrics: HashMap<String, Metric>,
+    /// Column to return as the `content` of search results, per indexed
+    /// column, e.g. search on `chunk_text` but display `title`. Indexed
+    /// columns without an entry display themselves.
+    #[serde(default)]
+    pub display_columns: HashMap<String, String>,
+    /// List column holding the groups allowed to see each document. When
+    /// set, the server only returns documents sharing a group with the
+    /// caller; documents with a NULL or empty list are visible to nobody.
+    #[serde(default)]
+    pub acl_column: Option<String>,
+    /// Number of related documents precomputed per document, per indexed
+    /// column. Listed columns are refreshed whenever documents are added.
+    #[serde(default)]
+    pub related_counts: HashMap<String, usize>,
+    /// Normalization of this collection's similarity scores, applied when
+    /// its results are merged with those of other collections.
+    #[serde(default)]
+    pub calibration: Option<ScoreCalibration>,
+    /// Normalization applied to documents before they are embedded and to
+    /// queries before they are searched.
+    #[serde(default)]
+    pub normalization: Option<Normalization>,
+    /// Splitting of long documents into chunks embedded separately. Search
+    /// results then carry the chunk that matched.
+    #[serde(default)]
+    pub chunking: Option<Chunking>,
+    /// Keep the embeddings of indexed documents in the database, so that
+    /// indexes can be rebuilt without embedding the documents again. The
+    /// embeddings of chunked columns are always kept with their chunks.
+    #[serde(default)]
+    pub store_embeddings: bool,
+    /// Dimensions the embeddings are truncated to before they are indexed
+    /// and searched, smaller than the model's for models trained with
+    /// Matryoshka representation learning, e.g. nomic-embed. Truncated
+    /// embeddings are scaled back to unit length.
+    #[serde(default)]
+    pub embedding_dim: Option<usize>,
+    /// Candidates fetched per result from quantized indexes, e.g. `b1`,
+    /// and ranked again by their distance to the query in the stored
+    /// embeddings. Requires `store_embeddings` or chunking.
+    #[serde(default)]
+    pub rescore: Option<usize>,
+    /// Templates applied to documents before they are indexed and to
+    /// queries before they are searched, e.g. `"passage: "` and
+    /// `"query: "` for E5 models. Unset ones fall back to the prompts in the
+    /// metadata of the model embedding the column.
+    #[serde(default, skip_serializing_if = "Prompts::is_empty")]
+    pub prompts: Prompts,
+    /// Columns that searches may filter on. Each gets a DuckDB index when
+    /// the collection is imported, and filters on other columns are
+    /// rejected rather than scanning the table.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filterable_columns: Vec<String>,
+    /// Latitude and longitude columns that geo filters of searches apply
+    /// to. Geo filters are rejected when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo_columns: Option<GeoColumns>,
+    /// DuckDB extensions loaded whenever the collection's database is
+    /// opened, e.g. `httpfs` to import from S3, as names or paths to
+    /// `.duckdb_extension` files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duckdb_extensions: Vec<String>,
+    /// Directory DuckDB extensions are installed to and loaded from. When
+    /// set, extensions are never downloaded and must be bundled there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duckdb_extension_dir: Option<String>,
+    /// Index columns holding the paths or URLs of images rather than text,
+    /// embedded with the image encoder of CLIP-style models for
+    /// text-to-image search.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_columns: Vec<String>,
+    /// Detection of the language of each document into a `lang` column, and
+    /// of each query to pick its prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub languages: Option<Languages>,
+    /// Analysis of documents and queries into the terms keyword matching
+    /// compares, e.g. when highlighting query words in snippets.
+    #[serde(default, skip_serializing_if = "Analyzer::is_default")]
+    pub analyzer: Analyzer,
+    /// Column whose values, e.g. titles, complete what is typed in a search
+    /// box, from a prefix index rebuilt after each import.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_column: Option<String>,
+    /// Directory local image paths are read from, relative ones included.
+    /// Paths leading outside of it are refused, and without it only image
+    /// URLs are read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_root: Option<String>,
+    /// Hosts image URLs may be fetched from, redirects included. Image URLs
+    /// are refused when empty, and so are hosts resolving to private,
+    /// loopback or link-local addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_url_hosts: Vec<String>,
+}
+
+/// Columns holding the latitude and longitude of each document, in degrees.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GeoColumns {
+    pub lat: String,
+    pub lon: String,
+}
+
+/// Model and variant embedding an index column.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ColumnModel {
+    pub model_name: String,
+    pub model_variant: String,
+}
+
+/// A model a collection needs loaded, with the backend running it.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct ModelRequest {
+    pub name: String,
+    pub variant: String,
+    pub backend: Backend,
+}
+
+impl ModelRequest {
+    pub fn new(name: &str, variant: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            variant: variant.to_string(),
+            backend: Backend::of_model(name),
+        }
+    }
+
+    /// Key of the loaded model in the collection manager.
+    pub fn key(&self) -> (String, String) {
+        (self.name.clone(), self.variant.clone())
+    }
+}
+
+impl std::fmt::Display for ModelRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.name, self.variant)
+    }
 }
 
 fn default_collection_name() -> String {
@@ -37,7 +269,7 @@ fn default_index_columns() -> Vec<String> {
 }
 
 fn default_model_name() -> String {
-    String::from("hf://mys/minilm")
+    String::from(DEFAULT_MODEL)
 }
 
 fn default_model_variant() -> String {
@@ -56,26 +288,303 @@ fn default_serialization_version() -> u32 {
     1
 }
 
-impl CollectionConfig {
-    pub fn default() -> Self {
+impl Default for CollectionConfig {
+    fn default() -> Self {
         CollectionConfig {
             name: default_collection_name(),
+            namespace: None,
             index_columns: default_index_columns(),
             model_name: default_model_name(),
             model_variant: default_model_variant(),
+            column_models: HashMap::new(),
             db_path: default_db_path(),
             index_dir: default_index_dir(),
             serialization_version: default_serialization_version(),
+            openai_base_url: None,
+            data_dir: None,
+            metric: None,
+            column_metrics: HashMap::new(),
+            display_columns: HashMap::new(),
+            acl_column: None,
+            related_counts: HashMap::new(),
+            calibration: None,
+            normalization: None,
+            chunking: None,
+            store_embeddings: false,
+            embedding_dim: None,
+            rescore: None,
+            prompts: Prompts::default(),
+            filterable_columns: Vec::new(),
+            geo_columns: None,
+            duckdb_extensions: Vec::new(),
+            duckdb_extension_dir: None,
+            image_columns: Vec::new(),
+            languages: None,
+            analyzer: Analyzer::default(),
+            prefix_column: None,
+            image_root: None,
+            image_url_hosts: Vec::new(),
         }
     }
+}
 
+impl CollectionConfig {
     pub fn from_file(name: &str) -> anyhow::Result<Self> {
-        let collection_dir = home_dir().join("collections").join(name);
-        let config_path = collection_dir.join("config.json");
-        let config_file = File::open(config_path)?;
-        let config: CollectionConfig = serde_json::from_reader(config_file)?;
+        Self::read(&collection_dir(name).join("config.json"))
+    }
+
+    /// Read the config at `path`, following a [`ConfigPointer`] to the
+    /// config in the collection's `data_dir`.
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let value: serde_json::Value = serde_json::from_reader(File::open(path)?)?;
+        if value.get("name").is_none() {
+            if let Ok(pointer) = serde_json::from_value::<ConfigPointer>(value.clone()) {
+                return Self::from_dir(Path::new(&pointer.data_dir));
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Load the config of a collection stored at an explicit directory.
+    /// The directory becomes the collection's `data_dir`, so a collection
+    /// directory that was moved or copied elsewhere keeps working.
+    pub fn from_dir(dir: &Path) -> anyhow::Result<Self> {
+        let config_file = File::open(dir.join("config.json"))?;
+        let mut config: CollectionConfig = serde_json::from_reader(config_file)?;
+        config.data_dir = Some(dir.to_string_lossy().to_string());
         Ok(config)
     }
+
+    /// Column shown as the content of search results on `index_column`.
+    pub fn display_column<'a>(&'a self, index_column: &'a str) -> &'a str {
+        self.display_columns
+            .get(index_column)
+            .map(String::as_str)
+            .unwrap_or(index_column)
+    }
+
+    /// Model and variant of the collection, embedding the index columns
+    /// without a model of their own.
+    pub fn model(&self) -> ColumnModel {
+        ColumnModel {
+            model_name: self.model_name.clone(),
+            model_variant: self.model_variant.clone(),
+        }
+    }
+
+    /// Model and variant embedding `index_column`: the column's own model,
+    /// then the collection's.
+    pub fn column_model(&self, index_column: &str) -> ColumnModel {
+        self.column_models
+            .get(index_column)
+            .cloned()
+            .unwrap_or_else(|| self.model())
+    }
+
+    /// Record the models columns are now embedded with. The collection's
+    /// model changes when every column embedded with it moved to the same
+    /// model, the other columns get theirs as their own.
+    pub fn set_column_models(&mut self, models: &HashMap<String, ColumnModel>) {
+        let moved: Vec<Option<&ColumnModel>> = self
+            .index_columns
+            .iter()
+            .filter(|column| !self.column_models.contains_key(*column))
+            .map(|column| models.get(column))
+            .collect();
+        if let [Some(model), rest @ ..] = moved.as_slice() {
+            if rest.iter().all(|other| *other == Some(*model)) {
+                self.model_name = model.model_name.clone();
+                self.model_variant = model.model_variant.clone();
+            }
+        }
+        self.column_models
+            .extend(models.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let collection_model = self.model();
+        self.column_models
+            .retain(|_, column_model| *column_model != collection_model);
+    }
+
+    /// Every model the collection needs loaded: the collection's model,
+    /// then those of its columns, each listed once.
+    pub fn requested_models(&self) -> Vec<ModelRequest> {
+        let mut columns: Vec<&String> = self.column_models.keys().collect();
+        columns.sort();
+        let mut models: Vec<ModelRequest> = Vec::new();
+        let column_models = columns
+            .into_iter()
+            .map(|column| &self.column_models[column]);
+        for model in std::iter::once(&self.model()).chain(column_models) {
+            let request = ModelRequest::new(&model.model_name, &model.model_variant);
+            if !models.contains(&request) {
+                models.push(request);
+            }
+        }
+        models
+    }
+
+    /// Metric of a new index on `index_column`: the column's configured
+    /// metric, then the collection's, then the model's, then cosine.
+    pub fn index_metric(&self, index_column: &str, model_metric: Option<Metric>) -> Metric {
+        self.column_metrics
+            .get(index_column)
+            .copied()
+            .or(self.metric)
+            .or(model_metric)
+            .unwrap_or_default()
+    }
+
+    /// Name the collection is loaded and served by, see [`qualified_name`].
+    pub fn qualified_name(&self) -> String {
+        qualified_name(self.namespace.as_deref(), &self.name)
+    }
+
+    /// Directory where the database and index files of this collection live.
+    pub fn dir(&self) -> PathBuf {
+        match self.data_dir.as_ref() {
+            Some(dir) => PathBuf::from(dir),
+            None => collection_dir(&self.qualified_name()),
+        }
+    }
+
+    /// Write this config to `config.json` in the collection directory so the
+    /// collection can later be loaded by name, e.g. by `letsearch serve`.
+    /// Collections in a custom `data_dir` get a [`ConfigPointer`] to it in
+    /// their default directory.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let dir = self.dir();
+        std::fs::create_dir_all(&dir)?;
+        let config_file = File::create(dir.join("config.json"))?;
+        serde_json::to_writer_pretty(config_file, self)?;
+
+        if let Some(data_dir) = &self.data_dir {
+            let default_dir = collection_dir(&self.qualified_name());
+            if default_dir != dir {
+                std::fs::create_dir_all(&default_dir)?;
+                let pointer_file = File::create(default_dir.join("config.json"))?;
+                let pointer = ConfigPointer {
+                    data_dir: data_dir.clone(),
+                };
+                serde_json::to_writer_pretty(pointer_file, &pointer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the files of this collection, e.g. to overwrite it. Its
+    /// default directory belongs to letsearch and is deleted whole, while a
+    /// custom `data_dir` may hold other files, so only the config, database
+    /// and indexes letsearch writes there are deleted.
+    pub fn remove_files(&self) -> std::io::Result<()> {
+        let dir = self.dir();
+        if !dir.exists() {
+            return Ok(());
+        }
+        if self.data_dir.is_none() {
+            return std::fs::remove_dir_all(&dir);
+        }
+
+        let db_path = self.db_path.as_str();
+        for file in [
+            String::from("config.json"),
+            db_path.to_string(),
+            format!("{}.wal", db_path),
+        ] {
+            match std::fs::remove_file(dir.join(file)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        // DuckDB spills to `{db_path}.tmp` when queries run out of memory.
+        for subdir in [self.index_dir.clone(), format!("{}.tmp", db_path)] {
+            match std::fs::remove_dir_all(dir.join(subdir)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// One line describing the collection, e.g. in errors.
+    pub fn summary(&self) -> String {
+        let columns: Vec<String> = self
+            .index_columns
+            .iter()
+            .map(|column| match self.column_models.get(column) {
+                Some(model) => format!(
+                    "{} with {} ({})",
+                    column, model.model_name, model.model_variant
+                ),
+                None => column.clone(),
+            })
+            .collect();
+        format!(
+            "model {} ({}), index columns [{}], stored in {}",
+            self.model_name,
+            self.model_variant,
+            columns.join(", "),
+            self.dir().display()
+        )
+    }
+
+    /// Settings in which `requested`, a config to create a collection with,
+    /// differs from this one. Settings changed by later commands, such as
+    /// the score calibration, are not compared.
+    pub fn differences(&self, requested: &CollectionConfig) -> Vec<ConfigDifference> {
+        const SKIPPED: &[&str] = &[
+            "serialization_version",
+            "data_dir",
+            "calibration",
+            "related_counts",
+        ];
+        let to_map = |config: &CollectionConfig| match serde_json::to_value(config) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        let (existing, requested_map) = (to_map(self), to_map(requested));
+
+        let mut differences: Vec<ConfigDifference> = existing
+            .iter()
+            .filter(|(field, _)| !SKIPPED.contains(&field.as_str()))
+            .filter_map(|(field, value)| {
+                let other = requested_map
+                    .get(field)
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null);
+                (*value != other).then(|| ConfigDifference {
+                    field: field.clone(),
+                    existing: value.clone(),
+                    requested: other,
+                })
+            })
+            .collect();
+        // Where the files live matters, not how the directory was given.
+        if self.dir() != requested.dir() {
+            differences.push(ConfigDifference {
+                field: String::from("dir"),
+                existing: self.dir().to_string_lossy().into(),
+                requested: requested.dir().to_string_lossy().into(),
+            });
+        }
+        differences
+    }
+}
+
+/// A setting in which an existing collection differs from the one requested.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ConfigDifference {
+    pub field: String,
+    pub existing: serde_json::Value,
+    pub requested: serde_json::Value,
+}
+
+impl std::fmt::Display for ConfigDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is {}, requested {}",
+            self.field, self.existing, self.requested
+        )
+    }
 }
 
 #[derive(Serialize)]
@@ -83,4 +592,317 @@ pub struct SearchResult {
     pub content: String,
     pub key: u64,
     pub score: f32,
+    /// Index column the document matched on.
+    pub column: String,
+    /// Chunk of the document that matched, when the collection is chunked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<MatchedChunk>,
+    /// Highlighted part of `content`, when requested, see
+    /// [`crate::collection::snippet::snippet`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Part of a chunked document that matched a query.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MatchedChunk {
+    /// Position of the chunk in the document, from 0.
+    pub index: usize,
+    pub text: String,
+    /// Byte range of the chunk in the indexed column's value, to highlight
+    /// it within the document.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Search results together with how their scores should be read.
+#[derive(Serialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    /// Metric of the searched index.
+    pub metric: Metric,
+    /// `similarity` (higher is better) or `distance` (lower is better).
+    pub score_type: ScoreType,
+    /// Whether a searched index was receiving new vectors. Searches are not
+    /// blocked by indexing, so recently added documents may be missing.
+    pub indexing: bool,
+    /// Whether the search timed out before the contents of the results
+    /// were read. Results are then ranked as usual but their `content` is
+    /// empty.
+    pub partial: bool,
+    /// Language detected for the query, for collections that detect the
+    /// languages of their documents.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_language: Option<String>,
+}
+
+/// Most results a search returns at once.
+pub const MAX_SEARCH_LIMIT: u32 = 100;
+/// Most top results a search skips to page through them.
+pub const MAX_SEARCH_OFFSET: u32 = 1000;
+
+/// Check the `limit` and `offset` of a search against [`MAX_SEARCH_LIMIT`]
+/// and [`MAX_SEARCH_OFFSET`], the bounds of every way to search.
+pub fn check_page(limit: u32, offset: u32) -> Result<(), ProjectError> {
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(ProjectError::InvalidLimit(MAX_SEARCH_LIMIT));
+    }
+    if offset > MAX_SEARCH_OFFSET {
+        return Err(ProjectError::InvalidOffset(MAX_SEARCH_OFFSET));
+    }
+    Ok(())
+}
+
+/// Time limit of a search.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchTimeout {
+    pub timeout: Duration,
+    /// When the limit is hit after the vector search, return the ranked
+    /// results without their contents, flagged as `partial`, instead of
+    /// failing.
+    pub partial_ok: bool,
+}
+
+/// A result of a search over several collections.
+#[derive(Serialize)]
+pub struct FederatedResult {
+    pub collection: String,
+    #[serde(flatten)]
+    pub result: SearchResult,
+}
+
+/// Results of a search over several collections, merged by calibrated score.
+#[derive(Serialize)]
+pub struct FederatedResults {
+    pub results: Vec<FederatedResult>,
+    /// Whether every searched collection has a score calibration. Raw
+    /// similarities of different models are not comparable, so the order
+    /// is only reliable when this is true.
+    pub calibrated: bool,
+    /// Whether a searched index was receiving new vectors.
+    pub indexing: bool,
+    /// Whether a searched collection timed out and returned partial results.
+    pub partial: bool,
+}
+
+/// Table recording the rows left out of an index, either NULL or failing
+/// with `--skip-errors`, one row per `(column_name, _key)`. They count as
+/// indexed so that incremental indexing resumes after them.
+pub const SKIPPED_TABLE: &str = "_letsearch_skipped";
+
+/// Table holding the embeddings of indexed documents when
+/// [`CollectionConfig::store_embeddings`] is set, one row per
+/// `(column_name, _key)` with the embedding as little-endian `f32`s.
+pub const EMBEDDINGS_TABLE: &str = "_letsearch_embeddings";
+
+/// Error recorded in [`SKIPPED_TABLE`] for NULL values.
+pub const NULL_VALUE: &str = "NULL value";
+
+/// A row left out of an index because it could not be embedded or added.
+#[derive(Serialize, Clone, Debug)]
+pub struct SkippedRow {
+    pub key: u64,
+    pub error: String,
+}
+
+/// Outcome of indexing a column.
+#[derive(Serialize, Default, Debug)]
+pub struct IndexReport {
+    pub indexed: u64,
+    /// Rows left out because their value is NULL.
+    pub nulls: u64,
+    pub skipped: Vec<SkippedRow>,
+}
+
+/// Sizes, shapes and modification times of a collection, reported by
+/// `GET /collections/{name}/stats` and `letsearch stats`.
+#[derive(Serialize)]
+pub struct DetailedStats {
+    pub name: String,
+    pub documents: u64,
+    pub model_name: String,
+    pub model_variant: String,
+    pub index_columns: Vec<String>,
+    /// Index of each indexed column that has one.
+    pub indexes: BTreeMap<String, IndexStats>,
+    /// Size of the database file and its write-ahead log.
+    pub db_bytes: u64,
+    /// RFC 3339 modification time of the database file.
+    pub db_modified: Option<String>,
+    /// RFC 3339 timestamp of the last successful write or import.
+    pub last_write: Option<String>,
+}
+
+/// Document and vector counts of a collection, reported by the healthcheck.
+#[derive(Serialize)]
+pub struct CollectionStats {
+    /// Qualified name, see [`qualified_name`].
+    pub name: String,
+    pub documents: u64,
+    /// Number of vectors in the index of each indexed column.
+    pub vectors: HashMap<String, u64>,
+    /// RFC 3339 timestamp of the last successful write or import.
+    pub last_write: Option<String>,
+}
+
+/// Name of the next temporary file holding documents added over HTTP or
+/// through [`crate::Collection::add_documents`].
+pub fn documents_file_path() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "letsearch_documents_{}_{}.jsonl",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::calibration::CalibrationMethod;
+
+    #[test]
+    fn test_config_differences() {
+        let existing = CollectionConfig {
+            name: String::from("docs"),
+            model_variant: String::from("i8"),
+            calibration: ScoreCalibration::fit(CalibrationMethod::MinMax, &[0.2, 0.8]),
+            ..CollectionConfig::default()
+        };
+        let mut requested = existing.clone();
+        requested.calibration = None;
+        assert!(existing.differences(&requested).is_empty());
+
+        requested.model_variant = String::from("f32");
+        requested.index_columns = vec![String::from("body")];
+        let differences = existing.differences(&requested);
+        assert_eq!(
+            differences
+                .iter()
+                .map(|d| d.field.as_str())
+                .collect::<Vec<_>>(),
+            vec!["index_columns", "model_variant"]
+        );
+        assert_eq!(
+            differences[1].to_string(),
+            "model_variant is \"i8\", requested \"f32\""
+        );
+
+        requested = existing.clone();
+        requested.data_dir = Some(String::from("/mnt/docs"));
+        assert_eq!(existing.differences(&requested)[0].field, "dir");
+    }
+
+    #[test]
+    fn test_check_page() {
+        assert!(check_page(10, 0).is_ok());
+        assert!(check_page(MAX_SEARCH_LIMIT, MAX_SEARCH_OFFSET).is_ok());
+        assert!(matches!(
+            check_page(0, 0),
+            Err(ProjectError::InvalidLimit(100))
+        ));
+        assert!(matches!(
+            check_page(u32::MAX, 0),
+            Err(ProjectError::InvalidLimit(_))
+        ));
+        assert!(matches!(
+            check_page(10, u32::MAX),
+            Err(ProjectError::InvalidOffset(1000))
+        ));
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        assert_eq!(qualified_name(Some("acme"), "docs"), "acme/docs");
+        assert_eq!(qualified_name(None, "docs"), "docs");
+        assert_eq!(split_name("acme/docs"), (Some("acme"), "docs"));
+        assert_eq!(split_name("docs"), (None, "docs"));
+
+        let config = CollectionConfig {
+            name: String::from("docs"),
+            namespace: Some(String::from("acme")),
+            ..CollectionConfig::default()
+        };
+        assert_eq!(config.qualified_name(), "acme/docs");
+        assert!(config
+            .dir()
+            .ends_with(Path::new("namespaces/acme/collections/docs")));
+        assert!(collection_dir("docs").ends_with(Path::new("collections/docs")));
+    }
+
+    #[test]
+    fn test_requested_models() {
+        let code = ColumnModel {
+            model_name: String::from("hf://org/code"),
+            model_variant: String::from("i8"),
+        };
+        let config = CollectionConfig {
+            index_columns: vec![String::from("text"), String::from("source")],
+            column_models: HashMap::from([
+                (String::from("source"), code.clone()),
+                (String::from("patch"), code.clone()),
+            ]),
+            ..CollectionConfig::default()
+        };
+        assert_eq!(
+            config.requested_models(),
+            vec![
+                ModelRequest::new(DEFAULT_MODEL, "f32"),
+                ModelRequest::new("hf://org/code", "i8")
+            ]
+        );
+        assert_eq!(
+            ModelRequest::new("openai://text-embedding-3-small", "f32").backend,
+            Backend::Remote
+        );
+        assert_eq!(config.requested_models()[1].backend, Backend::ONNX);
+        assert_eq!(config.column_model("source"), code);
+        assert_eq!(config.column_model("text").model_name, DEFAULT_MODEL);
+
+        // Moving only some of the collection's columns gives them their own
+        // model, moving all of them changes the collection's.
+        let text = HashMap::from([(String::from("text"), code.clone())]);
+        let mut moved = config.clone();
+        moved.set_column_models(&text);
+        assert_eq!(moved.model(), code);
+        assert!(moved.column_models.is_empty());
+        let mut config = config;
+        config.index_columns.push(String::from("title"));
+        config.set_column_models(&text);
+        assert_eq!(config.model_name, DEFAULT_MODEL);
+        assert_eq!(config.column_model("text"), code);
+    }
+
+    #[test]
+    fn test_collection_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let data_dir = dir.join("fast");
+        std::fs::create_dir_all(data_dir.join("index/text")).unwrap();
+        for file in ["config.json", "data.db", "data.db.wal", "notes.txt"] {
+            std::fs::write(data_dir.join(file), "{}").unwrap();
+        }
+        let config = CollectionConfig {
+            name: String::from("docs"),
+            data_dir: Some(data_dir.to_string_lossy().to_string()),
+            ..CollectionConfig::default()
+        };
+        config.remove_files().unwrap();
+        let left: Vec<_> = std::fs::read_dir(&data_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, vec!["notes.txt"]);
+
+        // The pointer in the default directory leads to the config.
+        let file = File::create(data_dir.join("config.json")).unwrap();
+        serde_json::to_writer(file, &config).unwrap();
+        let pointer = dir.join("pointer.json");
+        let data_dir = data_dir.to_string_lossy().to_string();
+        let file = File::create(&pointer).unwrap();
+        serde_json::to_writer(file, &ConfigPointer { data_dir }).unwrap();
+        let read = CollectionConfig::read(&pointer).unwrap();
+        assert_eq!(read.name, "docs");
+        assert_eq!(read.data_dir, config.data_dir);
+    }
 }
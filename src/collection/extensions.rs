@@ -0,0 +1,93 @@
+use crate::collection::collection_utils::is_valid_identifier;
+use anyhow::anyhow;
+use std::path::Path;
+
+/// File suffix of DuckDB extension binaries, e.g. `httpfs.duckdb_extension`.
+const EXTENSION_SUFFIX: &str = ".duckdb_extension";
+
+/// Name an extension is loaded by: `extension` itself, or the file name
+/// without [`EXTENSION_SUFFIX`] for paths to extension binaries.
+fn extension_name(extension: &str) -> anyhow::Result<&str> {
+    let name = match Path::new(extension)
+        .file_name()
+        .and_then(|name| name.to_str())
+    {
+        Some(file_name) if extension.ends_with(EXTENSION_SUFFIX) => {
+            file_name.trim_end_matches(EXTENSION_SUFFIX)
+        }
+        _ => extension,
+    };
+    if is_valid_identifier(name) {
+        Ok(name)
+    } else {
+        Err(anyhow!("Invalid DuckDB extension '{}'", extension))
+    }
+}
+
+/// Install and load `extensions` on `conn`, e.g. `httpfs` or `iceberg`.
+/// Extensions are names, downloaded from the DuckDB repository unless
+/// already installed, or paths to `.duckdb_extension` files installed from
+/// disk.
+///
+/// With an `extension_dir`, DuckDB installs extensions there and nothing is
+/// downloaded: named extensions must already be in it, e.g. bundled with
+/// the deployment for offline hosts, and extensions are no longer installed
+/// implicitly by functions such as `read_json_auto`.
+pub fn load_extensions(
+    conn: &duckdb::Connection,
+    extensions: &[String],
+    extension_dir: Option<&str>,
+) -> anyhow::Result<()> {
+    if let Some(dir) = extension_dir {
+        conn.execute_batch(&format!(
+            "SET extension_directory = '{}'; SET autoinstall_known_extensions = false;",
+            dir.replace('\'', "''")
+        ))?;
+    }
+    for extension in extensions {
+        let name = extension_name(extension)?;
+        if extension.ends_with(EXTENSION_SUFFIX) {
+            conn.execute_batch(&format!("INSTALL '{}';", extension.replace('\'', "''")))?;
+        } else if extension_dir.is_none() {
+            conn.execute_batch(&format!("INSTALL {};", name))?;
+        }
+        conn.execute_batch(&format!("LOAD {};", name))
+            .map_err(|e| anyhow!("Failed to load DuckDB extension '{}': {}", name, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_name() {
+        assert_eq!(extension_name("httpfs").unwrap(), "httpfs");
+        assert_eq!(
+            extension_name("/opt/ext/postgres_scanner.duckdb_extension").unwrap(),
+            "postgres_scanner"
+        );
+        assert!(extension_name("httpfs; ATTACH 'x'").is_err());
+        assert!(extension_name("/opt/ext/.duckdb_extension").is_err());
+    }
+
+    #[test]
+    fn test_offline_extensions_are_not_downloaded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        load_extensions(&conn, &[], dir.to_str()).unwrap();
+        let autoinstall: bool = conn
+            .query_row(
+                "SELECT current_setting('autoinstall_known_extensions');",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!autoinstall);
+
+        let missing = load_extensions(&conn, &[String::from("iceberg")], dir.to_str());
+        assert!(missing.unwrap_err().to_string().contains("iceberg"));
+    }
+}
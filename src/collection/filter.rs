@@ -0,0 +1,515 @@
+use crate::collection::collection_utils::is_valid_identifier;
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use duckdb::types::{TimeUnit, Value};
+use serde::{Deserialize, Serialize};
+
+/// A structured filter over the columns of a collection table.
+///
+/// Filters are plain JSON so they can be sent over HTTP, e.g.:
+///
+/// ```json
+/// {"and": [
+///     {"field": "lang", "op": "eq", "value": "en"},
+///     {"not": {"field": "year", "op": "lt", "value": 2020}}
+/// ]}
+/// ```
+///
+/// Ranges, e.g. of dates, can be given as one [`Range`] instead of two
+/// conditions: `{"field": "published_at", "gte": "2024-01-01", "lt": "2025-01-01"}`.
+///
+/// A filter is compiled to a parameterized SQL `WHERE` clause with
+/// [`Filter::to_sql`]. Field names are validated as identifiers and values are
+/// always passed as bind parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Filter {
+    And { and: Vec<Filter> },
+    Or { or: Vec<Filter> },
+    Not { not: Box<Filter> },
+    Condition(Condition),
+    Range(Range),
+}
+
+/// A single comparison of a column against a value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub field: String,
+    pub op: FilterOp,
+    /// Ignored by `is_null` / `is_not_null`, a JSON array for `in` / `not_in`.
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+/// Bounds on a column, at least one of them. Bounds that are ISO 8601 dates
+/// or timestamps, e.g. `"2024-01-01"`, are compared as timestamps.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gte: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lte: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    NotIn,
+    IsNull,
+    IsNotNull,
+}
+
+impl Filter {
+    /// Compile the filter to a SQL boolean expression and its bind parameters,
+    /// in the order they appear in the expression.
+    pub fn to_sql(&self) -> anyhow::Result<(String, Vec<Value>)> {
+        let mut params = Vec::new();
+        let sql = self.build(&mut params)?;
+        Ok((sql, params))
+    }
+
+    /// Whether the filter matches every row whatever its values, like
+    /// `{"and": []}` or an empty `not_in` list.
+    pub fn matches_all(&self) -> bool {
+        match self {
+            Filter::And { and } => and.iter().all(Filter::matches_all),
+            Filter::Or { or } => or.iter().any(Filter::matches_all),
+            Filter::Not { not } => not.matches_none(),
+            Filter::Condition(condition) => {
+                condition.op == FilterOp::NotIn && condition.is_empty_list()
+            }
+            Filter::Range(_) => false,
+        }
+    }
+
+    /// Whether the filter matches no row whatever its values.
+    fn matches_none(&self) -> bool {
+        match self {
+            Filter::And { and } => and.iter().any(Filter::matches_none),
+            Filter::Or { or } => or.iter().all(Filter::matches_none),
+            Filter::Not { not } => not.matches_all(),
+            Filter::Condition(condition) => {
+                condition.op == FilterOp::In && condition.is_empty_list()
+            }
+            Filter::Range(_) => false,
+        }
+    }
+
+    /// Names of the columns the filter compares, in order, with repeats.
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            Filter::And { and: filters } | Filter::Or { or: filters } => {
+                filters.iter().flat_map(Filter::fields).collect()
+            }
+            Filter::Not { not } => not.fields(),
+            Filter::Condition(condition) => vec![condition.field.as_str()],
+            Filter::Range(range) => vec![range.field.as_str()],
+        }
+    }
+
+    fn build(&self, params: &mut Vec<Value>) -> anyhow::Result<String> {
+        match self {
+            Filter::And { and } => Self::join(and, " AND ", "TRUE", params),
+            Filter::Or { or } => Self::join(or, " OR ", "FALSE", params),
+            Filter::Not { not } => Ok(format!("NOT ({})", not.build(params)?)),
+            Filter::Condition(condition) => condition.build(params),
+            Filter::Range(range) => range.build(params),
+        }
+    }
+
+    fn join(
+        filters: &[Filter],
+        separator: &str,
+        empty: &str,
+        params: &mut Vec<Value>,
+    ) -> anyhow::Result<String> {
+        if filters.is_empty() {
+            return Ok(empty.to_string());
+        }
+        let parts = filters
+            .iter()
+            .map(|f| f.build(params).map(|sql| format!("({})", sql)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(parts.join(separator))
+    }
+}
+
+impl Condition {
+    fn is_empty_list(&self) -> bool {
+        self.value
+            .as_array()
+            .is_some_and(|values| values.is_empty())
+    }
+
+    fn build(&self, params: &mut Vec<Value>) -> anyhow::Result<String> {
+        if !is_valid_identifier(&self.field) {
+            return Err(anyhow!(
+                "Invalid field name '{}': only alphanumeric characters and underscores are allowed",
+                self.field
+            ));
+        }
+        let field = &self.field;
+
+        let sql = match self.op {
+            FilterOp::IsNull => format!("{} IS NULL", field),
+            FilterOp::IsNotNull => format!("{} IS NOT NULL", field),
+            FilterOp::In | FilterOp::NotIn => {
+                let values = self.value.as_array().ok_or_else(|| {
+                    anyhow!("Operator '{:?}' on '{}' expects an array", self.op, field)
+                })?;
+                let negate = self.op == FilterOp::NotIn;
+                if values.is_empty() {
+                    return Ok(String::from(if negate { "TRUE" } else { "FALSE" }));
+                }
+                for value in values {
+                    params.push(json_to_sql_value(field, value)?);
+                }
+                let placeholders = vec!["?"; values.len()].join(", ");
+                let keyword = if negate { "NOT IN" } else { "IN" };
+                format!("{} {} ({})", field, keyword, placeholders)
+            }
+            op => {
+                let operator = match op {
+                    FilterOp::Eq => "=",
+                    FilterOp::Ne => "<>",
+                    FilterOp::Gt => ">",
+                    FilterOp::Gte => ">=",
+                    FilterOp::Lt => "<",
+                    _ => "<=",
+                };
+                params.push(json_to_sql_value(field, &self.value)?);
+                format!("{} {} ?", field, operator)
+            }
+        };
+        Ok(sql)
+    }
+}
+
+impl Range {
+    fn build(&self, params: &mut Vec<Value>) -> anyhow::Result<String> {
+        if !is_valid_identifier(&self.field) {
+            return Err(anyhow!(
+                "Invalid field name '{}': only alphanumeric characters and underscores are allowed",
+                self.field
+            ));
+        }
+        let bounds = [
+            (">", &self.gt),
+            (">=", &self.gte),
+            ("<", &self.lt),
+            ("<=", &self.lte),
+        ];
+        let mut parts = Vec::new();
+        for (operator, bound) in bounds {
+            if let Some(value) = bound {
+                params.push(range_bound_to_sql_value(&self.field, value)?);
+                parts.push(format!("{} {} ?", self.field, operator));
+            }
+        }
+        if parts.is_empty() {
+            return Err(anyhow!(
+                "Range on '{}' needs at least one of gt, gte, lt and lte",
+                self.field
+            ));
+        }
+        Ok(parts.join(" AND "))
+    }
+}
+
+/// Like [`json_to_sql_value`], but ISO 8601 dates and timestamps are bound
+/// as timestamps, which DuckDB compares with `DATE` and `TIMESTAMP` columns
+/// where it would reject strings.
+fn range_bound_to_sql_value(field: &str, value: &serde_json::Value) -> anyhow::Result<Value> {
+    let Some(s) = value.as_str() else {
+        return json_to_sql_value(field, value);
+    };
+    let timestamp = DateTime::parse_from_rfc3339(s)
+        .map(|t| t.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(Default::default()))
+        });
+    match timestamp {
+        Ok(t) => Ok(Value::Timestamp(
+            TimeUnit::Microsecond,
+            t.and_utc().timestamp_micros(),
+        )),
+        Err(_) => json_to_sql_value(field, value),
+    }
+}
+
+/// Mean radius of the earth in meters, for great-circle distances.
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Documents within `radius_m` meters of a point, by great-circle distance
+/// between the point and the collection's
+/// [`crate::collection::collection_utils::GeoColumns`], e.g.
+/// `{"lat": 52.52, "lon": 13.405, "radius_m": 5000}`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GeoFilter {
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_m: f64,
+}
+
+impl GeoFilter {
+    /// Compile the filter to a SQL haversine predicate on the `lat_column`
+    /// and `lon_column` columns, in degrees, and its bind parameters. Rows
+    /// with a NULL coordinate never match.
+    pub fn to_sql(
+        &self,
+        lat_column: &str,
+        lon_column: &str,
+    ) -> anyhow::Result<(String, Vec<Value>)> {
+        if !(-90.0..=90.0).contains(&self.lat) {
+            return Err(anyhow!("Latitude {} is not between -90 and 90", self.lat));
+        }
+        if !(-180.0..=180.0).contains(&self.lon) {
+            return Err(anyhow!(
+                "Longitude {} is not between -180 and 180",
+                self.lon
+            ));
+        }
+        if !(self.radius_m.is_finite() && self.radius_m >= 0.0) {
+            return Err(anyhow!(
+                "Radius {} is not a distance in meters",
+                self.radius_m
+            ));
+        }
+        for column in [lat_column, lon_column] {
+            if !is_valid_identifier(column) {
+                return Err(anyhow!("Invalid geo column '{}'", column));
+            }
+        }
+        let sql = format!(
+            "2 * {radius} * asin(sqrt(pow(sin(radians({lat} - ?) / 2), 2) \
+             + cos(radians(?)) * cos(radians({lat})) * pow(sin(radians({lon} - ?) / 2), 2))) <= ?",
+            radius = EARTH_RADIUS_M,
+            lat = lat_column,
+            lon = lon_column,
+        );
+        let params = vec![
+            Value::Double(self.lat),
+            Value::Double(self.lat),
+            Value::Double(self.lon),
+            Value::Double(self.radius_m),
+        ];
+        Ok((sql, params))
+    }
+}
+
+fn json_to_sql_value(field: &str, value: &serde_json::Value) -> anyhow::Result<Value> {
+    match value {
+        serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+        serde_json::Value::Bool(b) => Ok(Value::Boolean(*b)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(Value::BigInt(i))
+            } else if let Some(u) = n.as_u64() {
+                Ok(Value::UBigInt(u))
+            } else {
+                n.as_f64().map(Value::Double).ok_or_else(|| {
+                    anyhow!(
+                        "Unsupported number {} for '{}': it is not a valid double",
+                        n,
+                        field
+                    )
+                })
+            }
+        }
+        _ => Err(anyhow!(
+            "Unsupported value for '{}': only strings, numbers and booleans can be compared",
+            field
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Filter {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_simple_condition() {
+        let (sql, params) = parse(r#"{"field": "lang", "op": "eq", "value": "en"}"#)
+            .to_sql()
+            .unwrap();
+        assert_eq!(sql, "lang = ?");
+        assert_eq!(params, vec![Value::Text("en".to_string())]);
+    }
+
+    #[test]
+    fn test_nested_filter() {
+        let filter = parse(
+            r#"{"and": [
+                {"field": "year", "op": "gte", "value": 2020},
+                {"or": [
+                    {"field": "lang", "op": "in", "value": ["en", "de"]},
+                    {"not": {"field": "draft", "op": "eq", "value": true}}
+                ]}
+            ]}"#,
+        );
+        let (sql, params) = filter.to_sql().unwrap();
+        assert_eq!(
+            sql,
+            "(year >= ?) AND ((lang IN (?, ?)) OR (NOT (draft = ?)))"
+        );
+        assert_eq!(filter.fields(), vec!["year", "lang", "draft"]);
+        assert_eq!(
+            params,
+            vec![
+                Value::BigInt(2020),
+                Value::Text("en".to_string()),
+                Value::Text("de".to_string()),
+                Value::Boolean(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_null_and_empty_lists() {
+        let (sql, params) = parse(r#"{"field": "title", "op": "is_null"}"#)
+            .to_sql()
+            .unwrap();
+        assert_eq!(sql, "title IS NULL");
+        assert!(params.is_empty());
+
+        let (sql, _) = parse(r#"{"field": "lang", "op": "not_in", "value": []}"#)
+            .to_sql()
+            .unwrap();
+        assert_eq!(sql, "TRUE");
+    }
+
+    #[test]
+    fn test_matches_all() {
+        assert!(parse(r#"{"and": []}"#).matches_all());
+        assert!(parse(r#"{"field": "lang", "op": "not_in", "value": []}"#).matches_all());
+        assert!(parse(r#"{"not": {"or": []}}"#).matches_all());
+        assert!(parse(
+            r#"{"or": [{"field": "lang", "op": "eq", "value": "en"}, {"not": {"field": "lang", "op": "in", "value": []}}]}"#
+        )
+        .matches_all());
+        assert!(!parse(r#"{"field": "lang", "op": "eq", "value": "en"}"#).matches_all());
+        assert!(
+            !parse(r#"{"and": [{"and": []}, {"field": "year", "op": "lt", "value": 2020}]}"#)
+                .matches_all()
+        );
+        assert!(!parse(r#"{"not": {"and": []}}"#).matches_all());
+    }
+
+    #[test]
+    fn test_rejects_invalid_input() {
+        assert!(
+            parse(r#"{"field": "a; DROP TABLE x", "op": "eq", "value": 1}"#)
+                .to_sql()
+                .is_err()
+        );
+        assert!(parse(r#"{"field": "lang", "op": "in", "value": "en"}"#)
+            .to_sql()
+            .is_err());
+        assert!(parse(r#"{"field": "meta", "op": "eq", "value": {"a": 1}}"#)
+            .to_sql()
+            .is_err());
+    }
+
+    #[test]
+    fn test_range_filter() {
+        let filter = parse(r#"{"field": "published_at", "gte": "2024-01-01", "lt": "2025-01-01"}"#);
+        assert!(matches!(filter, Filter::Range(_)));
+        assert_eq!(filter.fields(), vec!["published_at"]);
+        let (sql, params) = filter.to_sql().unwrap();
+        assert_eq!(sql, "published_at >= ? AND published_at < ?");
+        assert_eq!(params.len(), 2);
+
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE posts (name TEXT, published_at TIMESTAMP, year INTEGER);
+             INSERT INTO posts VALUES ('old', '2023-06-01 10:00:00', 2023),
+                 ('new', '2024-03-01 08:30:00', 2024), ('newest', '2025-01-01 00:00:00', 2025);",
+        )
+        .unwrap();
+        let names = |filter: &Filter| -> Vec<String> {
+            let (sql, params) = filter.to_sql().unwrap();
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT name FROM posts WHERE {} ORDER BY name;",
+                    sql
+                ))
+                .unwrap();
+            stmt.query_map(duckdb::params_from_iter(params.iter()), |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(names(&filter), vec!["new"]);
+        assert_eq!(
+            names(&parse(
+                r#"{"field": "published_at", "gt": "2024-03-01T08:30:00Z", "lte": "2025-01-01 00:00:00"}"#
+            )),
+            vec!["newest"]
+        );
+        assert_eq!(
+            names(&parse(r#"{"field": "year", "gt": 2023}"#)),
+            vec!["new", "newest"]
+        );
+
+        assert!(parse(r#"{"field": "year"}"#).to_sql().is_err());
+        assert!(serde_json::from_str::<Filter>(r#"{"field": "year", "between": [1, 2]}"#).is_err());
+    }
+
+    #[test]
+    fn test_geo_filter() {
+        let filter: GeoFilter =
+            serde_json::from_str(r#"{"lat": 52.52, "lon": 13.405, "radius_m": 5000}"#).unwrap();
+        let (sql, params) = filter.to_sql("latitude", "longitude").unwrap();
+        assert!(sql.starts_with("2 * 6371008.8 * asin("));
+        assert!(sql.contains("radians(latitude - ?)") && sql.ends_with("<= ?"));
+        assert_eq!(params.len(), 4);
+        assert_eq!(params[3], Value::Double(5000.0));
+
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE places (name TEXT, latitude DOUBLE, longitude DOUBLE);
+             INSERT INTO places VALUES ('potsdam', 52.39, 13.06), ('mitte', 52.52, 13.41),
+                 ('kreuzberg', 52.50, 13.40), ('nowhere', NULL, NULL);",
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT name FROM places WHERE {} ORDER BY name;",
+                sql
+            ))
+            .unwrap();
+        let names: Vec<String> = stmt
+            .query_map(duckdb::params_from_iter(params.iter()), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(names, vec!["kreuzberg", "mitte"]);
+
+        let far = GeoFilter {
+            lat: 91.0,
+            ..filter
+        };
+        assert!(far.to_sql("latitude", "longitude").is_err());
+        assert!(filter.to_sql("lat; --", "longitude").is_err());
+    }
+}
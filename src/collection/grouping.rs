@@ -0,0 +1,97 @@
+use crate::collection::related::Neighbour;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the hits of a document with several vectors, e.g. the chunks of a
+/// long document, are combined into a single result.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// Score of the document's best hit.
+    Max,
+    /// Mean score of the document's hits among the nearest vectors.
+    Mean,
+}
+
+impl GroupBy {
+    /// Combine the hits of each key into one, ordered by distance then key.
+    /// Taking the smallest distance gives the best score and averaging the
+    /// distances the mean score, as scores decrease with the distance.
+    pub fn group(&self, hits: &[Neighbour]) -> Vec<Neighbour> {
+        let mut groups: HashMap<u64, (f32, usize)> = HashMap::new();
+        for hit in hits {
+            groups
+                .entry(hit.key)
+                .and_modify(|(distance, count)| {
+                    match self {
+                        GroupBy::Max => *distance = distance.min(hit.distance),
+                        GroupBy::Mean => *distance += hit.distance,
+                    }
+                    *count += 1;
+                })
+                .or_insert((hit.distance, 1));
+        }
+
+        let mut grouped: Vec<Neighbour> = groups
+            .into_iter()
+            .map(|(key, (distance, count))| Neighbour {
+                key,
+                distance: match self {
+                    GroupBy::Max => distance,
+                    GroupBy::Mean => distance / count as f32,
+                },
+            })
+            .collect();
+        grouped.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.key.cmp(&b.key)));
+        grouped
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GroupBy::Max => "max",
+            GroupBy::Mean => "mean",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "max" => Ok(GroupBy::Max),
+            "mean" => Ok(GroupBy::Mean),
+            _ => Err(anyhow::anyhow!(
+                "Unknown grouping '{}', expected one of max, mean",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(key: u64, distance: f32) -> Neighbour {
+        Neighbour { key, distance }
+    }
+
+    #[test]
+    fn test_group() {
+        let hits = [hit(1, 0.1), hit(2, 0.2), hit(1, 0.5), hit(3, 0.3)];
+        assert_eq!(
+            GroupBy::Max.group(&hits),
+            vec![hit(1, 0.1), hit(2, 0.2), hit(3, 0.3)]
+        );
+        assert_eq!(
+            GroupBy::Mean.group(&hits),
+            vec![hit(2, 0.2), hit(1, 0.3), hit(3, 0.3)]
+        );
+    }
+}
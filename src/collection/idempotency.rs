@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Table recording completed write operations by the id their client gave
+/// them, so that a retried request returns the original result instead of
+/// being applied twice.
+pub const OPERATIONS_TABLE: &str = "_letsearch_operations";
+
+/// How long a completed operation id is remembered by default.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest accepted operation id.
+pub const MAX_OPERATION_ID_LEN: usize = 255;
+
+/// Result of claiming an operation id before running a write.
+#[derive(Debug)]
+pub enum OperationClaim {
+    /// The id is new: run the write, then record its response. The id stays
+    /// claimed until the guard is dropped.
+    New(OperationGuard),
+    /// The same request completed before with this JSON response.
+    Replay(String),
+    /// A request with this id is still running.
+    InProgress,
+    /// The id was already used for a different request.
+    Mismatch,
+}
+
+/// Ids of the write operations running in this process.
+#[derive(Clone, Debug, Default)]
+pub struct RunningOperations(Arc<Mutex<HashSet<String>>>);
+
+impl RunningOperations {
+    /// Mark `op_id` as running, or `None` when it already is. The returned
+    /// guard releases the id when dropped, so that it is released even when
+    /// the request is cancelled or panics before completing.
+    pub fn claim(&self, op_id: &str) -> Option<OperationGuard> {
+        let mut running = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !running.insert(op_id.to_string()) {
+            return None;
+        }
+        Some(OperationGuard {
+            op_id: op_id.to_string(),
+            running: self.clone(),
+        })
+    }
+}
+
+/// Claim on a running operation id, see [`RunningOperations::claim`].
+#[derive(Debug)]
+pub struct OperationGuard {
+    op_id: String,
+    running: RunningOperations,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.running
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.op_id);
+    }
+}
+
+/// Check that a client-supplied operation id can be stored.
+pub fn validate_operation_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_OPERATION_ID_LEN {
+        return Err(format!(
+            "Operation id must be between 1 and {} bytes long",
+            MAX_OPERATION_ID_LEN
+        ));
+    }
+    if id.chars().any(char::is_control) {
+        return Err(String::from(
+            "Operation id must not contain control characters",
+        ));
+    }
+    Ok(())
+}
+
+/// Fingerprint of a write request, used to tell a replay from a different
+/// request reusing the same operation id. Object keys of `body` are sorted,
+/// so the fingerprint does not depend on their order in the request.
+pub fn fingerprint(endpoint: &str, body: &serde_json::Value) -> String {
+    // 64-bit FNV-1a: stable across builds, unlike the std hashers, as
+    // fingerprints are persisted.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in endpoint
+        .bytes()
+        .chain(std::iter::once(0))
+        .chain(body.to_string().into_bytes())
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fingerprint_and_validate() {
+        let a = json!({"filter": {"field": "lang", "op": "eq", "value": "en"}, "dry_run": false});
+        let b: serde_json::Value = serde_json::from_str(
+            r#"{"dry_run": false, "filter": {"value": "en", "op": "eq", "field": "lang"}}"#,
+        )
+        .unwrap();
+        assert_eq!(fingerprint("delete", &a), fingerprint("delete", &b));
+        assert_ne!(fingerprint("delete", &a), fingerprint("documents", &a));
+        assert_ne!(
+            fingerprint("delete", &a),
+            fingerprint("delete", &json!({"dry_run": true}))
+        );
+        assert_eq!(fingerprint("", &json!(null)).len(), 16);
+
+        assert!(validate_operation_id("batch-2024-01-01/17").is_ok());
+        assert!(validate_operation_id("").is_err());
+        assert!(validate_operation_id(&"x".repeat(MAX_OPERATION_ID_LEN + 1)).is_err());
+        assert!(validate_operation_id("a\nb").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim_released_when_dropped() {
+        let running = RunningOperations::default();
+        let write = {
+            let running = running.clone();
+            async move {
+                let _guard = running.claim("op-1").unwrap();
+                std::future::pending::<()>().await;
+            }
+        };
+        // The write is cancelled mid-flight, like a request whose client
+        // disconnected.
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), async {
+            tokio::join!(write, async {
+                tokio::task::yield_now().await;
+                assert!(running.claim("op-1").is_none());
+            })
+        })
+        .await;
+        assert!(timed_out.is_err());
+        assert!(running.claim("op-1").is_some());
+
+        let guard = running.claim("op-2").unwrap();
+        assert!(running.claim("op-2").is_none());
+        drop(guard);
+        assert!(running.claim("op-2").is_some());
+    }
+}
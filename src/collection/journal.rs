@@ -0,0 +1,226 @@
+use log::warn;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File next to `index.bin` journaling the changes not saved yet.
+pub const JOURNAL_FILE: &str = "journal.bin";
+
+/// Size of the journal at which indexing saves the index, which empties it,
+/// so that the journal does not end up holding every embedded vector.
+pub const JOURNAL_CHECKPOINT_BYTES: u64 = 256 * 1024 * 1024;
+
+const ADD: u8 = 1;
+const REMOVE: u8 = 2;
+
+/// A change of a vector index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalEntry {
+    /// One vector per key, `dimensions` scalars of `scalar_bytes` each,
+    /// stored as they were given to the index.
+    Add {
+        keys: Vec<u64>,
+        dimensions: usize,
+        scalar_bytes: usize,
+        vectors: Vec<u8>,
+    },
+    Remove {
+        keys: Vec<u64>,
+    },
+}
+
+impl JournalEntry {
+    fn encode(&self) -> Vec<u8> {
+        let mut record = Vec::new();
+        match self {
+            JournalEntry::Add {
+                keys,
+                dimensions,
+                scalar_bytes,
+                vectors,
+            } => {
+                record.push(ADD);
+                record.extend((keys.len() as u32).to_le_bytes());
+                record.push(*scalar_bytes as u8);
+                record.extend((*dimensions as u32).to_le_bytes());
+                record.extend(keys.iter().flat_map(|key| key.to_le_bytes()));
+                record.extend(vectors);
+            }
+            JournalEntry::Remove { keys } => {
+                record.push(REMOVE);
+                record.extend((keys.len() as u32).to_le_bytes());
+                record.extend(keys.iter().flat_map(|key| key.to_le_bytes()));
+            }
+        }
+        record
+    }
+}
+
+/// Reads records from a journal, `None` at its end or at a record cut short.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<usize> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?) as usize)
+    }
+
+    fn keys(&mut self, count: usize) -> Option<Vec<u64>> {
+        let bytes = self.take(count.checked_mul(8)?)?;
+        Some(
+            bytes
+                .chunks_exact(8)
+                .map(|key| u64::from_le_bytes(key.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    fn entry(&mut self) -> Option<JournalEntry> {
+        let kind = self.take(1)?[0];
+        let count = self.u32()?;
+        match kind {
+            ADD => {
+                let scalar_bytes = self.take(1)?[0] as usize;
+                let dimensions = self.u32()?;
+                let keys = self.keys(count)?;
+                let len = count.checked_mul(dimensions)?.checked_mul(scalar_bytes)?;
+                let vectors = self.take(len)?.to_vec();
+                Some(JournalEntry::Add {
+                    keys,
+                    dimensions,
+                    scalar_bytes,
+                    vectors,
+                })
+            }
+            REMOVE => Some(JournalEntry::Remove {
+                keys: self.keys(count)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Append-only log of the changes made to a vector index since it was last
+/// saved. Each change is written and synced before it is applied, so that
+/// a crash before the next save loses none of the vectors whose rows were
+/// already committed to the database.
+pub struct Journal {
+    path: PathBuf,
+    /// Opened on the first change, so that unchanged indexes get no file.
+    file: Mutex<Option<File>>,
+}
+
+impl Journal {
+    pub fn new(index_dir: &Path) -> Self {
+        Journal {
+            path: index_dir.join(JOURNAL_FILE),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Durably record `entry`.
+    pub fn append(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.is_none() {
+            *file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        let file = file.as_mut().expect("journal file was just opened");
+        file.write_all(&entry.encode())?;
+        file.sync_data()
+    }
+
+    /// Entries recorded since the journal was last cleared. A last record
+    /// cut short by a crash was never applied, so it is left out.
+    pub fn entries(&self) -> io::Result<Vec<JournalEntry>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut reader = Reader { data: &data };
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.entry() {
+            entries.push(entry);
+        }
+        if !reader.data.is_empty() {
+            warn!(
+                "Ignoring {} bytes of an incomplete record at the end of {:?}",
+                reader.data.len(),
+                self.path
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Size of the journal file in bytes.
+    pub fn len(&self) -> u64 {
+        fs::metadata(&self.path).map_or(0, |metadata| metadata.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget every entry, once the index they changed is saved.
+    pub fn clear(&self) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        *file = None;
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let journal = Journal::new(dir);
+        assert!(journal.entries().unwrap().is_empty());
+
+        let add = JournalEntry::Add {
+            keys: vec![1, 2],
+            dimensions: 2,
+            scalar_bytes: 4,
+            vectors: [1.0f32, 0.0, 0.0, 1.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        };
+        let remove = JournalEntry::Remove { keys: vec![1] };
+        journal.append(&add).unwrap();
+        journal.append(&remove).unwrap();
+        assert_eq!(journal.entries().unwrap(), vec![add.clone(), remove]);
+
+        // A record cut short by a crash is dropped.
+        let len = journal.len();
+        journal.append(&add).unwrap();
+        let file = OpenOptions::new().write(true).open(&journal.path).unwrap();
+        file.set_len(len + 10).unwrap();
+        assert_eq!(journal.entries().unwrap().len(), 2);
+
+        journal.clear().unwrap();
+        assert!(journal.is_empty());
+    }
+}
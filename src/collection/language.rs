@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Column holding the language detected for each document, as an ISO 639-3
+/// code such as `eng` or `deu`.
+pub const LANG_COLUMN: &str = "lang";
+
+/// Language of documents whose language could not be told, so that they are
+/// not detected again on every import.
+pub const UNDETERMINED: &str = "und";
+
+/// Confidence below which a language is not told. Short texts, such as
+/// queries of a few words, rarely reach it.
+const MIN_CONFIDENCE: f64 = 0.25;
+
+/// Characters a document's language is detected from. The beginning of a
+/// document tells its language as well as the whole of it.
+const DETECTION_CHARS: usize = 2000;
+
+/// Language detection for multilingual collections: the language of each
+/// document is stored in [`LANG_COLUMN`], which searches may filter on, and
+/// the language of each query picks its prompt.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Languages {
+    /// Column the language of each document is detected from.
+    pub column: String,
+    /// Query prompts by language code, used in place of the collection's
+    /// query prompt for queries detected in that language, e.g. to instruct
+    /// a model in the language of the query.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub query_prompts: HashMap<String, String>,
+}
+
+/// ISO 639-3 code of the language `text` is written in, when it can be told
+/// with enough confidence.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let text = match text.char_indices().nth(DETECTION_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+    whatlang::detect(text)
+        .filter(|info| info.confidence() >= MIN_CONFIDENCE)
+        .map(|info| info.lang().code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("The quick brown fox jumps over the lazy dog near the river bank"),
+            Some("eng")
+        );
+        assert_eq!(
+            detect_language("Der schnelle braune Fuchs springt über den faulen Hund am Fluss"),
+            Some("deu")
+        );
+        assert_eq!(
+            detect_language("Le renard brun rapide saute par-dessus le chien paresseux"),
+            Some("fra")
+        );
+        assert_eq!(detect_language("red running shoes"), None);
+        assert_eq!(detect_language("42"), None);
+    }
+}
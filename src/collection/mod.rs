@@ -1,2 +1,19 @@
+pub mod aggregate;
+pub mod analyzer;
+pub mod calibration;
+pub mod chunking;
 pub mod collection_utils;
+pub mod extensions;
+pub mod filter;
+pub mod grouping;
+pub mod idempotency;
+pub mod journal;
+pub mod language;
+pub mod normalization;
+pub mod prefix;
+pub mod related;
+pub mod snippet;
+pub mod spelling;
+pub mod sql;
+pub mod suggest;
 pub mod vector_index;
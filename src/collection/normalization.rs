@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// One text normalization step.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NormalizationStep {
+    /// Canonical composition: the same character always has the same code
+    /// points, whether it was typed precomposed or with combining marks.
+    Nfc,
+    /// Compatibility composition: also folds ligatures, full-width and
+    /// half-width forms, circled digits and the like to their plain form.
+    Nfkc,
+    /// Fold full-width ASCII and half-width katakana only, leaving other
+    /// compatibility characters alone.
+    FoldWidth,
+    /// Remove accents and other combining marks, e.g. `é` to `e`. Also
+    /// removes the voicing marks of kana, so it is not for Japanese.
+    StripDiacritics,
+    Lowercase,
+    /// Lowercase with the Turkish dotted and dotless i: `I` to `ı` and `İ`
+    /// to `i`.
+    TurkishLowercase,
+    /// Spell out umlauts and the sharp s the way they are typed without a
+    /// German keyboard, e.g. `Müller` to `Mueller` and `Straße` to `Strasse`.
+    GermanFolding,
+}
+
+impl NormalizationStep {
+    fn name(&self) -> &'static str {
+        match self {
+            NormalizationStep::Nfc => "nfc",
+            NormalizationStep::Nfkc => "nfkc",
+            NormalizationStep::FoldWidth => "fold-width",
+            NormalizationStep::StripDiacritics => "strip-diacritics",
+            NormalizationStep::Lowercase => "lowercase",
+            NormalizationStep::TurkishLowercase => "turkish-lowercase",
+            NormalizationStep::GermanFolding => "german-folding",
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self {
+            NormalizationStep::Nfc => text.nfc().collect(),
+            NormalizationStep::Nfkc => text.nfkc().collect(),
+            NormalizationStep::FoldWidth => text
+                .chars()
+                .flat_map(|c| match c {
+                    '\u{3000}' => vec![' '],
+                    '\u{FF01}'..='\u{FF5E}' => {
+                        vec![char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)]
+                    }
+                    '\u{FF61}'..='\u{FF9F}' => c.nfkc().collect(),
+                    _ => vec![c],
+                })
+                // Half-width voicing marks become combining marks, composed
+                // back with their kana here.
+                .nfc()
+                .collect(),
+            NormalizationStep::StripDiacritics => text
+                .nfd()
+                .filter(|c| !is_combining_mark(*c))
+                .nfc()
+                .collect(),
+            NormalizationStep::Lowercase => text.to_lowercase(),
+            NormalizationStep::TurkishLowercase => text
+                .chars()
+                .map(|c| match c {
+                    'I' => 'ı',
+                    'İ' => 'i',
+                    _ => c,
+                })
+                .collect::<String>()
+                .to_lowercase(),
+            NormalizationStep::GermanFolding => {
+                let mut folded = String::with_capacity(text.len());
+                for c in text.nfc() {
+                    match c {
+                        'ä' => folded.push_str("ae"),
+                        'ö' => folded.push_str("oe"),
+                        'ü' => folded.push_str("ue"),
+                        'Ä' => folded.push_str("Ae"),
+                        'Ö' => folded.push_str("Oe"),
+                        'Ü' => folded.push_str("Ue"),
+                        'ß' => folded.push_str("ss"),
+                        'ẞ' => folded.push_str("SS"),
+                        _ => folded.push(c),
+                    }
+                }
+                folded
+            }
+        }
+    }
+}
+
+impl FromStr for NormalizationStep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "nfc" => Ok(NormalizationStep::Nfc),
+            "nfkc" => Ok(NormalizationStep::Nfkc),
+            "fold-width" => Ok(NormalizationStep::FoldWidth),
+            "strip-diacritics" => Ok(NormalizationStep::StripDiacritics),
+            "lowercase" => Ok(NormalizationStep::Lowercase),
+            "turkish-lowercase" => Ok(NormalizationStep::TurkishLowercase),
+            "german-folding" => Ok(NormalizationStep::GermanFolding),
+            _ => Err(anyhow::anyhow!(
+                "Unknown normalization step '{}', expected one of nfc, nfkc, fold-width, strip-diacritics, lowercase, turkish-lowercase, german-folding",
+                s
+            )),
+        }
+    }
+}
+
+/// Named normalization presets and their steps.
+pub const PRESETS: &[(&str, &[NormalizationStep])] = &[
+    ("nfc", &[NormalizationStep::Nfc]),
+    ("nfkc", &[NormalizationStep::Nfkc]),
+    (
+        "cjk",
+        &[NormalizationStep::Nfkc, NormalizationStep::Lowercase],
+    ),
+    (
+        "turkish",
+        &[NormalizationStep::Nfc, NormalizationStep::TurkishLowercase],
+    ),
+    (
+        "german",
+        &[
+            NormalizationStep::Nfkc,
+            NormalizationStep::Lowercase,
+            NormalizationStep::GermanFolding,
+        ],
+    ),
+    (
+        "latin",
+        &[
+            NormalizationStep::Nfkc,
+            NormalizationStep::StripDiacritics,
+            NormalizationStep::Lowercase,
+        ],
+    ),
+];
+
+/// Text normalization of a collection, applied to every text before it is
+/// embedded, both documents at indexing time and queries at search time, so
+/// that both sides go through the same steps. Stored texts are unchanged.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Normalization {
+    /// Preset the steps were chosen with, if any. The steps themselves are
+    /// stored too, so that a collection keeps normalizing the same way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    pub steps: Vec<NormalizationStep>,
+}
+
+impl Normalization {
+    pub fn apply(&self, text: &str) -> String {
+        self.steps
+            .iter()
+            .fold(text.to_string(), |text, step| step.apply(&text))
+    }
+
+    pub fn apply_all(&self, texts: Vec<String>) -> Vec<String> {
+        if self.steps.is_empty() {
+            return texts;
+        }
+        texts.iter().map(|text| self.apply(text)).collect()
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps = self
+            .steps
+            .iter()
+            .map(NormalizationStep::name)
+            .collect::<Vec<_>>()
+            .join(",");
+        match &self.preset {
+            Some(preset) => write!(f, "{} ({})", preset, steps),
+            None => f.write_str(&steps),
+        }
+    }
+}
+
+/// Parse a preset name, e.g. `cjk`, or comma-separated steps, e.g.
+/// `nfkc,strip-diacritics,lowercase`.
+impl FromStr for Normalization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        if let Some((preset, steps)) = PRESETS.iter().find(|(preset, _)| *preset == name) {
+            return Ok(Normalization {
+                preset: Some(preset.to_string()),
+                steps: steps.to_vec(),
+            });
+        }
+        let steps = s
+            .split(',')
+            .map(NormalizationStep::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                let presets: Vec<&str> = PRESETS.iter().map(|(preset, _)| *preset).collect();
+                anyhow::anyhow!("{}, or a preset: {}", e, presets.join(", "))
+            })?;
+        Ok(Normalization {
+            preset: None,
+            steps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization() {
+        let cjk: Normalization = "cjk".parse().unwrap();
+        assert_eq!(cjk.preset.as_deref(), Some("cjk"));
+        assert_eq!(cjk.apply("ＬＥＴＳ　ｻｰﾁ"), "lets サーチ");
+
+        let width: Normalization = "fold-width".parse().unwrap();
+        assert_eq!(width.apply("ＡＢＣ１ ｶﾞ ①"), "ABC1 ガ ①");
+
+        let turkish: Normalization = "turkish".parse().unwrap();
+        assert_eq!(turkish.apply("DİYARBAKIR"), "diyarbakır");
+
+        let german: Normalization = "german".parse().unwrap();
+        assert_eq!(
+            german.apply("Müller, STRAẞE"),
+            german.apply("mueller, strasse")
+        );
+
+        let latin: Normalization = "latin".parse().unwrap();
+        assert_eq!(latin.apply("Ｃafé Crème ﬁn"), "cafe creme fin");
+
+        // Precomposed and combining forms end up equal.
+        let nfc: Normalization = "nfc, lowercase".parse().unwrap();
+        assert_eq!(nfc.preset, None);
+        assert_eq!(nfc.apply("Mu\u{308}nchen"), nfc.apply("MÜNCHEN"));
+        assert_eq!(nfc.to_string(), "nfc,lowercase");
+
+        assert!("nfkd".parse::<Normalization>().is_err());
+        assert_eq!(
+            Normalization::default().apply_all(vec!["Ä".into()]),
+            vec!["Ä"]
+        );
+    }
+}
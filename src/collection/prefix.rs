@@ -0,0 +1,132 @@
+use crate::collection::suggest::{normalize_phrase, Suggestion};
+use fst::automaton::{Automaton, Str};
+use fst::{IntoStreamer, Map, Streamer};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// File of a collection's prefix index, in its index directory.
+pub const PREFIX_INDEX_FILE: &str = "prefix.fst";
+
+/// Completions of what is typed in a search box from the values of one
+/// column, such as titles. The values are kept in a finite state transducer
+/// (FST) mapping each of them, normalized like suggestions, to the number of
+/// documents holding it: FSTs are small and list the keys starting with a
+/// prefix without scanning the others, but cannot be changed, so the index
+/// is rebuilt from the column after each import.
+pub struct PrefixIndex {
+    map: Map<Vec<u8>>,
+}
+
+impl PrefixIndex {
+    /// Index of `values`, one per document.
+    pub fn build(values: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        // FSTs are built from keys in lexicographic order.
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        for value in values {
+            if let Some(phrase) = normalize_phrase(&value) {
+                *counts.entry(phrase).or_insert(0) += 1;
+            }
+        }
+        Ok(Self {
+            map: Map::from_iter(counts)?,
+        })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            map: Map::new(std::fs::read(path)?)?,
+        })
+    }
+
+    /// Write the index to `path`, replacing the previous one at once.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let staged = path.with_extension("fst.tmp");
+        std::fs::write(&staged, self.map.as_fst().as_bytes())?;
+        std::fs::rename(&staged, path)?;
+        Ok(())
+    }
+
+    /// Number of distinct values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Values starting with `prefix`, those of the most documents first.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        let normalized = match normalize_phrase(prefix) {
+            Some(normalized) => normalized,
+            None => return Vec::new(),
+        };
+        // Keep a trailing space so that "rust " only completes whole words.
+        let normalized = if prefix.ends_with(char::is_whitespace) {
+            format!("{} ", normalized)
+        } else {
+            normalized
+        };
+
+        let mut stream = self
+            .map
+            .search(Str::new(&normalized).starts_with())
+            .into_stream();
+        let mut completions = Vec::new();
+        while let Some((key, count)) = stream.next() {
+            completions.push(Suggestion {
+                text: String::from_utf8_lossy(key).into_owned(),
+                count,
+            });
+        }
+        completions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.text.cmp(&b.text)));
+        completions.truncate(limit);
+        completions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_index() {
+        let titles = [
+            "Rust Async Book",
+            "rust async book",
+            "Rustacean Station",
+            "Rust",
+            "Vector Search",
+            "  ",
+        ];
+        let index = PrefixIndex::build(titles.iter().map(|title| title.to_string())).unwrap();
+        assert_eq!(index.len(), 4);
+
+        let texts = |completions: Vec<Suggestion>| {
+            completions
+                .into_iter()
+                .map(|completion| (completion.text, completion.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts(index.complete("RUS", 10)),
+            vec![
+                (String::from("rust async book"), 2),
+                (String::from("rust"), 1),
+                (String::from("rustacean station"), 1),
+            ]
+        );
+        assert_eq!(
+            texts(index.complete("rust ", 10)),
+            vec![(String::from("rust async book"), 2)]
+        );
+        assert_eq!(index.complete("rus", 1).len(), 1);
+        assert!(index.complete("python", 10).is_empty());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(PREFIX_INDEX_FILE);
+        index.save(&path).unwrap();
+        let loaded = PrefixIndex::load(&path).unwrap();
+        assert_eq!(loaded.complete("vec", 10), index.complete("vec", 10));
+    }
+}
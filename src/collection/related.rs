@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+
+/// Table holding the precomputed related documents of every collection
+/// document, one row per `(_key, column_name, rank)`.
+pub const RELATED_TABLE: &str = "_letsearch_related";
+
+/// A precomputed neighbour of a document: its key and raw index distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Neighbour {
+    pub key: u64,
+    pub distance: f32,
+}
+
+/// Merge `candidates` into the `existing` related documents of a document
+/// and keep the `count` closest ones, ordered by distance then key.
+/// A key appearing in both keeps its smallest distance.
+pub fn merge_neighbours(
+    existing: &[Neighbour],
+    candidates: &[Neighbour],
+    count: usize,
+) -> Vec<Neighbour> {
+    let mut best: HashMap<u64, f32> = HashMap::new();
+    for neighbour in existing.iter().chain(candidates) {
+        best.entry(neighbour.key)
+            .and_modify(|distance| *distance = distance.min(neighbour.distance))
+            .or_insert(neighbour.distance);
+    }
+
+    let mut merged: Vec<Neighbour> = best
+        .into_iter()
+        .map(|(key, distance)| Neighbour { key, distance })
+        .collect();
+    merged.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.key.cmp(&b.key)));
+    merged.truncate(count);
+    merged
+}
+
+/// Documents whose related lists may change when `added` documents get the
+/// given neighbours: every neighbour gains the added document as a candidate,
+/// since similarity is symmetric. Neighbours that were added themselves are
+/// skipped, as their lists are computed from scratch.
+pub fn reverse_candidates(added: &[(u64, Vec<Neighbour>)]) -> HashMap<u64, Vec<Neighbour>> {
+    let added_keys: HashSet<u64> = added.iter().map(|(key, _)| *key).collect();
+    let mut candidates: HashMap<u64, Vec<Neighbour>> = HashMap::new();
+    for (key, neighbours) in added {
+        for neighbour in neighbours {
+            if added_keys.contains(&neighbour.key) {
+                continue;
+            }
+            candidates
+                .entry(neighbour.key)
+                .or_default()
+                .push(Neighbour {
+                    key: *key,
+                    distance: neighbour.distance,
+                });
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(key: u64, distance: f32) -> Neighbour {
+        Neighbour { key, distance }
+    }
+
+    #[test]
+    fn test_merge_neighbours() {
+        let existing = vec![n(1, 0.1), n(2, 0.3), n(3, 0.5)];
+        let candidates = vec![n(4, 0.2), n(3, 0.4), n(5, 0.9)];
+        assert_eq!(
+            merge_neighbours(&existing, &candidates, 3),
+            vec![n(1, 0.1), n(4, 0.2), n(2, 0.3)]
+        );
+        assert_eq!(merge_neighbours(&existing, &candidates, 10)[3], n(3, 0.4));
+    }
+
+    #[test]
+    fn test_reverse_candidates() {
+        let added = vec![(10, vec![n(1, 0.2), n(11, 0.1)]), (11, vec![n(10, 0.1)])];
+        let candidates = reverse_candidates(&added);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[&1], vec![n(10, 0.2)]);
+    }
+}
@@ -0,0 +1,333 @@
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::collection::analyzer::Analyzer;
+use crate::collection::collection_utils::MatchedChunk;
+
+/// Marker added where a snippet was cut from a longer text.
+const ELLIPSIS: &str = "…";
+
+/// Terms shorter than this only match whole words, longer ones also match
+/// the start of words, e.g. `banana` in `bananas`.
+const MIN_PREFIX_TERM_CHARS: usize = 3;
+
+/// How snippets of matched documents are cut and highlighted.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SnippetOptions {
+    /// Longest snippet, in characters, not counting markers.
+    pub size: usize,
+    /// Inserted before each query term found in the snippet.
+    pub pre_tag: String,
+    /// Inserted after each query term found in the snippet.
+    pub post_tag: String,
+    /// HTML-escape the content around the markers, so that snippets can be
+    /// shown as HTML with the default markers.
+    pub escape: bool,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        SnippetOptions {
+            size: 200,
+            pre_tag: String::from("<mark>"),
+            post_tag: String::from("</mark>"),
+            escape: true,
+        }
+    }
+}
+
+/// Occurrence of a query term in the content, in bytes and characters.
+struct TermMatch {
+    term: usize,
+    start: usize,
+    end: usize,
+    start_char: usize,
+    end_char: usize,
+}
+
+fn find_matches(content: &str, query: &str, analyzer: &Analyzer) -> Vec<TermMatch> {
+    let mut terms: Vec<String> = analyzer.terms(query);
+    terms.sort();
+    terms.dedup();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    let mut chars = 0;
+    let mut last = 0;
+    for (start, word) in content.unicode_word_indices() {
+        chars += content[last..start].chars().count();
+        let word_chars = word.chars().count();
+        let term = analyzer.term(word).and_then(|lower| {
+            terms.iter().position(|term| {
+                lower == *term
+                    || (term.chars().count() >= MIN_PREFIX_TERM_CHARS
+                        && lower.starts_with(term.as_str()))
+            })
+        });
+        if let Some(term) = term {
+            matches.push(TermMatch {
+                term,
+                start,
+                end: start + word.len(),
+                start_char: chars,
+                end_char: chars + word_chars,
+            });
+        }
+        chars += word_chars;
+        last = start + word.len();
+    }
+    matches
+}
+
+/// Character window of `size` with the most distinct query terms, then the
+/// most matches, with the matches centered in it.
+fn best_window(matches: &[TermMatch], size: usize, total: usize) -> (usize, usize) {
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    for (i, first) in matches.iter().enumerate() {
+        let inside: Vec<&TermMatch> = matches[i..]
+            .iter()
+            .take_while(|m| m.end_char <= first.start_char + size)
+            .collect();
+        let mut terms: Vec<usize> = inside.iter().map(|m| m.term).collect();
+        terms.sort();
+        terms.dedup();
+        let span_end = inside.last().map_or(first.end_char, |m| m.end_char);
+        let better = best
+            .is_none_or(|(distinct, count, _, _)| (terms.len(), inside.len()) > (distinct, count));
+        if better {
+            best = Some((terms.len(), inside.len(), first.start_char, span_end));
+        }
+    }
+
+    let (start, span_end) = match best {
+        Some((_, _, start, span_end)) => (start, span_end),
+        None => (0, 0),
+    };
+    let lead = size.saturating_sub(span_end - start) / 2;
+    let start = start.saturating_sub(lead).min(total.saturating_sub(size));
+    (start, (start + size).min(total))
+}
+
+/// Append `text` to `snippet`, HTML-escaped if `escape` is set.
+fn push_text(snippet: &mut String, text: &str, escape: bool) {
+    if !escape {
+        snippet.push_str(text);
+        return;
+    }
+    for c in text.chars() {
+        match c {
+            '&' => snippet.push_str("&amp;"),
+            '<' => snippet.push_str("&lt;"),
+            '>' => snippet.push_str("&gt;"),
+            '"' => snippet.push_str("&quot;"),
+            '\'' => snippet.push_str("&#39;"),
+            c => snippet.push(c),
+        }
+    }
+}
+
+/// Cut a snippet of at most `options.size` characters from `content` around
+/// the words of `query`, wrapping them in the options' markers. Words are
+/// compared as the terms `analyzer` makes of them. Without any
+/// of them in the content, the snippet starts at the matched chunk, if any,
+/// or else at the start of the content. Cut ends are marked with `…`. The
+/// content is HTML-escaped unless `options.escape` is unset, the markers
+/// never are.
+pub fn snippet(
+    content: &str,
+    query: &str,
+    chunk: Option<&MatchedChunk>,
+    analyzer: &Analyzer,
+    options: &SnippetOptions,
+) -> String {
+    let size = options.size.max(1);
+    let total = content.chars().count();
+    let matches = find_matches(content, query, analyzer);
+
+    let (start_char, end_char) = if total <= size {
+        (0, total)
+    } else if !matches.is_empty() {
+        best_window(&matches, size, total)
+    } else {
+        let start = chunk
+            .filter(|chunk| content.is_char_boundary(chunk.start))
+            .map_or(0, |chunk| content[..chunk.start].chars().count())
+            .min(total - size);
+        (start, start + size)
+    };
+
+    // Byte offsets of the window, moved inwards to whole words.
+    let byte_at = |char_index: usize| {
+        content
+            .char_indices()
+            .nth(char_index)
+            .map_or(content.len(), |(i, _)| i)
+    };
+    let mut start = byte_at(start_char);
+    let mut end = byte_at(end_char);
+    let inside_word = |i: usize| {
+        content[..i]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+            && content[i..]
+                .chars()
+                .next()
+                .is_some_and(char::is_alphanumeric)
+    };
+    // A cut start skips to the next word, leaving out punctuation too.
+    let next_word = content
+        .unicode_word_indices()
+        .map(|(i, _)| i)
+        .find(|&i| i >= start);
+    if let Some(word) = next_word.filter(|_| start > 0) {
+        start = word.min(end);
+    }
+    if end < content.len() && inside_word(end) {
+        if let Some((i, _)) = content[start..end]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+        {
+            end = start + i;
+        }
+    }
+    if end < content.len() {
+        end = start + content[start..end].trim_end().len();
+    }
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str(ELLIPSIS);
+    }
+    let mut position = start;
+    for m in matches.iter().filter(|m| m.start >= start && m.end <= end) {
+        push_text(&mut snippet, &content[position..m.start], options.escape);
+        snippet.push_str(&options.pre_tag);
+        push_text(&mut snippet, &content[m.start..m.end], options.escape);
+        snippet.push_str(&options.post_tag);
+        position = m.end;
+    }
+    push_text(&mut snippet, &content[position..end], options.escape);
+    if end < content.len() {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(size: usize) -> SnippetOptions {
+        SnippetOptions {
+            size,
+            pre_tag: String::from("["),
+            post_tag: String::from("]"),
+            escape: false,
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(
+            snippet(
+                "Apples and Bananas",
+                "banana",
+                None,
+                &Analyzer::default(),
+                &options(100)
+            ),
+            "Apples and [Bananas]"
+        );
+        // Short terms only match whole words.
+        assert_eq!(
+            snippet("an answer", "an", None, &Analyzer::default(), &options(100)),
+            "[an] answer"
+        );
+
+        let content = "The orchard grows many fruits. Later in the year the farmers pick \
+                       ripe apples and pears, and sell apples at the market in town.";
+        assert_eq!(
+            snippet(
+                content,
+                "apples market",
+                None,
+                &Analyzer::default(),
+                &options(40)
+            ),
+            "…and sell [apples] at the [market] in town."
+        );
+
+        // Without the query terms, the matched chunk is shown.
+        let chunk = MatchedChunk {
+            index: 1,
+            text: String::new(),
+            start: content.find("Later").unwrap(),
+            end: content.len(),
+        };
+        assert_eq!(
+            snippet(
+                content,
+                "harvest",
+                Some(&chunk),
+                &Analyzer::default(),
+                &options(30)
+            ),
+            "…Later in the year the farmers…"
+        );
+        assert_eq!(
+            snippet(content, "", None, &Analyzer::default(), &options(20)),
+            "The orchard grows…"
+        );
+        assert_eq!(
+            snippet(
+                "ünïcödé wörds",
+                "wörds",
+                None,
+                &Analyzer::default(),
+                &options(100)
+            ),
+            "ünïcödé [wörds]"
+        );
+
+        // Query words are matched by their stem, stopwords are not.
+        let english = Analyzer {
+            stemmer: Some("english".parse().unwrap()),
+            stopwords: vec!["english".parse().unwrap()],
+            ..Analyzer::default()
+        };
+        assert_eq!(
+            snippet(
+                "The farmer runs to the market",
+                "the running",
+                None,
+                &english,
+                &options(100)
+            ),
+            "The farmer [runs] to the market"
+        );
+    }
+
+    #[test]
+    fn test_snippet_escape() {
+        let content = "<script>alert('x')</script> & apples";
+        assert_eq!(
+            snippet(
+                content,
+                "apples",
+                None,
+                &Analyzer::default(),
+                &SnippetOptions::default()
+            ),
+            "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; <mark>apples</mark>"
+        );
+        assert_eq!(
+            snippet(content, "apples", None, &Analyzer::default(), &options(100)),
+            "<script>alert('x')</script> & [apples]"
+        );
+    }
+}
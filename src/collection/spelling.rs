@@ -0,0 +1,258 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Table of the words of the indexed text, one row per word with the number
+/// of documents it occurs in, which spelling corrections are picked from.
+pub const TERMS_TABLE: &str = "_letsearch_terms";
+
+/// Most edits a word of a query is corrected by.
+pub const MAX_EDIT_DISTANCE: usize = 2;
+
+/// Characters at the beginning of a word its deletes are listed for. Words
+/// are told apart well enough by their beginning, and listing the deletes of
+/// whole words would multiply the size of the dictionary.
+const PREFIX_CHARS: usize = 7;
+
+/// A corrected query, with the number of edits it is away from the query and
+/// the number of documents its rarest word occurs in.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Correction {
+    pub text: String,
+    pub distance: usize,
+    pub count: u64,
+}
+
+/// Words of a document that make terms of the dictionary: lowercased, with
+/// at least two characters and not numbers. Each word is listed once.
+pub fn extract_terms(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.unicode_words()
+        .filter(|word| word.chars().count() >= 2 && !word.chars().all(|c| c.is_numeric()))
+        .map(|word| word.to_lowercase())
+        .filter(|word| seen.insert(word.clone()))
+        .collect()
+}
+
+/// Number of edits turning `a` into `b`: insertions, deletions, substitutions
+/// and transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// `word` and the strings left by deleting up to `max_distance` of its
+/// characters.
+fn deletes(word: &str, max_distance: usize) -> HashSet<String> {
+    let mut all = HashSet::from([word.to_string()]);
+    let mut current = vec![word.to_string()];
+    for _ in 0..max_distance {
+        let mut next = Vec::new();
+        for word in &current {
+            let chars: Vec<char> = word.chars().collect();
+            for i in 0..chars.len() {
+                let delete: String = chars[..i].iter().chain(&chars[i + 1..]).collect();
+                if all.insert(delete.clone()) {
+                    next.push(delete);
+                }
+            }
+        }
+        current = next;
+    }
+    all
+}
+
+fn prefix(word: &str) -> &str {
+    match word.char_indices().nth(PREFIX_CHARS) {
+        Some((end, _)) => &word[..end],
+        None => word,
+    }
+}
+
+/// Edits a word is corrected by: none for words of one or two characters,
+/// which would be "corrected" into most short words, and more for longer
+/// words.
+fn max_distance(word: &str) -> usize {
+    (word.chars().count().saturating_sub(1) / 2).min(MAX_EDIT_DISTANCE)
+}
+
+/// Dictionary of the words of the indexed text for did-you-mean corrections,
+/// looked up with symmetric deletes (SymSpell): the strings left by deleting
+/// up to [`MAX_EDIT_DISTANCE`] characters from each word are listed once, so
+/// that the words close to a misspelling are found by deleting characters
+/// from it instead of comparing it to every word.
+#[derive(Default)]
+pub struct SpellingDictionary {
+    terms: Vec<(String, u64)>,
+    positions: HashMap<String, usize>,
+    deletes: HashMap<String, Vec<usize>>,
+}
+
+impl SpellingDictionary {
+    /// Dictionary of `terms`, given with the number of documents each occurs in.
+    pub fn new(terms: impl IntoIterator<Item = (String, u64)>) -> Self {
+        let mut dictionary = Self::default();
+        for (term, count) in terms {
+            let position = dictionary.terms.len();
+            for delete in deletes(prefix(&term), MAX_EDIT_DISTANCE) {
+                dictionary.deletes.entry(delete).or_default().push(position);
+            }
+            dictionary.positions.insert(term.clone(), position);
+            dictionary.terms.push((term, count));
+        }
+        dictionary
+    }
+
+    /// Words within `max_distance` edits of `word`, with their distance and
+    /// count, the closest and most common first.
+    pub fn lookup(&self, word: &str, max_distance: usize) -> Vec<(&str, usize, u64)> {
+        let mut candidates = HashSet::new();
+        for delete in deletes(prefix(word), max_distance) {
+            if let Some(positions) = self.deletes.get(&delete) {
+                candidates.extend(positions.iter().copied());
+            }
+        }
+        let mut matches: Vec<(&str, usize, u64)> = candidates
+            .into_iter()
+            .filter_map(|position| {
+                let (term, count) = &self.terms[position];
+                let distance = edit_distance(word, term);
+                (distance <= max_distance).then_some((term.as_str(), distance, *count))
+            })
+            .collect();
+        matches.sort_by_key(|&(term, distance, count)| (distance, Reverse(count), term));
+        matches
+    }
+
+    /// Up to `limit` corrections of `query`, the fewest edits first and then
+    /// those whose words are the most common. Words of the dictionary are
+    /// kept, and so are words with nothing close to them. Corrections are
+    /// lowercased, with their words separated by single spaces. A query
+    /// spelled right has none.
+    pub fn correct(&self, query: &str, limit: usize) -> Vec<Correction> {
+        // Candidates of each word of the query, as (word, edits, count).
+        let mut options: Vec<Vec<(String, usize, Option<u64>)>> = Vec::new();
+        for word in query.unicode_words().map(|word| word.to_lowercase()) {
+            let candidates = match self.positions.get(&word) {
+                Some(&position) => vec![(word, 0, Some(self.terms[position].1))],
+                None => {
+                    let candidates: Vec<_> = self
+                        .lookup(&word, max_distance(&word))
+                        .into_iter()
+                        .take(limit)
+                        .map(|(term, distance, count)| (term.to_string(), distance, Some(count)))
+                        .collect();
+                    if candidates.is_empty() {
+                        vec![(word, 0, None)]
+                    } else {
+                        candidates
+                    }
+                }
+            };
+            options.push(candidates);
+        }
+
+        // Combine the candidates of each word, keeping the best `limit`
+        // queries after each of them.
+        let mut beam: Vec<(Vec<String>, usize, Option<u64>)> = vec![(Vec::new(), 0, None)];
+        for candidates in options {
+            let mut next = Vec::new();
+            for (words, distance, count) in &beam {
+                for (word, edits, word_count) in &candidates {
+                    let mut words = words.clone();
+                    words.push(word.clone());
+                    let count = match (count, word_count) {
+                        (Some(a), Some(b)) => Some((*a).min(*b)),
+                        (a, b) => a.or(*b),
+                    };
+                    next.push((words, distance + edits, count));
+                }
+            }
+            next.sort_by_key(|(_, distance, count)| (*distance, Reverse(count.unwrap_or(0))));
+            next.truncate(limit);
+            beam = next;
+        }
+
+        beam.into_iter()
+            .filter(|(_, distance, _)| *distance > 0)
+            .map(|(words, distance, count)| Correction {
+                text: words.join(" "),
+                distance,
+                count: count.unwrap_or(0),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("search", "search"), 0);
+        assert_eq!(edit_distance("serch", "search"), 1);
+        assert_eq!(edit_distance("teh", "the"), 1);
+        assert_eq!(edit_distance("vectr", "vector"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("straße", "strasse"), 2);
+    }
+
+    #[test]
+    fn test_correct() {
+        assert_eq!(
+            extract_terms("Vector search, vector INDEX of 42 documents: a"),
+            vec!["vector", "search", "index", "of", "documents"]
+        );
+
+        let dictionary = SpellingDictionary::new(vec![
+            (String::from("vector"), 5),
+            (String::from("search"), 8),
+            (String::from("seas"), 1),
+            (String::from("searches"), 2),
+            (String::from("rust"), 3),
+        ]);
+        assert_eq!(
+            dictionary.lookup("seach", 2),
+            vec![("search", 1, 8), ("seas", 2, 1)]
+        );
+        assert_eq!(
+            dictionary.correct("Vectr serch", 3),
+            vec![Correction {
+                text: String::from("vector search"),
+                distance: 2,
+                count: 5,
+            }]
+        );
+        let corrections = dictionary.correct("seach", 3);
+        assert_eq!(corrections.len(), 2);
+        assert_eq!(corrections[1].text, "seas");
+        assert_eq!(
+            dictionary.correct("rust serch engine", 1)[0].text,
+            "rust search engine"
+        );
+        assert!(dictionary.correct("rust search", 3).is_empty());
+        assert!(dictionary.correct("xyzzy", 3).is_empty());
+    }
+}
@@ -0,0 +1,410 @@
+use anyhow::anyhow;
+use duckdb::ffi;
+use serde::Serialize;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+/// Threads a query runs on, so that a query leaves the rest of the machine
+/// to the server until it ends or is interrupted.
+const SQL_THREADS: &str = "1";
+
+/// Memory a query may use before DuckDB fails it.
+const SQL_MEMORY_LIMIT: &str = "1GB";
+
+/// Types of the statements a read-only query may be: DuckDB parses
+/// `DESCRIBE`, `SHOW` and `SUMMARIZE` into `SELECT`s too.
+const ALLOWED_STATEMENTS: [ffi::duckdb_statement_type; 2] = [
+    ffi::duckdb_statement_type_DUCKDB_STATEMENT_TYPE_SELECT,
+    ffi::duckdb_statement_type_DUCKDB_STATEMENT_TYPE_EXPLAIN,
+];
+
+/// Columns and rows returned by a read-only query.
+#[derive(Serialize, Debug)]
+pub struct SqlResults {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Whether rows beyond the row limit were left out.
+    pub truncated: bool,
+}
+
+/// A DuckDB instance, closed once no connection to it is left.
+struct Instance(ffi::duckdb_database);
+
+/// Handles of a connection and of the instance it belongs to, closed once
+/// neither the [`ReadOnlyDatabase`] nor an [`InterruptHandle`] holds them.
+struct Handles {
+    instance: Arc<Instance>,
+    conn: ffi::duckdb_connection,
+}
+
+// SAFETY: DuckDB instances may be shared between threads, and connections
+// are only used to run queries through `&mut ReadOnlyDatabase`, one at a
+// time, and to interrupt them, which DuckDB allows from any thread.
+unsafe impl Send for Instance {}
+unsafe impl Sync for Instance {}
+unsafe impl Send for Handles {}
+unsafe impl Sync for Handles {}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // SAFETY: the instance was opened by `ReadOnlyDatabase::open`, and
+        // its connections, which hold it, are closed.
+        unsafe { ffi::duckdb_close(&mut self.0) }
+    }
+}
+
+impl Drop for Handles {
+    fn drop(&mut self) {
+        // SAFETY: the connection was opened by `ReadOnlyDatabase::open` or
+        // `connect` and is closed once, before its instance.
+        unsafe { ffi::duckdb_disconnect(&mut self.conn) }
+    }
+}
+
+/// A connection to a database opened for read-only queries, in a DuckDB
+/// instance of its own. It uses DuckDB's C API, as the duckdb crate cannot
+/// interrupt a running query.
+pub struct ReadOnlyDatabase {
+    handles: Arc<Handles>,
+}
+
+/// Stops the query running on a [`ReadOnlyDatabase`], from any thread.
+#[derive(Clone)]
+pub struct InterruptHandle(Arc<Handles>);
+
+impl InterruptHandle {
+    /// Make the running query fail as soon as DuckDB checks for interrupts,
+    /// which it does between chunks of rows.
+    pub fn interrupt(&self) {
+        // SAFETY: the connection stays open while this handle exists.
+        unsafe { ffi::duckdb_interrupt(self.0.conn) }
+    }
+}
+
+/// Error message owned by DuckDB, or `fallback` when there is none.
+///
+/// # Safety
+///
+/// `message` must be null or a valid C string.
+unsafe fn error_message(message: *const c_char, fallback: &str) -> String {
+    if message.is_null() {
+        return fallback.to_string();
+    }
+    CStr::from_ptr(message).to_string_lossy().into_owned()
+}
+
+impl ReadOnlyDatabase {
+    /// Open the database at `path` for read-only queries. The instance is
+    /// what keeps queries from changing the database or reading files or
+    /// URLs: it is opened read-only, external access is disabled and its
+    /// configuration locked, so that queries cannot enable it again. Queries
+    /// also run on [`SQL_THREADS`] within [`SQL_MEMORY_LIMIT`]. The instance
+    /// sees the database as it was when opened, i.e. as of its last
+    /// checkpoint.
+    ///
+    /// DuckDB locks the file with `fcntl`, whose locks belong to the process:
+    /// opened on the file of a collection that this process has open for
+    /// writing, the instance shares its lock, which is released when the
+    /// instance closes. Keep it open as long as it is needed rather than
+    /// opening one per query.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let path = CString::new(path.to_string_lossy().as_bytes())?;
+        let settings = [
+            ("access_mode", "READ_ONLY"),
+            ("enable_external_access", "false"),
+            ("threads", SQL_THREADS),
+            ("max_memory", SQL_MEMORY_LIMIT),
+            ("lock_configuration", "true"),
+        ];
+        let settings = settings
+            .iter()
+            .map(|(name, value)| Ok((CString::new(*name)?, CString::new(*value)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // SAFETY: every handle is checked before use, and freed or handed
+        // over to `Handles` on every path.
+        unsafe {
+            let mut config: ffi::duckdb_config = ptr::null_mut();
+            if ffi::duckdb_create_config(&mut config) != ffi::DuckDBSuccess {
+                ffi::duckdb_destroy_config(&mut config);
+                return Err(anyhow!("Failed to create the database config"));
+            }
+            for (name, value) in &settings {
+                if ffi::duckdb_set_config(config, name.as_ptr(), value.as_ptr())
+                    != ffi::DuckDBSuccess
+                {
+                    ffi::duckdb_destroy_config(&mut config);
+                    return Err(anyhow!("Failed to set {:?}", name));
+                }
+            }
+
+            let mut db: ffi::duckdb_database = ptr::null_mut();
+            let mut error: *mut c_char = ptr::null_mut();
+            let state = ffi::duckdb_open_ext(path.as_ptr(), &mut db, config, &mut error);
+            ffi::duckdb_destroy_config(&mut config);
+            if state != ffi::DuckDBSuccess {
+                let message = error_message(error, "Failed to open the database");
+                ffi::duckdb_free(error.cast());
+                return Err(anyhow!(message));
+            }
+            Self::connect_to(Arc::new(Instance(db)))
+        }
+    }
+
+    /// Another connection to the same instance, to run a query while this
+    /// one runs another.
+    pub fn connect(&self) -> anyhow::Result<Self> {
+        Self::connect_to(self.handles.instance.clone())
+    }
+
+    fn connect_to(instance: Arc<Instance>) -> anyhow::Result<Self> {
+        let mut conn: ffi::duckdb_connection = ptr::null_mut();
+        // SAFETY: the instance is open while `instance` is held.
+        if unsafe { ffi::duckdb_connect(instance.0, &mut conn) } != ffi::DuckDBSuccess {
+            return Err(anyhow!("Failed to connect to the database"));
+        }
+        Ok(ReadOnlyDatabase {
+            handles: Arc::new(Handles { instance, conn }),
+        })
+    }
+
+    /// Handle to stop a query run by [`ReadOnlyDatabase::run`] from another
+    /// thread, e.g. when it takes too long.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.handles.clone())
+    }
+
+    /// Run `query`, a single statement of one of the
+    /// [`ALLOWED_STATEMENTS`] types, and return up to `max_rows` rows. The
+    /// result is materialized within [`SQL_MEMORY_LIMIT`] before its first
+    /// `max_rows` rows are read.
+    pub fn run(&mut self, query: &str, max_rows: usize) -> anyhow::Result<SqlResults> {
+        let query = CString::new(query)?;
+        let conn = self.handles.conn;
+        // SAFETY: the extracted statements, the prepared statement and the
+        // result are destroyed on every path, as DuckDB requires even when
+        // they failed.
+        unsafe {
+            let mut extracted: ffi::duckdb_extracted_statements = ptr::null_mut();
+            let count = ffi::duckdb_extract_statements(conn, query.as_ptr(), &mut extracted);
+            if count != 1 {
+                let message = if count == 0 {
+                    error_message(
+                        ffi::duckdb_extract_statements_error(extracted),
+                        "Query is empty",
+                    )
+                } else {
+                    "Only a single statement is allowed".to_string()
+                };
+                ffi::duckdb_destroy_extracted(&mut extracted);
+                return Err(anyhow!(message));
+            }
+            let mut statement: ffi::duckdb_prepared_statement = ptr::null_mut();
+            let state = ffi::duckdb_prepare_extracted_statement(conn, extracted, 0, &mut statement);
+            ffi::duckdb_destroy_extracted(&mut extracted);
+            let results = if state != ffi::DuckDBSuccess {
+                Err(anyhow!(error_message(
+                    ffi::duckdb_prepare_error(statement),
+                    "Failed to prepare the query"
+                )))
+            } else if !ALLOWED_STATEMENTS.contains(&ffi::duckdb_prepared_statement_type(statement))
+            {
+                Err(anyhow!("Only SELECT queries are allowed"))
+            } else {
+                let mut result: ffi::duckdb_result = std::mem::zeroed();
+                let results =
+                    if ffi::duckdb_execute_prepared(statement, &mut result) == ffi::DuckDBSuccess {
+                        Ok(read_result(&mut result, max_rows))
+                    } else {
+                        Err(anyhow!(error_message(
+                            ffi::duckdb_result_error(&mut result),
+                            "Query failed"
+                        )))
+                    };
+                ffi::duckdb_destroy_result(&mut result);
+                results
+            };
+            ffi::duckdb_destroy_prepare(&mut statement);
+            results
+        }
+    }
+}
+
+/// Columns and first `max_rows` rows of a materialized result.
+///
+/// # Safety
+///
+/// `result` must be the result of a successful `duckdb_query`.
+unsafe fn read_result(result: &mut ffi::duckdb_result, max_rows: usize) -> SqlResults {
+    let column_count = ffi::duckdb_column_count(result);
+    let row_count = ffi::duckdb_row_count(result);
+    let columns = (0..column_count)
+        .map(|col| error_message(ffi::duckdb_column_name(result, col), ""))
+        .collect();
+    let types: Vec<ffi::DUCKDB_TYPE> = (0..column_count)
+        .map(|col| ffi::duckdb_column_type(result, col))
+        .collect();
+
+    let rows = (0..row_count.min(max_rows as u64))
+        .map(|row| {
+            types
+                .iter()
+                .enumerate()
+                .map(|(col, &column_type)| read_value(result, col as u64, row, column_type))
+                .collect()
+        })
+        .collect();
+    SqlResults {
+        columns,
+        rows,
+        truncated: row_count > max_rows as u64,
+    }
+}
+
+/// A value of a materialized result as JSON: numbers and booleans as such,
+/// other types as DuckDB casts them to text.
+///
+/// # Safety
+///
+/// `col` and `row` must be within `result`, a successful result.
+unsafe fn read_value(
+    result: &mut ffi::duckdb_result,
+    col: u64,
+    row: u64,
+    column_type: ffi::DUCKDB_TYPE,
+) -> serde_json::Value {
+    if ffi::duckdb_value_is_null(result, col, row) {
+        return serde_json::Value::Null;
+    }
+    match column_type {
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_BOOLEAN => ffi::duckdb_value_boolean(result, col, row).into(),
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_TINYINT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_SMALLINT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_INTEGER
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_BIGINT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_UTINYINT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_USMALLINT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_UINTEGER => ffi::duckdb_value_int64(result, col, row).into(),
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_UBIGINT => ffi::duckdb_value_uint64(result, col, row).into(),
+        ffi::DUCKDB_TYPE_DUCKDB_TYPE_FLOAT
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_DOUBLE
+        | ffi::DUCKDB_TYPE_DUCKDB_TYPE_DECIMAL => ffi::duckdb_value_double(result, col, row).into(),
+        column_type => {
+            let text = ffi::duckdb_value_varchar(result, col, row);
+            if text.is_null() {
+                return serde_json::Value::Null;
+            }
+            let value = CStr::from_ptr(text).to_string_lossy().into_owned();
+            ffi::duckdb_free(text.cast());
+            // Sums of integers are HUGEINT, which is still a number.
+            let huge = column_type == ffi::DUCKDB_TYPE_DUCKDB_TYPE_HUGEINT
+                || column_type == ffi::DUCKDB_TYPE_DUCKDB_TYPE_UHUGEINT;
+            match value.parse::<i64>() {
+                Ok(number) if huge => number.into(),
+                _ if huge => value.parse::<f64>().map_or(value.into(), Into::into),
+                _ => value.into(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_run_read_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("docs.db");
+        duckdb::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE docs (lang TEXT, year INTEGER);
+                 INSERT INTO docs VALUES ('en', 2020), ('en', 2022), ('de', 2021);",
+            )
+            .unwrap();
+
+        let mut read_only = ReadOnlyDatabase::open(&path).unwrap();
+        let results = read_only
+            .run(
+                "SELECT lang, year, sum(year) AS total FROM docs GROUP BY ALL ORDER BY year",
+                2,
+            )
+            .unwrap();
+        assert_eq!(results.columns, vec!["lang", "year", "total"]);
+        assert_eq!(
+            results.rows,
+            vec![
+                vec![json!("en"), json!(2020), json!(2020)],
+                vec![json!("de"), json!(2021), json!(2021)]
+            ]
+        );
+        assert!(results.truncated);
+
+        let describe = read_only.run("DESCRIBE docs", 10).unwrap();
+        assert_eq!(describe.rows.len(), 2);
+
+        let denied = [
+            "DROP TABLE docs",
+            "DELETE FROM docs",
+            "ATTACH 'other.db'",
+            "COPY docs TO 'out.csv'",
+            "SET enable_external_access = true",
+            "INSTALL httpfs",
+            "SELECT 1; DROP TABLE docs",
+            "SELECT 'unterminated",
+            // Files cannot be read, however the query names them.
+            "SELECT * FROM read_csv('/etc/passwd')",
+            "SELECT * FROM '/etc/passwd'",
+            "SELECT * FROM query('SELECT * FROM read_text(''/etc/passwd'')')",
+        ];
+        for query in denied {
+            assert!(read_only.run(query, 10).is_err(), "{}", query);
+        }
+
+        // Connections to the instance run queries of their own.
+        let mut other = read_only.connect().unwrap();
+        drop(read_only);
+        let results = other.run("SELECT count(*) FROM docs", 10).unwrap();
+        assert_eq!(results.rows, vec![vec![json!(3)]]);
+        drop(other);
+        let count: i64 = duckdb::Connection::open(&path)
+            .unwrap()
+            .query_row("SELECT count(*) FROM docs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("docs.db");
+        duckdb::Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE docs (text TEXT); CHECKPOINT;")
+            .unwrap();
+
+        let mut read_only = ReadOnlyDatabase::open(&path).unwrap();
+        let handle = read_only.interrupt_handle();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            handle.interrupt();
+        });
+        let start = std::time::Instant::now();
+        let endless = read_only.run("SELECT count(*) FROM range(1e15)", 10);
+        assert!(endless.is_err());
+        assert!(start.elapsed() < std::time::Duration::from_secs(30));
+        stopper.join().unwrap();
+
+        // The connection serves the next query.
+        let results = read_only.run("SELECT count(*) AS n FROM docs", 10).unwrap();
+        assert_eq!(results.rows, vec![vec![json!(0)]]);
+        drop(read_only);
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Table of the autocomplete suggestions of a collection, one row per
+/// `(phrase, source)` with the number of times the phrase was seen.
+pub const SUGGESTIONS_TABLE: &str = "_letsearch_suggestions";
+
+/// Phrases longer than this many characters are not suggested, as they are
+/// rarely typed again, e.g. pasted paragraphs.
+pub const MAX_PHRASE_CHARS: usize = 100;
+
+/// Recorded queries are suggested once searched this many times, so that a
+/// query searched by a single user is not shown to the others.
+pub const MIN_QUERY_COUNT: u64 = 3;
+
+/// Words that neither start nor end an extracted phrase.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been",
+    "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he",
+    "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "may", "more", "most", "no",
+    "not", "of", "on", "one", "or", "other", "our", "she", "should", "so", "some", "such", "than",
+    "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "to", "up",
+    "was", "we", "were", "what", "when", "where", "which", "while", "who", "will", "with", "would",
+    "you", "your",
+];
+
+/// Where a suggestion comes from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionSource {
+    /// A query searched on the server.
+    Query,
+    /// A phrase extracted from the indexed text.
+    Text,
+}
+
+impl fmt::Display for SuggestionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SuggestionSource::Query => "query",
+            SuggestionSource::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A completion of a prefix, with how often it was searched or how many
+/// documents contain it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    pub count: u64,
+}
+
+/// Normalize a query or phrase for storage and prefix matching: lowercase,
+/// words separated by single spaces, without surrounding punctuation.
+/// Returns `None` when nothing is left or the phrase is too long.
+pub fn normalize_phrase(text: &str) -> Option<String> {
+    let phrase = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let phrase = phrase.trim_matches(|c: char| !c.is_alphanumeric());
+    if phrase.is_empty() || phrase.chars().count() > MAX_PHRASE_CHARS {
+        return None;
+    }
+    Some(phrase.to_string())
+}
+
+/// Candidate phrases of a document: runs of up to `max_words` words
+/// delimited by punctuation and stopwords. Each phrase is listed once.
+pub fn extract_phrases(text: &str, max_words: usize) -> Vec<String> {
+    let mut phrases = Vec::new();
+    let mut seen = HashSet::new();
+    let mut run: Vec<String> = Vec::new();
+    let mut flush = |run: &mut Vec<String>| {
+        let keep = match run.as_slice() {
+            [] => false,
+            // Single words only make suggestions when they are not too short
+            // and not numbers.
+            [word] => word.chars().count() >= 3 && !word.chars().all(|c| c.is_numeric()),
+            words => words.len() <= max_words,
+        };
+        if keep {
+            let phrase = run.join(" ");
+            if seen.insert(phrase.clone()) {
+                phrases.push(phrase);
+            }
+        }
+        run.clear();
+    };
+
+    for token in text.split_word_bounds() {
+        if token.trim().is_empty() {
+            continue;
+        }
+        if !token.chars().any(char::is_alphanumeric) {
+            flush(&mut run);
+            continue;
+        }
+        let word = token.to_lowercase();
+        if STOPWORDS.contains(&word.as_str()) {
+            flush(&mut run);
+        } else {
+            run.push(word);
+        }
+    }
+    flush(&mut run);
+    phrases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_phrase() {
+        assert_eq!(
+            normalize_phrase("  Rust   Async?  "),
+            Some(String::from("rust async"))
+        );
+        assert_eq!(normalize_phrase(" ?! "), None);
+        assert_eq!(normalize_phrase(&"a".repeat(MAX_PHRASE_CHARS + 1)), None);
+    }
+
+    #[test]
+    fn test_extract_phrases() {
+        let text = "The vector index of letsearch is fast. Vector index, and 42 more: \
+                    a very long run of many content words here";
+        assert_eq!(
+            extract_phrases(text, 3),
+            vec!["vector index", "letsearch", "fast", "very long run"]
+        );
+    }
+}
@@ -1,29 +1,345 @@
+use crate::collection::journal::{Journal, JournalEntry};
+use crate::collection::related::Neighbour;
+use crate::estimate::VectorDType;
 use anyhow;
+use chrono::{DateTime, Utc};
 use log::{debug, info};
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::{fs, u64, usize};
-use usearch::{new_index, Index, IndexOptions, VectorType};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, RwLock};
+use usearch::{b1x8, f16 as UsearchF16, new_index, Index, IndexOptions, MetricKind, VectorType};
 
+/// A neighbour returned by [`VectorIndex::search`] with the raw distance
+/// reported by usearch. Lower is always closer.
 #[derive(Serialize)]
 pub struct SimilarityResult {
     pub key: u64,
-    pub score: f32,
+    pub distance: f32,
+}
+
+/// Distance metric of a vector index.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// Cosine distance, `1 - cos(a, b)`.
+    #[default]
+    Cos,
+    /// Inner product distance, `1 - a·b`. Meant for normalized embeddings.
+    IP,
+    /// Squared Euclidean distance.
+    L2sq,
+}
+
+impl Metric {
+    pub fn kind(&self) -> MetricKind {
+        match self {
+            Metric::Cos => MetricKind::Cos,
+            Metric::IP => MetricKind::IP,
+            Metric::L2sq => MetricKind::L2sq,
+        }
+    }
+
+    /// Kind usearch compares vectors of `dtype` with. Binary vectors can
+    /// only be compared by Hamming distance, see [`Metric::from_hamming`].
+    pub fn kind_for(&self, dtype: VectorDType) -> MetricKind {
+        match dtype {
+            VectorDType::B1 => MetricKind::Hamming,
+            _ => self.kind(),
+        }
+    }
+
+    /// Rough distance of this metric between unit vectors whose signs
+    /// differ in `bits` of their `dimensions`, taking the share of
+    /// differing signs as the share of a half turn between them.
+    pub fn from_hamming(&self, bits: f32, dimensions: usize) -> f32 {
+        let cos = (std::f32::consts::PI * bits / dimensions.max(1) as f32).cos();
+        match self {
+            Metric::Cos | Metric::IP => 1.0 - cos,
+            Metric::L2sq => 2.0 * (1.0 - cos),
+        }
+    }
+
+    /// Convert a raw distance to a similarity score where higher is better:
+    /// the cosine similarity for `cos`, the dot product for `ip` and
+    /// `1 / (1 + distance)` in `(0, 1]` for `l2sq`.
+    pub fn similarity(&self, distance: f32) -> f32 {
+        match self {
+            Metric::Cos | Metric::IP => 1.0 - distance,
+            Metric::L2sq => 1.0 / (1.0 + distance),
+        }
+    }
+
+    /// Distance between two vectors as computed by an index of this metric.
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        match self {
+            Metric::Cos => {
+                let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt()
+                    * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot / norms
+                }
+            }
+            Metric::IP => 1.0 - dot,
+            Metric::L2sq => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Metric::Cos => "cos",
+            Metric::IP => "ip",
+            Metric::L2sq => "l2sq",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cos" => Ok(Metric::Cos),
+            "ip" => Ok(Metric::IP),
+            "l2sq" => Ok(Metric::L2sq),
+            _ => Err(anyhow::anyhow!(
+                "Unknown metric '{}', expected one of cos, ip, l2sq",
+                s
+            )),
+        }
+    }
+}
+
+/// What the `score` of a search result holds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreType {
+    /// Metric-aware similarity, higher is better. See [`Metric::similarity`].
+    #[default]
+    Similarity,
+    /// Raw distance as computed by the index, lower is better.
+    Distance,
+}
+
+impl ScoreType {
+    pub fn score(&self, metric: Metric, distance: f32) -> f32 {
+        match self {
+            ScoreType::Similarity => metric.similarity(distance),
+            ScoreType::Distance => distance,
+        }
+    }
+
+    /// Order two scores of this type best first.
+    pub fn cmp_best_first(&self, a: f32, b: f32) -> Ordering {
+        match self {
+            ScoreType::Similarity => b.total_cmp(&a),
+            ScoreType::Distance => a.total_cmp(&b),
+        }
+    }
+}
+
+/// Bounds on how weak a search result may be, to leave out poor matches.
+/// Both bounds hold whichever [`ScoreType`] the results are returned with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScoreThreshold {
+    /// Lowest similarity, see [`Metric::similarity`].
+    pub min_score: Option<f32>,
+    /// Highest raw distance.
+    pub max_distance: Option<f32>,
+}
+
+impl ScoreThreshold {
+    /// Whether a result at `distance` in an index of `metric` is kept.
+    pub fn accepts(&self, metric: Metric, distance: f32) -> bool {
+        self.min_score
+            .is_none_or(|min_score| metric.similarity(distance) >= min_score)
+            && self
+                .max_distance
+                .is_none_or(|max_distance| distance <= max_distance)
+    }
+}
+
+/// HNSW graph parameters picked from the size of a collection when its index
+/// is built. usearch falls back to fixed defaults when given zeros, which
+/// loses recall on large collections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexPreset {
+    /// Fewer than 100k vectors.
+    Small,
+    /// Fewer than 1M vectors.
+    Medium,
+    Large,
+}
+
+impl IndexPreset {
+    /// Pick a preset for `rows` vectors of `dimensions` dimensions. High
+    /// dimensional vectors get the next bigger preset, as they need a denser
+    /// graph for the same recall.
+    pub fn for_collection(rows: u64, dimensions: usize) -> Self {
+        let preset = match rows {
+            0..=99_999 => IndexPreset::Small,
+            100_000..=999_999 => IndexPreset::Medium,
+            _ => IndexPreset::Large,
+        };
+        if dimensions >= 1024 {
+            match preset {
+                IndexPreset::Small => IndexPreset::Medium,
+                _ => IndexPreset::Large,
+            }
+        } else {
+            preset
+        }
+    }
+
+    pub fn connectivity(&self) -> usize {
+        match self {
+            IndexPreset::Small => 16,
+            IndexPreset::Medium => 24,
+            IndexPreset::Large => 32,
+        }
+    }
+
+    pub fn expansion_add(&self) -> usize {
+        match self {
+            IndexPreset::Small => 128,
+            IndexPreset::Medium => 192,
+            IndexPreset::Large => 256,
+        }
+    }
+
+    pub fn expansion_search(&self) -> usize {
+        match self {
+            IndexPreset::Small => 64,
+            IndexPreset::Medium => 128,
+            IndexPreset::Large => 256,
+        }
+    }
+}
+
+impl fmt::Display for IndexPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IndexPreset::Small => "small",
+            IndexPreset::Medium => "medium",
+            IndexPreset::Large => "large",
+        };
+        write!(
+            f,
+            "{} (connectivity={}, expansion_add={}, expansion_search={})",
+            name,
+            self.connectivity(),
+            self.expansion_add(),
+            self.expansion_search()
+        )
+    }
+}
+
+/// Settings of a rebuilt index that replace the ones it would otherwise be
+/// built with. Unset fields keep them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IndexOverrides {
+    pub metric: Option<Metric>,
+    pub dtype: Option<VectorDType>,
+    /// Graph connectivity instead of the preset's.
+    pub connectivity: Option<usize>,
+    /// Expansion factor while building instead of the preset's.
+    pub expansion_add: Option<usize>,
+}
+
+/// File next to `index.bin` recording how the index was built, since the
+/// usearch file format does not expose its metric and scalar type.
+const METADATA_FILE: &str = "metadata.json";
+
+#[derive(Serialize, Deserialize)]
+struct IndexMetadata {
+    metric: Metric,
+    /// Unknown for indexes saved before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dtype: Option<VectorDType>,
+}
+
+/// Shape and size of a vector index.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IndexStats {
+    pub vectors: u64,
+    pub dimensions: usize,
+    pub metric: Metric,
+    /// Memory held by the vectors and the graph.
+    pub memory_bytes: u64,
+    /// Size of the saved index file, without the changes not saved yet.
+    pub file_bytes: u64,
+    /// RFC 3339 modification time of the saved index file.
+    pub modified: Option<String>,
+}
+
+/// Scalars of the vectors given to an index. Binary indexes keep whether
+/// each one is positive, see [`VectorDType::B1`].
+pub trait Scalar: VectorType {
+    fn is_positive(&self) -> bool;
+}
+
+impl Scalar for f32 {
+    fn is_positive(&self) -> bool {
+        *self > 0.0
+    }
+}
+
+impl Scalar for UsearchF16 {
+    fn is_positive(&self) -> bool {
+        // Positive halves have the sign bit clear and other bits set.
+        UsearchF16::to_i16s(std::slice::from_ref(self))[0] > 0
+    }
+}
+
+/// Pack `vector` into one bit per scalar, set for positive ones, first
+/// scalar in the highest bit, as usearch does.
+pub fn binarize<T: Scalar>(vector: &[T]) -> Vec<b1x8> {
+    let mut bits = vec![b1x8(0); vector.len().div_ceil(8)];
+    for (i, value) in vector.iter().enumerate() {
+        if value.is_positive() {
+            bits[i / 8].0 |= 128 >> (i % 8);
+        }
+    }
+    bits
 }
 
 struct PtrBox<T: VectorType> {
     ptr: *const T,
 }
 
+/// A usearch index stored under `path`.
+///
+/// Vectors can be added and searched concurrently from several threads.
+/// Growing the capacity reallocates the graph, which usearch does not allow
+/// while other threads use it, so that takes an exclusive lock.
 pub struct VectorIndex {
     pub index: Option<Index>,
     path: PathBuf,
+    metric: Metric,
+    dtype: Option<VectorDType>,
+    /// Whether the index is a read-only memory map of its saved file.
+    viewed: bool,
+    resize_lock: RwLock<()>,
+    /// Changes since the index was last saved, replayed when it is loaded.
+    journal: Journal,
+    /// Vectors being added by any thread, counted before the capacity check
+    /// so that concurrent adds cannot together outgrow the capacity.
+    pending: AtomicUsize,
 }
 
 impl VectorIndex {
-    pub fn new(index_dir: PathBuf, overwrite: bool) -> anyhow::Result<Self> {
+    pub fn new(index_dir: PathBuf, overwrite: bool, metric: Metric) -> anyhow::Result<Self> {
         debug!("creating new VectorIndex instance");
         let index_dir_str = index_dir
             .to_str()
@@ -37,7 +353,13 @@ impl VectorIndex {
 
         Ok(VectorIndex {
             index: None,
+            metric,
+            dtype: None,
+            viewed: false,
+            resize_lock: RwLock::new(()),
+            journal: Journal::new(&index_dir),
             path: index_dir,
+            pending: AtomicUsize::new(0),
         })
     }
 
@@ -51,10 +373,34 @@ impl VectorIndex {
             .reserve(capacity)
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
         self.index = Some(index);
+        self.dtype = VectorDType::from_scalar_kind(options.quantization);
         Ok(self)
     }
 
-    pub fn from(path: PathBuf) -> anyhow::Result<Self> {
+    /// Load the index saved under `path`. Indexes saved without a metadata
+    /// file are assumed to use `default_metric`.
+    pub fn from(path: PathBuf, default_metric: Metric) -> anyhow::Result<Self> {
+        Self::open(path, default_metric, false)
+    }
+
+    /// Memory-map the index saved under `path` instead of reading it, so
+    /// that large indexes open instantly and only the pages that searches
+    /// touch take up memory. The index can be searched but not changed.
+    pub fn view(path: PathBuf, default_metric: Metric) -> anyhow::Result<Self> {
+        Self::open(path, default_metric, true)
+    }
+
+    fn open(path: PathBuf, default_metric: Metric, view: bool) -> anyhow::Result<Self> {
+        let journal = Journal::new(&path);
+        if view && !journal.is_empty() {
+            // A view cannot apply the journal, so the saved file is brought
+            // up to date first.
+            info!(
+                "Applying the journal of {:?} before memory-mapping it",
+                path
+            );
+            Self::open(path.clone(), default_metric, false)?.save()?;
+        }
         let index_path = path.join("index.bin");
         let index_path_str = index_path
             .to_str()
@@ -62,23 +408,127 @@ impl VectorIndex {
         info!("Index path: {:?}", index_path_str);
         let config = IndexOptions::default();
         let index = Index::new(&config)?;
-        index.load(index_path_str)?;
-        info!("vector index loaded from {:?}", path.to_string_lossy());
+        if view {
+            index.view(index_path_str)?;
+            info!(
+                "vector index memory-mapped from {:?}",
+                path.to_string_lossy()
+            );
+        } else {
+            index.load(index_path_str)?;
+            info!("vector index loaded from {:?}", path.to_string_lossy());
+        }
         info!("vector count: {:?}", index.size());
         info!("vector dimensions: {:?}", index.dimensions());
 
-        Ok(VectorIndex {
+        // Expansion factors are not stored in the index file, so they are
+        // picked again from the current size. The graph connectivity is kept.
+        let preset = IndexPreset::for_collection(index.size() as u64, index.dimensions());
+        index.change_expansion_add(preset.expansion_add());
+        index.change_expansion_search(preset.expansion_search());
+        debug!("index preset: {}", preset);
+
+        let (metric, dtype) = match fs::read_to_string(path.join(METADATA_FILE)) {
+            Ok(metadata) => {
+                let metadata = serde_json::from_str::<IndexMetadata>(&metadata)?;
+                (metadata.metric, metadata.dtype)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (default_metric, None),
+            Err(e) => return Err(e.into()),
+        };
+        info!("vector metric: {}", metric);
+
+        let vector_index = VectorIndex {
             index: Some(index),
-            path: path,
-        })
+            path,
+            metric,
+            dtype,
+            viewed: view,
+            resize_lock: RwLock::new(()),
+            pending: AtomicUsize::new(0),
+            journal,
+        };
+        if !view {
+            vector_index.replay()?;
+        }
+        Ok(vector_index)
+    }
+
+    /// Apply the changes journaled since the index was last saved, e.g. by a
+    /// process that crashed while indexing.
+    ///
+    /// A crash between saving the index and clearing the journal leaves
+    /// changes in the journal that the saved index already has, so batches
+    /// whose keys were all in the index before replaying are skipped.
+    fn replay(&self) -> anyhow::Result<()> {
+        let entries = self.journal.entries()?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let index = self.writable()?;
+        let saved: Vec<bool> = entries
+            .iter()
+            .map(|entry| match entry {
+                JournalEntry::Add { keys, .. } => keys.iter().all(|key| index.contains(*key)),
+                JournalEntry::Remove { .. } => false,
+            })
+            .collect();
+        let (mut added, mut removed) = (0, 0);
+        for (entry, saved) in entries.iter().zip(saved) {
+            match entry {
+                JournalEntry::Add { .. } if saved => {}
+                JournalEntry::Add {
+                    keys,
+                    dimensions,
+                    scalar_bytes,
+                    vectors,
+                } => {
+                    match scalar_bytes {
+                        2 => {
+                            let vectors: Vec<i16> = vectors
+                                .chunks_exact(2)
+                                .map(|v| i16::from_ne_bytes([v[0], v[1]]))
+                                .collect();
+                            let vectors = UsearchF16::from_i16s(&vectors);
+                            self.insert(index, keys, vectors.as_ptr(), *dimensions, None)?;
+                        }
+                        4 => {
+                            let vectors: Vec<f32> = vectors
+                                .chunks_exact(4)
+                                .map(|v| f32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+                                .collect();
+                            self.insert(index, keys, vectors.as_ptr(), *dimensions, None)?;
+                        }
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "Journal of {:?} has vectors of {}-byte scalars",
+                                self.path,
+                                scalar_bytes
+                            ))
+                        }
+                    }
+                    added += keys.len();
+                }
+                JournalEntry::Remove { keys } => {
+                    for key in keys {
+                        removed += index
+                            .remove(*key)
+                            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+                    }
+                }
+            }
+        }
+        info!(
+            "Replayed the journal of {:?}: {} vectors added, {} removed",
+            self.path, added, removed
+        );
+        Ok(())
     }
 
     pub fn save(&self) -> anyhow::Result<()> {
-        let index = self
-            .index
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))?;
+        let index = self.writable()?;
         let index_path = self.path.join("index.bin");
+        let _guard = self.resize_lock.write().unwrap_or_else(|e| e.into_inner());
         index
             .save(
                 index_path
@@ -86,29 +536,164 @@ impl VectorIndex {
                     .ok_or_else(|| anyhow::anyhow!("Invalid unicode in index path"))?,
             )
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        fs::write(
+            self.path.join(METADATA_FILE),
+            serde_json::to_string(&IndexMetadata {
+                metric: self.metric,
+                dtype: self.dtype,
+            })?,
+        )?;
+        self.journal.clear()?;
 
         Ok(())
     }
 
-    pub fn add<T: VectorType>(
-        &self,
-        keys: &Vec<u64>,
-        vectors_ptr: *const T,
-        vector_dim: usize,
-    ) -> anyhow::Result<()> {
+    /// The index, unless it is a read-only view.
+    fn writable(&self) -> anyhow::Result<&Index> {
+        if self.viewed {
+            return Err(anyhow::anyhow!(
+                "Index {:?} is memory-mapped read-only and cannot be changed. Load it without memory-mapping to add or remove documents",
+                self.path
+            ));
+        }
+        self.index
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))
+    }
+
+    /// Whether the index is a read-only view, see [`VectorIndex::view`].
+    pub fn read_only(&self) -> bool {
+        self.viewed
+    }
+
+    /// Metric the index was built with. Search scores must be computed with it.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Scalar type the vectors are stored as, unknown for indexes saved
+    /// before it was recorded.
+    pub fn dtype(&self) -> Option<VectorDType> {
+        self.dtype
+    }
+
+    /// Dimensions of the vectors in the index, 0 before it is initialized.
+    pub fn dimensions(&self) -> usize {
+        self.index.as_ref().map_or(0, |index| index.dimensions())
+    }
+
+    /// The vector stored under `key`, or `None` when there is none. Of a
+    /// key with several vectors, e.g. the chunks of a document, the first
+    /// one is returned.
+    pub fn get(&self, key: u64) -> anyhow::Result<Option<Vec<f32>>> {
         let index = self
             .index
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))?;
-        let current_capacity = index.capacity();
-        let size = index.size();
-        let count = keys.len();
-        let required_capacity = size + count;
-        if required_capacity > current_capacity {
-            let extra_capacity = (required_capacity as f64 * 1.1) as usize;
-            index.reserve(extra_capacity)?;
+
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        let mut vector = vec![0f32; index.dimensions()];
+        if index.get(key, &mut vector)? == 0 {
+            return Ok(None);
         }
+        Ok(Some(vector))
+    }
 
+    /// Size of the journal of changes not saved yet.
+    pub fn journal_bytes(&self) -> u64 {
+        self.journal.len()
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let file = fs::metadata(self.path.join("index.bin")).ok();
+        IndexStats {
+            vectors: self.index.as_ref().map_or(0, |index| index.size()) as u64,
+            dimensions: self.index.as_ref().map_or(0, |index| index.dimensions()),
+            metric: self.metric,
+            memory_bytes: self.index.as_ref().map_or(0, |index| index.memory_usage()) as u64,
+            file_bytes: file.as_ref().map_or(0, |file| file.len()),
+            modified: file
+                .and_then(|file| file.modified().ok())
+                .map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+        }
+    }
+
+    /// Add one vector per key, read from `keys.len() * vector_dim` values
+    /// starting at `vectors_ptr`. The vectors are journaled before they are
+    /// added, so that they survive a crash before the next save. Both happen
+    /// under the lock [`VectorIndex::save`] takes to clear the journal, so
+    /// that a save either has the vectors or leaves them in the journal.
+    pub fn add<T: Scalar>(
+        &self,
+        keys: &[u64],
+        vectors_ptr: *const T,
+        vector_dim: usize,
+    ) -> anyhow::Result<()> {
+        let index = self.writable()?;
+        if vector_dim != index.dimensions() {
+            return Err(anyhow::anyhow!(
+                "Vectors have {} dimensions but the index expects {}",
+                vector_dim,
+                index.dimensions()
+            ));
+        }
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                vectors_ptr as *const u8,
+                keys.len() * vector_dim * std::mem::size_of::<T>(),
+            )
+        };
+        let entry = JournalEntry::Add {
+            keys: keys.to_vec(),
+            dimensions: vector_dim,
+            scalar_bytes: std::mem::size_of::<T>(),
+            vectors: bytes.to_vec(),
+        };
+        self.insert(index, keys, vectors_ptr, vector_dim, Some(&entry))
+    }
+
+    /// Insert the vectors, after appending `entry` to the journal if given.
+    fn insert<T: Scalar>(
+        &self,
+        index: &Index,
+        keys: &[u64],
+        vectors_ptr: *const T,
+        vector_dim: usize,
+        entry: Option<&JournalEntry>,
+    ) -> anyhow::Result<()> {
+        // Vectors other threads are adding are not in `size()` yet, so they
+        // are counted as well. Those already added are counted twice until
+        // their batch is done, which only grows the index a little early.
+        let pending = self.pending.fetch_add(keys.len(), AtomicOrdering::SeqCst) + keys.len();
+        let result = self.insert_reserved(index, keys, vectors_ptr, vector_dim, pending, entry);
+        self.pending.fetch_sub(keys.len(), AtomicOrdering::SeqCst);
+        result
+    }
+
+    fn insert_reserved<T: Scalar>(
+        &self,
+        index: &Index,
+        keys: &[u64],
+        vectors_ptr: *const T,
+        vector_dim: usize,
+        pending: usize,
+        entry: Option<&JournalEntry>,
+    ) -> anyhow::Result<()> {
+        let required_capacity = index.size() + pending;
+        if required_capacity > index.capacity() {
+            let _guard = self.resize_lock.write().unwrap_or_else(|e| e.into_inner());
+            // Another thread may have grown the index while we waited.
+            if required_capacity > index.capacity() {
+                let extra_capacity = (required_capacity as f64 * 1.1) as usize;
+                index.reserve(extra_capacity)?;
+            }
+        }
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entry {
+            self.journal.append(entry)?;
+        }
+
+        let binary = self.dtype == Some(VectorDType::B1);
         let shared_vectors = Arc::new(PtrBox { ptr: vectors_ptr });
         keys.par_iter()
             .enumerate()
@@ -116,41 +701,488 @@ impl VectorIndex {
                 let vectors = shared_vectors.clone();
                 let vector_offset = unsafe { vectors.ptr.add(i * vector_dim) };
                 let vector: &[T] = unsafe { std::slice::from_raw_parts(vector_offset, vector_dim) };
-                index
-                    .add(keys[i], vector)
-                    .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+                // The usearch bindings only take binary vectors packed.
+                let added = if binary {
+                    index.add(keys[i], &binarize(vector))
+                } else {
+                    index.add(keys[i], vector)
+                };
+                added.map_err(|e| anyhow::anyhow!(e.to_string()))?;
                 Ok(())
             })?;
 
         Ok(())
     }
 
-    pub fn search<T: VectorType>(
+    /// Find the `count` nearest neighbours of the vectors stored under each
+    /// of `keys`, excluding the key itself. Keys without a vector are skipped.
+    pub fn neighbours(
         &self,
-        vector: *const T,
-        vector_dim: usize,
+        keys: &[u64],
+        count: usize,
+    ) -> anyhow::Result<Vec<(u64, Vec<Neighbour>)>> {
+        let index = self
+            .index
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))?;
+
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        let dimensions = index.dimensions();
+        let results = keys
+            .par_iter()
+            .map(|key| -> anyhow::Result<Option<(u64, Vec<Neighbour>)>> {
+                let mut vector = vec![0f32; dimensions];
+                if index.get(*key, &mut vector)? == 0 {
+                    return Ok(None);
+                }
+                let matches = match self.dtype {
+                    Some(VectorDType::B1) => index.search(&binarize(&vector), count + 1)?,
+                    _ => index.search(&vector, count + 1)?,
+                };
+                let neighbours = self
+                    .to_similarity_results(matches)
+                    .into_iter()
+                    .filter(|result| result.key != *key)
+                    .map(|result| Neighbour {
+                        key: result.key,
+                        distance: result.distance,
+                    })
+                    .take(count)
+                    .collect();
+                Ok(Some((*key, neighbours)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Remove all vectors stored under `keys` and return how many were removed.
+    /// Keys that are not in the index are ignored.
+    pub fn remove(&self, keys: &[u64]) -> anyhow::Result<usize> {
+        let index = self.writable()?;
+        // Journaled under the lock of `save`, like additions.
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        self.journal.append(&JournalEntry::Remove {
+            keys: keys.to_vec(),
+        })?;
+
+        let mut removed = 0;
+        for key in keys {
+            removed += index
+                .remove(*key)
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        }
+        Ok(removed)
+    }
+
+    pub fn search<T: Scalar>(
+        &self,
+        query_vector: &[T],
+        count: usize,
+    ) -> anyhow::Result<Vec<SimilarityResult>> {
+        let index = self
+            .index
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))?;
+
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        let matches = match self.dtype {
+            Some(VectorDType::B1) => index.search(&binarize(query_vector), count)?,
+            _ => index.search(query_vector, count)?,
+        };
+        Ok(self.to_similarity_results(matches))
+    }
+
+    /// Like [`VectorIndex::search`], but only considers keys for which
+    /// `filter` returns `true`, so that `count` results are returned even
+    /// when most of the nearest neighbours are filtered out.
+    pub fn filtered_search<T: Scalar, F: Fn(u64) -> bool>(
+        &self,
+        query_vector: &[T],
         count: usize,
+        filter: F,
     ) -> anyhow::Result<Vec<SimilarityResult>> {
-        let query_vector: &[T] = unsafe { std::slice::from_raw_parts(vector, vector_dim) };
         let index = self
             .index
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("VectorIndex not initialized"))?;
 
-        let matches = index.search(query_vector, count)?;
-        let results: Vec<SimilarityResult> = matches
+        let _guard = self.resize_lock.read().unwrap_or_else(|e| e.into_inner());
+        let matches = match self.dtype {
+            Some(VectorDType::B1) => {
+                index.filtered_search(&binarize(query_vector), count, filter)?
+            }
+            _ => index.filtered_search(query_vector, count, filter)?,
+        };
+        Ok(self.to_similarity_results(matches))
+    }
+
+    /// Results of `matches`, with the Hamming distances of binary indexes
+    /// converted to the index's metric.
+    fn to_similarity_results(&self, matches: usearch::ffi::Matches) -> Vec<SimilarityResult> {
+        let dimensions = self.dimensions();
+        let binary = self.dtype == Some(VectorDType::B1);
+        matches
             .keys
             .iter()
             .zip(matches.distances.iter())
             .map(|(key, distance)| SimilarityResult {
                 key: *key,
-                score: 1.0 - distance,
+                distance: if binary {
+                    self.metric.from_hamming(*distance, dimensions)
+                } else {
+                    *distance
+                },
             })
-            .collect();
-
-        Ok(results)
+            .collect()
     }
 }
 
 unsafe impl<T: VectorType> Send for PtrBox<T> {}
 unsafe impl<T: VectorType> Sync for PtrBox<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_preset_for_collection() {
+        assert_eq!(IndexPreset::for_collection(0, 384), IndexPreset::Small);
+        assert_eq!(
+            IndexPreset::for_collection(250_000, 384),
+            IndexPreset::Medium
+        );
+        assert_eq!(
+            IndexPreset::for_collection(5_000_000, 384),
+            IndexPreset::Large
+        );
+        assert_eq!(
+            IndexPreset::for_collection(1_000, 3072),
+            IndexPreset::Medium
+        );
+        assert_eq!(
+            IndexPreset::for_collection(500_000, 1024),
+            IndexPreset::Large
+        );
+    }
+
+    #[test]
+    fn test_metric_distance() {
+        let a = [1.0, 0.0];
+        let b = [0.0, 2.0];
+        assert_eq!(Metric::Cos.distance(&a, &b), 1.0);
+        assert_eq!(Metric::Cos.distance(&a, &[3.0, 0.0]), 0.0);
+        assert_eq!(Metric::IP.distance(&a, &[0.5, 1.0]), 0.5);
+        assert_eq!(Metric::L2sq.distance(&a, &b), 5.0);
+    }
+
+    #[test]
+    fn test_score_threshold() {
+        let threshold = ScoreThreshold {
+            min_score: Some(0.5),
+            max_distance: None,
+        };
+        assert!(threshold.accepts(Metric::Cos, 0.2));
+        assert!(!threshold.accepts(Metric::Cos, 0.6));
+        // `1 / (1 + 1.5)` is below 0.5.
+        assert!(!threshold.accepts(Metric::L2sq, 1.5));
+
+        let threshold = ScoreThreshold {
+            min_score: Some(0.5),
+            max_distance: Some(0.1),
+        };
+        assert!(!threshold.accepts(Metric::Cos, 0.2));
+        assert!(ScoreThreshold::default().accepts(Metric::IP, 2.0));
+    }
+
+    #[test]
+    fn test_metric_from_str() {
+        assert_eq!("cos".parse::<Metric>().unwrap(), Metric::Cos);
+        assert_eq!("IP".parse::<Metric>().unwrap(), Metric::IP);
+        assert_eq!("L2sq".parse::<Metric>().unwrap(), Metric::L2sq);
+        for metric in [Metric::Cos, Metric::IP, Metric::L2sq] {
+            assert_eq!(metric.to_string().parse::<Metric>().unwrap(), metric);
+        }
+        let error = "euclidean".parse::<Metric>().unwrap_err().to_string();
+        assert!(error.contains("expected one of cos, ip, l2sq"), "{}", error);
+        assert!("".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(Metric::Cos.similarity(0.0), 1.0);
+        assert_eq!(Metric::Cos.similarity(2.0), -1.0);
+        assert_eq!(Metric::IP.similarity(0.25), 0.75);
+        assert_eq!(Metric::L2sq.similarity(0.0), 1.0);
+        assert_eq!(Metric::L2sq.similarity(3.0), 0.25);
+
+        // A closer result always scores higher, whatever the metric, and
+        // comes first in both score types.
+        for metric in [Metric::Cos, Metric::IP, Metric::L2sq] {
+            let (near, far) = (0.1, 0.8);
+            assert!(
+                metric.similarity(near) > metric.similarity(far),
+                "{}",
+                metric
+            );
+            for score_type in [ScoreType::Similarity, ScoreType::Distance] {
+                let (near, far) = (
+                    score_type.score(metric, near),
+                    score_type.score(metric, far),
+                );
+                assert_eq!(score_type.cmp_best_first(near, far), Ordering::Less);
+            }
+        }
+        assert_eq!(ScoreType::Distance.score(Metric::L2sq, 3.0), 3.0);
+    }
+
+    #[test]
+    fn test_concurrent_add_and_search() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::Cos).unwrap();
+        let options = IndexOptions {
+            dimensions: 4,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        // A small capacity forces the index to grow while being searched.
+        index.with_options(&options, 8).unwrap();
+        let index = Arc::new(index);
+
+        let writer = {
+            let index = index.clone();
+            std::thread::spawn(move || {
+                for batch in 0..50u64 {
+                    let keys: Vec<u64> = (batch * 10..batch * 10 + 10).collect();
+                    let vectors: Vec<f32> = keys
+                        .iter()
+                        .flat_map(|k| [*k as f32 + 1.0, 1.0, 0.0, 0.0])
+                        .collect();
+                    index.add::<f32>(&keys, vectors.as_ptr(), 4).unwrap();
+                }
+            })
+        };
+        for _ in 0..200 {
+            index.search::<f32>(&[1.0, 1.0, 0.0, 0.0], 5).unwrap();
+        }
+        writer.join().unwrap();
+
+        assert_eq!(index.index.as_ref().unwrap().size(), 500);
+    }
+
+    #[test]
+    fn test_concurrent_adds_grow_capacity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::L2sq).unwrap();
+        let options = IndexOptions {
+            dimensions: 2,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        let index = Arc::new(index);
+
+        // Every writer starts from the same size, so each must count the
+        // vectors the others are adding to reserve enough room.
+        let writers: Vec<_> = (0..8u64)
+            .map(|writer| {
+                let index = index.clone();
+                std::thread::spawn(move || {
+                    for batch in 0..20u64 {
+                        let start = (writer * 20 + batch) * 4;
+                        let keys: Vec<u64> = (start..start + 4).collect();
+                        let vectors: Vec<f32> =
+                            keys.iter().flat_map(|k| [*k as f32, 1.0]).collect();
+                        index.add::<f32>(&keys, vectors.as_ptr(), 2).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let inner = index.index.as_ref().unwrap();
+        assert_eq!(inner.size(), 640);
+        assert!(inner.capacity() >= inner.size());
+        assert_eq!(index.pending.load(AtomicOrdering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_concurrent_add_and_save() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::L2sq).unwrap();
+        let options = IndexOptions {
+            dimensions: 2,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        let index = Arc::new(index);
+
+        let writers: Vec<_> = (0..4u64)
+            .map(|writer| {
+                let index = index.clone();
+                std::thread::spawn(move || {
+                    for batch in 0..50u64 {
+                        let start = (writer * 50 + batch) * 2;
+                        let keys: Vec<u64> = (start..start + 2).collect();
+                        let vectors: Vec<f32> =
+                            keys.iter().flat_map(|k| [*k as f32, 1.0]).collect();
+                        index.add::<f32>(&keys, vectors.as_ptr(), 2).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..20 {
+            index.save().unwrap();
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        // Dropped without a final save, as by a crash: every vector is
+        // either in the saved file or in the journal.
+        drop(index);
+
+        let index = VectorIndex::from(dir.to_path_buf(), Metric::L2sq).unwrap();
+        assert_eq!(index.stats().vectors, 400);
+    }
+
+    #[test]
+    fn test_stats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::L2sq).unwrap();
+        let options = IndexOptions {
+            dimensions: 2,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        index
+            .add::<f32>(&[1, 2], [1.0, 0.0, 0.0, 1.0].as_ptr(), 2)
+            .unwrap();
+
+        let stats = index.stats();
+        assert_eq!(stats.vectors, 2);
+        assert_eq!(stats.dimensions, 2);
+        assert_eq!(stats.metric, Metric::L2sq);
+        assert!(stats.memory_bytes > 0);
+        assert_eq!(stats.file_bytes, 0);
+        assert_eq!(stats.modified, None);
+
+        index.save().unwrap();
+        let stats = index.stats();
+        assert!(stats.file_bytes > 0);
+        assert!(stats.modified.is_some());
+    }
+
+    #[test]
+    fn test_view() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::IP).unwrap();
+        let options = IndexOptions {
+            dimensions: 2,
+            quantization: usearch::ScalarKind::F16,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        index
+            .add::<f32>(&[1, 2], [1.0, 0.0, 0.0, 1.0].as_ptr(), 2)
+            .unwrap();
+        index.save().unwrap();
+
+        let view = VectorIndex::view(dir.to_path_buf(), Metric::Cos).unwrap();
+        assert_eq!(view.metric(), Metric::IP);
+        assert_eq!(view.dtype(), Some(VectorDType::F16));
+        let results = view.search::<f32>(&[0.0, 1.0], 1).unwrap();
+        assert_eq!(results[0].key, 2);
+        assert_eq!(view.get(2).unwrap(), Some(vec![0.0, 1.0]));
+        assert_eq!(view.get(3).unwrap(), None);
+        assert!(view.add::<f32>(&[3], [1.0, 1.0].as_ptr(), 2).is_err());
+        assert!(view.remove(&[1]).is_err());
+    }
+
+    #[test]
+    fn test_binary_index() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::Cos).unwrap();
+        let options = IndexOptions {
+            dimensions: 8,
+            metric: Metric::Cos.kind_for(VectorDType::B1),
+            quantization: usearch::ScalarKind::B1,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        let same = [0.5f32, -0.5, 0.5, -0.5, 0.5, -0.5, 0.5, -0.5];
+        let opposite = same.map(|v| -v);
+        index.add::<f32>(&[1], same.as_ptr(), 8).unwrap();
+        index.add::<f32>(&[2], opposite.as_ptr(), 8).unwrap();
+        index.save().unwrap();
+
+        let index = VectorIndex::from(dir.to_path_buf(), Metric::IP).unwrap();
+        assert_eq!(index.dtype(), Some(VectorDType::B1));
+        let results = index.search::<f32>(&same, 2).unwrap();
+        assert_eq!(results[0].key, 1);
+        assert!(results[0].distance.abs() < 1e-6);
+        assert_eq!(results[1].key, 2);
+        assert!((results[1].distance - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_journal_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut index = VectorIndex::new(dir.to_path_buf(), true, Metric::Cos).unwrap();
+        let options = IndexOptions {
+            dimensions: 2,
+            quantization: usearch::ScalarKind::F16,
+            multi: true,
+            ..IndexOptions::default()
+        };
+        index.with_options(&options, 4).unwrap();
+        index.save().unwrap();
+        index
+            .add::<f32>(&[1, 2], [1.0, 0.0, 0.0, 1.0].as_ptr(), 2)
+            .unwrap();
+        let one = half::f16::from_f32(1.0).to_bits() as i16;
+        let vector = UsearchF16::from_i16s(&[one, one]).to_vec();
+        index.add::<UsearchF16>(&[3], vector.as_ptr(), 2).unwrap();
+        index.remove(&[1]).unwrap();
+        assert!(index.journal_bytes() > 0);
+        // Dropped without saving, as by a crash.
+        drop(index);
+
+        let index = VectorIndex::from(dir.to_path_buf(), Metric::Cos).unwrap();
+        assert_eq!(index.stats().vectors, 2);
+        assert_eq!(index.get(1).unwrap(), None);
+        assert_eq!(index.get(3).unwrap(), Some(vec![1.0, 1.0]));
+        index.save().unwrap();
+        assert_eq!(index.journal_bytes(), 0);
+
+        // Replaying a journal the saved index already has changes nothing.
+        index.add::<f32>(&[4], [1.0, 0.0].as_ptr(), 2).unwrap();
+        std::fs::copy(
+            dir.join(crate::collection::journal::JOURNAL_FILE),
+            dir.join("journal.tmp"),
+        )
+        .unwrap();
+        index.save().unwrap();
+        std::fs::rename(
+            dir.join("journal.tmp"),
+            dir.join(crate::collection::journal::JOURNAL_FILE),
+        )
+        .unwrap();
+        drop(index);
+        let view = VectorIndex::view(dir.to_path_buf(), Metric::Cos).unwrap();
+        assert_eq!(view.stats().vectors, 3);
+        assert_eq!(view.journal_bytes(), 0);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::model::hardware::AUTO_VARIANT;
+use log::{info, warn};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Name of the settings file looked up in the working directory and in the
+/// platform config directory.
+pub const CONFIG_FILE_NAME: &str = "letsearch.toml";
+
+pub const DEFAULT_MODEL: &str = "hf://mys/minilm";
+pub const DEFAULT_VARIANT: &str = AUTO_VARIANT;
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: i32 = 7898;
+
+/// Defaults of the CLI read from `letsearch.toml`. Command-line options
+/// take precedence over them.
+///
+/// ```toml
+/// model = "hf://mys/minilm"
+/// variant = "i8"
+/// host = "0.0.0.0"
+/// port = 8080
+/// data_dir = "/var/lib/letsearch"
+/// workers = 2
+/// keep_alive = 30
+/// client_timeout = 10
+/// max_payload_mb = 64
+/// ```
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Model of new collections.
+    pub model: Option<String>,
+    /// Model variant of new collections.
+    pub variant: Option<String>,
+    /// Host the server listens to.
+    pub host: Option<String>,
+    /// Port the server listens to.
+    pub port: Option<i32>,
+    /// Directory holding collections and downloaded models, instead of the
+    /// platform data directory. `LETSEARCH_HOME` takes precedence over it.
+    pub data_dir: Option<String>,
+    /// HTTP worker threads of the server.
+    pub workers: Option<usize>,
+    /// Seconds the server keeps idle connections open, `0` to close them.
+    pub keep_alive: Option<u64>,
+    /// Seconds clients have to send the headers of a request, `0` for no limit.
+    pub client_timeout: Option<u64>,
+    /// Largest request body the server accepts, in MiB.
+    pub max_payload_mb: Option<usize>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+impl Settings {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid settings in '{}': {}", path.display(), e))
+    }
+
+    pub fn model(&self, arg: &Option<String>) -> String {
+        arg.clone()
+            .or_else(|| self.model.clone())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+    }
+
+    pub fn variant(&self, arg: &Option<String>) -> String {
+        arg.clone()
+            .or_else(|| self.variant.clone())
+            .unwrap_or_else(|| DEFAULT_VARIANT.to_string())
+    }
+
+    pub fn host(&self, arg: &Option<String>) -> String {
+        arg.clone()
+            .or_else(|| self.host.clone())
+            .unwrap_or_else(|| DEFAULT_HOST.to_string())
+    }
+
+    pub fn port(&self, arg: Option<i32>) -> i32 {
+        arg.or(self.port).unwrap_or(DEFAULT_PORT)
+    }
+
+    pub fn workers(&self, arg: Option<usize>) -> Option<usize> {
+        arg.or(self.workers)
+    }
+
+    pub fn keep_alive(&self, arg: Option<u64>) -> Option<Duration> {
+        arg.or(self.keep_alive).map(Duration::from_secs)
+    }
+
+    pub fn client_timeout(&self, arg: Option<u64>) -> Option<Duration> {
+        arg.or(self.client_timeout).map(Duration::from_secs)
+    }
+
+    /// Largest request body in bytes.
+    pub fn max_payload(&self, arg_mb: Option<usize>) -> Option<usize> {
+        arg_mb.or(self.max_payload_mb).map(|mb| mb * 1024 * 1024)
+    }
+}
+
+/// Settings file to read: `LETSEARCH_CONFIG` when set, then `letsearch.toml`
+/// in the working directory, then in the platform config directory, e.g.
+/// `~/.config/letsearch` on Linux or `%APPDATA%\letsearch` on Windows.
+pub fn config_file() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("LETSEARCH_CONFIG") {
+        return Some(path.into());
+    }
+    let local = PathBuf::from(CONFIG_FILE_NAME);
+    if local.is_file() {
+        return Some(local);
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("letsearch").join(CONFIG_FILE_NAME))
+        .filter(|path| path.is_file())
+}
+
+/// Read the settings file, if any, so that [`settings`] returns it. Call
+/// this before anything resolves the home directory.
+pub fn init() -> anyhow::Result<&'static Settings> {
+    let settings = match config_file() {
+        Some(path) => {
+            let settings = Settings::from_file(&path)?;
+            info!("Read settings from '{}'", path.display());
+            settings
+        }
+        None => Settings::default(),
+    };
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+/// Settings read by [`init`], or the defaults when it was not called.
+pub fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+/// Home directory used when neither `LETSEARCH_HOME` nor `data_dir` is set:
+/// `~/.letsearch` when it exists, otherwise the platform data directory,
+/// e.g. `$XDG_DATA_HOME/letsearch` on Linux or `%APPDATA%\letsearch` on
+/// Windows.
+pub fn default_home_dir() -> PathBuf {
+    let dot_dir = dirs::home_dir().map(|home| home.join(".letsearch"));
+    if let Some(dir) = dot_dir.as_ref().filter(|dir| dir.is_dir()) {
+        return dir.clone();
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("letsearch"))
+        .or(dot_dir)
+        .unwrap_or_else(|| PathBuf::from(".letsearch"))
+}
+
+/// Warn when `.letsearch` in the working directory, where earlier versions
+/// stored everything, is not the home directory in use.
+pub fn warn_legacy_home(home: &Path) {
+    let legacy = Path::new(".letsearch");
+    if !legacy.is_dir() {
+        return;
+    }
+    let same = match (legacy.canonicalize(), home.canonicalize()) {
+        (Ok(legacy), Ok(home)) => legacy == home,
+        _ => false,
+    };
+    if !same {
+        warn!(
+            "Found '.letsearch' in the working directory, but letsearch now stores its files in '{}'. Set LETSEARCH_HOME=.letsearch to keep using it",
+            home.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_precedence() {
+        let settings: Settings = toml::from_str(
+            r#"
+            model = "hf://org/model"
+            port = 8080
+            keep_alive = 30
+            max_payload_mb = 4
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.model(&None), "hf://org/model");
+        assert_eq!(
+            settings.model(&Some("hf://mys/minilm".into())),
+            "hf://mys/minilm"
+        );
+        assert_eq!(settings.variant(&None), DEFAULT_VARIANT);
+        assert_eq!(settings.host(&None), DEFAULT_HOST);
+        assert_eq!(settings.port(None), 8080);
+        assert_eq!(settings.port(Some(9000)), 9000);
+        assert_eq!(settings.workers(Some(2)), Some(2));
+        assert_eq!(settings.keep_alive(None), Some(Duration::from_secs(30)));
+        assert_eq!(settings.keep_alive(Some(0)), Some(Duration::ZERO));
+        assert_eq!(settings.client_timeout(None), None);
+        assert_eq!(settings.max_payload(None), Some(4 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(toml::from_str::<Settings>("modle = \"hf://org/model\"").is_err());
+    }
+}
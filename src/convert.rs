@@ -0,0 +1,269 @@
+use crate::collection::vector_index::Metric;
+use crate::model::metadata::{ModelMetadata, Variant, METADATA_FILE};
+use anyhow::anyhow;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Model variants that `letsearch convert` can produce.
+pub const SUPPORTED_VARIANTS: [&str; 3] = ["f32", "f16", "i8"];
+
+/// Derive the f16 and i8 variants from the exported f32 model.
+const QUANTIZE_SCRIPT: &str = r#"
+import sys
+from pathlib import Path
+
+out, variants = Path(sys.argv[1]), sys.argv[2].split(",")
+source = str(out / "model-f32.onnx")
+if "f16" in variants:
+    import onnx
+    from onnxconverter_common import float16
+    model = float16.convert_float_to_float16(onnx.load(source))
+    onnx.save(model, str(out / "model-f16.onnx"))
+if "i8" in variants:
+    from onnxruntime.quantization import QuantType, quantize_dynamic
+    quantize_dynamic(source, str(out / "model-i8.onnx"), weight_type=QuantType.QInt8)
+"#;
+
+const UPLOAD_SCRIPT: &str = r#"
+import sys
+from huggingface_hub import HfApi
+
+folder, repo_id, private = sys.argv[1], sys.argv[2], sys.argv[3] == "1"
+api = HfApi()
+api.create_repo(repo_id, private=private, exist_ok=True)
+api.upload_folder(folder_path=folder, repo_id=repo_id)
+"#;
+
+/// Options for [`run_convert`].
+#[derive(Clone, Debug)]
+pub struct ConvertConfig {
+    /// HuggingFace model id, e.g. `sentence-transformers/all-MiniLM-L6-v2`.
+    pub model_id: String,
+    pub output_dir: PathBuf,
+    /// Variants to produce. `f32` is always produced, as the others are
+    /// derived from it.
+    pub variants: Vec<String>,
+    /// Similarity metric the model was trained for. Detected from the
+    /// sentence-transformers config when `None`.
+    pub metric: Option<Metric>,
+    /// Upload the converted model to this HuggingFace repo.
+    pub push_to_hub: Option<String>,
+    pub private: bool,
+    pub hf_token: Option<String>,
+    /// Python interpreter with `optimum[exporters]`, `onnxconverter-common`
+    /// and `huggingface_hub` installed.
+    pub python: String,
+}
+
+/// File name of the ONNX model of `variant` in a converted repo.
+fn model_file(variant: &str) -> String {
+    format!("model-{}.onnx", variant)
+}
+
+/// Build the `metadata.json` that [`crate::hf_ops::download_model`] reads,
+/// with the oldest version that holds it.
+pub fn model_metadata(
+    variants: &[String],
+    metric: Option<Metric>,
+    max_len: Option<usize>,
+) -> ModelMetadata {
+    let variants = variants
+        .iter()
+        .map(|variant| Variant {
+            variant: variant.clone(),
+            path: model_file(variant),
+        })
+        .collect();
+    let mut metadata = ModelMetadata::new(variants, vec![String::from("tokenizer.json")]);
+    metadata.metric = metric;
+    metadata.max_len = max_len;
+    metadata.letsearch_version = metadata.min_version();
+    metadata
+}
+
+/// Read the `max_seq_length` that sentence-transformers stores in
+/// `sentence_bert_config.json`, beyond which the model truncates its inputs.
+fn detect_max_len(out: &Path) -> Option<usize> {
+    let config = fs::read_to_string(out.join("sentence_bert_config.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&config).ok()?;
+    config["max_seq_length"]
+        .as_u64()
+        .filter(|&max_len| max_len > 0)
+        .map(|max_len| max_len as usize)
+}
+
+/// Read the metric from the `similarity_fn_name` that sentence-transformers
+/// stores in `config_sentence_transformers.json`, if the export kept it.
+fn detect_metric(out: &Path) -> Option<Metric> {
+    let config = fs::read_to_string(out.join("config_sentence_transformers.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&config).ok()?;
+    match config["similarity_fn_name"].as_str()? {
+        "cosine" => Some(Metric::Cos),
+        "dot" => Some(Metric::IP),
+        "euclidean" => Some(Metric::L2sq),
+        other => {
+            warn!("Similarity function '{}' has no letsearch metric", other);
+            None
+        }
+    }
+}
+
+fn model_card(model_id: &str, variants: &[String]) -> String {
+    format!(
+        "---\ntags:\n- letsearch\n- onnx\nbase_model: {id}\n---\n\n\
+         # {id} for letsearch\n\n\
+         ONNX export of [{id}](https://huggingface.co/{id}) with the variants {variants}, \
+         converted with `letsearch convert`.\n",
+        id = model_id,
+        variants = variants.join(", ")
+    )
+}
+
+fn run_python(python: &str, args: &[&str], token: Option<&str>, step: &str) -> anyhow::Result<()> {
+    let mut command = Command::new(python);
+    command.args(args);
+    if let Some(token) = token {
+        command.env("HF_TOKEN", token);
+    }
+    let status = command
+        .status()
+        .map_err(|e| anyhow!("Failed to run '{}' to {}: {}", python, step, e))?;
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to {} ({}). Make sure the Python dependencies are installed: \
+             pip install 'optimum[exporters]' onnxconverter-common huggingface_hub",
+            step,
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// Export a sentence-transformers model to ONNX with optimum, derive the
+/// requested variants and write the `metadata.json` that makes the directory
+/// a letsearch model. Optionally upload the result to the HuggingFace Hub,
+/// tagged so that `letsearch list-models` finds it.
+pub fn run_convert(config: &ConvertConfig) -> anyhow::Result<()> {
+    let mut variants = vec![String::from("f32")];
+    for variant in &config.variants {
+        if !SUPPORTED_VARIANTS.contains(&variant.as_str()) {
+            return Err(anyhow!(
+                "Unsupported variant '{}', expected one of {}",
+                variant,
+                SUPPORTED_VARIANTS.join(", ")
+            ));
+        }
+        if !variants.contains(variant) {
+            variants.push(variant.clone());
+        }
+    }
+
+    let out = &config.output_dir;
+    let out_str = out
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid unicode in output directory path"))?;
+    let token = config.hf_token.as_deref();
+
+    info!("Exporting {} to ONNX", config.model_id);
+    run_python(
+        &config.python,
+        &[
+            "-m",
+            "optimum.exporters.onnx",
+            "--model",
+            &config.model_id,
+            "--task",
+            "feature-extraction",
+            "--library-name",
+            "sentence_transformers",
+            out_str,
+        ],
+        token,
+        "export the model to ONNX",
+    )?;
+    fs::rename(out.join("model.onnx"), out.join(model_file("f32")))?;
+    if !out.join("tokenizer.json").exists() {
+        return Err(anyhow!(
+            "The export did not produce a tokenizer.json, which letsearch requires"
+        ));
+    }
+
+    if variants.len() > 1 {
+        info!("Creating variants {}", variants[1..].join(", "));
+        run_python(
+            &config.python,
+            &["-c", QUANTIZE_SCRIPT, out_str, &variants.join(",")],
+            token,
+            "create the model variants",
+        )?;
+    }
+
+    let metric = config.metric.or_else(|| detect_metric(out));
+    match metric {
+        Some(metric) => info!("Recording {} as the model's metric", metric),
+        None => info!("No metric recorded, indexes will default to cos"),
+    }
+    let max_len = detect_max_len(out);
+    if let Some(max_len) = max_len {
+        info!("Recording {} tokens as the model's input length", max_len);
+    }
+    fs::write(
+        out.join(METADATA_FILE),
+        serde_json::to_string_pretty(&model_metadata(&variants, metric, max_len))?,
+    )?;
+    fs::write(
+        out.join("README.md"),
+        model_card(&config.model_id, &variants),
+    )?;
+    info!("Converted model written to {}", out_str);
+
+    if let Some(repo_id) = config.push_to_hub.as_ref() {
+        info!("Uploading to hf://{}", repo_id);
+        let private = if config.private { "1" } else { "0" };
+        run_python(
+            &config.python,
+            &["-c", UPLOAD_SCRIPT, out_str, repo_id, private],
+            token,
+            "upload the model to the HuggingFace Hub",
+        )?;
+        info!("Uploaded. Use it with --model hf://{}", repo_id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_model_metadata() {
+        let metadata = serde_json::to_value(model_metadata(
+            &[String::from("f32"), String::from("i8")],
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(metadata["letsearch_version"], 1);
+        assert_eq!(
+            metadata["variants"],
+            json!([
+                {"variant": "f32", "path": "model-f32.onnx"},
+                {"variant": "i8", "path": "model-i8.onnx"},
+            ])
+        );
+        assert_eq!(metadata["required_files"], json!(["tokenizer.json"]));
+        assert!(metadata.get("metric").is_none());
+
+        let metadata = model_metadata(&[String::from("f32")], Some(Metric::IP), Some(256));
+        assert_eq!(metadata.letsearch_version, 2);
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(ModelMetadata::parse(&json).unwrap(), metadata);
+        let metadata = serde_json::to_value(metadata).unwrap();
+        assert_eq!(metadata["metric"], "ip");
+        assert_eq!(metadata["max_len"], 256);
+    }
+}
@@ -0,0 +1,522 @@
+//! Crawling of web sites into documents, for `letsearch index --crawl`.
+//!
+//! Pages are listed by a sitemap, including sitemap indexes, or found by
+//! following the links of a start page within its host. The readable text
+//! of each page is taken from its main content, leaving out navigation,
+//! scripts and other boilerplate, and pages disallowed by the host's
+//! `robots.txt` are skipped.
+
+use anyhow::anyhow;
+use arrow::array::StringArray;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use futures::future::join_all;
+use log::{info, warn};
+use reqwest::Url;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// User agent sent with every request, and matched against `robots.txt`.
+const USER_AGENT: &str = concat!("letsearch/", env!("CARGO_PKG_VERSION"));
+
+/// Elements whose text is never part of a page's readable text.
+const SKIPPED_ELEMENTS: [&str; 12] = [
+    "script", "style", "noscript", "template", "svg", "nav", "header", "footer", "aside", "form",
+    "iframe", "button",
+];
+
+/// Elements that start a new line of readable text.
+const BLOCK_ELEMENTS: [&str; 20] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "li",
+    "ul",
+    "ol",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "br",
+    "tr",
+    "pre",
+    "blockquote",
+    "table",
+    "dd",
+];
+
+/// Extensions of links that are not web pages, left out when following links.
+const SKIPPED_EXTENSIONS: [&str; 14] = [
+    "pdf", "jpg", "jpeg", "png", "gif", "svg", "webp", "zip", "gz", "mp3", "mp4", "css", "js",
+    "xml",
+];
+
+/// Limits of a crawl.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrawlOptions {
+    /// Pages fetched at most.
+    pub max_pages: usize,
+    /// Pages fetched at the same time.
+    pub concurrency: usize,
+    /// Time after which a request is given up.
+    pub timeout: Duration,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: 1000,
+            concurrency: 4,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A crawled page, imported as a row with `url`, `title` and `text` columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub url: String,
+    pub title: String,
+    pub text: String,
+}
+
+/// Locations listed by a sitemap.
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// Pages of a `<urlset>`.
+    Pages(Vec<String>),
+    /// Further sitemaps of a `<sitemapindex>`.
+    Index(Vec<String>),
+}
+
+/// Parse a sitemap, or return `None` when `xml` is not one, e.g. for HTML.
+pub fn parse_sitemap(xml: &str) -> Option<Sitemap> {
+    let document = roxmltree::Document::parse(xml).ok()?;
+    let root = document.root_element();
+    let locations = root
+        .descendants()
+        .filter(|node| node.has_tag_name("loc"))
+        .filter_map(|node| node.text())
+        .map(|loc| loc.trim().to_string())
+        .collect();
+    match root.tag_name().name() {
+        "urlset" => Some(Sitemap::Pages(locations)),
+        "sitemapindex" => Some(Sitemap::Index(locations)),
+        _ => None,
+    }
+}
+
+/// Rules of a `robots.txt` applying to letsearch: the groups for `*` and
+/// for `letsearch`, as path prefixes.
+#[derive(Debug, Default, PartialEq)]
+pub struct Robots {
+    /// Prefixes with whether they are allowed.
+    rules: Vec<(String, bool)>,
+}
+
+impl Robots {
+    pub fn parse(robots_txt: &str) -> Self {
+        let mut rules = Vec::new();
+        let mut applies = false;
+        // Consecutive user-agent lines share the rules that follow them.
+        let mut in_agents = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match field.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    let agent = value.to_lowercase();
+                    let matches = agent == "*" || agent.starts_with("letsearch");
+                    applies = if in_agents {
+                        applies || matches
+                    } else {
+                        matches
+                    };
+                    in_agents = true;
+                }
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    if applies && !value.is_empty() {
+                        let allowed = field.trim().eq_ignore_ascii_case("allow");
+                        rules.push((value.to_string(), allowed));
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        Self { rules }
+    }
+
+    /// Whether `path` may be fetched: the longest matching rule decides,
+    /// and paths no rule matches are allowed.
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, allowed)| (prefix.len(), *allowed))
+            .is_none_or(|(_, allowed)| *allowed)
+    }
+}
+
+/// Append the text under `element` to `text`, starting a new line at each
+/// block element and leaving out [`SKIPPED_ELEMENTS`]. With `mark_headings`,
+/// headings start with Markdown `#` markers of their level.
+fn collect_text(element: ElementRef, text: &mut String, mark_headings: bool) {
+    for child in element.children() {
+        match child.value() {
+            // Line breaks in the HTML source are plain whitespace.
+            Node::Text(fragment) => text.push_str(&fragment.replace(['\n', '\r'], " ")),
+            Node::Element(child_element) => {
+                let name = child_element.name();
+                if SKIPPED_ELEMENTS.contains(&name) {
+                    continue;
+                }
+                let block = BLOCK_ELEMENTS.contains(&name);
+                if block {
+                    text.push('\n');
+                }
+                if let Some(level) = name.strip_prefix('h').and_then(|l| l.parse().ok()) {
+                    if mark_headings && (1..=6).contains(&level) {
+                        text.push_str(&"#".repeat(level));
+                        text.push(' ');
+                    }
+                }
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, text, mark_headings);
+                }
+                if block {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Collapse the whitespace within lines and drop empty lines.
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("Invalid selector")
+}
+
+/// The readable text of an HTML document, one line per block, taken from its
+/// `<article>` or `<main>` element when it has one and from its body
+/// otherwise. With `mark_headings`, headings start with Markdown `#` markers.
+pub(crate) fn readable_text(document: &Html, mark_headings: bool) -> String {
+    let content = ["article", "main", "[role=main]", "body"]
+        .iter()
+        .find_map(|css| document.select(&selector(css)).next());
+    let mut text = String::new();
+    if let Some(content) = content {
+        collect_text(content, &mut text, mark_headings);
+    }
+    normalize_whitespace(&text)
+}
+
+/// The title of an HTML document: its `<title>`, or its first `<h1>`.
+pub(crate) fn html_title(document: &Html) -> String {
+    document
+        .select(&selector("title"))
+        .chain(document.select(&selector("h1")))
+        .map(|element| normalize_whitespace(&element.text().collect::<String>()))
+        .find(|title| !title.is_empty())
+        .unwrap_or_default()
+}
+
+/// The readable text of an HTML page and the links it has to other pages of
+/// its host, see [`readable_text`].
+pub fn extract_page(url: &Url, html: &str) -> (Page, Vec<Url>) {
+    let document = Html::parse_document(html);
+    let title = html_title(&document);
+
+    let links = document
+        .select(&selector("a[href]"))
+        .filter_map(|link| url.join(link.value().attr("href")?).ok())
+        .filter_map(|mut link| {
+            link.set_fragment(None);
+            let same_host = link.host_str() == url.host_str();
+            let is_page = !Path::new(link.path())
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    SKIPPED_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                });
+            (same_host && is_page && matches!(link.scheme(), "http" | "https")).then_some(link)
+        })
+        .collect();
+
+    let page = Page {
+        url: url.to_string(),
+        title,
+        text: readable_text(&document, false),
+    };
+    (page, links)
+}
+
+/// Fetches pages of a crawl, with the `robots.txt` of each host.
+struct Crawler {
+    http: reqwest::Client,
+    robots: HashMap<String, Arc<Robots>>,
+}
+
+/// A fetched response: its final URL, whether it is HTML, and its body.
+struct Response {
+    url: Url,
+    html: bool,
+    body: String,
+}
+
+impl Crawler {
+    fn new(options: &CrawlOptions) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(options.timeout)
+            .build()?;
+        Ok(Self {
+            http,
+            robots: HashMap::new(),
+        })
+    }
+
+    async fn fetch(http: &reqwest::Client, url: Url) -> anyhow::Result<Response> {
+        let response = http.get(url.clone()).send().await?.error_for_status()?;
+        let html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("html"));
+        Ok(Response {
+            url: response.url().clone(),
+            html,
+            body: response.text().await?,
+        })
+    }
+
+    /// Whether the host's `robots.txt` allows fetching `url`. Hosts whose
+    /// `robots.txt` cannot be fetched allow every page.
+    async fn allowed(&mut self, url: &Url) -> bool {
+        let origin = url.origin().ascii_serialization();
+        let robots = match self.robots.get(&origin) {
+            Some(robots) => robots.clone(),
+            None => {
+                let robots = match url.join("/robots.txt") {
+                    Ok(robots_url) => match Self::fetch(&self.http, robots_url).await {
+                        Ok(response) => Robots::parse(&response.body),
+                        Err(_) => Robots::default(),
+                    },
+                    Err(_) => Robots::default(),
+                };
+                let robots = Arc::new(robots);
+                self.robots.insert(origin, robots.clone());
+                robots
+            }
+        };
+        robots.allows(url.path())
+    }
+}
+
+/// Crawl the pages listed by the sitemap at `start`, or reachable from the
+/// page at `start` by links within its host, and return those with text.
+pub async fn crawl(start: &str, options: &CrawlOptions) -> anyhow::Result<Vec<Page>> {
+    let start = Url::parse(start).map_err(|e| anyhow!("Invalid crawl URL '{}': {}", start, e))?;
+    let mut crawler = Crawler::new(options)?;
+    let first = Crawler::fetch(&crawler.http, start.clone()).await?;
+
+    // Pages listed by sitemaps are fetched as they are, and other pages
+    // are followed by their links.
+    let follow_links = first.html || parse_sitemap(&first.body).is_none();
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    let mut pending = vec![first];
+    if !follow_links {
+        let mut sitemaps = vec![pending.remove(0)];
+        let mut visited_sitemaps = 0;
+        while let Some(sitemap) = sitemaps.pop() {
+            visited_sitemaps += 1;
+            match parse_sitemap(&sitemap.body) {
+                Some(Sitemap::Pages(pages)) => {
+                    for page in pages.iter().filter_map(|page| Url::parse(page).ok()) {
+                        if seen.insert(page.clone()) {
+                            queue.push_back(page);
+                        }
+                    }
+                }
+                Some(Sitemap::Index(children)) if visited_sitemaps < options.max_pages => {
+                    for child in children.iter().filter_map(|child| Url::parse(child).ok()) {
+                        match Crawler::fetch(&crawler.http, child.clone()).await {
+                            Ok(response) => sitemaps.push(response),
+                            Err(e) => warn!("Skipping sitemap '{}': {}", child, e),
+                        }
+                    }
+                }
+                _ => warn!("Skipping '{}': not a sitemap", sitemap.url),
+            }
+        }
+        info!("Found {} pages in the sitemap", queue.len());
+    } else {
+        seen.insert(start.clone());
+    }
+
+    let mut pages = Vec::new();
+    let mut fetched = 0;
+    while fetched < options.max_pages && (!pending.is_empty() || !queue.is_empty()) {
+        let mut batch = Vec::new();
+        while batch.len() + pending.len() < options.concurrency.max(1)
+            && fetched + batch.len() + pending.len() < options.max_pages
+        {
+            let Some(url) = queue.pop_front() else {
+                break;
+            };
+            if crawler.allowed(&url).await {
+                batch.push(url);
+            } else {
+                info!("Skipping '{}': disallowed by robots.txt", url);
+            }
+        }
+        let responses = join_all(
+            batch
+                .into_iter()
+                .map(|url| async { (url.clone(), Crawler::fetch(&crawler.http, url).await) }),
+        )
+        .await;
+        for (url, response) in responses {
+            match response {
+                Ok(response) => pending.push(response),
+                Err(e) => warn!("Skipping '{}': {}", url, e),
+            }
+        }
+
+        for response in pending.drain(..) {
+            fetched += 1;
+            if !response.html {
+                continue;
+            }
+            let (page, links) = extract_page(&response.url, &response.body);
+            if follow_links {
+                for link in links {
+                    if seen.insert(link.clone()) {
+                        queue.push_back(link);
+                    }
+                }
+            }
+            if !page.text.is_empty() {
+                pages.push(page);
+            }
+        }
+    }
+    info!("Crawled {} pages, {} with text", fetched, pages.len());
+    Ok(pages)
+}
+
+/// Write `pages` to an Arrow IPC file with `url`, `title` and `text` columns.
+pub fn write_pages(pages: &[Page], path: &Path) -> anyhow::Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("url", DataType::Utf8, false),
+        Field::new("title", DataType::Utf8, false),
+        Field::new("text", DataType::Utf8, false),
+    ]));
+    let column = |values: Vec<&str>| Arc::new(StringArray::from(values));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            column(pages.iter().map(|page| page.url.as_str()).collect()),
+            column(pages.iter().map(|page| page.title.as_str()).collect()),
+            column(pages.iter().map(|page| page.text.as_str()).collect()),
+        ],
+    )?;
+    let mut writer = FileWriter::try_new(std::fs::File::create(path)?, &schema)?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sitemap() {
+        let urlset = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc> https://example.com/ </loc><lastmod>2024-01-01</lastmod></url>
+              <url><loc>https://example.com/docs</loc></url>
+            </urlset>"#;
+        assert_eq!(
+            parse_sitemap(urlset),
+            Some(Sitemap::Pages(vec![
+                String::from("https://example.com/"),
+                String::from("https://example.com/docs")
+            ]))
+        );
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc>https://example.com/sitemap-1.xml</loc></sitemap>
+            </sitemapindex>"#;
+        assert_eq!(
+            parse_sitemap(index),
+            Some(Sitemap::Index(vec![String::from(
+                "https://example.com/sitemap-1.xml"
+            )]))
+        );
+        assert_eq!(parse_sitemap("<html><body>hi</body></html>"), None);
+        assert_eq!(parse_sitemap("<!doctype html><p>unclosed"), None);
+    }
+
+    #[test]
+    fn test_extract_page() {
+        let html = r#"<!doctype html><html><head><title> Search  guide </title>
+            <script>var tracking = 1;</script></head>
+            <body><nav><a href="/">Home</a> <a href="/docs#top">Docs</a></nav>
+            <article><h1>Getting started</h1><p>Install   letsearch and
+            index a file.</p><aside>Related posts</aside>
+            <p>Then <a href="/docs/search">search</a> it.</p></article>
+            <footer><a href="https://other.com/">Elsewhere</a>
+            <a href="/manual.pdf">Manual</a></footer></body></html>"#;
+        let url = Url::parse("https://example.com/guide").unwrap();
+        let (page, links) = extract_page(&url, html);
+        assert_eq!(page.title, "Search guide");
+        assert_eq!(
+            page.text,
+            "Getting started\nInstall letsearch and index a file.\nThen search it."
+        );
+        let links: Vec<&str> = links.iter().map(Url::as_str).collect();
+        assert_eq!(
+            links,
+            vec![
+                "https://example.com/",
+                "https://example.com/docs",
+                "https://example.com/docs/search"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_robots() {
+        let robots = Robots::parse(
+            "User-agent: googlebot\nDisallow: /\n\n\
+             User-agent: *\nUser-agent: bingbot\nDisallow: /private # secret\n\
+             Allow: /private/press\nDisallow:\n",
+        );
+        assert!(robots.allows("/docs"));
+        assert!(!robots.allows("/private/notes"));
+        assert!(robots.allows("/private/press/2024"));
+        assert!(Robots::default().allows("/anything"));
+    }
+}
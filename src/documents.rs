@@ -0,0 +1,527 @@
+//! Text extraction from PDF, DOCX, Markdown, HTML and plain text documents,
+//! and transcription of audio files, so that a folder of documents can be
+//! imported with `letsearch index` and `letsearch add-docs`.
+//!
+//! Each document becomes one or more rows: its text, optionally split into
+//! chunks, with the path of the file it came from and, for PDFs, the page
+//! number. Markdown and HTML are stripped to plain text, their frontmatter or
+//! `<meta>` tags fill columns of their own, and their headings bound chunks.
+
+use crate::chunker::MarkdownChunker;
+use crate::collection::collection_utils::is_valid_identifier;
+use crate::ingest::FileFormat;
+use crate::transcribe::Transcriber;
+use anyhow::anyhow;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use scraper::{Html, Selector};
+use std::fs::File;
+use std::io::Read;
+
+/// Column holding the path of the document a row was extracted from.
+pub const SOURCE_COLUMN: &str = "source";
+
+/// Column holding the 1-based page number of PDF rows, NULL for other documents.
+pub const PAGE_COLUMN: &str = "page";
+
+/// Namespace of the WordprocessingML elements in `word/document.xml`.
+const WORD_NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// `<meta>` names that describe how a page is displayed rather than what it
+/// is about, left out of the metadata of HTML documents.
+const SKIPPED_META: [&str; 7] = [
+    "viewport",
+    "theme-color",
+    "color-scheme",
+    "referrer",
+    "robots",
+    "generator",
+    "format-detection",
+];
+
+/// A row extracted from a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentChunk {
+    pub text: String,
+    pub source: String,
+    pub page: Option<i32>,
+    /// Frontmatter or `<meta>` fields of the document, by column name.
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Text and metadata extracted from a document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// Text of each page, with its 1-based number for PDFs. Headings of
+    /// Markdown and HTML documents start with `#` markers of their level.
+    pub pages: Vec<(Option<i32>, String)>,
+    /// Frontmatter or `<meta>` fields, by column name.
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Text of the paragraphs of a DOCX file, separated by blank lines.
+pub fn docx_to_text(path: &str) -> anyhow::Result<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| anyhow!("'{}' is not a DOCX file: {}", path, e))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| anyhow!("'{}' is not a DOCX file: {}", path, e))?
+        .read_to_string(&mut xml)?;
+    document_xml_text(&xml)
+}
+
+fn document_xml_text(xml: &str) -> anyhow::Result<String> {
+    let document = roxmltree::Document::parse(xml)?;
+    let mut text = String::new();
+    append_text(document.root(), &mut text);
+    let paragraphs: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    Ok(paragraphs.join("\n\n"))
+}
+
+fn append_text(node: roxmltree::Node, text: &mut String) {
+    if node.tag_name().namespace() == Some(WORD_NAMESPACE) {
+        match node.tag_name().name() {
+            "t" => {
+                text.push_str(node.text().unwrap_or_default());
+                return;
+            }
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            _ => {}
+        }
+    }
+    for child in node.children() {
+        append_text(child, text);
+    }
+    if node.has_tag_name((WORD_NAMESPACE, "p")) {
+        text.push('\n');
+    }
+}
+
+/// Column name for a frontmatter or `<meta>` field: lowercase, with runs of
+/// other characters than letters and digits replaced by `_`, e.g. `og_title`
+/// for `og:title`. `None` for names that cannot be a column or that are
+/// taken by [`SOURCE_COLUMN`] and [`PAGE_COLUMN`].
+fn metadata_column(name: &str) -> Option<String> {
+    let column = name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    let usable = is_valid_identifier(&column)
+        && !column.starts_with(|c: char| c.is_ascii_digit())
+        && column != SOURCE_COLUMN
+        && column != PAGE_COLUMN;
+    usable.then_some(column)
+}
+
+/// Add a field to `metadata` unless a field of the same column is already there.
+fn push_metadata(metadata: &mut Vec<(String, String)>, name: &str, value: String) {
+    if let Some(column) = metadata_column(name) {
+        if !value.is_empty() && !metadata.iter().any(|(existing, _)| *existing == column) {
+            metadata.push((column, value));
+        }
+    }
+}
+
+fn yaml_value_text(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(flag) => Some(flag.to_string()),
+        serde_yaml::Value::Sequence(items) => Some(
+            items
+                .iter()
+                .filter_map(yaml_value_text)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        _ => None,
+    }
+}
+
+fn toml_value_text(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text.clone()),
+        toml::Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(toml_value_text)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        toml::Value::Table(_) => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// Split the frontmatter off a Markdown document: YAML between `---` lines or
+/// TOML between `+++` lines at its very start. Returns the fields of the
+/// frontmatter, with lists joined by commas and nested tables left out, and
+/// the rest of the document.
+fn split_frontmatter(markdown: &str) -> anyhow::Result<(Vec<(String, String)>, &str)> {
+    let markdown = markdown.trim_start_matches('\u{feff}');
+    let Some(fence) = ["---", "+++"]
+        .into_iter()
+        .find(|fence| markdown.lines().next().map(str::trim_end) == Some(*fence))
+    else {
+        return Ok((Vec::new(), markdown));
+    };
+    let start = markdown.find('\n').map_or(markdown.len(), |i| i + 1);
+    let mut offset = start;
+    let mut end = None;
+    for line in markdown[start..].split_inclusive('\n') {
+        if line.trim_end() == fence {
+            end = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let Some((end, body)) = end else {
+        return Ok((Vec::new(), markdown));
+    };
+
+    let frontmatter = &markdown[start..end];
+    let mut metadata = Vec::new();
+    if fence == "---" {
+        match serde_yaml::from_str(frontmatter)? {
+            serde_yaml::Value::Mapping(fields) => {
+                for (name, value) in &fields {
+                    if let (Some(name), Some(value)) = (name.as_str(), yaml_value_text(value)) {
+                        push_metadata(&mut metadata, name, value);
+                    }
+                }
+            }
+            serde_yaml::Value::Null => {}
+            // Text between two horizontal rules rather than frontmatter.
+            _ => return Ok((Vec::new(), markdown)),
+        }
+    } else {
+        for (name, value) in &frontmatter.parse::<toml::Table>()? {
+            if let Some(value) = toml_value_text(value) {
+                push_metadata(&mut metadata, name, value);
+            }
+        }
+    }
+    Ok((metadata, &markdown[body..]))
+}
+
+/// Plain text of a Markdown document, one block per paragraph, with its
+/// headings kept as `#` markers of their level. Inline markup, links and
+/// embedded HTML are stripped, keeping the text they show.
+fn markdown_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                text.push_str("\n\n");
+                text.push_str(&"#".repeat(level as usize));
+                text.push(' ');
+            }
+            Event::Text(fragment) | Event::Code(fragment) => text.push_str(&fragment),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::End(TagEnd::TableCell) => text.push(' '),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => text.push('\n'),
+            Event::End(
+                TagEnd::Heading(_)
+                | TagEnd::Paragraph
+                | TagEnd::Item
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::Table,
+            )
+            | Event::Rule => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    let blocks: Vec<&str> = text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .collect();
+    blocks.join("\n\n")
+}
+
+/// Read a Markdown file: its frontmatter and its text.
+pub fn read_markdown(markdown: &str) -> anyhow::Result<Document> {
+    let (metadata, body) = split_frontmatter(markdown)?;
+    Ok(Document {
+        pages: vec![(None, markdown_text(body))],
+        metadata,
+    })
+}
+
+/// Read an HTML file: its title and `<meta>` tags, and its readable text.
+pub fn read_html(html: &str) -> Document {
+    let document = Html::parse_document(html);
+    let mut metadata = Vec::new();
+    push_metadata(&mut metadata, "title", crate::crawl::html_title(&document));
+    let meta = Selector::parse("meta[content]").expect("Invalid selector");
+    for element in document.select(&meta) {
+        let element = element.value();
+        let Some(name) = element.attr("name").or_else(|| element.attr("property")) else {
+            continue;
+        };
+        if !SKIPPED_META.contains(&name.to_lowercase().as_str()) {
+            let value = element.attr("content").unwrap_or_default().trim();
+            push_metadata(&mut metadata, name, value.to_string());
+        }
+    }
+    let text = crate::crawl::readable_text(&document, true)
+        .lines()
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Document {
+        pages: vec![(None, text)],
+        metadata,
+    }
+}
+
+/// Text and metadata of the document at `path`: one page per PDF page, and a
+/// single one without a number for other documents. Audio files are read as
+/// their transcript by `transcriber`.
+pub fn read_document(
+    path: &str,
+    format: FileFormat,
+    transcriber: Option<&Transcriber>,
+) -> anyhow::Result<Document> {
+    let read_text = || -> anyhow::Result<String> {
+        let bytes = std::fs::read(path)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    };
+    match format {
+        FileFormat::Pdf => Ok(Document {
+            pages: crate::pdf::pdf_pages_to_markdown(path)?
+                .into_iter()
+                .enumerate()
+                .map(|(page, text)| (Some(page as i32 + 1), text))
+                .collect(),
+            metadata: Vec::new(),
+        }),
+        FileFormat::Docx => Ok(Document {
+            pages: vec![(None, docx_to_text(path)?)],
+            metadata: Vec::new(),
+        }),
+        FileFormat::Text => Ok(Document {
+            pages: vec![(None, read_text()?)],
+            metadata: Vec::new(),
+        }),
+        FileFormat::Markdown => read_markdown(&read_text()?)
+            .map_err(|e| anyhow!("Invalid frontmatter in '{}': {}", path, e)),
+        FileFormat::Html => Ok(read_html(&read_text()?)),
+        FileFormat::Audio => {
+            let transcriber = transcriber.ok_or_else(|| {
+                anyhow!(
+                    "'{}' is audio, pass --transcribe-command to import its transcript",
+                    path
+                )
+            })?;
+            Ok(Document {
+                pages: vec![(None, transcriber.transcribe(path)?)],
+                metadata: Vec::new(),
+            })
+        }
+        _ => Err(anyhow!("'{}' is not a document", path)),
+    }
+}
+
+/// Remove the `#` markers of heading lines from `text`.
+fn strip_heading_markers(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let level = line.chars().take_while(|&c| c == '#').count();
+            match line[level..].strip_prefix(' ') {
+                Some(heading) if (1..=6).contains(&level) => heading,
+                _ => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Extract the rows of the document at `path`, splitting the text of each
+/// page with `chunker` when given. Pages without text are left out.
+///
+/// Chunks of Markdown and HTML documents are cut at their headings first, and
+/// the heading markers are removed from the stored text.
+pub fn document_chunks(
+    path: &str,
+    format: FileFormat,
+    chunker: Option<&MarkdownChunker>,
+    transcriber: Option<&Transcriber>,
+) -> anyhow::Result<Vec<DocumentChunk>> {
+    let document = read_document(path, format, transcriber)?;
+    let markup = matches!(format, FileFormat::Markdown | FileFormat::Html);
+    let mut chunks = Vec::new();
+    for (page, text) in document.pages {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let texts = match chunker {
+            Some(chunker) => chunker.chunk(&text),
+            None => vec![text],
+        };
+        chunks.extend(texts.into_iter().map(|text| DocumentChunk {
+            text: if markup {
+                strip_heading_markers(&text)
+            } else {
+                text
+            },
+            source: path.to_string(),
+            page,
+            metadata: document.metadata.clone(),
+        }));
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_docx_to_text() {
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<w:document xmlns:w="{WORD_NAMESPACE}">
+  <w:body>
+    <w:p><w:r><w:t>Quarterly</w:t></w:r><w:r><w:t xml:space="preserve"> report</w:t></w:r></w:p>
+    <w:p><w:r><w:t>Revenue</w:t><w:tab/><w:t>grew</w:t></w:r></w:p>
+    <w:p></w:p>
+    <w:tbl><w:tr><w:tc><w:p><w:r><w:t>In a table</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+  </w:body>
+</w:document>"#
+        );
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("test.docx");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("word/document.xml", options).unwrap();
+        writer.write_all(xml.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            docx_to_text(path).unwrap(),
+            "Quarterly report\n\nRevenue\tgrew\n\nIn a table"
+        );
+        let chunks = document_chunks(path, FileFormat::Docx, None, None).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source, path);
+        assert_eq!(chunks[0].page, None);
+
+        assert!(docx_to_text("Cargo.toml").is_err());
+    }
+
+    #[test]
+    fn test_empty_text_document() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("test.txt");
+        std::fs::write(&path, " \n\n").unwrap();
+        let chunks = document_chunks(path.to_str().unwrap(), FileFormat::Text, None, None).unwrap();
+        assert!(chunks.is_empty());
+    }
+
+    #[test]
+    fn test_markdown_frontmatter_and_text() {
+        let document = read_markdown(
+            "---\ntitle: Getting started\ntags: [install, linux]\nweight: 2\nauthor:\n  name: nested\n---\n\
+             # Install\n\nRun **letsearch** from [the releases](https://example.com).\n\n\
+             ```sh\nletsearch --help\n```\n\n<div>raw html</div>\n\n## Linux\n\n- one\n- two\n",
+        )
+        .unwrap();
+        assert_eq!(
+            document.metadata,
+            vec![
+                (String::from("title"), String::from("Getting started")),
+                (String::from("tags"), String::from("install, linux")),
+                (String::from("weight"), String::from("2")),
+            ]
+        );
+        assert_eq!(
+            document.pages,
+            vec![(
+                None,
+                String::from(
+                    "# Install\n\nRun letsearch from the releases.\n\nletsearch --help\n\n\
+                     ## Linux\n\none\n\ntwo"
+                )
+            )]
+        );
+
+        let toml =
+            read_markdown("+++\ntitle = \"Wiki\"\n\"og:type\" = \"article\"\n+++\nBody").unwrap();
+        assert_eq!(
+            toml.metadata,
+            vec![
+                (String::from("og_type"), String::from("article")),
+                (String::from("title"), String::from("Wiki")),
+            ]
+        );
+        assert_eq!(toml.pages[0].1, "Body");
+
+        // A horizontal rule is not frontmatter.
+        let plain = read_markdown("---\nJust text\n---\nMore").unwrap();
+        assert!(plain.metadata.is_empty());
+        assert!(read_markdown("---\ntitle: [unclosed\n---\n").is_err());
+    }
+
+    #[test]
+    fn test_html_metadata_and_text() {
+        let document = read_html(
+            "<html><head><title>Release notes</title>\
+             <meta name=\"description\" content=\"What changed\">\
+             <meta property=\"og:title\" content=\"Notes\">\
+             <meta name=\"viewport\" content=\"width=device-width\"></head>\
+             <body><nav>Home</nav><h1>Release notes</h1><p>Faster <b>indexing</b>.</p>\
+             <h2>Fixes</h2><p>Fewer bugs.</p></body></html>",
+        );
+        assert_eq!(
+            document.metadata,
+            vec![
+                (String::from("title"), String::from("Release notes")),
+                (String::from("description"), String::from("What changed")),
+                (String::from("og_title"), String::from("Notes")),
+            ]
+        );
+        assert_eq!(
+            document.pages[0].1,
+            "# Release notes\n\nFaster indexing.\n\n## Fixes\n\nFewer bugs."
+        );
+    }
+
+    #[test]
+    fn test_markdown_chunks_at_headings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("test.md");
+        let section = "word ".repeat(30);
+        std::fs::write(
+            &path,
+            format!("---\nsource: ignored\ncategory: guide\n---\n# One\n\n{section}\n\n# Two\n\n{section}\n"),
+        )
+        .unwrap();
+        let chunker = MarkdownChunker::new(crate::chunker::ChunkerConfig {
+            max_tokens: 50,
+            overlap_tokens: 0,
+            tokenizer_path: None,
+        })
+        .unwrap();
+        let path = path.to_str().unwrap();
+        let chunks = document_chunks(path, FileFormat::Markdown, Some(&chunker), None).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.starts_with("One\n\nword"));
+        assert!(chunks[1].text.starts_with("Two\n\nword"));
+        assert_eq!(
+            chunks[1].metadata,
+            vec![(String::from("category"), String::from("guide"))]
+        );
+    }
+}
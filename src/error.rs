@@ -1,5 +1,8 @@
+use crate::collection::collection_utils::ConfigDifference;
+use crate::collection::vector_index::Metric;
 use actix::MailboxError;
 use indicatif::style::TemplateError;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -7,8 +10,63 @@ use tokio::task::JoinError;
 pub enum ProjectError {
     #[error("Collection '{0}' not found")]
     CollectionNotFound(String),
+    #[error("Collection '{name}' already exists: {summary}{}", describe_differences(.differences))]
+    AlreadyExists {
+        name: String,
+        /// One-line summary of the existing collection's config.
+        summary: String,
+        /// Settings in which the requested config differs from the existing one.
+        differences: Vec<ConfigDifference>,
+    },
     #[error("Model with ID '{0}' not found")]
     ModelNotFound(u32),
+    #[error("Model '{0}' is not loaded, loaded models: {1}")]
+    ModelNotLoaded(String, String),
+    #[error("Several variants of model '{0}' are loaded, choose one of {1}")]
+    AmbiguousModel(String, String),
+    #[error("Column '{0}' not found")]
+    ColumnNotFound(String),
+    #[error("Column '{column}' is not of type {expected}")]
+    ColumnType {
+        column: String,
+        expected: &'static str,
+    },
+    #[error("Column '{0}' is not filterable, filterable columns: {1}")]
+    NotFilterable(String, String),
+    #[error("Collection '{0}' has no geo columns, index it with --geo-columns")]
+    NoGeoColumns(String),
+    #[error("Collection '{0}' has no prefix index, index it with --prefix-column")]
+    NoPrefixIndex(String),
+    #[error("Limit should be between 1 and {0}")]
+    InvalidLimit(u32),
+    #[error("Offset should be between 0 and {0}")]
+    InvalidOffset(u32),
+    #[error(
+        "Query vector has {given} dimensions but the index of column '{column}' has {expected}"
+    )]
+    InvalidVector {
+        column: String,
+        given: usize,
+        expected: usize,
+    },
+    #[error("Cannot search columns with different metrics together ({0} and {1})")]
+    MixedMetrics(Metric, Metric),
+    #[error("Document with key {0} not found")]
+    DocumentNotFound(u64),
+    #[error("Vector index for column '{0}' not found")]
+    IndexNotFound(String),
+    #[error("Embedding error: {0}")]
+    Embedding(anyhow::Error),
+    #[error("Vector index error: {0}")]
+    VectorIndex(anyhow::Error),
+    #[error(
+        "Collection '{0}' has memory-mapped indexes that cannot be changed, serve it without --mmap-indexes"
+    )]
+    ReadOnlyIndexes(String),
+    #[error("Collection '{0}' is being written to, try again once it is done")]
+    CollectionBusy(String),
+    #[error("Search timed out after {0:?}")]
+    Timeout(Duration),
     #[error("Database error: {0}")]
     DatabaseError(#[from] duckdb::Error),
     #[error("Anyhow error: {0}")]
@@ -19,4 +77,18 @@ pub enum ProjectError {
     JoinError(#[from] JoinError),
     #[error("Template error: {0}")]
     TemplateError(#[from] TemplateError),
+    #[error(
+        "Filter matches every document of collection '{0}', set \"all\": true to delete them all"
+    )]
+    DeleteAll(String),
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(anyhow::Error),
+}
+
+fn describe_differences(differences: &[ConfigDifference]) -> String {
+    if differences.is_empty() {
+        return String::from(". The requested config is the same");
+    }
+    let differences: Vec<String> = differences.iter().map(|d| d.to_string()).collect();
+    format!(". The requested config differs: {}", differences.join("; "))
 }
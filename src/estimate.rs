@@ -0,0 +1,345 @@
+use crate::actors::model_actor::{ModelManagerActor, Predict};
+use crate::collection::vector_index::{binarize, IndexPreset, Metric};
+use crate::model::model_utils::ModelOutputDType;
+use actix::Addr;
+use anyhow::anyhow;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::Span;
+use usearch::{new_index, IndexOptions, ScalarKind};
+
+/// Scalar type vectors are stored as in an index.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorDType {
+    F32,
+    F16,
+    I8,
+    /// One bit per dimension, set for positive values. Searched by Hamming
+    /// distance, see [`crate::collection::vector_index::VectorIndex`].
+    B1,
+}
+
+impl VectorDType {
+    /// Bytes a vector of `dimensions` takes.
+    pub fn vector_bytes(&self, dimensions: usize) -> u64 {
+        let dimensions = dimensions as u64;
+        match self {
+            VectorDType::F32 => dimensions * 4,
+            VectorDType::F16 => dimensions * 2,
+            VectorDType::I8 => dimensions,
+            VectorDType::B1 => dimensions.div_ceil(8),
+        }
+    }
+
+    pub fn scalar_kind(&self) -> ScalarKind {
+        match self {
+            VectorDType::F32 => ScalarKind::F32,
+            VectorDType::F16 => ScalarKind::F16,
+            VectorDType::I8 => ScalarKind::I8,
+            VectorDType::B1 => ScalarKind::B1,
+        }
+    }
+
+    /// The dtype of an index quantized to `kind`, if it is one of these.
+    pub fn from_scalar_kind(kind: ScalarKind) -> Option<Self> {
+        [
+            VectorDType::F32,
+            VectorDType::F16,
+            VectorDType::I8,
+            VectorDType::B1,
+        ]
+        .into_iter()
+        .find(|dtype| dtype.scalar_kind() == kind)
+    }
+}
+
+impl From<ModelOutputDType> for VectorDType {
+    fn from(dtype: ModelOutputDType) -> Self {
+        match dtype {
+            ModelOutputDType::F32 => VectorDType::F32,
+            ModelOutputDType::F16 => VectorDType::F16,
+            ModelOutputDType::Int8 => VectorDType::I8,
+        }
+    }
+}
+
+impl FromStr for VectorDType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "f32" => Ok(VectorDType::F32),
+            "f16" => Ok(VectorDType::F16),
+            "i8" | "int8" => Ok(VectorDType::I8),
+            "b1" | "binary" => Ok(VectorDType::B1),
+            _ => Err(anyhow!(
+                "Unknown dtype '{}', expected f32, f16, i8 or b1",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VectorDType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VectorDType::F32 => "f32",
+            VectorDType::F16 => "f16",
+            VectorDType::I8 => "i8",
+            VectorDType::B1 => "b1",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parse a row count given as an integer or in scientific notation, e.g.
+/// `10e6`.
+pub fn parse_count(value: &str) -> Result<u64, String> {
+    let value = value.trim().replace('_', "");
+    if let Ok(count) = value.parse::<u64>() {
+        return Ok(count);
+    }
+    match value.parse::<f64>() {
+        Ok(count) if count >= 0.0 && count.fract() == 0.0 && count < u64::MAX as f64 => {
+            Ok(count as u64)
+        }
+        _ => Err(format!("expected a whole number of rows, got `{}`", value)),
+    }
+}
+
+/// Shape of a collection to estimate the size of.
+#[derive(Clone, Debug)]
+pub struct EstimateConfig {
+    pub rows: u64,
+    pub dimensions: usize,
+    pub dtype: VectorDType,
+    pub metric: Metric,
+    pub index_columns: usize,
+    /// Average size of a row in the source files, before DuckDB compresses it.
+    pub avg_row_bytes: u64,
+}
+
+/// Predicted sizes of a collection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeEstimate {
+    /// Graph parameters the index would be built with.
+    pub preset: IndexPreset,
+    /// Bytes of the vectors of one index column.
+    pub vector_bytes: u64,
+    /// Bytes of the HNSW graph of one index column.
+    pub graph_bytes: u64,
+    /// Bytes of every index, on disk and in memory once loaded.
+    pub index_bytes: u64,
+    /// Upper bound of the DuckDB file, as DuckDB compresses most columns.
+    pub db_bytes: u64,
+}
+
+/// Bytes usearch's HNSW graph takes per vector: a key and level, the
+/// neighbours on the base level, which holds twice the connectivity, and the
+/// neighbours on upper levels, which a node reaches with probability 1/M
+/// each.
+fn graph_bytes_per_vector(connectivity: usize) -> f64 {
+    let m = connectivity as f64;
+    let base_level = 4.0 + 2.0 * m * 4.0;
+    let upper_levels = (4.0 + m * 4.0) / (m - 1.0);
+    8.0 + 2.0 + base_level + upper_levels
+}
+
+pub fn estimate_size(config: &EstimateConfig) -> SizeEstimate {
+    let preset = IndexPreset::for_collection(config.rows, config.dimensions);
+    let vector_bytes = config.rows * config.dtype.vector_bytes(config.dimensions);
+    let graph_bytes = (config.rows as f64 * graph_bytes_per_vector(preset.connectivity())) as u64;
+    // Every row also gets an 8 byte `_key`.
+    let db_bytes = config.rows * (config.avg_row_bytes + 8);
+
+    SizeEstimate {
+        preset,
+        vector_bytes,
+        graph_bytes,
+        index_bytes: (vector_bytes + graph_bytes) * config.index_columns as u64,
+        db_bytes,
+    }
+}
+
+/// Vectors added to the sample index of [`benchmark_index`].
+const BENCHMARK_VECTORS: usize = 5_000;
+
+/// Time to add `config.rows` vectors to the index of one column, measured by
+/// building a small index with the same parameters. Insertion into an HNSW
+/// graph takes time logarithmic in its size, so the sample is scaled by
+/// `n log n`.
+pub fn benchmark_index(config: &EstimateConfig) -> anyhow::Result<Duration> {
+    let preset = IndexPreset::for_collection(config.rows, config.dimensions);
+    let options = IndexOptions {
+        dimensions: config.dimensions,
+        metric: config.metric.kind_for(config.dtype),
+        quantization: config.dtype.scalar_kind(),
+        connectivity: preset.connectivity(),
+        expansion_add: preset.expansion_add(),
+        expansion_search: preset.expansion_search(),
+        multi: true,
+    };
+    let index = new_index(&options).map_err(|e| anyhow!(e.to_string()))?;
+    index
+        .reserve(BENCHMARK_VECTORS)
+        .map_err(|e| anyhow!(e.to_string()))?;
+
+    // Pseudo-random vectors, as real embeddings are spread in every direction.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let vectors: Vec<f32> = (0..BENCHMARK_VECTORS * config.dimensions)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        })
+        .collect();
+
+    let start = Instant::now();
+    vectors
+        .par_chunks(config.dimensions)
+        .enumerate()
+        .try_for_each(|(key, vector)| {
+            let added = match config.dtype {
+                VectorDType::B1 => index.add(key as u64, &binarize(vector)),
+                _ => index.add(key as u64, vector),
+            };
+            added.map_err(|e| anyhow!(e.to_string()))
+        })?;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let sample = BENCHMARK_VECTORS as f64;
+    let rows = (config.rows as f64).max(2.0);
+    Ok(Duration::from_secs_f64(
+        elapsed * (rows * rows.ln()) / (sample * sample.ln()),
+    ))
+}
+
+/// Embedding throughput of a loaded model in rows per second, measured by
+/// embedding batches of `batch_size` texts of `text_chars` characters for
+/// about `duration`, one batch at a time.
+pub async fn benchmark_model(
+    model_manager: &Addr<ModelManagerActor>,
+    model_id: u32,
+    batch_size: usize,
+    text_chars: usize,
+    duration: Duration,
+) -> anyhow::Result<f64> {
+    const WORDS: &[&str] = &[
+        "search",
+        "vector",
+        "index",
+        "document",
+        "embedding",
+        "model",
+        "query",
+        "result",
+        "collection",
+        "column",
+        "server",
+        "capacity",
+    ];
+    let texts: Vec<String> = (0..batch_size)
+        .map(|i| {
+            let mut text = String::with_capacity(text_chars + 16);
+            let mut word = i;
+            while text.len() < text_chars {
+                text.push_str(WORDS[word % WORDS.len()]);
+                text.push(' ');
+                word = word.wrapping_mul(7).wrapping_add(3);
+            }
+            text
+        })
+        .collect();
+
+    // The first batch pays one-off costs, e.g. connecting to a remote API.
+    model_manager
+        .send(Predict {
+            id: model_id,
+            texts: texts.clone(),
+            span: Span::current(),
+        })
+        .await??;
+
+    let start = Instant::now();
+    let mut rows = 0;
+    while rows == 0 || start.elapsed() < duration {
+        model_manager
+            .send(Predict {
+                id: model_id,
+                texts: texts.clone(),
+                span: Span::current(),
+            })
+            .await??;
+        rows += texts.len();
+    }
+    Ok(rows as f64 / start.elapsed().as_secs_f64())
+}
+
+/// Format a byte count with a binary unit, e.g. `7.2 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Format a duration rounded to its two largest units, e.g. `2h 15m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3_599 => format!("{}m {}s", secs / 60, secs % 60),
+        3_600..=86_399 => format!("{}h {}m", secs / 3_600, secs % 3_600 / 60),
+        _ => format!("{}d {}h", secs / 86_400, secs % 86_400 / 3_600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_size() {
+        assert_eq!(parse_count("10e6"), Ok(10_000_000));
+        assert_eq!(parse_count("1_000"), Ok(1_000));
+        assert!(parse_count("1.5").is_err());
+        assert_eq!("int8".parse::<VectorDType>().unwrap(), VectorDType::I8);
+        assert_eq!(VectorDType::B1.vector_bytes(384), 48);
+
+        let estimate = estimate_size(&EstimateConfig {
+            rows: 10_000_000,
+            dimensions: 384,
+            dtype: VectorDType::F16,
+            metric: Metric::Cos,
+            index_columns: 2,
+            avg_row_bytes: 1_000,
+        });
+        assert_eq!(estimate.preset, IndexPreset::Large);
+        assert_eq!(estimate.vector_bytes, 7_680_000_000);
+        // 32 neighbours of 4 bytes on the base level take most of the graph.
+        assert!(estimate.graph_bytes > 10_000_000 * 2 * 32 * 4);
+        assert!(estimate.graph_bytes < 10_000_000 * 3 * 32 * 4);
+        assert_eq!(
+            estimate.index_bytes,
+            2 * (estimate.vector_bytes + estimate.graph_bytes)
+        );
+        assert_eq!(estimate.db_bytes, 10_080_000_000);
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(7_680_000_000), "7.2 GiB");
+        assert_eq!(format_duration(Duration::from_secs(8_100)), "2h 15m");
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A query and the keys of the documents relevant to it, as read by
+/// `letsearch eval`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Qrel {
+    pub query: String,
+    /// Relevance grade of each relevant document. Documents not listed are
+    /// not relevant.
+    pub relevant: HashMap<u64, f32>,
+}
+
+/// Relevant documents of a qrels line: either a list of keys, all equally
+/// relevant, or an object mapping keys to relevance grades.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Relevant {
+    Keys(Vec<u64>),
+    Grades(HashMap<String, f32>),
+}
+
+#[derive(Deserialize)]
+struct QrelLine {
+    query: String,
+    relevant: Relevant,
+}
+
+/// Read queries and their relevant documents from a JSONL file, one query
+/// per line, e.g. `{"query": "vector search", "relevant": [3, 17]}` or with
+/// graded relevance `{"query": "vector search", "relevant": {"3": 2, "17": 1}}`.
+/// Blank lines are skipped.
+pub fn read_qrels(path: &str) -> anyhow::Result<Vec<Qrel>> {
+    let mut qrels = Vec::new();
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed: QrelLine = serde_json::from_str(line)
+            .map_err(|e| anyhow!("Line {} of '{}' is not a valid qrel: {}", i + 1, path, e))?;
+        let relevant = match parsed.relevant {
+            Relevant::Keys(keys) => keys.into_iter().map(|key| (key, 1.0)).collect(),
+            Relevant::Grades(grades) => grades
+                .into_iter()
+                .map(|(key, grade)| {
+                    key.parse::<u64>().map(|key| (key, grade)).map_err(|_| {
+                        anyhow!("Line {} of '{}' has an invalid key '{}'", i + 1, path, key)
+                    })
+                })
+                .collect::<anyhow::Result<HashMap<_, _>>>()?,
+        };
+        if !relevant.values().any(|grade| *grade > 0.0) {
+            return Err(anyhow!(
+                "Line {} of '{}' has no relevant document",
+                i + 1,
+                path
+            ));
+        }
+        qrels.push(Qrel {
+            query: parsed.query,
+            relevant,
+        });
+    }
+    Ok(qrels)
+}
+
+/// Retrieval quality of one query, or the mean over all queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalMetrics {
+    /// Share of the relevant documents found in the top k.
+    pub recall: f64,
+    /// Reciprocal rank of the first relevant document in the top k, 0 when
+    /// there is none.
+    pub mrr: f64,
+    /// Normalized discounted cumulative gain of the top k.
+    pub ndcg: f64,
+}
+
+impl EvalMetrics {
+    /// Metrics of `ranked`, the keys returned for a query best first, cut
+    /// off at `k`.
+    pub fn of(ranked: &[u64], relevant: &HashMap<u64, f32>, k: usize) -> Self {
+        let ranked = &ranked[..ranked.len().min(k)];
+        let grade = |key: &u64| relevant.get(key).copied().unwrap_or_default().max(0.0) as f64;
+
+        let num_relevant = relevant.values().filter(|grade| **grade > 0.0).count();
+        let found = ranked.iter().filter(|key| grade(key) > 0.0).count();
+        let recall = if num_relevant == 0 {
+            0.0
+        } else {
+            found as f64 / num_relevant.min(k) as f64
+        };
+
+        let mrr = ranked
+            .iter()
+            .position(|key| grade(key) > 0.0)
+            .map_or(0.0, |rank| 1.0 / (rank + 1) as f64);
+
+        let gain = |grade: f64, rank: usize| (2f64.powf(grade) - 1.0) / ((rank + 2) as f64).log2();
+        let dcg: f64 = ranked
+            .iter()
+            .enumerate()
+            .map(|(rank, key)| gain(grade(key), rank))
+            .sum();
+        let mut ideal: Vec<f64> = relevant
+            .values()
+            .map(|grade| grade.max(0.0) as f64)
+            .collect();
+        ideal.sort_by(|a, b| b.total_cmp(a));
+        let idcg: f64 = ideal
+            .into_iter()
+            .take(k)
+            .enumerate()
+            .map(|(rank, grade)| gain(grade, rank))
+            .sum();
+        let ndcg = if idcg > 0.0 { dcg / idcg } else { 0.0 };
+
+        Self { recall, mrr, ndcg }
+    }
+
+    /// Mean of per-query metrics.
+    pub fn mean(metrics: &[EvalMetrics]) -> Self {
+        if metrics.is_empty() {
+            return Self::default();
+        }
+        let n = metrics.len() as f64;
+        Self {
+            recall: metrics.iter().map(|m| m.recall).sum::<f64>() / n,
+            mrr: metrics.iter().map(|m| m.mrr).sum::<f64>() / n,
+            ndcg: metrics.iter().map(|m| m.ndcg).sum::<f64>() / n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_qrels_and_metrics() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("qrels");
+        std::fs::write(
+            &path,
+            "{\"query\": \"a\", \"relevant\": [1, 2]}\n\n{\"query\": \"b\", \"relevant\": {\"7\": 2, \"8\": 0}}\n",
+        )
+        .unwrap();
+        let qrels = read_qrels(&path.to_string_lossy()).unwrap();
+        assert_eq!(qrels.len(), 2);
+        assert_eq!(qrels[0].relevant, HashMap::from([(1, 1.0), (2, 1.0)]));
+        assert_eq!(qrels[1].relevant, HashMap::from([(7, 2.0), (8, 0.0)]));
+        std::fs::write(&path, "{\"query\": \"a\", \"relevant\": []}\n").unwrap();
+        assert!(read_qrels(&path.to_string_lossy()).is_err());
+
+        let relevant = HashMap::from([(1, 1.0), (2, 1.0)]);
+        let perfect = EvalMetrics::of(&[1, 2, 3], &relevant, 3);
+        assert_eq!(
+            perfect,
+            EvalMetrics {
+                recall: 1.0,
+                mrr: 1.0,
+                ndcg: 1.0
+            }
+        );
+
+        let second = EvalMetrics::of(&[3, 1, 4], &relevant, 3);
+        assert_eq!(second.recall, 0.5);
+        assert_eq!(second.mrr, 0.5);
+        // DCG 1/log2(3) over IDCG 1 + 1/log2(3).
+        assert!((second.ndcg - 0.386_852_8).abs() < 1e-6);
+
+        // Results past k do not count.
+        assert_eq!(
+            EvalMetrics::of(&[3, 1], &relevant, 1),
+            EvalMetrics::default()
+        );
+
+        let mean = EvalMetrics::mean(&[perfect, second]);
+        assert_eq!(mean.recall, 0.75);
+        assert_eq!(mean.mrr, 0.75);
+    }
+}
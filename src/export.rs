@@ -0,0 +1,141 @@
+use anyhow::anyhow;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// One search result of one query, as written by `letsearch export-results`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ResultRow {
+    pub query: String,
+    /// 1-based position of the result for its query.
+    pub rank: u32,
+    pub key: u64,
+    pub score: f32,
+    pub content: String,
+    /// Index column the result matched on.
+    pub column: String,
+}
+
+/// Read the queries to export results for. Each line is either a JSON object
+/// with a `query` field, a JSON string, or plain text. Blank lines are skipped.
+pub fn read_export_queries(path: &str) -> anyhow::Result<Vec<String>> {
+    let mut queries = Vec::new();
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let query = match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(object)) => object
+                .get("query")
+                .and_then(|query| query.as_str())
+                .ok_or_else(|| anyhow!("Line {} of '{}' has no string 'query' field", i + 1, path))?
+                .to_string(),
+            Ok(serde_json::Value::String(query)) => query,
+            _ => line.to_string(),
+        };
+        queries.push(query);
+    }
+    Ok(queries)
+}
+
+/// Write `rows` to `path` in the format given by its extension: `.parquet`,
+/// `.csv` or `.jsonl`.
+pub fn write_results(path: &str, rows: &[ResultRow]) -> anyhow::Result<()> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("jsonl") | Some("ndjson") => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for row in rows {
+                serde_json::to_writer(&mut writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        Some("parquet") => copy_with_duckdb(path, rows, "FORMAT PARQUET"),
+        Some("csv") => copy_with_duckdb(path, rows, "FORMAT CSV, HEADER"),
+        _ => Err(anyhow!(
+            "Cannot detect the output format of '{}', expected a .parquet, .csv or .jsonl file",
+            path
+        )),
+    }
+}
+
+/// Load `rows` into an in-memory DuckDB table and `COPY` it to `path`.
+fn copy_with_duckdb(path: &str, rows: &[ResultRow], options: &str) -> anyhow::Result<()> {
+    let conn = duckdb::Connection::open_in_memory()?;
+    conn.execute_batch(
+        "CREATE TABLE results (query VARCHAR, rank INTEGER, key UBIGINT, score FLOAT, content VARCHAR, column_name VARCHAR);",
+    )?;
+    {
+        let mut appender = conn.appender("results")?;
+        for row in rows {
+            appender.append_row(duckdb::params![
+                row.query,
+                row.rank,
+                row.key,
+                row.score,
+                row.content,
+                row.column
+            ])?;
+        }
+        appender.flush()?;
+    }
+    conn.execute(
+        &format!(
+            "COPY (SELECT query, rank, key, score, content, column_name AS \"column\" FROM results) TO '{}' ({});",
+            path.replace('\'', "''"),
+            options
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_queries_and_write_results() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let queries_path = dir.join("q.jsonl").to_string_lossy().to_string();
+        std::fs::write(
+            &queries_path,
+            "{\"query\": \"vector search\", \"id\": 1}\n\n\"it's a string\"\nplain text\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_export_queries(&queries_path).unwrap(),
+            vec!["vector search", "it's a string", "plain text"]
+        );
+
+        let rows = vec![ResultRow {
+            query: String::from("vector search"),
+            rank: 1,
+            key: 42,
+            score: 0.5,
+            content: String::from("a, \"quoted\" document"),
+            column: String::from("text"),
+        }];
+        let csv_path = dir.join("results.csv").to_string_lossy().to_string();
+        write_results(&csv_path, &rows).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert!(csv.starts_with("query,rank,key,score,content,column\n"));
+        assert!(csv.contains("vector search,1,42,0.5,"));
+
+        let jsonl_path = dir.join("results.jsonl").to_string_lossy().to_string();
+        write_results(&jsonl_path, &rows).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&jsonl_path).unwrap().trim()).unwrap();
+        assert_eq!(line["key"], 42);
+
+        assert!(write_results(&dir.join("results.txt").to_string_lossy(), &rows).is_err());
+    }
+}
@@ -1,16 +1,25 @@
 use crate::collection::collection_utils::home_dir;
+use crate::ingest::FileFormat;
+use crate::model::hardware::{Hardware, AUTO_VARIANT};
+use crate::model::metadata::{ModelMetadata, Variant, METADATA_FILE};
+use crate::output::{format_rows, OutputFormat};
 use anyhow;
 use futures::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
 use reqwest;
 use reqwest::header::CONTENT_LENGTH;
 use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+/// File next to a downloaded model's weights holding its [`ModelCard`].
+const MODEL_CARD_FILE: &str = "model_card.json";
+
 #[derive(Deserialize, Debug)]
 #[allow(non_snake_case)]
 #[allow(dead_code)]
@@ -38,6 +47,87 @@ pub struct ModelInfo {
     pub author: Option<String>,
     pub config: Option<serde_json::Value>,
     pub securityStatus: Option<serde_json::Value>,
+    /// `false`, or how access requests to a gated repo are approved:
+    /// `"auto"` or `"manual"`.
+    pub gated: Option<serde_json::Value>,
+    /// Metadata header of the repo's README, e.g. its license.
+    pub cardData: Option<serde_json::Value>,
+}
+
+/// License and provenance of a HuggingFace model, stored next to its
+/// downloaded weights so that the models powering each collection can be
+/// audited.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ModelCard {
+    pub repo_id: String,
+    /// Commit of the repo when the card was fetched.
+    pub sha: Option<String>,
+    pub license: Option<String>,
+    /// Link to the license text, for licenses declared as `other`.
+    pub license_link: Option<String>,
+    /// Whether access has to be requested on the Hub before downloading.
+    pub gated: bool,
+    pub pipeline_tag: Option<String>,
+    pub tags: Vec<String>,
+    pub last_modified: Option<String>,
+    /// When the card was fetched, in RFC 3339.
+    pub fetched_at: String,
+}
+
+impl ModelCard {
+    fn from_info(repo_id: &str, info: ModelInfo) -> Self {
+        let card_data = info.cardData.unwrap_or_default();
+        let tags = info.tags.unwrap_or_default();
+        // The license is either a string or a list in the README header, and
+        // is also mirrored as a `license:` tag.
+        let license = match &card_data["license"] {
+            serde_json::Value::String(license) => Some(license.clone()),
+            serde_json::Value::Array(licenses) => {
+                let licenses: Vec<&str> = licenses.iter().filter_map(|l| l.as_str()).collect();
+                (!licenses.is_empty()).then(|| licenses.join(", "))
+            }
+            _ => None,
+        }
+        .or_else(|| {
+            tags.iter()
+                .find_map(|tag| tag.strip_prefix("license:"))
+                .map(String::from)
+        });
+        let gated = match info.gated {
+            Some(serde_json::Value::Bool(gated)) => gated,
+            Some(serde_json::Value::String(_)) => true,
+            _ => false,
+        };
+
+        ModelCard {
+            repo_id: repo_id.to_string(),
+            sha: info.sha,
+            license,
+            license_link: card_data["license_link"].as_str().map(String::from),
+            gated,
+            pipeline_tag: info.pipeline_tag,
+            tags,
+            last_modified: info.lastModified,
+            fetched_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// Error for a repo the Hub refused to serve, which is either gated,
+/// private or missing. `repo_path` is a model's repo id, or
+/// `datasets/<repo id>` for a dataset.
+fn access_denied(repo_path: &str, status: StatusCode, token: &Option<String>) -> anyhow::Error {
+    let hint = match token {
+        Some(_) => "The HuggingFace token cannot access it: request access on its page, or use a token of an account that has it",
+        None => "Request access on its page if needed, then pass --hf-token or set the HF_TOKEN environment variable",
+    };
+    anyhow::anyhow!(
+        "Access to 'hf://{}' was denied ({}): it is gated, private or does not exist. {}: https://huggingface.co/{}",
+        repo_path,
+        status,
+        hint,
+        repo_path
+    )
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -58,15 +148,33 @@ pub struct BlobLfsInfo {
     pub pointer_size: Option<u64>,
 }
 
-#[allow(dead_code)]
-pub async fn get_model_info(repo_id: &str, files_metadata: bool) -> anyhow::Result<ModelInfo> {
+pub async fn get_model_info(
+    repo_id: &str,
+    files_metadata: bool,
+    token: Option<String>,
+) -> anyhow::Result<ModelInfo> {
     let metadata_param = if files_metadata { "?blobs=true" } else { "" };
     let url = format!(
         "https://huggingface.co/api/models/{}{}",
         repo_id, metadata_param
     );
     let client = reqwest::Client::builder().build()?;
-    let response = client.get(&url).send().await?;
+    let response = match token.as_ref() {
+        Some(token) => client.get(&url).header(
+            AUTHORIZATION,
+            HeaderValue::from_str(format!("BEARER {token}").as_str())
+                .map_err(|e| anyhow::anyhow!("Invalid token formatting: {}", e))?,
+        ),
+        None => client.get(&url),
+    }
+    .send()
+    .await?;
+    if matches!(
+        response.status(),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+    ) {
+        return Err(access_denied(repo_id, response.status(), &token));
+    }
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
             "Failed to fetch model info: {}",
@@ -92,7 +200,10 @@ async fn get_models(filter: &str, token: Option<String>) -> anyhow::Result<Vec<M
     .await?;
 
     if !response.status().is_success() {
-        return Err(anyhow::anyhow!("Failed to list models: {}", response.status()).into());
+        return Err(anyhow::anyhow!(
+            "Failed to list models: {}",
+            response.status()
+        ));
     }
 
     let models: Vec<Model> = response.json().await?;
@@ -131,6 +242,12 @@ async fn download_file(
     .send()
     .await?;
 
+    if matches!(
+        response.status(),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    ) {
+        return Err(access_denied(repo_id, response.status(), &token));
+    }
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
             "Failed to download file: {}",
@@ -170,72 +287,97 @@ async fn download_file(
     Ok(destination_path.to_string_lossy().to_string())
 }
 
+/// Repo id of a `hf://` model path and the directory it is downloaded to.
+fn model_cache_dir(model_path: &str) -> anyhow::Result<(String, PathBuf)> {
+    let repo_id = model_path.replace("hf://", "").to_string();
+    let (username, repo_name) = repo_id.split_once("/").ok_or_else(|| {
+        anyhow::anyhow!("This is probabably not a proper HuggingFace path. Check it out")
+    })?;
+    let destination_dir = home_dir().join("models").join(username).join(repo_name);
+    Ok((repo_id, destination_dir))
+}
+
+/// Fetch the license and provenance of a model from the Hub.
+pub async fn fetch_model_card(
+    model_path: &str,
+    token: Option<String>,
+) -> anyhow::Result<ModelCard> {
+    let (repo_id, _) = model_cache_dir(model_path)?;
+    let info = get_model_info(&repo_id, false, token).await?;
+    Ok(ModelCard::from_info(&repo_id, info))
+}
+
+/// Card stored when a `hf://` model was downloaded. `None` for other models
+/// and for models downloaded before cards were stored.
+pub fn read_model_card(model_path: &str) -> anyhow::Result<Option<ModelCard>> {
+    if !model_path.starts_with("hf://") {
+        return Ok(None);
+    }
+    let (_, model_dir) = model_cache_dir(model_path)?;
+    let card_path = model_dir.join(MODEL_CARD_FILE);
+    if !card_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(card_path)?)?))
+}
+
+fn save_model_card(model_dir: &Path, card: &ModelCard) -> anyhow::Result<()> {
+    fs::create_dir_all(model_dir)?;
+    fs::write(
+        model_dir.join(MODEL_CARD_FILE),
+        serde_json::to_string_pretty(card)?,
+    )?;
+    Ok(())
+}
+
 pub async fn download_model(
     model_path: String,
     variant: String,
     token: Option<String>,
 ) -> anyhow::Result<(String, String)> {
-    let cache_dir = home_dir().join("models");
-    let repo_id = model_path.replace("hf://", "").to_string();
-    let (username, repo_name) = repo_id.split_once("/").ok_or_else(|| {
-        anyhow::anyhow!("This is probabably not a proper HuggingFace path. Check it out")
-    })?;
-    let destination_dir = cache_dir.join(username).join(repo_name);
+    let (repo_id, destination_dir) = model_cache_dir(&model_path)?;
 
     let config_path = download_file(
         repo_id.as_str(),
-        "metadata.json",
+        METADATA_FILE,
         destination_dir.clone(),
         token.clone(),
     )
     .await?;
 
-    // Read the metadata.json file
-    let config_content = fs::read_to_string(config_path)?;
-    let config: serde_json::Value = serde_json::from_str(&config_content)?;
-
-    // Parse the "letsearch_version" and "variants"
-    let version = config["letsearch_version"].as_i64().ok_or_else(|| {
-        anyhow::anyhow!("This is probably not a letsearch-compatible model. Check it out")
-    })?;
-    assert_eq!(version, 1);
-
-    let variants = config["variants"]
-        .as_array()
-        .ok_or_else(|| anyhow::anyhow!("This is probably not a letsearch model. check it out"))?;
+    // The card is informational, so a model can still be used when the Hub
+    // does not return it.
+    if !destination_dir.join(MODEL_CARD_FILE).exists() {
+        match fetch_model_card(&model_path, token.clone()).await {
+            Ok(card) => save_model_card(&destination_dir, &card)?,
+            Err(e) => warn!("Failed to fetch the model card of {}: {}", model_path, e),
+        }
+    }
 
-    // Check if the requested variant exists
-    let variant_info = variants
-        .iter()
-        .find(|v| v["variant"] == variant)
-        .ok_or_else(|| anyhow::anyhow!("Variant not found in config"))?;
+    let metadata = ModelMetadata::parse(&fs::read_to_string(config_path)?)
+        .map_err(|e| anyhow::anyhow!("{} is not a usable letsearch model: {:#}", model_path, e))?;
 
     // Download the ONNX model for the specified variant
-    let local_model_path = match variant_info["path"].as_str() {
-        Some(model_file) => PathBuf::from(
-            download_file(
-                &repo_id.as_str(),
-                model_file,
-                destination_dir.clone(),
-                token.clone(),
-            )
-            .await?,
-        ),
-        None => unreachable!("unreachable"), // we already varified it's a letsearch model, so there shouldn't be a variant without a path key
-    };
+    let model_file = &metadata.variant(&variant)?.path;
+    let local_model_path = PathBuf::from(
+        download_file(
+            repo_id.as_str(),
+            model_file,
+            destination_dir.clone(),
+            token.clone(),
+        )
+        .await?,
+    );
 
-    if let Some(required_files) = config["required_files"].as_array() {
-        for file_name in required_files {
-            download_file(
-                repo_id.as_str(),
-                file_name
-                    .as_str()
-                    .ok_or_else(|| anyhow::anyhow!("File name is not a string"))?,
-                destination_dir.clone(),
-                token.clone(),
-            )
-            .await?;
-        }
+    let image_file = metadata.image.as_ref().map(|image| &image.path);
+    for file_name in metadata.required_files.iter().chain(image_file) {
+        download_file(
+            repo_id.as_str(),
+            file_name,
+            destination_dir.clone(),
+            token.clone(),
+        )
+        .await?;
     }
 
     let model_dir = local_model_path
@@ -254,7 +396,388 @@ pub async fn download_model(
     Ok((model_dir, model_file))
 }
 
-pub async fn list_models(token: Option<String>) -> anyhow::Result<()> {
+/// Entry of `metadata` for the variant fastest on `hardware`.
+fn select_variant<'a>(
+    metadata: &'a ModelMetadata,
+    hardware: &Hardware,
+) -> anyhow::Result<&'a Variant> {
+    let chosen = hardware
+        .choose_variant(&metadata.variant_names())
+        .ok_or_else(|| anyhow::anyhow!("The model has no variants"))?;
+    metadata.variant(chosen)
+}
+
+/// The variant of a model to load: `variant` itself, unless it is `auto`,
+/// in which case the variant of `hf://` models, or the model file of local
+/// models, that runs fastest on this machine.
+pub async fn resolve_variant(
+    model_path: &str,
+    variant: &str,
+    token: Option<String>,
+) -> anyhow::Result<String> {
+    if variant != AUTO_VARIANT {
+        return Ok(variant.to_string());
+    }
+
+    let hardware = Hardware::detect();
+    let resolved = if model_path.starts_with("hf://") {
+        let (repo_id, destination_dir) = model_cache_dir(model_path)?;
+        let metadata_path =
+            download_file(repo_id.as_str(), METADATA_FILE, destination_dir, token).await?;
+        let metadata = ModelMetadata::parse(&fs::read_to_string(metadata_path)?)?;
+        select_variant(&metadata, &hardware)?.variant.clone()
+    } else {
+        let metadata = ModelMetadata::read(Path::new(model_path))?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} has no metadata.json to pick a variant from. Pass --variant with the name of its ONNX file",
+                model_path
+            )
+        })?;
+        select_variant(&metadata, &hardware)?.path.clone()
+    };
+    info!(
+        "Selected variant {} of {} for this machine ({}). Pass --variant to pin another",
+        resolved, model_path, hardware
+    );
+    Ok(resolved)
+}
+
+/// A dataset given as `hf://datasets/<owner>/<name>`, without a path to
+/// files in it, optionally at a revision: `hf://datasets/<owner>/<name>@<branch>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetRepo {
+    pub repo_id: String,
+    pub revision: Option<String>,
+}
+
+impl DatasetRepo {
+    /// Parse a dataset path. `None` for other paths, including paths to files
+    /// or globs in a dataset, which DuckDB reads as they are.
+    pub fn parse(path: &str) -> Option<Self> {
+        let rest = path.strip_prefix("hf://datasets/")?.trim_end_matches('/');
+        let (repo_id, revision) = match rest.split_once('@') {
+            Some((repo_id, revision)) if !revision.is_empty() => {
+                (repo_id, Some(revision.to_string()))
+            }
+            Some(_) => return None,
+            None => (rest, None),
+        };
+        match repo_id.split('/').collect::<Vec<_>>().as_slice() {
+            [owner, name] if !owner.is_empty() && !name.is_empty() => Some(DatasetRepo {
+                repo_id: repo_id.to_string(),
+                revision,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Config and split to import from the datasets given as [`DatasetRepo`].
+#[derive(Clone, Debug, Default)]
+pub struct DatasetSelection {
+    /// Subset of a dataset with several, e.g. `en`.
+    pub config: Option<String>,
+    /// e.g. `train`. Every split when `None`.
+    pub split: Option<String>,
+}
+
+impl DatasetSelection {
+    fn is_empty(&self) -> bool {
+        self.config.is_none() && self.split.is_none()
+    }
+}
+
+#[derive(Deserialize)]
+struct ParquetFile {
+    config: String,
+    split: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct ParquetFilesResponse {
+    parquet_files: Vec<ParquetFile>,
+    #[serde(default)]
+    partial: bool,
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+}
+
+async fn hub_get(url: &str, token: &Option<String>) -> anyhow::Result<reqwest::Response> {
+    let client = reqwest::Client::builder().build()?;
+    let response = match token.as_ref() {
+        Some(token) => client.get(url).header(
+            AUTHORIZATION,
+            HeaderValue::from_str(format!("BEARER {token}").as_str())
+                .map_err(|e| anyhow::anyhow!("Invalid token formatting: {}", e))?,
+        ),
+        None => client.get(url),
+    }
+    .send()
+    .await?;
+    Ok(response)
+}
+
+/// Path DuckDB reads a file of a dataset at a revision from. Its HuggingFace
+/// secret is used for private datasets.
+fn dataset_file_path(repo_id: &str, revision: &str, path: &str) -> String {
+    format!(
+        "hf://datasets/{}@{}/{}",
+        repo_id,
+        revision.replace('/', "%2F"),
+        path
+    )
+}
+
+/// Parquet files the Hub converted a dataset to, as listed by the datasets
+/// server. Only the main branch is converted.
+async fn converted_parquet_files(
+    repo: &DatasetRepo,
+    selection: &DatasetSelection,
+    token: &Option<String>,
+) -> anyhow::Result<Vec<String>> {
+    let url = format!(
+        "https://datasets-server.huggingface.co/parquet?dataset={}",
+        repo.repo_id
+    );
+    let response = hub_get(&url, token).await?;
+    if matches!(
+        response.status(),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+    ) {
+        return Err(access_denied(
+            &format!("datasets/{}", repo.repo_id),
+            response.status(),
+            token,
+        ));
+    }
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to list the parquet files of dataset '{}': {}",
+            repo.repo_id,
+            response.status()
+        ));
+    }
+    let listing: ParquetFilesResponse = response.json().await?;
+    if listing.partial {
+        warn!(
+            "Dataset '{}' is only partially converted to parquet on the Hub, not every row will be imported",
+            repo.repo_id
+        );
+    }
+
+    let converted_prefix = format!("https://huggingface.co/datasets/{}/resolve/", repo.repo_id);
+    let files: Vec<String> = listing
+        .parquet_files
+        .iter()
+        .filter(|f| selection.config.as_ref().is_none_or(|c| *c == f.config))
+        .filter(|f| selection.split.as_ref().is_none_or(|s| *s == f.split))
+        .map(|f| match f.url.strip_prefix(&converted_prefix) {
+            // Read through hf:// rather than https:// so that DuckDB
+            // authenticates for private datasets.
+            Some(rest) => format!("hf://datasets/{}@{}", repo.repo_id, rest),
+            None => f.url.clone(),
+        })
+        .collect();
+    if files.is_empty() {
+        let mut available: Vec<String> = listing
+            .parquet_files
+            .iter()
+            .map(|f| format!("{}/{}", f.config, f.split))
+            .collect();
+        available.sort();
+        available.dedup();
+        return Err(anyhow::anyhow!(
+            "No parquet files of dataset '{}' match the config and split, available config/split pairs: {}",
+            repo.repo_id,
+            available.join(", ")
+        ));
+    }
+    Ok(files)
+}
+
+/// Whether a file of a dataset repo belongs to the selection, going by the
+/// Hub's layout conventions: a config is a directory, and a split is either a
+/// directory or the prefix of the file name, e.g. `en/train-00000-of-00002.parquet`.
+fn in_selection(path: &str, selection: &DatasetSelection) -> bool {
+    let components: Vec<&str> = path.split('/').collect();
+    let (file_name, dirs) = components.split_last().unwrap_or((&"", &[]));
+    let config_matches = selection
+        .config
+        .as_ref()
+        .is_none_or(|config| dirs.contains(&config.as_str()));
+    let split_matches = selection.split.as_ref().is_none_or(|split| {
+        dirs.contains(&split.as_str())
+            || file_name.starts_with(&format!("{}-", split))
+            || file_name.starts_with(&format!("{}.", split))
+    });
+    config_matches && split_matches
+}
+
+/// Data files of a dataset repo at a revision. Parquet files are preferred
+/// over JSONL files when a revision has both.
+async fn revision_files(
+    repo: &DatasetRepo,
+    revision: &str,
+    selection: &DatasetSelection,
+    token: &Option<String>,
+) -> anyhow::Result<Vec<String>> {
+    let mut url = Some(format!(
+        "https://huggingface.co/api/datasets/{}/tree/{}?recursive=true",
+        repo.repo_id,
+        revision.replace('/', "%2F")
+    ));
+    let mut paths = Vec::new();
+    while let Some(page) = url.take() {
+        let response = hub_get(&page, token).await?;
+        if matches!(
+            response.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+        ) {
+            return Err(access_denied(
+                &format!("datasets/{}", repo.repo_id),
+                response.status(),
+                token,
+            ));
+        }
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to list the files of dataset '{}' at '{}': {}",
+                repo.repo_id,
+                revision,
+                response.status()
+            ));
+        }
+        // Large repos are listed in pages linked with `rel="next"`.
+        url = response
+            .headers()
+            .get(reqwest::header::LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(|link| {
+                link.split(',')
+                    .find(|part| part.contains("rel=\"next\""))
+                    .and_then(|part| part.split_once('<'))
+                    .and_then(|(_, rest)| rest.split_once('>'))
+                    .map(|(next, _)| next.to_string())
+            });
+        let entries: Vec<TreeEntry> = response.json().await?;
+        paths.extend(
+            entries
+                .into_iter()
+                .filter(|entry| entry.kind == "file")
+                .map(|entry| entry.path),
+        );
+    }
+
+    for format in [FileFormat::Parquet, FileFormat::Jsonl] {
+        let mut files: Vec<&String> = paths
+            .iter()
+            .filter(|path| FileFormat::from_extension(path) == Some(format))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        files.retain(|path| in_selection(path, selection));
+        if files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No files of dataset '{}' at '{}' match the config and split",
+                repo.repo_id,
+                revision
+            ));
+        }
+        files.sort();
+        return Ok(files
+            .into_iter()
+            .map(|path| dataset_file_path(&repo.repo_id, revision, path))
+            .collect());
+    }
+    Err(anyhow::anyhow!(
+        "Dataset '{}' has no parquet or JSONL files at '{}'",
+        repo.repo_id,
+        revision
+    ))
+}
+
+/// Replace the `hf://datasets/<owner>/<name>[@<revision>]` paths among
+/// `inputs` with the files of the selected config and split. Other paths
+/// are kept as they are.
+///
+/// Without a revision, the parquet files the Hub converted the dataset to
+/// are used, so that datasets in any format can be imported. At a revision,
+/// the dataset's own parquet or JSONL files are used.
+pub async fn resolve_dataset_inputs(
+    inputs: &[String],
+    selection: &DatasetSelection,
+    token: Option<String>,
+) -> anyhow::Result<Vec<String>> {
+    let mut resolved = Vec::with_capacity(inputs.len());
+    let mut any_dataset = false;
+    for input in inputs {
+        let Some(repo) = DatasetRepo::parse(input) else {
+            resolved.push(input.clone());
+            continue;
+        };
+        any_dataset = true;
+        let files = match &repo.revision {
+            Some(revision) => revision_files(&repo, revision, selection, &token).await?,
+            None => converted_parquet_files(&repo, selection, &token).await?,
+        };
+        info!("Resolved '{}' to {} file(s)", input, files.len());
+        resolved.extend(files);
+    }
+    if !any_dataset && !selection.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A dataset config or split is only used with hf://datasets/<owner>/<name> paths"
+        ));
+    }
+    Ok(resolved)
+}
+
+/// List letsearch-compatible models on HuggingFace Hub, most downloaded first.
+/// JSON and CSV print only the models, without the spinner and hints.
+pub async fn list_models(token: Option<String>, output: OutputFormat) -> anyhow::Result<()> {
+    if output != OutputFormat::Table {
+        let mut models = get_models("letsearch", token).await?;
+        models.sort_by_key(|m| std::cmp::Reverse(m.downloads));
+        if output == OutputFormat::Json {
+            let models: Vec<serde_json::Value> = models
+                .iter()
+                .map(|m| {
+                    serde_json::json!({
+                        "model": format!("hf://{}", m.modelId),
+                        "downloads": m.downloads,
+                        "likes": m.likes,
+                        "private": m.private,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&models)?);
+        } else {
+            let or_empty = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+            let rows: Vec<Vec<String>> = models
+                .iter()
+                .map(|m| {
+                    vec![
+                        format!("hf://{}", m.modelId),
+                        or_empty(m.downloads),
+                        or_empty(m.likes),
+                        m.private.to_string(),
+                    ]
+                })
+                .collect();
+            println!(
+                "{}",
+                format_rows(output, &["model", "downloads", "likes", "private"], &rows)
+            );
+        }
+        return Ok(());
+    }
+
     // Create an indefinite spinner progress bar
     let progress_bar = ProgressBar::new_spinner();
     progress_bar.set_style(
@@ -270,19 +793,19 @@ pub async fn list_models(token: Option<String>) -> anyhow::Result<()> {
     if models.is_empty() {
         progress_bar.finish_and_clear();
         println!("No letsearch-compatible models found on HuggingFace Hub :(");
-        println!("Maybe trying to convert your own?");
+        println!("Maybe try converting your own with `letsearch convert`?");
         return Ok(());
     } else {
         let count = models.len();
         progress_bar.finish_with_message(format!("{} model(s) found!", count));
 
         println!("===============");
-        models.sort_by(|a, b| b.downloads.cmp(&a.downloads));
+        models.sort_by_key(|m| std::cmp::Reverse(m.downloads));
         for model in models {
             println!("     hf://{}", model.modelId);
         }
     }
-    println!("");
+    println!();
     println!("If you cannot see a private model of yours, try using `--hf-token` argument or setting `HF_TOKEN` as an environment variable.");
 
     Ok(())
@@ -290,7 +813,11 @@ pub async fn list_models(token: Option<String>) -> anyhow::Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use crate::hf_ops::{download_model, get_model_info, list_models};
+    use crate::hf_ops::{
+        dataset_file_path, download_model, get_model_info, in_selection, list_models, DatasetRepo,
+        DatasetSelection, ModelCard, ModelInfo,
+    };
+    use crate::output::OutputFormat;
     use std::env::temp_dir;
     use std::path::PathBuf;
 
@@ -318,10 +845,85 @@ mod tests {
     #[tokio::test]
     async fn test_get_model_info() {
         let repo_id = "mys/minilm";
-        let model_info = get_model_info(repo_id, false).await.unwrap();
+        let model_info = get_model_info(repo_id, false, None).await.unwrap();
         assert!(model_info.modelId.is_some());
     }
 
+    #[test]
+    fn test_model_card_from_info() {
+        let info: ModelInfo = serde_json::from_value(serde_json::json!({
+            "modelId": "org/gated-model",
+            "sha": "abc123",
+            "private": false,
+            "gated": "manual",
+            "tags": ["onnx", "license:other"],
+            "cardData": {"license": "other", "license_link": "https://example.com/LICENSE"},
+        }))
+        .unwrap();
+        let card = ModelCard::from_info("org/gated-model", info);
+        assert_eq!(card.license.as_deref(), Some("other"));
+        assert_eq!(
+            card.license_link.as_deref(),
+            Some("https://example.com/LICENSE")
+        );
+        assert!(card.gated);
+        assert_eq!(card.sha.as_deref(), Some("abc123"));
+
+        // Without a README header, the license comes from the tags.
+        let info: ModelInfo = serde_json::from_value(serde_json::json!({
+            "private": false,
+            "gated": false,
+            "tags": ["license:apache-2.0"],
+        }))
+        .unwrap();
+        let card = ModelCard::from_info("mys/minilm", info);
+        assert_eq!(card.license.as_deref(), Some("apache-2.0"));
+        assert!(!card.gated);
+    }
+
+    #[test]
+    fn test_dataset_paths() {
+        assert_eq!(
+            DatasetRepo::parse("hf://datasets/neural-bridge/rag-dataset-1200"),
+            Some(DatasetRepo {
+                repo_id: String::from("neural-bridge/rag-dataset-1200"),
+                revision: None,
+            })
+        );
+        assert_eq!(
+            DatasetRepo::parse("hf://datasets/org/data@refs/pr/1/")
+                .unwrap()
+                .revision
+                .as_deref(),
+            Some("refs/pr/1")
+        );
+        // Files and globs in a dataset are read by DuckDB as they are.
+        assert_eq!(
+            DatasetRepo::parse("hf://datasets/org/data/**/*.parquet"),
+            None
+        );
+        assert_eq!(DatasetRepo::parse("hf://datasets/org"), None);
+        assert_eq!(DatasetRepo::parse("hf://mys/minilm"), None);
+        assert_eq!(
+            dataset_file_path("org/data", "refs/pr/1", "en/train.parquet"),
+            "hf://datasets/org/data@refs%2Fpr%2F1/en/train.parquet"
+        );
+
+        let selection = DatasetSelection {
+            config: Some(String::from("en")),
+            split: Some(String::from("train")),
+        };
+        assert!(in_selection("en/train-00000-of-00002.parquet", &selection));
+        assert!(in_selection("data/en/train/0000.parquet", &selection));
+        assert!(!in_selection("de/train-00000-of-00001.parquet", &selection));
+        assert!(!in_selection("en/test-00000-of-00001.parquet", &selection));
+        assert!(!in_selection("en/trainer.parquet", &selection));
+        assert!(in_selection(
+            "any/file.parquet",
+            &DatasetSelection::default()
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_models() {
         let models = super::get_models("letsearch", None).await.unwrap();
@@ -331,6 +933,6 @@ mod tests {
     #[tokio::test]
     async fn test_list_models() {
         // This function primarily prints to stdout, so we'll just check if it completes without error.
-        list_models(None).await.unwrap();
+        list_models(None, OutputFormat::Table).await.unwrap();
     }
 }
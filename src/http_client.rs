@@ -0,0 +1,114 @@
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Minimal HTTP client for a running `letsearch serve` instance.
+///
+/// Responses are unwrapped from the `{"status", "data", "time"}` envelope used
+/// by the server, and error responses are turned into `anyhow` errors carrying
+/// the server's message.
+#[derive(Clone)]
+pub struct LetsearchClient {
+    base_url: String,
+    api_key: Option<String>,
+    http: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct SearchRequest<'a> {
+    column_name: &'a str,
+    query: &'a str,
+    limit: u32,
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    status: String,
+    data: Option<T>,
+    message: Option<String>,
+}
+
+/// A single search hit as returned over HTTP.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteSearchResult {
+    pub content: String,
+    pub key: u64,
+    pub score: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteSearchResults {
+    pub results: Vec<RemoteSearchResult>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteCollection {
+    pub name: String,
+    pub index_columns: Vec<String>,
+}
+
+impl LetsearchClient {
+    /// * `base_url` – server root, e.g. `"http://localhost:7898"`.
+    /// * `api_key`  – sent as a bearer token when the server requires one.
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn get_collection(&self, name: &str) -> anyhow::Result<RemoteCollection> {
+        let request = self
+            .http
+            .get(format!("{}/collections/{}", self.base_url, name));
+        self.send(request).await
+    }
+
+    pub async fn search(
+        &self,
+        collection_name: &str,
+        column: &str,
+        query: &str,
+        limit: u32,
+    ) -> anyhow::Result<RemoteSearchResults> {
+        let request = self
+            .http
+            .post(format!(
+                "{}/collections/{}/search",
+                self.base_url, collection_name
+            ))
+            .json(&SearchRequest {
+                column_name: column,
+                query,
+                limit,
+            });
+        self.send(request).await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<T> {
+        let request = match self.api_key.as_ref() {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        let envelope: Envelope<T> = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse response ({}): {}", status, e))?;
+
+        match envelope.data {
+            Some(data) if envelope.status == "ok" => Ok(data),
+            _ => Err(anyhow!(
+                "Request failed ({}): {}",
+                status,
+                envelope.message.unwrap_or_else(|| envelope.status.clone())
+            )),
+        }
+    }
+}
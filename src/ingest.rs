@@ -0,0 +1,550 @@
+use crate::collection::collection_utils::is_valid_identifier;
+use anyhow::anyhow;
+use percent_encoding::percent_decode_str;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// File formats that documents can be imported from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileFormat {
+    Jsonl,
+    Parquet,
+    /// Arrow IPC, including Feather v2.
+    Arrow,
+    Pdf,
+    Docx,
+    /// Plain text, imported as a single document.
+    Text,
+    Markdown,
+    Html,
+    /// Audio, imported as its transcript, see [`crate::transcribe`].
+    Audio,
+}
+
+impl FileFormat {
+    /// Detect the format from the file extension.
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "jsonl" | "ndjson" | "json" => Some(FileFormat::Jsonl),
+            "parquet" => Some(FileFormat::Parquet),
+            "arrow" | "feather" | "ipc" => Some(FileFormat::Arrow),
+            "pdf" => Some(FileFormat::Pdf),
+            "docx" => Some(FileFormat::Docx),
+            "txt" => Some(FileFormat::Text),
+            "md" | "markdown" => Some(FileFormat::Markdown),
+            "html" | "htm" => Some(FileFormat::Html),
+            "mp3" | "wav" | "m4a" | "flac" | "ogg" | "opus" => Some(FileFormat::Audio),
+            _ => None,
+        }
+    }
+
+    /// Whether files of this format are documents whose text is extracted
+    /// into rows, rather than tables of rows.
+    pub fn is_document(&self) -> bool {
+        matches!(
+            self,
+            FileFormat::Pdf
+                | FileFormat::Docx
+                | FileFormat::Text
+                | FileFormat::Markdown
+                | FileFormat::Html
+                | FileFormat::Audio
+        )
+    }
+
+    /// Detect the format of a local file from its first bytes.
+    pub fn sniff(path: &Path) -> anyhow::Result<Option<Self>> {
+        let mut header = [0u8; 8];
+        let read = File::open(path)?.read(&mut header)?;
+        let header = &header[..read];
+
+        let format = if header.starts_with(b"PAR1") {
+            Some(FileFormat::Parquet)
+        } else if header.starts_with(b"ARROW1") || header.starts_with(&[0xff, 0xff, 0xff, 0xff]) {
+            Some(FileFormat::Arrow)
+        } else if header.starts_with(b"%PDF") {
+            Some(FileFormat::Pdf)
+        } else if header
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|b| *b == b'{')
+        {
+            Some(FileFormat::Jsonl)
+        } else {
+            None
+        };
+        Ok(format)
+    }
+}
+
+/// A single file to import, with its detected format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputFile {
+    pub path: String,
+    pub format: FileFormat,
+}
+
+/// Extensions of SQLite files, imported as a [`DatabaseSource`]. Other
+/// files, such as `.db` ones that many formats use, are told by their header.
+const SQLITE_EXTENSIONS: [&str; 2] = ["sqlite", "sqlite3"];
+
+/// Whether `path` is a SQLite file, by its extension or its header.
+fn is_sqlite_path(path: &Path) -> bool {
+    let by_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SQLITE_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    by_extension || is_sqlite_file(path)
+}
+
+fn is_sqlite_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| &header == b"SQLite format 3\0")
+}
+
+fn is_remote(path: &str) -> bool {
+    path.contains("://")
+}
+
+fn has_glob_chars(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+fn detect_local(path: &Path) -> anyhow::Result<Option<FileFormat>> {
+    match path.to_str().and_then(FileFormat::from_extension) {
+        Some(format) => Ok(Some(format)),
+        None => FileFormat::sniff(path),
+    }
+}
+
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_dir(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expand the paths given on the command line into the list of files to import.
+///
+/// * Local directories are walked recursively. Files whose format cannot be
+///   detected are skipped there, since directories often hold other files too.
+/// * Local glob patterns are expanded, and each match is detected on its own.
+/// * Local files are detected by extension, falling back to their first bytes.
+/// * Remote paths such as `hf://` are passed through and detected by extension.
+///   DuckDB resolves any glob in them.
+pub fn expand_inputs(inputs: &[String]) -> anyhow::Result<Vec<InputFile>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        if is_remote(input) {
+            let format = FileFormat::from_extension(input).ok_or_else(|| {
+                anyhow!("Cannot detect the format of '{}' from its extension", input)
+            })?;
+            files.push(InputFile {
+                path: input.clone(),
+                format,
+            });
+            continue;
+        }
+
+        let path = Path::new(input);
+        let mut candidates = Vec::new();
+        let skip_unknown = if path.is_dir() {
+            collect_dir(path, &mut candidates)?;
+            true
+        } else if has_glob_chars(input) {
+            for entry in glob::glob(input)? {
+                let entry = entry?;
+                if entry.is_dir() {
+                    collect_dir(&entry, &mut candidates)?;
+                } else {
+                    candidates.push(entry);
+                }
+            }
+            if candidates.is_empty() {
+                return Err(anyhow!("No files match '{}'", input));
+            }
+            true
+        } else {
+            candidates.push(path.to_path_buf());
+            false
+        };
+
+        for candidate in candidates {
+            let path_str = candidate.to_string_lossy().to_string();
+            match detect_local(&candidate)? {
+                Some(format) => files.push(InputFile {
+                    path: path_str,
+                    format,
+                }),
+                None if !skip_unknown && is_sqlite_file(&candidate) => {
+                    return Err(anyhow!(
+                        "'{}' is a SQLite database, import it with --source {} and --source-table or --source-query",
+                        path_str,
+                        path_str
+                    ))
+                }
+                None if skip_unknown => log::debug!("Skipping '{}': unknown format", path_str),
+                None => return Err(anyhow!("Cannot detect the format of '{}'", path_str)),
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Databases that documents can be copied from with DuckDB's scanners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DatabaseKind {
+    Postgres,
+    Mysql,
+    /// A local SQLite file.
+    Sqlite,
+}
+
+/// Rows of a [`DatabaseSource`] to copy.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceRows {
+    /// A table, optionally qualified with its schema, e.g. `public.articles`.
+    Table(String),
+    /// A query whose result is copied, run by the database itself, or by
+    /// DuckDB over the tables of SQLite files.
+    Query(String),
+}
+
+/// A table or query of a Postgres, MySQL or SQLite database to import
+/// documents from, e.g. an application's database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseSource {
+    /// `postgres://` or `mysql://` URL of the database, with credentials, or
+    /// the path of a SQLite file, optionally as a `sqlite://` URL.
+    pub url: String,
+    pub kind: DatabaseKind,
+    pub rows: SourceRows,
+}
+
+impl DatabaseSource {
+    pub fn new(url: &str, rows: SourceRows) -> anyhow::Result<Self> {
+        let kind = match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => DatabaseKind::Postgres,
+            Some("mysql") => DatabaseKind::Mysql,
+            Some("sqlite") => DatabaseKind::Sqlite,
+            None if is_sqlite_path(Path::new(url)) => DatabaseKind::Sqlite,
+            _ => {
+                return Err(anyhow!(
+                "Unsupported source '{}', expected a postgres:// or mysql:// URL or a SQLite file",
+                Self::redact(url)
+            ))
+            }
+        };
+        if let SourceRows::Table(table) = &rows {
+            if !table.split('.').all(is_valid_identifier) {
+                return Err(anyhow!("Invalid source table '{}'", table));
+            }
+        }
+        Ok(Self {
+            url: url.to_string(),
+            kind,
+            rows,
+        })
+    }
+
+    /// DuckDB extension reading this kind of database.
+    pub fn extension(&self) -> &'static str {
+        match self.kind {
+            DatabaseKind::Postgres => "postgres",
+            DatabaseKind::Mysql => "mysql",
+            DatabaseKind::Sqlite => "sqlite",
+        }
+    }
+
+    /// `url` without its password, for messages and logs.
+    pub fn redact(url: &str) -> String {
+        match reqwest::Url::parse(url) {
+            Ok(mut parsed) if parsed.password().is_some() => {
+                let _ = parsed.set_password(Some("***"));
+                parsed.to_string()
+            }
+            _ => url.to_string(),
+        }
+    }
+
+    /// Connection string of the database as DuckDB's scanner expects it:
+    /// the URL itself for Postgres, `key=value` pairs for MySQL and the
+    /// file's path for SQLite. The parts of a MySQL URL are percent-decoded,
+    /// as the pairs hold them as they are.
+    fn connection_string(&self) -> anyhow::Result<String> {
+        match self.kind {
+            DatabaseKind::Postgres => return Ok(self.url.clone()),
+            DatabaseKind::Sqlite => {
+                let path = self.url.strip_prefix("sqlite://").unwrap_or(&self.url);
+                return Ok(path.to_string());
+            }
+            DatabaseKind::Mysql => {}
+        }
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| anyhow!("Invalid source '{}': {}", Self::redact(&self.url), e))?;
+        let decode = |part: &str, name: &str| -> anyhow::Result<String> {
+            percent_decode_str(part)
+                .decode_utf8()
+                .map(|decoded| mysql_value(&decoded))
+                .map_err(|e| {
+                    anyhow!(
+                        "Invalid {} in source '{}': {}",
+                        name,
+                        Self::redact(&self.url),
+                        e
+                    )
+                })
+        };
+        let mut pairs = Vec::new();
+        if let Some(host) = url.host_str() {
+            pairs.push(format!("host={}", decode(host, "host")?));
+        }
+        if let Some(port) = url.port() {
+            pairs.push(format!("port={}", port));
+        }
+        if !url.username().is_empty() {
+            pairs.push(format!("user={}", decode(url.username(), "user")?));
+        }
+        if let Some(password) = url.password() {
+            pairs.push(format!("passwd={}", decode(password, "password")?));
+        }
+        let database = url.path().trim_start_matches('/');
+        if !database.is_empty() {
+            pairs.push(format!("database={}", decode(database, "database")?));
+        }
+        Ok(pairs.join(" "))
+    }
+
+    /// Statement attaching the database read-only as `alias`.
+    pub fn attach_sql(&self, alias: &str) -> anyhow::Result<String> {
+        Ok(format!(
+            "ATTACH '{}' AS {} (TYPE {}, READ_ONLY);",
+            self.connection_string()?.replace('\'', "''"),
+            alias,
+            self.extension()
+        ))
+    }
+
+    /// Schema that unqualified tables of queries are looked up in while
+    /// copying from the database attached as `alias`. SQLite has no
+    /// pass-through queries, so DuckDB runs them over the file's tables.
+    pub fn search_path(&self, alias: &str) -> Option<String> {
+        match (&self.kind, &self.rows) {
+            (DatabaseKind::Sqlite, SourceRows::Query(_)) => Some(format!("{}.main", alias)),
+            _ => None,
+        }
+    }
+
+    /// Query selecting the rows to copy from the database attached as
+    /// `alias`. With a `cursor` column of `table`, only rows whose cursor is
+    /// past the largest one already in `table` are selected. `table` should
+    /// be qualified with its catalog, as [`DatabaseSource::search_path`] may
+    /// hide it otherwise.
+    ///
+    /// The cursor must be unique and only grow as rows are written: a row
+    /// committed after a sync with the same value as the largest one synced,
+    /// e.g. in the same `updated_at` second, is never selected.
+    pub fn select_sql(&self, alias: &str, cursor: Option<(&str, &str)>) -> String {
+        let from = match &self.rows {
+            SourceRows::Table(table) => format!("{}.{}", alias, table),
+            SourceRows::Query(query) if self.kind == DatabaseKind::Sqlite => {
+                format!("({})", query)
+            }
+            SourceRows::Query(query) => format!(
+                "{}_query('{}', '{}')",
+                self.extension(),
+                alias,
+                query.replace('\'', "''")
+            ),
+        };
+        match cursor {
+            Some((column, table)) => format!(
+                "SELECT * FROM {from} WHERE (SELECT MAX({column}) FROM {table}) IS NULL \
+                 OR {column} > (SELECT MAX({column}) FROM {table})"
+            ),
+            None => format!("SELECT * FROM {}", from),
+        }
+    }
+}
+
+/// Value of a MySQL connection string pair, in double quotes so that spaces
+/// and `=` in it do not end it, with `\` and `"` escaped by a backslash.
+fn mysql_value(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            FileFormat::from_extension("a/b.jsonl"),
+            Some(FileFormat::Jsonl)
+        );
+        assert_eq!(
+            FileFormat::from_extension("B.PARQUET"),
+            Some(FileFormat::Parquet)
+        );
+        assert_eq!(
+            FileFormat::from_extension("x.feather"),
+            Some(FileFormat::Arrow)
+        );
+        assert_eq!(
+            FileFormat::from_extension("hf://datasets/a/**/*.parquet"),
+            Some(FileFormat::Parquet)
+        );
+        assert_eq!(
+            FileFormat::from_extension("reports/q3.docx"),
+            Some(FileFormat::Docx)
+        );
+        assert_eq!(
+            FileFormat::from_extension("notes.txt"),
+            Some(FileFormat::Text)
+        );
+        assert_eq!(
+            FileFormat::from_extension("wiki/Home.markdown"),
+            Some(FileFormat::Markdown)
+        );
+        assert_eq!(
+            FileFormat::from_extension("site/index.htm"),
+            Some(FileFormat::Html)
+        );
+        assert_eq!(
+            FileFormat::from_extension("podcasts/episode-12.MP3"),
+            Some(FileFormat::Audio)
+        );
+        assert_eq!(FileFormat::from_extension("README"), None);
+    }
+
+    #[test]
+    fn test_expand_directory_and_sniffing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.jsonl"), "{\"text\": \"a\"}\n").unwrap();
+        std::fs::write(dir.join("nested").join("data"), "PAR1....").unwrap();
+        std::fs::write(dir.join("notes.txt"), "a document").unwrap();
+        std::fs::write(dir.join("server.log"), "not a document").unwrap();
+
+        let files = expand_inputs(&[dir.to_string_lossy().to_string()]).unwrap();
+        let formats: Vec<FileFormat> = files.iter().map(|f| f.format).collect();
+        assert_eq!(
+            formats,
+            vec![FileFormat::Jsonl, FileFormat::Parquet, FileFormat::Text]
+        );
+
+        let pattern = dir.join("*.jsonl").to_string_lossy().to_string();
+        assert_eq!(expand_inputs(&[pattern]).unwrap().len(), 1);
+
+        let unknown = dir.join("server.log").to_string_lossy().to_string();
+        assert!(expand_inputs(&[unknown]).is_err());
+    }
+
+    #[test]
+    fn test_database_source() {
+        let table = SourceRows::Table(String::from("public.articles"));
+        let postgres = DatabaseSource::new("postgres://app:secret@db/app", table).unwrap();
+        assert_eq!(postgres.kind, DatabaseKind::Postgres);
+        assert_eq!(
+            postgres.attach_sql("src").unwrap(),
+            "ATTACH 'postgres://app:secret@db/app' AS src (TYPE postgres, READ_ONLY);"
+        );
+        assert_eq!(
+            postgres.select_sql("src", None),
+            "SELECT * FROM src.public.articles"
+        );
+        assert_eq!(
+            postgres.select_sql("src", Some(("updated_at", "docs"))),
+            "SELECT * FROM src.public.articles WHERE (SELECT MAX(updated_at) FROM docs) IS NULL \
+             OR updated_at > (SELECT MAX(updated_at) FROM docs)"
+        );
+
+        let query = SourceRows::Query(String::from("SELECT * FROM posts WHERE status = 'live'"));
+        let mysql = DatabaseSource::new("mysql://app:secret@db:3306/blog", query).unwrap();
+        assert_eq!(
+            mysql.attach_sql("src").unwrap(),
+            r#"ATTACH 'host="db" port=3306 user="app" passwd="secret" database="blog"' AS src (TYPE mysql, READ_ONLY);"#
+        );
+        // Percent-encoded parts are decoded, and quoted so that spaces, `=`
+        // and quotes stay in their value.
+        let table = SourceRows::Table(String::from("posts"));
+        let encoded =
+            DatabaseSource::new("mysql://app:p%40ss%20w%3Drd%22%5C@db/blog", table).unwrap();
+        assert_eq!(
+            encoded.connection_string().unwrap(),
+            r#"host="db" user="app" passwd="p@ss w=rd\"\\" database="blog""#
+        );
+        assert_eq!(
+            mysql.select_sql("src", None),
+            "SELECT * FROM mysql_query('src', 'SELECT * FROM posts WHERE status = ''live''')"
+        );
+
+        assert_eq!(
+            DatabaseSource::redact("postgres://app:secret@db/app"),
+            "postgres://app:***@db/app"
+        );
+        let invalid = SourceRows::Table(String::from("articles; DROP TABLE x"));
+        assert!(DatabaseSource::new("postgres://db/app", invalid).is_err());
+        let mongodb = SourceRows::Table(String::from("articles"));
+        assert!(DatabaseSource::new("mongodb://db/app", mongodb).is_err());
+    }
+
+    #[test]
+    fn test_sqlite_source() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let file = dir.join("corpus");
+        std::fs::write(&file, b"SQLite format 3\0rest of the header").unwrap();
+        let path = file.to_string_lossy().to_string();
+
+        let query = SourceRows::Query(String::from("SELECT * FROM docs"));
+        let sqlite = DatabaseSource::new(&path, query).unwrap();
+        assert_eq!(sqlite.kind, DatabaseKind::Sqlite);
+        assert_eq!(
+            sqlite.attach_sql("src").unwrap(),
+            format!("ATTACH '{}' AS src (TYPE sqlite, READ_ONLY);", path)
+        );
+        assert_eq!(sqlite.search_path("src").unwrap(), "src.main");
+        assert_eq!(
+            sqlite.select_sql("src", None),
+            "SELECT * FROM (SELECT * FROM docs)"
+        );
+
+        let table = SourceRows::Table(String::from("docs"));
+        let by_url = DatabaseSource::new("sqlite://data/app.sqlite3", table).unwrap();
+        assert_eq!(
+            by_url.attach_sql("src").unwrap(),
+            "ATTACH 'data/app.sqlite3' AS src (TYPE sqlite, READ_ONLY);"
+        );
+        assert_eq!(by_url.search_path("src"), None);
+
+        // `.db` files are only SQLite when their header says so.
+        let other_db = dir.join("cache.db");
+        std::fs::write(&other_db, b"Berkeley DB, not SQLite").unwrap();
+        let table = || SourceRows::Table(String::from("docs"));
+        assert!(DatabaseSource::new(&other_db.to_string_lossy(), table()).is_err());
+        let sqlite_db = dir.join("app.db");
+        std::fs::write(&sqlite_db, b"SQLite format 3\0rest of the header").unwrap();
+        let sqlite = DatabaseSource::new(&sqlite_db.to_string_lossy(), table()).unwrap();
+        assert_eq!(sqlite.kind, DatabaseKind::Sqlite);
+
+        let error = expand_inputs(&[path]).unwrap_err().to_string();
+        assert!(error.contains("--source"), "{}", error);
+    }
+}
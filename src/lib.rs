@@ -1,8 +1,45 @@
+//! letsearch indexes documents into vector search collections and serves
+//! them. Besides the `letsearch` binary, the crate can be embedded in other
+//! Rust projects through [`Client`], without going through the HTTP layer:
+//!
+//! ```no_run
+//! use letsearch::{Client, SearchOptions};
+//!
+//! let client = Client::new()?;
+//! let collection = client.collection_blocking("my_collection")?;
+//! let results = collection.search_blocking("what is letsearch?", &SearchOptions::default())?;
+//! for result in results.results {
+//!     println!("{:.4} {}", result.score, result.content);
+//! }
+//! # Ok::<(), letsearch::error::ProjectError>(())
+//! ```
+//!
+//! The other modules are public for the binary and the benchmarks, and may
+//! change between releases.
+
 pub mod actors;
 pub mod chunker;
+pub mod client;
 pub mod collection;
+pub mod config;
+pub mod convert;
+pub mod crawl;
+pub mod documents;
 pub mod error;
+pub mod estimate;
+pub mod eval;
+pub mod export;
 pub mod hf_ops;
+pub mod http_client;
+pub mod ingest;
+pub mod loadtest;
+pub mod logging;
 pub mod model;
+pub mod output;
 pub mod pdf;
+pub mod rate_limit;
 pub mod serve;
+pub mod storage;
+pub mod transcribe;
+
+pub use client::{Client, ClientConfig, Collection, SearchOptions};
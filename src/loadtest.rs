@@ -0,0 +1,235 @@
+use crate::http_client::LetsearchClient;
+use anyhow::anyhow;
+use log::info;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Options for [`run_loadtest`].
+#[derive(Clone, Debug)]
+pub struct LoadTestConfig {
+    pub url: String,
+    pub collection_name: String,
+    /// Column to search. Defaults to the first index column of the collection.
+    pub column: Option<String>,
+    /// Queries sent round-robin by all workers.
+    pub queries: Vec<String>,
+    /// Highest number of concurrent requests. The test ramps up to it in
+    /// powers of two to trace the saturation curve.
+    pub concurrency: usize,
+    /// Total test duration, split evenly across concurrency levels.
+    pub duration: Duration,
+    pub limit: u32,
+    pub api_key: Option<String>,
+}
+
+/// Results of running the test at a single concurrency level.
+#[derive(Debug, Clone)]
+pub struct LevelReport {
+    pub concurrency: usize,
+    pub requests: usize,
+    pub errors: usize,
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LevelReport {
+    pub fn throughput(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+}
+
+/// Parse durations such as `60s`, `500ms`, `2m` or a plain number of seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", value))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration unit in `{}`", value)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration `{}` is too long", value))
+}
+
+/// Return the `q`-th percentile (0.0 - 1.0) of sorted latencies.
+fn percentile(sorted: &[Duration], q: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Concurrency levels of the ramp: powers of two up to and including `max`.
+fn concurrency_levels(max: usize) -> Vec<usize> {
+    let mut levels: Vec<usize> = std::iter::successors(Some(1usize), |c| c.checked_mul(2))
+        .take_while(|c| *c < max)
+        .collect();
+    levels.push(max.max(1));
+    levels
+}
+
+async fn run_level(
+    client: &LetsearchClient,
+    config: &LoadTestConfig,
+    column: &str,
+    concurrency: usize,
+    duration: Duration,
+) -> LevelReport {
+    let next_query = Arc::new(AtomicUsize::new(0));
+    let queries = Arc::new(config.queries.clone());
+    let start = Instant::now();
+    let deadline = start + duration;
+
+    let workers: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let collection_name = config.collection_name.clone();
+            let column = column.to_string();
+            let limit = config.limit;
+            let queries = queries.clone();
+            let next_query = next_query.clone();
+
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut errors = 0;
+                while Instant::now() < deadline {
+                    let i = next_query.fetch_add(1, Ordering::Relaxed) % queries.len();
+                    let sent = Instant::now();
+                    let result = client
+                        .search(&collection_name, &column, &queries[i], limit)
+                        .await;
+                    latencies.push(sent.elapsed());
+                    if result.is_err() {
+                        errors += 1;
+                    }
+                }
+                (latencies, errors)
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for (worker_latencies, worker_errors) in futures::future::join_all(workers)
+        .await
+        .into_iter()
+        .flatten()
+    {
+        latencies.extend(worker_latencies);
+        errors += worker_errors;
+    }
+    let elapsed = start.elapsed();
+    latencies.sort();
+
+    LevelReport {
+        concurrency,
+        requests: latencies.len(),
+        errors,
+        elapsed,
+        p50: percentile(&latencies, 0.50),
+        p90: percentile(&latencies, 0.90),
+        p99: percentile(&latencies, 0.99),
+        max: latencies.last().copied().unwrap_or_default(),
+    }
+}
+
+/// Send search requests to a running server at increasing concurrency and
+/// report latency percentiles, error rates and throughput for every level.
+pub async fn run_loadtest(config: LoadTestConfig) -> anyhow::Result<Vec<LevelReport>> {
+    if config.queries.is_empty() {
+        return Err(anyhow!("At least one query is required"));
+    }
+
+    let client = LetsearchClient::new(&config.url, config.api_key.clone());
+    let column = match config.column.clone() {
+        Some(column) => column,
+        None => client
+            .get_collection(&config.collection_name)
+            .await?
+            .index_columns
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Collection '{}' has no index columns",
+                    config.collection_name
+                )
+            })?,
+    };
+
+    // Fail fast on a wrong URL, collection or API key instead of reporting a
+    // 100% error rate after the full duration.
+    client
+        .search(
+            &config.collection_name,
+            &column,
+            &config.queries[0],
+            config.limit,
+        )
+        .await?;
+
+    let levels = concurrency_levels(config.concurrency);
+    let level_duration = config.duration / levels.len() as u32;
+    let mut reports = Vec::with_capacity(levels.len());
+    for concurrency in levels {
+        info!(
+            "Running {} concurrent clients for {:?}",
+            concurrency, level_duration
+        );
+        reports.push(run_level(&client, &config, &column, concurrency, level_duration).await);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("60s").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("5").unwrap(), Duration::from_secs(5));
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("99999999999999999999999h").is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 0.5), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies, 1.0), Duration::from_millis(100));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_concurrency_levels() {
+        assert_eq!(concurrency_levels(1), vec![1]);
+        assert_eq!(concurrency_levels(8), vec![1, 2, 4, 8]);
+        assert_eq!(concurrency_levels(10), vec![1, 2, 4, 8, 10]);
+    }
+}
@@ -0,0 +1,489 @@
+use env_logger::fmt::Formatter;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+use std::time::Instant;
+use tracing::span::{Attributes, Id, Record as SpanValues};
+use tracing::subscriber::Interest;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Filter, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Log filter used until it is changed, in `RUST_LOG` syntax: info, with the
+/// warnings of ONNX Runtime execution providers silenced.
+pub const DEFAULT_FILTER: &str = "info,ort::execution_providers=error";
+
+/// Target of the timings of closed spans, logged at debug level, e.g.
+/// enabled with `info,letsearch::trace=debug`.
+pub const SPAN_TARGET: &str = "letsearch::trace";
+
+tokio::task_local! {
+    /// Id of the HTTP request being handled, added to the records logged
+    /// while handling it.
+    pub static REQUEST_ID: String;
+}
+
+/// How log records are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// `[timestamp LEVEL] message` lines for people.
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors such as Loki or ELK.
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "Unknown log format '{}', expected one of text, json",
+                s
+            )),
+        }
+    }
+}
+
+/// Logger whose filter can be replaced while the process runs, e.g. to
+/// debug a module of a running server.
+struct ReloadableLogger {
+    format: LogFormat,
+    /// Current filter and the logger built from it.
+    inner: RwLock<(String, env_logger::Logger)>,
+}
+
+static LOGGER: OnceLock<ReloadableLogger> = OnceLock::new();
+
+/// A record as a JSON object with its timestamp, level, target, message
+/// and, when logged while handling a request, the request's id.
+fn json_line(record: &Record, timestamp: &str) -> String {
+    let mut line = serde_json::json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Ok(request_id) = REQUEST_ID.try_with(|id| id.clone()) {
+        line["request_id"] = request_id.into();
+    }
+    line.to_string()
+}
+
+fn build_logger(filter: &str, format: LogFormat) -> env_logger::Logger {
+    env_logger::Builder::new()
+        .format(move |buf: &mut Formatter, record: &Record| match format {
+            LogFormat::Text => writeln!(
+                buf,
+                "[{} {}] {}",
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                record.level(),
+                record.args()
+            ),
+            LogFormat::Json => {
+                let timestamp =
+                    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                writeln!(buf, "{}", json_line(record, &timestamp))
+            }
+        })
+        .parse_filters(filter)
+        .build()
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.1.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.1.log(record)
+    }
+
+    fn flush(&self) {
+        let inner = self.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.1.flush()
+    }
+}
+
+/// Fields of a span or an event, in the order they were recorded.
+#[derive(Default)]
+struct Fields(Vec<(&'static str, String)>);
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// ` key=value` pairs of every field but `skip` and the `otel.` ones,
+    /// which name spans for OTLP exporters.
+    fn pairs(&self, skip: &str) -> String {
+        self.0
+            .iter()
+            .filter(|(field, _)| *field != skip && !field.starts_with("otel."))
+            .map(|(field, value)| format!(" {}={}", field, value))
+            .collect()
+    }
+}
+
+impl tracing::field::Visit for Fields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.push((field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+        self.0.push((field.name(), format!("{:?}", value)));
+    }
+}
+
+/// Timing and request id of an open span, kept in its extensions.
+struct SpanData {
+    /// Names of the span's ancestors and its own, outermost first.
+    path: String,
+    fields: Fields,
+    /// `request_id` field of the span or of its closest ancestor with one.
+    request_id: Option<String>,
+    start: Instant,
+}
+
+/// Layer that forwards the events of `tracing` to the logger and logs how
+/// long each span was open when it closes, under [`SPAN_TARGET`]. Spans
+/// inherit the request id of their parent, so the stages of a request
+/// handled by different actors can be told apart in the logs.
+pub struct SpanLogger;
+
+fn log_level(level: &tracing::Level) -> log::Level {
+    match *level {
+        tracing::Level::ERROR => log::Level::Error,
+        tracing::Level::WARN => log::Level::Warn,
+        tracing::Level::INFO => log::Level::Info,
+        tracing::Level::DEBUG => log::Level::Debug,
+        tracing::Level::TRACE => log::Level::Trace,
+    }
+}
+
+/// Line logged when a span closes, e.g.
+/// `span request:search closed in 0.012000 secs request_id=abc collection=books`.
+fn span_line(path: &str, secs: f64, request_id: Option<&str>, fields: &Fields) -> String {
+    let request_id = request_id.map_or_else(String::new, |id| format!(" request_id={}", id));
+    format!(
+        "span {} closed in {:.6} secs{}{}",
+        path,
+        secs,
+        request_id,
+        fields.pairs("request_id")
+    )
+}
+
+/// Filter of the [`SpanLogger`], following the log filter: events pass when
+/// the logger takes their records, and spans when their timings are logged.
+struct LogFilter;
+
+impl<S> Filter<S> for LogFilter {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        if metadata.is_span() {
+            return log::log_enabled!(target: SPAN_TARGET, log::Level::Debug);
+        }
+        let level = log_level(metadata.level());
+        level <= log::max_level()
+            && log::logger().enabled(
+                &Metadata::builder()
+                    .level(level)
+                    .target(metadata.target())
+                    .build(),
+            )
+    }
+
+    fn callsite_enabled(&self, _metadata: &'static tracing::Metadata<'static>) -> Interest {
+        // The log filter can change while the process runs.
+        Interest::sometimes()
+    }
+}
+
+impl<S> Layer<S> for SpanLogger
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        let parent = span.parent();
+        let parent = parent.as_ref().map(|parent| parent.extensions());
+        let parent = parent
+            .as_ref()
+            .and_then(|extensions| extensions.get::<SpanData>());
+        let path = match parent {
+            Some(parent) => format!("{}:{}", parent.path, span.name()),
+            None => span.name().to_string(),
+        };
+        let request_id = fields
+            .get("request_id")
+            .map(String::from)
+            .or_else(|| parent.and_then(|parent| parent.request_id.clone()));
+        span.extensions_mut().insert(SpanData {
+            path,
+            fields,
+            request_id,
+            start: Instant::now(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &SpanValues<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            values.record(&mut data.fields);
+            if let Some(request_id) = data.fields.get("request_id") {
+                data.request_id = Some(request_id.to_string());
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = fields.get("message").unwrap_or_default();
+        let log = || {
+            log::logger().log(
+                &Record::builder()
+                    .args(format_args!("{}{}", message, fields.pairs("message")))
+                    .level(log_level(metadata.level()))
+                    .target(metadata.target())
+                    .module_path(metadata.module_path())
+                    .file(metadata.file())
+                    .line(metadata.line())
+                    .build(),
+            )
+        };
+
+        // Events in an actor have the request id of their span only.
+        let request_id = ctx
+            .event_span(event)
+            .and_then(|span| span.extensions().get::<SpanData>()?.request_id.clone())
+            .filter(|_| REQUEST_ID.try_with(|_| ()).is_err());
+        match request_id {
+            Some(request_id) => REQUEST_ID.sync_scope(request_id, log),
+            None => log(),
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+
+        let line = span_line(
+            &data.path,
+            data.start.elapsed().as_secs_f64(),
+            data.request_id.as_deref(),
+            &data.fields,
+        );
+        let log = || log::debug!(target: SPAN_TARGET, "{}", line);
+        match data.request_id {
+            Some(request_id) => REQUEST_ID.sync_scope(request_id, log),
+            None => log(),
+        }
+    }
+}
+
+/// Layer exporting the spans of letsearch, and the events in them, to the
+/// OTLP/HTTP collector at `endpoint`, e.g. `http://localhost:4318`. Spans
+/// are sent in batches from the Tokio runtime, which must be running.
+#[cfg(feature = "otlp")]
+fn otlp_layer(endpoint: &str) -> anyhow::Result<impl Layer<tracing_subscriber::Registry>> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        // The server runs on a current-thread runtime, on which shutting
+        // down would wait for an export that never gets to run, so spans are
+        // exported from a thread of their own.
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::TokioCurrentThread)
+        .with_resource(opentelemetry_sdk::Resource::new([
+            opentelemetry::KeyValue::new("service.name", "letsearch"),
+        ]))
+        .build();
+    let tracer = provider.tracer("letsearch");
+    opentelemetry::global::set_tracer_provider(provider);
+    // Spans of dependencies are left out, the HTTP client of the exporter
+    // among them.
+    let letsearch = tracing_subscriber::filter::filter_fn(|metadata| {
+        metadata.target().starts_with("letsearch") && *metadata.level() <= tracing::Level::INFO
+    });
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(letsearch))
+}
+
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer(_endpoint: &str) -> anyhow::Result<tracing_subscriber::layer::Identity> {
+    Err(anyhow::anyhow!(
+        "Spans cannot be exported over OTLP: letsearch was built without the otlp feature"
+    ))
+}
+
+/// Install the logger with [`DEFAULT_FILTER`], writing records in `format`,
+/// and the [`SpanLogger`] for the spans and events of `tracing`, which share
+/// the logger's filter. With an `otlp_endpoint`, spans are exported to it
+/// too, whatever the filter.
+pub fn init(format: LogFormat, otlp_endpoint: Option<&str>) -> anyhow::Result<()> {
+    let logger = LOGGER.get_or_init(|| {
+        let logger = build_logger(DEFAULT_FILTER, format);
+        log::set_max_level(logger.filter());
+        ReloadableLogger {
+            format,
+            inner: RwLock::new((DEFAULT_FILTER.to_string(), logger)),
+        }
+    });
+    log::set_logger(logger).expect("Failed to install the logger");
+    let otlp = otlp_endpoint.map(otlp_layer).transpose()?;
+    let subscriber = tracing_subscriber::registry()
+        .with(otlp)
+        .with(SpanLogger.with_filter(LogFilter));
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to install the span logger");
+    Ok(())
+}
+
+/// Send the spans not exported yet, before the process exits.
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Current log filter, or `None` when the logger was not installed with
+/// [`init`].
+pub fn filter() -> Option<String> {
+    LOGGER.get().map(|logger| {
+        let inner = logger.inner.read().unwrap_or_else(|e| e.into_inner());
+        inner.0.clone()
+    })
+}
+
+/// Replace the log filter, given in `RUST_LOG` syntax, e.g.
+/// `info,letsearch::actors::collection_actor=debug`.
+pub fn set_filter(filter: &str) -> anyhow::Result<()> {
+    let logger = LOGGER
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("The logger was not installed"))?;
+    validate_filter(filter)?;
+
+    let replacement = build_logger(filter, logger.format);
+    let level = replacement.filter();
+    *logger.inner.write().unwrap_or_else(|e| e.into_inner()) = (filter.to_string(), replacement);
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Check a filter before it is applied: env_logger itself ignores invalid
+/// directives with a warning on stderr.
+fn validate_filter(filter: &str) -> anyhow::Result<()> {
+    if filter.trim().is_empty() {
+        return Err(anyhow::anyhow!("The log filter is empty"));
+    }
+    for directive in filter.split(',').map(str::trim) {
+        let level = match directive.split_once('=') {
+            Some((module, level)) if !module.trim().is_empty() => level,
+            Some(_) => return Err(anyhow::anyhow!("Invalid log directive '{}'", directive)),
+            // A bare directive is either a level or a module logged at
+            // every level.
+            None => continue,
+        };
+        level.trim().parse::<LevelFilter>().map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid log level '{}', expected one of off, error, warn, info, debug, trace",
+                level.trim()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_filter() {
+        assert!(validate_filter(DEFAULT_FILTER).is_ok());
+        assert!(validate_filter("debug").is_ok());
+        assert!(validate_filter("info, letsearch::collection=TRACE").is_ok());
+        assert!(validate_filter("letsearch::serve").is_ok());
+        assert!(validate_filter("").is_err());
+        assert!(validate_filter("letsearch=verbose").is_err());
+        assert!(validate_filter("=debug").is_err());
+    }
+
+    #[test]
+    fn test_json_line() {
+        let record = |message: &str| {
+            let line = json_line(
+                &Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(log::Level::Warn)
+                    .target("letsearch::serve")
+                    .build(),
+                "2024-01-01T00:00:00.000Z",
+            );
+            serde_json::from_str::<serde_json::Value>(&line).unwrap()
+        };
+        let line = record("a \"quoted\" message");
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], "letsearch::serve");
+        assert_eq!(line["message"], "a \"quoted\" message");
+        assert!(line.get("request_id").is_none());
+
+        let line = REQUEST_ID.sync_scope(String::from("abc"), || record("handled"));
+        assert_eq!(line["request_id"], "abc");
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    }
+
+    #[test]
+    fn test_span_line() {
+        let mut fields = Fields::default();
+        fields.0.push(("request_id", String::from("abc")));
+        fields.0.push(("collection", String::from("books")));
+        fields.0.push(("otel.kind", String::from("server")));
+        assert_eq!(
+            span_line("request:search", 0.012, Some("abc"), &fields),
+            "span request:search closed in 0.012000 secs request_id=abc collection=books"
+        );
+        assert_eq!(
+            span_line("inference", 0.5, None, &Fields::default()),
+            "span inference closed in 0.500000 secs"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::model::model_utils::{Embedder, Embeddings, ModelOutputDType};
+use crate::model::model_utils::{Backend, Embedder, Embeddings, ModelOutputDType};
 use async_trait::async_trait;
 use ndarray::Array2;
 use serde::{Deserialize, Serialize};
@@ -85,6 +85,10 @@ impl Embedder for GeminiEmbedder {
         Ok(ModelOutputDType::F32)
     }
 
+    fn backend(&self) -> Backend {
+        Backend::Gemini
+    }
+
     async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
         let model_full = format!("models/{}", self.model_name);
 
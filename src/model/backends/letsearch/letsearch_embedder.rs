@@ -0,0 +1,132 @@
+use crate::model::model_utils::{Backend, Embedder, Embeddings, ModelOutputDType};
+use async_trait::async_trait;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Embedding client for another letsearch instance's `POST /embed` endpoint.
+///
+/// This offloads inference to a machine with a GPU while the collection and
+/// its index live on the serving host. The remote instance must run the same
+/// model the collection was created with; [`LetsearchEmbedder::model_name`]
+/// reports which model it actually serves so callers can check. The output
+/// is always f32.
+pub struct LetsearchEmbedder {
+    base_url: String,
+    api_key: Option<String>,
+    model_name: String,
+    output_dim: i64,
+    client: reqwest::Client,
+}
+
+impl LetsearchEmbedder {
+    /// Create a new `LetsearchEmbedder` and probe the remote instance for the
+    /// output dimension and model name.
+    ///
+    /// * `base_url` – server root, e.g. `"http://gpu-box:7898"`.
+    /// * `api_key`  – sent as a bearer token when the remote requires one.
+    pub async fn connect(base_url: &str, api_key: Option<String>) -> anyhow::Result<Self> {
+        let mut embedder = Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model_name: String::new(),
+            output_dim: 0,
+            client: reqwest::Client::new(),
+        };
+
+        let probe = embedder.request(vec![String::from("letsearch")]).await?;
+        embedder.output_dim = probe.dim;
+        embedder.model_name = probe.model;
+
+        Ok(embedder)
+    }
+
+    /// Model served by the remote instance.
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    async fn request(&self, texts: Vec<String>) -> anyhow::Result<EmbedResponse> {
+        let url = format!("{}/embed", self.base_url);
+        let request = self.client.post(&url).json(&EmbedRequest { texts });
+        let request = match self.api_key.as_ref() {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Embedding request to {} failed: {}", url, e))?;
+        let status = response.status();
+        let envelope: Envelope = response.json().await.map_err(|e| {
+            anyhow::anyhow!("Failed to parse embedding response ({}): {}", status, e)
+        })?;
+
+        envelope.data.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Remote letsearch returned an error ({}): {}",
+                status,
+                envelope.message.unwrap_or_default()
+            )
+        })
+    }
+}
+
+// ---------- Serde helpers for the letsearch /embed API ----------
+
+#[derive(Serialize)]
+struct EmbedRequest {
+    texts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+    dim: i64,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    data: Option<EmbedResponse>,
+    message: Option<String>,
+}
+
+// -------------------------------------------------------------------------
+
+#[async_trait]
+impl Embedder for LetsearchEmbedder {
+    fn output_dim(&self) -> anyhow::Result<i64> {
+        Ok(self.output_dim)
+    }
+
+    fn output_dtype(&self) -> anyhow::Result<ModelOutputDType> {
+        Ok(ModelOutputDType::F32)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Remote
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
+        let expected = texts.len();
+        let response = self.request(texts).await?;
+        if response.embeddings.len() != expected || response.dim != self.output_dim {
+            return Err(anyhow::anyhow!(
+                "Remote letsearch returned {} vectors of dimension {} for {} inputs, expected dimension {}",
+                response.embeddings.len(),
+                response.dim,
+                expected,
+                self.output_dim
+            ));
+        }
+
+        let dim = self.output_dim as usize;
+        let flat: Vec<f32> = response.embeddings.into_iter().flatten().collect();
+        let result = Array2::from_shape_vec((expected, dim), flat)
+            .map_err(|e| anyhow::anyhow!("Malformed embeddings from remote letsearch: {}", e))?;
+
+        Ok(Embeddings::F32(Arc::new(result)))
+    }
+}
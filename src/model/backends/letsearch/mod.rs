@@ -0,0 +1 @@
+pub mod letsearch_embedder;
@@ -1,2 +1,4 @@
 pub mod gemini;
+pub mod letsearch;
 pub mod onnx;
+pub mod openai;
@@ -1,20 +1,25 @@
+use crate::model::backends::onnx::image_onnx::ImageEncoderONNX;
+use crate::model::metadata::ModelMetadata;
+use crate::model::model_utils::{
+    Backend, Embedder, Embeddings, ModelOutputDType, ModelTrait, ONNXModelTrait,
+};
 use async_trait::async_trait;
-use crate::model::model_utils::{Embedder, Embeddings, ModelOutputDType, ModelTrait, ONNXModelTrait};
 use half::f16;
-use log::info;
-use ndarray::Array2;
+use log::{info, warn};
+use ndarray::{Array2, ArrayView3, Axis};
 use ort::session::builder::GraphOptimizationLevel;
 use ort::session::Session;
 use ort::value::Tensor;
 #[cfg(feature = "cuda")]
 use ort::CUDAExecutionProvider;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::cell::UnsafeCell;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::Once;
 use std::thread::available_parallelism;
-use tokenizers::{PaddingParams, Tokenizer};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
 static ORT_INIT: Once = Once::new();
 
@@ -31,14 +36,14 @@ static ORT_INIT: Once = Once::new();
 ///    is genuinely mutated.
 /// 4. All callers (`predict_f16`, `predict_f32`) fully consume outputs into owned
 ///    `Array2` within the same scope, so there is no aliasing of output borrows.
-struct SyncUnsafeSession(UnsafeCell<Session>);
+pub(crate) struct SyncUnsafeSession(UnsafeCell<Session>);
 
 // SAFETY: OrtSession is thread-safe at the C level. Session is already Send + Sync.
 unsafe impl Send for SyncUnsafeSession {}
 unsafe impl Sync for SyncUnsafeSession {}
 
 impl SyncUnsafeSession {
-    fn new(session: Session) -> Self {
+    pub(crate) fn new(session: Session) -> Self {
         Self(UnsafeCell::new(session))
     }
 
@@ -48,38 +53,230 @@ impl SyncUnsafeSession {
     ///
     /// This is safe because ONNX Runtime handles thread safety at the C level,
     /// and all callers consume the outputs within the same scope (no aliasing).
-    fn get_mut(&self) -> &mut Session {
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) fn get_mut(&self) -> &mut Session {
         unsafe { &mut *self.0.get() }
     }
 }
 
+/// How ONNX Runtime sessions are created for a model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OnnxSessionOptions {
+    /// Number of sessions per model. Predict calls are spread over them in
+    /// turn, so that several batches run at once.
+    pub sessions: usize,
+    /// Threads used within one operator of a session. Defaults to the
+    /// available cores divided by the number of sessions.
+    pub intra_threads: Option<usize>,
+    /// Threads running independent operators of a session in parallel.
+    /// Values above 1 switch the session to parallel execution.
+    pub inter_threads: Option<usize>,
+}
+
+impl Default for OnnxSessionOptions {
+    fn default() -> Self {
+        Self {
+            sessions: 1,
+            intra_threads: None,
+            inter_threads: None,
+        }
+    }
+}
+
+impl OnnxSessionOptions {
+    fn intra_threads(&self) -> anyhow::Result<usize> {
+        match self.intra_threads {
+            Some(threads) => Ok(threads.max(1)),
+            None => Ok((available_parallelism()?.get() / self.sessions.max(1)).max(1)),
+        }
+    }
+}
+
+/// How the per-token embeddings of an output are reduced to one vector per
+/// text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pooling {
+    /// Mean of the embeddings of the tokens that are not padding.
+    #[default]
+    Mean,
+    /// Embedding of the first token.
+    Cls,
+}
+
+/// Model output holding the embeddings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ModelOutput {
+    index: usize,
+    /// Set for outputs with one embedding per token, `None` for outputs that
+    /// are already one embedding per text.
+    pooling: Option<Pooling>,
+}
+
+/// Outputs with one embedding per token, pooled when a model has no
+/// output with one embedding per text.
+const TOKEN_OUTPUTS: [&str; 2] = ["last_hidden_state", "token_embeddings"];
+
+/// Pick the output holding the embeddings among `outputs`, given as their
+/// names and ranks: the output called `name` when given, otherwise
+/// `sentence_embedding`, the only output with one embedding per text, or
+/// a pooled output with one embedding per token, in that order.
+fn select_output(
+    outputs: &[(&str, usize)],
+    name: Option<&str>,
+    pooling: Option<Pooling>,
+) -> anyhow::Result<ModelOutput> {
+    let available = || {
+        outputs
+            .iter()
+            .map(|(name, rank)| format!("{} (rank {})", name, rank))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let position = |name: &str| outputs.iter().position(|(output, _)| *output == name);
+
+    let index = match name {
+        Some(name) => position(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Model has no output '{}'. Its outputs are: {}",
+                name,
+                available()
+            )
+        })?,
+        None => {
+            let pooled: Vec<usize> = (0..outputs.len()).filter(|&i| outputs[i].1 == 2).collect();
+            match position("sentence_embedding") {
+                Some(index) => index,
+                None if pooled.len() == 1 => pooled[0],
+                None => TOKEN_OUTPUTS
+                    .iter()
+                    .find_map(|name| position(name))
+                    .or_else(|| (outputs.len() == 1).then_some(0))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Cannot tell which output of the model holds the embeddings. Its outputs are: {}. \
+                             Set \"output\" in the model's metadata.json to one of them",
+                            available()
+                        )
+                    })?,
+            }
+        }
+    };
+
+    let (name, rank) = outputs[index];
+    match rank {
+        2 => {
+            if pooling.is_some() {
+                warn!(
+                    "Output '{}' has one embedding per text, ignoring the pooling of metadata.json",
+                    name
+                );
+            }
+            Ok(ModelOutput {
+                index,
+                pooling: None,
+            })
+        }
+        3 => Ok(ModelOutput {
+            index,
+            pooling: Some(pooling.unwrap_or_default()),
+        }),
+        _ => Err(anyhow::anyhow!(
+            "Output '{}' has rank {}, but embeddings need rank 2, or rank 3 to be pooled. The model's outputs are: {}",
+            name,
+            rank,
+            available()
+        )),
+    }
+}
+
+/// Reduce the `(texts, tokens, dimensions)` token embeddings of a batch to
+/// one vector per text. `mask` is the attention mask of the batch.
+fn pool_tokens(tokens: ArrayView3<f32>, mask: &[i64], pooling: Pooling) -> Array2<f32> {
+    match pooling {
+        Pooling::Cls => tokens.index_axis(Axis(1), 0).to_owned(),
+        Pooling::Mean => {
+            let (texts, token_len, dim) = tokens.dim();
+            let mut pooled = Array2::<f32>::zeros((texts, dim));
+            for (i, mut row) in pooled.rows_mut().into_iter().enumerate() {
+                let mask = &mask[i * token_len..(i + 1) * token_len];
+                let mut count = 0f32;
+                for (token, _) in mask.iter().enumerate().filter(|(_, m)| **m != 0) {
+                    row += &tokens.slice(ndarray::s![i, token, ..]);
+                    count += 1.0;
+                }
+                row /= count.max(1.0);
+            }
+            pooled
+        }
+    }
+}
+
+/// Create a session for the ONNX file at `path`, configured by `options`.
+pub(crate) fn build_session(path: &Path, options: &OnnxSessionOptions) -> anyhow::Result<Session> {
+    ORT_INIT.call_once(|| {
+        let _ = ort::init().with_name("onnx_model").commit();
+    });
+
+    let mut builder = Session::builder()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?
+        .with_optimization_level(GraphOptimizationLevel::Level3)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?
+        .with_intra_threads(options.intra_threads()?)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    if let Some(inter_threads) = options.inter_threads {
+        builder = builder
+            .with_parallel_execution(inter_threads > 1)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .with_inter_threads(inter_threads.max(1))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    }
+    builder
+        .commit_from_file(path)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
 pub struct EncoderONNX {
     pub tokenizer: Arc<Tokenizer>,
     model: Arc<SyncUnsafeSession>,
     pub needs_token_type_ids: bool,
+    /// Output holding the embeddings.
+    output: ModelOutput,
     pub output_dtype: ModelOutputDType,
     pub output_dim: i64,
+    /// Encoder of images into the same space, for CLIP-style models.
+    image: Option<Arc<ImageEncoderONNX>>,
 }
 
 impl ModelTrait for EncoderONNX {
     fn new(model_dir: &str, model_file: &str) -> anyhow::Result<Self> {
-        ORT_INIT.call_once(|| {
-            let _ = ort::init().with_name("onnx_model").commit();
-        });
+        Self::with_options(model_dir, model_file, &OnnxSessionOptions::default())
+    }
+}
 
+impl EncoderONNX {
+    /// Load a model into a single session configured by `options`. Its
+    /// `sessions` count is only used to split the cores between sessions.
+    pub fn with_options(
+        model_dir: &str,
+        model_file: &str,
+        options: &OnnxSessionOptions,
+    ) -> anyhow::Result<Self> {
         let model_source_path = Path::new(model_dir);
+        let session = build_session(&model_source_path.join(model_file), options)?;
 
-        let session = Session::builder()
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?
-            .with_optimization_level(GraphOptimizationLevel::Level3)
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?
-            .with_intra_threads(available_parallelism()?.get())
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?
-            .commit_from_file(model_source_path.join(model_file))
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-
+        let metadata = ModelMetadata::read(model_source_path)?;
         let mut tokenizer = Tokenizer::from_file(model_source_path.join("tokenizer.json"))
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        if let Some(max_len) = metadata.as_ref().and_then(|m| m.max_len) {
+            tokenizer
+                .with_truncation(Some(TruncationParams {
+                    max_length: max_len,
+                    ..TruncationParams::default()
+                }))
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            info!("Truncating inputs to {} tokens", max_len);
+        }
 
         tokenizer.with_padding(Some(PaddingParams {
             strategy: tokenizers::PaddingStrategy::BatchLongest,
@@ -90,15 +287,32 @@ impl ModelTrait for EncoderONNX {
             pad_token: "<pad>".into(),
         }));
 
-        // determine output index dynamically
-        let output_idx = session
+        // determine the output holding the embeddings
+        let output_name = metadata.as_ref().and_then(|m| m.output.clone());
+        let pooling = metadata.as_ref().and_then(|m| m.pooling);
+        let outputs: Vec<(&str, usize)> = session
             .outputs()
             .iter()
-            .position(|o| o.name() == "sentence_embedding")
-            .unwrap_or_else(|| if session.outputs().len() > 1 { 1 } else { 0 });
+            .map(|o| {
+                (
+                    o.name(),
+                    o.dtype().tensor_shape().map_or(0, |shape| shape.len()),
+                )
+            })
+            .collect();
+        let output = select_output(&outputs, output_name.as_deref(), pooling)?;
+        info!(
+            "Model output: {} ({})",
+            outputs[output.index].0,
+            match output.pooling {
+                Some(Pooling::Mean) => "mean pooled",
+                Some(Pooling::Cls) => "first token",
+                None => "pooled by the model",
+            }
+        );
 
         // determine output dtype
-        let dtype = session.outputs()[output_idx]
+        let dtype = session.outputs()[output.index]
             .dtype()
             .tensor_type()
             .ok_or_else(|| anyhow::anyhow!("Could not determine output tensor type"))?
@@ -112,7 +326,7 @@ impl ModelTrait for EncoderONNX {
         };
 
         // determine model output dimension
-        let dim = session.outputs()[output_idx]
+        let dim = session.outputs()[output.index]
             .dtype()
             .tensor_shape()
             .ok_or_else(|| anyhow::anyhow!("Could not determine tensor dimensions"))?
@@ -130,25 +344,59 @@ impl ModelTrait for EncoderONNX {
             .collect::<Vec<&str>>()
             .contains(&tti_name);
 
+        let image = match metadata.as_ref().and_then(|m| m.image.as_ref()) {
+            Some(image_model) => {
+                let image =
+                    ImageEncoderONNX::with_options(model_source_path, image_model, options)?;
+                if image.output_dim != dim {
+                    return Err(anyhow::anyhow!(
+                        "The image model embeds into {} dimensions but the text model into {}",
+                        image.output_dim,
+                        dim
+                    ));
+                }
+                Some(Arc::new(image))
+            }
+            None => None,
+        };
+
         Ok(Self {
             model: Arc::new(SyncUnsafeSession::new(session)),
             tokenizer: Arc::new(tokenizer),
             output_dim: dim,
+            output,
             output_dtype,
             needs_token_type_ids,
+            image,
         })
     }
 }
 
 impl ONNXModelTrait for EncoderONNX {
     fn predict_f16(&self, texts: Vec<&str>) -> anyhow::Result<Arc<Array2<f16>>> {
-        assert_eq!(self.output_dtype, ModelOutputDType::F16);
-        run_predict_f16(&self.model, &self.tokenizer, self.needs_token_type_ids, texts)
+        if self.output_dtype != ModelOutputDType::F16 {
+            return Err(anyhow::anyhow!("Model output is not f16"));
+        }
+        run_predict_f16(
+            &self.model,
+            &self.tokenizer,
+            self.needs_token_type_ids,
+            self.output,
+            texts,
+        )
     }
 
     fn predict_f32(&self, texts: Vec<&str>) -> anyhow::Result<Arc<Array2<f32>>> {
-        assert_eq!(self.output_dtype, ModelOutputDType::F32);
-        run_predict_f32(&self.model, &self.tokenizer, self.needs_token_type_ids, texts)
+        if self.output_dtype != ModelOutputDType::F32 {
+            return Err(anyhow::anyhow!("Model output is not f32"));
+        }
+        run_predict_f32(
+            &self.model,
+            &self.tokenizer,
+            self.needs_token_type_ids,
+            self.output,
+            texts,
+        )
     }
 
     fn output_dtype(&self) -> anyhow::Result<ModelOutputDType> {
@@ -160,10 +408,26 @@ impl ONNXModelTrait for EncoderONNX {
     }
 }
 
+/// View the data of a `(texts, tokens, dimensions)` output tensor.
+fn token_embeddings<'a, T>(shape: &[i64], data: &'a [T]) -> anyhow::Result<ArrayView3<'a, T>> {
+    if shape.len() != 3 {
+        return Err(anyhow::anyhow!(
+            "Expected token embeddings of rank 3, got shape {:?}",
+            shape
+        ));
+    }
+    ArrayView3::from_shape(
+        (shape[0] as usize, shape[1] as usize, shape[2] as usize),
+        data,
+    )
+    .map_err(|e| anyhow::anyhow!(e.to_string()))
+}
+
 fn run_predict_f16(
     model: &SyncUnsafeSession,
     tokenizer: &Tokenizer,
     needs_token_type_ids: bool,
+    output: ModelOutput,
     texts: Vec<&str>,
 ) -> anyhow::Result<Arc<Array2<f16>>> {
     let inputs: Vec<String> = texts.par_iter().map(|s| s.to_string()).collect();
@@ -172,7 +436,10 @@ fn run_predict_f16(
         let encodings = tokenizer
             .encode_batch(inputs.clone(), true)
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-        let padded_token_length = encodings[0].len();
+        let padded_token_length = encodings
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Cannot embed an empty batch"))?
+            .len();
 
         let ids: Vec<i64> = encodings
             .par_iter()
@@ -211,20 +478,26 @@ fn run_predict_f16(
             session
                 .run(ort::inputs![
                     "input_ids" => Tensor::from_array((shape, ids)).map_err(|e| anyhow::anyhow!(e.to_string()))?,
-                    "attention_mask" => Tensor::from_array((shape, mask)).map_err(|e| anyhow::anyhow!(e.to_string()))?
+                    "attention_mask" => Tensor::from_array((shape, mask.clone())).map_err(|e| anyhow::anyhow!(e.to_string()))?
                 ])
                 .map_err(|e| anyhow::anyhow!(e.to_string()))?
         };
 
-        let (output_shape, output_data) = outputs[1]
+        let (output_shape, output_data) = outputs[output.index]
             .try_extract_tensor::<f16>()
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-        ndarray::ArrayView2::from_shape(
-            (output_shape[0] as usize, output_shape[1] as usize),
-            output_data,
-        )
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?
-        .to_owned()
+        match output.pooling {
+            Some(pooling) => {
+                let tokens = token_embeddings(output_shape, output_data)?.mapv(f16::to_f32);
+                pool_tokens(tokens.view(), &mask, pooling).mapv(f16::from_f32)
+            }
+            None => ndarray::ArrayView2::from_shape(
+                (output_shape[0] as usize, output_shape[1] as usize),
+                output_data,
+            )
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .to_owned(),
+        }
     };
 
     Ok(Arc::new(embeddings_tensor))
@@ -234,6 +507,7 @@ fn run_predict_f32(
     model: &SyncUnsafeSession,
     tokenizer: &Tokenizer,
     needs_token_type_ids: bool,
+    output: ModelOutput,
     texts: Vec<&str>,
 ) -> anyhow::Result<Arc<Array2<f32>>> {
     let inputs: Vec<String> = texts.par_iter().map(|s| s.to_string()).collect();
@@ -242,7 +516,10 @@ fn run_predict_f32(
         let encodings = tokenizer
             .encode_batch(inputs.clone(), true)
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-        let padded_token_length = encodings[0].len();
+        let padded_token_length = encodings
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Cannot embed an empty batch"))?
+            .len();
 
         let ids: Vec<i64> = encodings
             .par_iter()
@@ -281,20 +558,25 @@ fn run_predict_f32(
             session
                 .run(ort::inputs![
                     "input_ids" => Tensor::from_array((shape, ids)).map_err(|e| anyhow::anyhow!(e.to_string()))?,
-                    "attention_mask" => Tensor::from_array((shape, mask)).map_err(|e| anyhow::anyhow!(e.to_string()))?
+                    "attention_mask" => Tensor::from_array((shape, mask.clone())).map_err(|e| anyhow::anyhow!(e.to_string()))?
                 ])
                 .map_err(|e| anyhow::anyhow!(e.to_string()))?
         };
 
-        let (output_shape, output_data) = outputs[1]
+        let (output_shape, output_data) = outputs[output.index]
             .try_extract_tensor::<f32>()
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-        ndarray::ArrayView2::from_shape(
-            (output_shape[0] as usize, output_shape[1] as usize),
-            output_data,
-        )
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?
-        .to_owned()
+        match output.pooling {
+            Some(pooling) => {
+                pool_tokens(token_embeddings(output_shape, output_data)?, &mask, pooling)
+            }
+            None => ndarray::ArrayView2::from_shape(
+                (output_shape[0] as usize, output_shape[1] as usize),
+                output_data,
+            )
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .to_owned(),
+        }
     };
 
     Ok(Arc::new(embeddings_tensor))
@@ -310,29 +592,128 @@ impl Embedder for EncoderONNX {
         Ok(self.output_dtype.clone())
     }
 
+    fn backend(&self) -> Backend {
+        Backend::ONNX
+    }
+
+    fn tokenizer(&self) -> Option<Arc<Tokenizer>> {
+        Some(self.tokenizer.clone())
+    }
+
+    fn supports_images(&self) -> bool {
+        self.image.is_some()
+    }
+
+    async fn predict_image(&self, images: Vec<Vec<u8>>) -> anyhow::Result<Embeddings> {
+        let image = self
+            .image
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("The model has no image encoder"))?;
+        let dtype = self.output_dtype.clone();
+        tokio::task::spawn_blocking(move || {
+            // Stored like the text embeddings, whatever the image model outputs.
+            let embeddings = image.predict(&images)?;
+            Ok(match dtype {
+                ModelOutputDType::F16 => Embeddings::F16(Arc::new(embeddings.mapv(f16::from_f32))),
+                _ => Embeddings::F32(Arc::new(embeddings)),
+            })
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Spawn blocking error: {}", e))?
+    }
+
     async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
         let model = self.model.clone();
         let tokenizer = self.tokenizer.clone();
         let dtype = self.output_dtype.clone();
         let needs_token_type_ids = self.needs_token_type_ids;
+        let output = self.output;
 
         tokio::task::spawn_blocking(move || {
             let texts_ref: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
             match dtype {
                 ModelOutputDType::F16 => {
-                    let result = run_predict_f16(&model, &tokenizer, needs_token_type_ids, texts_ref)?;
+                    let result = run_predict_f16(
+                        &model,
+                        &tokenizer,
+                        needs_token_type_ids,
+                        output,
+                        texts_ref,
+                    )?;
                     Ok(Embeddings::F16(result))
                 }
                 ModelOutputDType::F32 => {
-                    let result = run_predict_f32(&model, &tokenizer, needs_token_type_ids, texts_ref)?;
+                    let result = run_predict_f32(
+                        &model,
+                        &tokenizer,
+                        needs_token_type_ids,
+                        output,
+                        texts_ref,
+                    )?;
                     Ok(Embeddings::F32(result))
                 }
-                ModelOutputDType::Int8 => {
-                    unimplemented!("int8 dynamic quantization not yet implemented")
-                }
+                ModelOutputDType::Int8 => Err(anyhow::anyhow!(
+                    "int8 dynamic quantization not yet implemented"
+                )),
             }
         })
         .await
         .map_err(|e| anyhow::anyhow!("Spawn blocking error: {}", e))?
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{array, Array3};
+
+    #[test]
+    fn test_select_output() {
+        let st = [("token_embeddings", 3), ("sentence_embedding", 2)];
+        assert_eq!(
+            select_output(&st, None, None).unwrap(),
+            ModelOutput {
+                index: 1,
+                pooling: None
+            }
+        );
+        // The only pooled output wins over pooling token embeddings.
+        let bert = [("last_hidden_state", 3), ("pooler_output", 2)];
+        assert_eq!(select_output(&bert, None, None).unwrap().index, 1);
+        let hidden = [("last_hidden_state", 3)];
+        assert_eq!(
+            select_output(&hidden, None, Some(Pooling::Cls)).unwrap(),
+            ModelOutput {
+                index: 0,
+                pooling: Some(Pooling::Cls)
+            }
+        );
+        assert_eq!(
+            select_output(&bert, Some("last_hidden_state"), None)
+                .unwrap()
+                .pooling,
+            Some(Pooling::Mean)
+        );
+
+        let err = select_output(&bert, Some("embeddings"), None).unwrap_err();
+        assert!(err.to_string().contains("last_hidden_state (rank 3)"));
+        let ambiguous = [("a", 2), ("b", 2)];
+        assert!(select_output(&ambiguous, None, None).is_err());
+    }
+
+    #[test]
+    fn test_pool_tokens() {
+        let tokens =
+            Array3::from_shape_vec((1, 3, 2), vec![1.0, 2.0, 3.0, 4.0, 100.0, 100.0]).unwrap();
+        // The last token is padding.
+        let mask = [1, 1, 0];
+        assert_eq!(
+            pool_tokens(tokens.view(), &mask, Pooling::Mean),
+            array![[2.0, 3.0]]
+        );
+        assert_eq!(
+            pool_tokens(tokens.view(), &mask, Pooling::Cls),
+            array![[1.0, 2.0]]
+        );
+    }
+}
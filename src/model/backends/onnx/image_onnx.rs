@@ -0,0 +1,175 @@
+use crate::model::backends::onnx::encoder_onnx::{
+    build_session, OnnxSessionOptions, SyncUnsafeSession,
+};
+use crate::model::metadata::ImageModel;
+use half::f16;
+use image::imageops::FilterType;
+use log::info;
+use ndarray::Array2;
+use ort::value::Tensor;
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Default name of the output holding the image embeddings of CLIP exports.
+const IMAGE_OUTPUT: &str = "image_embeds";
+
+/// Vision half of a CLIP-style model, embedding images into the space of
+/// the texts of the model it belongs to.
+pub struct ImageEncoderONNX {
+    session: SyncUnsafeSession,
+    config: ImageModel,
+    output: usize,
+    output_f16: bool,
+    pub output_dim: i64,
+}
+
+impl ImageEncoderONNX {
+    /// Load the image model described by `config` from `model_dir`.
+    pub fn with_options(
+        model_dir: &Path,
+        config: &ImageModel,
+        options: &OnnxSessionOptions,
+    ) -> anyhow::Result<Self> {
+        let session = build_session(&model_dir.join(&config.path), options)?;
+
+        let outputs: Vec<(&str, usize)> = session
+            .outputs()
+            .iter()
+            .map(|o| {
+                (
+                    o.name(),
+                    o.dtype().tensor_shape().map_or(0, |shape| shape.len()),
+                )
+            })
+            .collect();
+        let position = |name: &str| outputs.iter().position(|(output, _)| *output == name);
+        let pooled: Vec<usize> = (0..outputs.len()).filter(|&i| outputs[i].1 == 2).collect();
+        let output = match config.output.as_deref() {
+            Some(name) => position(name),
+            None => position(IMAGE_OUTPUT).or_else(|| (pooled.len() == 1).then_some(pooled[0])),
+        }
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Cannot tell which output of the image model holds the embeddings. Its outputs are: {}. \
+                 Set \"output\" of \"image\" in the model's metadata.json to one of them",
+                outputs.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        if outputs[output].1 != 2 {
+            return Err(anyhow::anyhow!(
+                "Image output '{}' has rank {}, but image embeddings need rank 2",
+                outputs[output].0,
+                outputs[output].1
+            ));
+        }
+        info!("Image model output: {}", outputs[output].0);
+
+        let dtype = session.outputs()[output].dtype();
+        let output_f16 = dtype.tensor_type().is_some_and(|t| t.to_string() == "f16");
+        let output_dim = dtype
+            .tensor_shape()
+            .and_then(|shape| shape.last().copied())
+            .ok_or_else(|| anyhow::anyhow!("Could not determine image tensor dimensions"))?;
+
+        Ok(Self {
+            session: SyncUnsafeSession::new(session),
+            config: config.clone(),
+            output,
+            output_f16,
+            output_dim,
+        })
+    }
+
+    /// Embed encoded `images`, one row per image.
+    pub fn predict(&self, images: &[Vec<u8>]) -> anyhow::Result<Array2<f32>> {
+        if images.is_empty() {
+            return Err(anyhow::anyhow!("Cannot embed an empty batch"));
+        }
+        let pixels = images
+            .par_iter()
+            .map(|bytes| preprocess(bytes, &self.config))
+            .collect::<anyhow::Result<Vec<Vec<f32>>>>()?
+            .concat();
+        let size = self.config.size as usize;
+        let shape = [images.len(), 3, size, size];
+
+        let session = self.session.get_mut();
+        let outputs = session
+            .run(ort::inputs![
+                Tensor::from_array((shape, pixels)).map_err(|e| anyhow::anyhow!(e.to_string()))?
+            ])
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let (embeddings, dim) = if self.output_f16 {
+            let (shape, data) = outputs[self.output]
+                .try_extract_tensor::<f16>()
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            (data.iter().map(|x| x.to_f32()).collect(), shape[1] as usize)
+        } else {
+            let (shape, data) = outputs[self.output]
+                .try_extract_tensor::<f32>()
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            (data.to_vec(), shape[1] as usize)
+        };
+        Array2::from_shape_vec((images.len(), dim), embeddings)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))
+    }
+}
+
+/// Decode an image and turn it into the `(3, size, size)` pixel values CLIP
+/// models take: the shortest edge resized to `size`, the center cropped,
+/// and each channel normalized with the mean and std of `config`.
+fn preprocess(bytes: &[u8], config: &ImageModel) -> anyhow::Result<Vec<f32>> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| anyhow::anyhow!("Cannot decode image: {}", e))?
+        .to_rgb8();
+    let size = config.size;
+    let (width, height) = image.dimensions();
+    let scale = size as f32 / width.min(height) as f32;
+    let resized = image::imageops::resize(
+        &image,
+        ((width as f32 * scale).round() as u32).max(size),
+        ((height as f32 * scale).round() as u32).max(size),
+        FilterType::CatmullRom,
+    );
+    let left = (resized.width() - size) / 2;
+    let top = (resized.height() - size) / 2;
+    let cropped = image::imageops::crop_imm(&resized, left, top, size, size).to_image();
+
+    let plane = (size * size) as usize;
+    let mut pixels = vec![0f32; 3 * plane];
+    for (i, pixel) in cropped.pixels().enumerate() {
+        for channel in 0..3 {
+            let value = pixel[channel] as f32 / 255.0;
+            pixels[channel * plane + i] = (value - config.mean[channel]) / config.std[channel];
+        }
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_preprocess() {
+        let image = RgbImage::from_pixel(64, 32, Rgb([255, 0, 51]));
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageOutputFormat::Png).unwrap();
+        let config = ImageModel {
+            path: "vision_model.onnx".to_string(),
+            size: 16,
+            mean: [0.5, 0.0, 0.0],
+            std: [0.5, 1.0, 0.5],
+            output: None,
+        };
+
+        let pixels = preprocess(bytes.get_ref(), &config).unwrap();
+        assert_eq!(pixels.len(), 3 * 16 * 16);
+        assert!(pixels[..256].iter().all(|&x| (x - 1.0).abs() < 1e-3));
+        assert!(pixels[256..512].iter().all(|&x| x.abs() < 1e-3));
+        assert!(pixels[512..].iter().all(|&x| (x - 0.4).abs() < 1e-3));
+        assert!(preprocess(b"not an image", &config).is_err());
+    }
+}
@@ -1 +1,2 @@
-pub mod encoder_onnx;
+pub mod encoder_onnx;
+pub mod image_onnx;
@@ -0,0 +1 @@
+pub mod openai_embedder;
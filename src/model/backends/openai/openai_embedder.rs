@@ -0,0 +1,249 @@
+use crate::model::model_utils::{Backend, Embedder, Embeddings, ModelOutputDType};
+use async_trait::async_trait;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Base URL used when neither `--openai-base-url` nor `OPENAI_BASE_URL` is set.
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Embedding client for any server implementing the OpenAI `/v1/embeddings` API.
+///
+/// Besides OpenAI itself this covers self-hosted servers such as vLLM,
+/// text-embeddings-inference or Ollama, which makes letsearch usable on
+/// machines where local ONNX inference is not possible. The output is always
+/// f32. The embedding dimension is discovered with a probe request in
+/// [`OpenAIEmbedder::connect`] because the API does not advertise it.
+pub struct OpenAIEmbedder {
+    base_url: String,
+    model_name: String,
+    api_key: Option<String>,
+    output_dim: i64,
+    client: reqwest::Client,
+}
+
+impl OpenAIEmbedder {
+    /// Create a new `OpenAIEmbedder` and probe the server for the output dimension.
+    ///
+    /// * `base_url`   – API root without the `/embeddings` suffix,
+    ///   e.g. `"https://api.openai.com/v1"` or `"http://localhost:8080/v1"`.
+    /// * `model_name` – the model identifier sent with every request.
+    /// * `api_key`    – optional bearer token. Local servers usually need none.
+    pub async fn connect(
+        base_url: &str,
+        model_name: &str,
+        api_key: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let mut embedder = Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model_name: model_name.to_string(),
+            api_key,
+            output_dim: 0,
+            client: reqwest::Client::new(),
+        };
+
+        let probe = embedder.request(vec![String::from("letsearch")]).await?;
+        embedder.output_dim = probe
+            .first()
+            .map(|v| v.len() as i64)
+            .ok_or_else(|| anyhow::anyhow!("Embedding server returned no vectors for probe"))?;
+
+        Ok(embedder)
+    }
+
+    async fn request(&self, texts: Vec<String>) -> anyhow::Result<Vec<Vec<f32>>> {
+        let url = format!("{}/embeddings", self.base_url);
+        let body = EmbeddingsRequest {
+            model: self.model_name.clone(),
+            input: texts,
+            encoding_format: "float",
+        };
+
+        let request = self.client.post(&url).json(&body);
+        let request = match self.api_key.as_ref() {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        };
+
+        let mut response: EmbeddingsResponse = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Embedding API request to {} failed: {}", url, e))?
+            .error_for_status()
+            .map_err(|e| anyhow::anyhow!("Embedding API returned an error: {}", e))?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse embedding API response: {}", e))?;
+
+        // The API does not guarantee that items come back in input order.
+        response.data.sort_by_key(|item| item.index);
+        Ok(response
+            .data
+            .into_iter()
+            .map(|item| item.embedding)
+            .collect())
+    }
+}
+
+// ---------- Serde helpers for the OpenAI embeddings API ----------
+
+#[derive(Serialize)]
+struct EmbeddingsRequest {
+    model: String,
+    input: Vec<String>,
+    encoding_format: &'static str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingItem {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+// -------------------------------------------------------------------------
+
+#[async_trait]
+impl Embedder for OpenAIEmbedder {
+    fn output_dim(&self) -> anyhow::Result<i64> {
+        Ok(self.output_dim)
+    }
+
+    fn output_dtype(&self) -> anyhow::Result<ModelOutputDType> {
+        Ok(ModelOutputDType::F32)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Remote
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
+        let expected = texts.len();
+        let vectors = self.request(texts).await?;
+        if vectors.len() != expected {
+            return Err(anyhow::anyhow!(
+                "Embedding API returned {} vectors for {} inputs",
+                vectors.len(),
+                expected
+            ));
+        }
+
+        let dim = self.output_dim as usize;
+        let mut result = Array2::<f32>::zeros((expected, dim));
+        for (i, vector) in vectors.iter().enumerate() {
+            if vector.len() != dim {
+                return Err(anyhow::anyhow!(
+                    "Embedding API returned dimension {} for item {}, expected {}",
+                    vector.len(),
+                    i,
+                    dim
+                ));
+            }
+            for (j, &v) in vector.iter().enumerate() {
+                result[[i, j]] = v;
+            }
+        }
+
+        Ok(Embeddings::F32(Arc::new(result)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+
+    /// Embeddings endpoint whose behaviour depends on the requested model:
+    /// `ok` embeds each text as `[index, len, 1]` and answers in reverse
+    /// order, `failing` answers `500`, `short` leaves out the last vector and
+    /// `ragged` returns a shorter last vector for batches.
+    async fn mock_embeddings(req: HttpRequest, body: web::Json<Value>) -> HttpResponse {
+        if body["encoding_format"] != "float" {
+            return HttpResponse::BadRequest().finish();
+        }
+        let auth = req
+            .headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        if auth.is_some_and(|auth| auth != "Bearer secret") {
+            return HttpResponse::Unauthorized().finish();
+        }
+        let inputs = body["input"].as_array().cloned().unwrap_or_default();
+        let mut data: Vec<Value> = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let len = text.as_str().unwrap_or_default().len();
+                json!({"embedding": [index as f32, len as f32, 1.0], "index": index})
+            })
+            .collect();
+        match body["model"].as_str() {
+            Some("failing") => return HttpResponse::InternalServerError().finish(),
+            Some("short") if data.len() > 1 => {
+                data.pop();
+            }
+            Some("ragged") if data.len() > 1 => {
+                data.last_mut().unwrap()["embedding"] = json!([1.0, 2.0]);
+            }
+            _ => {}
+        }
+        data.reverse();
+        HttpResponse::Ok().json(json!({"data": data, "model": body["model"]}))
+    }
+
+    #[actix_web::test]
+    async fn test_openai_embedder() {
+        let server =
+            HttpServer::new(|| App::new().route("/v1/embeddings", web::post().to(mock_embeddings)))
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+        let base_url = format!("http://{}/v1/", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let embedder = OpenAIEmbedder::connect(&base_url, "ok", Some(String::from("secret")))
+            .await
+            .unwrap();
+        assert_eq!(embedder.output_dim().unwrap(), 3);
+        let texts = vec![String::from("a"), String::from("abc")];
+        match embedder.embed(texts).await.unwrap() {
+            Embeddings::F32(embeddings) => assert_eq!(
+                embeddings
+                    .rows()
+                    .into_iter()
+                    .map(|row| row.to_vec())
+                    .collect::<Vec<_>>(),
+                vec![vec![0.0, 1.0, 1.0], vec![1.0, 3.0, 1.0]]
+            ),
+            Embeddings::F16(_) => panic!("expected f32 embeddings"),
+        }
+
+        let unauthorized =
+            OpenAIEmbedder::connect(&base_url, "ok", Some(String::from("wrong"))).await;
+        assert!(unauthorized.is_err());
+        let failing = OpenAIEmbedder::connect(&base_url, "failing", None).await;
+        let error = failing.err().unwrap().to_string();
+        assert!(error.contains("500"), "{}", error);
+
+        let texts = vec![String::from("a"), String::from("b")];
+        let short = OpenAIEmbedder::connect(&base_url, "short", None)
+            .await
+            .unwrap();
+        let error = short.embed(texts.clone()).await.err().unwrap().to_string();
+        assert!(error.contains("1 vectors for 2 inputs"), "{}", error);
+        let ragged = OpenAIEmbedder::connect(&base_url, "ragged", None)
+            .await
+            .unwrap();
+        let error = ragged.embed(texts).await.err().unwrap().to_string();
+        assert!(error.contains("dimension 2 for item 1"), "{}", error);
+
+        handle.stop(true).await;
+    }
+}
@@ -0,0 +1,228 @@
+use crate::model::model_utils::{Backend, Embedder, Embeddings, ModelOutputDType};
+use async_trait::async_trait;
+use log::debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::time::Instant;
+
+/// How long the first request of a batch waits for others to join it.
+pub const DEFAULT_BATCH_WINDOW: Duration = Duration::from_millis(2);
+
+/// Most texts coalesced into a single model run. A larger request still runs,
+/// but on its own.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 64;
+
+/// Most calls waiting to join a batch. Callers beyond it wait to be queued.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Most batches run on the inner model at once.
+const MAX_CONCURRENT_BATCHES: usize = 4;
+
+struct Job {
+    texts: Vec<String>,
+    reply: oneshot::Sender<anyhow::Result<Embeddings>>,
+}
+
+/// Wraps an [`Embedder`] and coalesces concurrent `embed` calls that arrive
+/// within a short window into one call on the inner model.
+///
+/// Search queries are single texts, so under load most of the inference time
+/// is per-call overhead. Batching them amortizes it with a latency cost of
+/// at most the batch window.
+pub struct BatchingEmbedder {
+    inner: Arc<dyn Embedder>,
+    sender: mpsc::Sender<Job>,
+}
+
+impl BatchingEmbedder {
+    /// Must be called from within a tokio runtime, which runs the batching loop.
+    pub fn new(inner: Arc<dyn Embedder>, window: Duration, max_batch_size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(collect_batches(
+            inner.clone(),
+            receiver,
+            window,
+            max_batch_size,
+        ));
+        Self { inner, sender }
+    }
+}
+
+async fn collect_batches(
+    model: Arc<dyn Embedder>,
+    mut receiver: mpsc::Receiver<Job>,
+    window: Duration,
+    max_batch_size: usize,
+) {
+    let running = Arc::new(Semaphore::new(MAX_CONCURRENT_BATCHES));
+    let mut pending: Option<Job> = None;
+
+    loop {
+        let first = match pending.take() {
+            Some(job) => job,
+            None => match receiver.recv().await {
+                Some(job) => job,
+                None => break,
+            },
+        };
+
+        let deadline = Instant::now() + window;
+        let mut total = first.texts.len();
+        let mut jobs = vec![first];
+        while total < max_batch_size {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(job)) if total + job.texts.len() > max_batch_size => {
+                    pending = Some(job);
+                    break;
+                }
+                Ok(Some(job)) => {
+                    total += job.texts.len();
+                    jobs.push(job);
+                }
+                _ => break,
+            }
+        }
+
+        // Run batches concurrently so that a slow batch does not hold up the
+        // requests queued behind it, up to a limit past which calls queue up.
+        let Ok(permit) = running.clone().acquire_owned().await else {
+            break;
+        };
+        let model = model.clone();
+        tokio::spawn(async move {
+            run_batch(model, jobs).await;
+            drop(permit);
+        });
+    }
+}
+
+async fn run_batch(model: Arc<dyn Embedder>, jobs: Vec<Job>) {
+    if jobs.len() > 1 {
+        debug!("Coalesced {} predict calls into one batch", jobs.len());
+    }
+
+    let sizes: Vec<usize> = jobs.iter().map(|job| job.texts.len()).collect();
+    let mut texts = Vec::with_capacity(sizes.iter().sum());
+    let mut replies = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        texts.extend(job.texts);
+        replies.push(job.reply);
+    }
+
+    match model
+        .embed(texts)
+        .await
+        .and_then(|embeddings| embeddings.split_rows(&sizes))
+    {
+        Ok(parts) => {
+            for (reply, part) in replies.into_iter().zip(parts) {
+                let _ = reply.send(Ok(part));
+            }
+        }
+        Err(e) => {
+            let message = e.to_string();
+            for reply in replies {
+                let _ = reply.send(Err(anyhow::anyhow!(message.clone())));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for BatchingEmbedder {
+    fn output_dim(&self) -> anyhow::Result<i64> {
+        self.inner.output_dim()
+    }
+
+    fn output_dtype(&self) -> anyhow::Result<ModelOutputDType> {
+        self.inner.output_dtype()
+    }
+
+    fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    fn tokenizer(&self) -> Option<Arc<tokenizers::Tokenizer>> {
+        self.inner.tokenizer()
+    }
+
+    fn supports_images(&self) -> bool {
+        self.inner.supports_images()
+    }
+
+    /// Images are embedded in the batches they come in.
+    async fn predict_image(&self, images: Vec<Vec<u8>>) -> anyhow::Result<Embeddings> {
+        self.inner.predict_image(images).await
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(Job { texts, reply })
+            .await
+            .map_err(|_| anyhow::anyhow!("Batching loop has stopped"))?;
+        response
+            .await
+            .map_err(|_| anyhow::anyhow!("Batching loop dropped the request"))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array2;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds every text as `[len(text)]` and counts the calls.
+    struct LengthEmbedder {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for LengthEmbedder {
+        fn output_dim(&self) -> anyhow::Result<i64> {
+            Ok(1)
+        }
+
+        fn output_dtype(&self) -> anyhow::Result<ModelOutputDType> {
+            Ok(ModelOutputDType::F32)
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::ONNX
+        }
+
+        async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let values = texts.iter().map(|t| t.len() as f32).collect();
+            Ok(Embeddings::F32(Arc::new(Array2::from_shape_vec(
+                (texts.len(), 1),
+                values,
+            )?)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_are_coalesced() {
+        let inner = Arc::new(LengthEmbedder {
+            calls: AtomicUsize::new(0),
+        });
+        let batcher = BatchingEmbedder::new(inner.clone(), Duration::from_millis(50), 64);
+
+        let inputs = [vec!["a"], vec!["bb", "ccc"], vec!["dddd"]];
+        let results = futures::future::join_all(
+            inputs
+                .iter()
+                .map(|texts| batcher.embed(texts.iter().map(|t| t.to_string()).collect())),
+        )
+        .await;
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        for (texts, result) in inputs.iter().zip(results) {
+            let rows = result.unwrap().to_f32_rows();
+            let expected: Vec<Vec<f32>> = texts.iter().map(|t| vec![t.len() as f32]).collect();
+            assert_eq!(rows, expected);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+/// Model variant that is chosen for the machine at load time.
+pub const AUTO_VARIANT: &str = "auto";
+
+/// Capabilities of the machine that decide which model variant runs fastest.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hardware {
+    pub avx512: bool,
+    /// AVX-512 VNNI or AVX-VNNI, which speed up int8 dot products.
+    pub vnni: bool,
+    pub avx2: bool,
+    pub neon: bool,
+    /// A CUDA GPU usable by ONNX Runtime.
+    pub gpu: bool,
+}
+
+impl Hardware {
+    pub fn detect() -> Self {
+        let mut hardware = Hardware {
+            gpu: cuda_available(),
+            ..Hardware::default()
+        };
+        #[cfg(target_arch = "x86_64")]
+        {
+            hardware.avx512 = std::arch::is_x86_feature_detected!("avx512f");
+            hardware.vnni = std::arch::is_x86_feature_detected!("avx512vnni")
+                || std::arch::is_x86_feature_detected!("avxvnni");
+            hardware.avx2 = std::arch::is_x86_feature_detected!("avx2");
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            hardware.neon = std::arch::is_aarch64_feature_detected!("neon");
+        }
+        hardware
+    }
+
+    /// Variants from the fastest to the slowest on this machine: f16 on a
+    /// GPU, i8 on CPUs with SIMD int8 arithmetic, f32 otherwise.
+    pub fn preferred_variants(&self) -> [&'static str; 3] {
+        if self.gpu {
+            ["f16", "f32", "i8"]
+        } else if self.vnni || self.avx512 || self.avx2 || self.neon {
+            ["i8", "f32", "f16"]
+        } else {
+            ["f32", "i8", "f16"]
+        }
+    }
+
+    /// The most preferred of the `available` variants of a model, or the
+    /// first one when none is known.
+    pub fn choose_variant<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.preferred_variants()
+            .iter()
+            .find_map(|preferred| available.iter().find(|v| *v == preferred).copied())
+            .or_else(|| available.first().copied())
+    }
+}
+
+impl fmt::Display for Hardware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.gpu {
+            return write!(f, "CUDA GPU");
+        }
+        let simd = if self.vnni {
+            "AVX-512 VNNI"
+        } else if self.avx512 {
+            "AVX-512"
+        } else if self.avx2 {
+            "AVX2"
+        } else if self.neon {
+            "NEON"
+        } else {
+            return write!(f, "CPU without SIMD int8 support, no GPU");
+        };
+        write!(f, "CPU with {}, no GPU", simd)
+    }
+}
+
+#[cfg(feature = "cuda")]
+fn cuda_available() -> bool {
+    use ort::ep::{ExecutionProvider, CUDA};
+
+    // ONNX Runtime may be built with CUDA on a machine without a GPU.
+    let device_present =
+        !cfg!(target_os = "linux") || std::path::Path::new("/proc/driver/nvidia/version").exists();
+    device_present && CUDA::default().is_available().unwrap_or(false)
+}
+
+#[cfg(not(feature = "cuda"))]
+fn cuda_available() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_variant() {
+        let cpu = Hardware {
+            avx2: true,
+            ..Hardware::default()
+        };
+        let gpu = Hardware {
+            gpu: true,
+            ..Hardware::default()
+        };
+        assert_eq!(cpu.choose_variant(&["f32", "f16", "i8"]), Some("i8"));
+        assert_eq!(gpu.choose_variant(&["f32", "f16", "i8"]), Some("f16"));
+        assert_eq!(gpu.choose_variant(&["f32", "i8"]), Some("f32"));
+        assert_eq!(
+            Hardware::default().choose_variant(&["i8", "f32"]),
+            Some("f32")
+        );
+        assert_eq!(cpu.choose_variant(&["q4"]), Some("q4"));
+        assert_eq!(cpu.choose_variant(&[]), None);
+    }
+}
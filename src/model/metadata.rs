@@ -0,0 +1,358 @@
+use crate::collection::vector_index::Metric;
+use crate::model::backends::onnx::encoder_onnx::Pooling;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path};
+
+/// File describing a letsearch model, next to its ONNX files.
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Newest `letsearch_version` of [`METADATA_FILE`] this build reads.
+/// Version 2 adds `max_len` and `prompts`, version 3 adds `image`; version 1
+/// files are read as they are.
+pub const LATEST_VERSION: u32 = 3;
+
+/// Mean and standard deviation of the pixels CLIP was trained on.
+const CLIP_MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const CLIP_STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+fn default_image_size() -> u32 {
+    224
+}
+
+fn default_image_mean() -> [f32; 3] {
+    CLIP_MEAN
+}
+
+fn default_image_std() -> [f32; 3] {
+    CLIP_STD
+}
+
+/// An ONNX file of the model, e.g. `{"variant": "f16", "path": "model-f16.onnx"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub variant: String,
+    /// Path of the ONNX file, relative to the model directory.
+    pub path: String,
+}
+
+/// Instructions added to texts before they are embedded, e.g. `"query: "`
+/// and `"passage: "` for E5 models. A template holding `{text}` has the
+/// text put in its place, any other is prepended to the text.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Prompts {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<String>,
+}
+
+impl Prompts {
+    pub fn is_empty(&self) -> bool {
+        self.query.is_none() && self.document.is_none()
+    }
+
+    /// These prompts, with those of `fallback` where they are not set.
+    pub fn or(&self, fallback: &Prompts) -> Prompts {
+        Prompts {
+            query: self.query.clone().or_else(|| fallback.query.clone()),
+            document: self.document.clone().or_else(|| fallback.document.clone()),
+        }
+    }
+
+    /// `text` as a query to embed.
+    pub fn query(&self, text: &str) -> String {
+        apply_template(self.query.as_deref(), text)
+    }
+
+    /// `text` as a document to embed.
+    pub fn document(&self, text: &str) -> String {
+        apply_template(self.document.as_deref(), text)
+    }
+}
+
+/// Image encoder of a CLIP-style model, e.g. `{"path": "vision_model.onnx"}`,
+/// embedding images into the space of the texts embedded by the variants.
+/// Images are resized so that their shorter side is `size`, cropped to a
+/// `size` square around their center, scaled to 0..1 and normalized with
+/// `mean` and `std` per RGB channel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ImageModel {
+    /// Path of the ONNX file, relative to the model directory. Its input
+    /// takes `(images, 3, size, size)` pixel values.
+    pub path: String,
+    #[serde(default = "default_image_size")]
+    pub size: u32,
+    #[serde(default = "default_image_mean")]
+    pub mean: [f32; 3],
+    #[serde(default = "default_image_std")]
+    pub std: [f32; 3],
+    /// Name of the ONNX output holding the embeddings. Defaults to
+    /// `image_embeds`, or the only output with one embedding per image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+fn apply_template(template: Option<&str>, text: &str) -> String {
+    match template {
+        Some(template) if template.contains("{text}") => template.replace("{text}", text),
+        Some(prefix) => format!("{}{}", prefix, text),
+        None => text.to_string(),
+    }
+}
+
+/// Contents of a model's [`METADATA_FILE`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ModelMetadata {
+    pub letsearch_version: u32,
+    pub variants: Vec<Variant>,
+    /// Files downloaded along with the ONNX file of a variant.
+    #[serde(default)]
+    pub required_files: Vec<String>,
+    /// Similarity metric the model was trained for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<Metric>,
+    /// Name of the ONNX output holding the embeddings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// How to pool an output with an embedding per token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pooling: Option<Pooling>,
+    /// Longest input in tokens; longer texts are truncated. Version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    /// Version 2.
+    #[serde(default, skip_serializing_if = "Prompts::is_empty")]
+    pub prompts: Prompts,
+    /// Encoder of images, for models such as CLIP. Version 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageModel>,
+}
+
+impl ModelMetadata {
+    /// Version 1 metadata of `variants`, without the optional keys.
+    pub fn new(variants: Vec<Variant>, required_files: Vec<String>) -> Self {
+        Self {
+            letsearch_version: 1,
+            variants,
+            required_files,
+            metric: None,
+            output: None,
+            pooling: None,
+            max_len: None,
+            prompts: Prompts::default(),
+            image: None,
+        }
+    }
+
+    /// Oldest version that can hold the keys that are set, so that models
+    /// which do not need version 2 stay readable by older letsearch builds.
+    pub fn min_version(&self) -> u32 {
+        if self.image.is_some() {
+            3
+        } else if self.max_len.is_some() || !self.prompts.is_empty() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Parse and [`validate`](Self::validate) the contents of a metadata file.
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("{} is not valid JSON: {}", METADATA_FILE, e))?;
+        match value.get("letsearch_version") {
+            Some(serde_json::Value::Number(_)) => {}
+            Some(version) => {
+                return Err(anyhow::anyhow!(
+                    "letsearch_version in {} must be a number, got {}",
+                    METADATA_FILE,
+                    version
+                ))
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                "{} has no letsearch_version. This is probably not a letsearch-compatible model",
+                METADATA_FILE
+            ))
+            }
+        }
+        let metadata: ModelMetadata = serde_json::from_value(value)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", METADATA_FILE, e))?;
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Read the metadata file in `model_dir`, if it has one.
+    pub fn read(model_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = model_dir.join(METADATA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&path)?;
+        Self::parse(&json)
+            .map(Some)
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Check what serde cannot: the version, that variants are unique and
+    /// point into the model directory, and that keys of newer versions are
+    /// not used in older files.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.letsearch_version == 0 || self.letsearch_version > LATEST_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported letsearch_version {} in {}, this letsearch reads versions 1 to {}. \
+                 Try upgrading letsearch",
+                self.letsearch_version,
+                METADATA_FILE,
+                LATEST_VERSION
+            ));
+        }
+        if self.min_version() > self.letsearch_version {
+            return Err(anyhow::anyhow!(
+                "max_len and prompts need letsearch_version 2, and image 3, in {}. This file has {}",
+                METADATA_FILE,
+                self.letsearch_version
+            ));
+        }
+        if self.variants.is_empty() {
+            return Err(anyhow::anyhow!("{} lists no variants", METADATA_FILE));
+        }
+        let mut names = HashSet::new();
+        for variant in &self.variants {
+            if !names.insert(variant.variant.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Variant '{}' is listed twice in {}",
+                    variant.variant,
+                    METADATA_FILE
+                ));
+            }
+            check_relative(&variant.path)
+                .with_context(|| format!("Invalid path of variant '{}'", variant.variant))?;
+        }
+        for file in &self.required_files {
+            check_relative(file).context("Invalid required file")?;
+        }
+        if self.max_len == Some(0) {
+            return Err(anyhow::anyhow!(
+                "max_len in {} must be positive",
+                METADATA_FILE
+            ));
+        }
+        if let Some(image) = &self.image {
+            check_relative(&image.path).context("Invalid path of the image model")?;
+            if image.size == 0 || image.std.contains(&0.0) {
+                return Err(anyhow::anyhow!(
+                    "The image size and std in {} must be positive",
+                    METADATA_FILE
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Entry of the variant named `name`.
+    pub fn variant(&self, name: &str) -> anyhow::Result<&Variant> {
+        self.variants
+            .iter()
+            .find(|v| v.variant == name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Variant '{}' not found, the model has {}",
+                    name,
+                    self.variant_names().join(", ")
+                )
+            })
+    }
+
+    pub fn variant_names(&self) -> Vec<&str> {
+        self.variants.iter().map(|v| v.variant.as_str()).collect()
+    }
+}
+
+/// Reject empty, absolute and `..` paths, which would not stay inside the
+/// model directory.
+fn check_relative(path: &str) -> anyhow::Result<()> {
+    let escapes = Path::new(path)
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if path.is_empty() || escapes {
+        return Err(anyhow::anyhow!(
+            "'{}' must be a path relative to the model directory",
+            path
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata() {
+        let v1 = r#"{
+            "letsearch_version": 1,
+            "variants": [{"variant": "f32", "path": "model-f32.onnx"}],
+            "required_files": ["tokenizer.json"],
+            "metric": "ip",
+            "pooling": "cls"
+        }"#;
+        let metadata = ModelMetadata::parse(v1).unwrap();
+        assert_eq!(metadata.variant("f32").unwrap().path, "model-f32.onnx");
+        assert_eq!(metadata.metric, Some(Metric::IP));
+        assert_eq!(metadata.pooling, Some(Pooling::Cls));
+        assert!(metadata.variant("f16").is_err());
+
+        let v2 = r#"{
+            "letsearch_version": 2,
+            "variants": [{"variant": "f32", "path": "onnx/model.onnx"}],
+            "max_len": 512,
+            "prompts": {"query": "query: ", "document": "passage: "}
+        }"#;
+        let metadata = ModelMetadata::parse(v2).unwrap();
+        assert_eq!(metadata.max_len, Some(512));
+        assert_eq!(metadata.prompts.query.as_deref(), Some("query: "));
+        assert_eq!(metadata.min_version(), 2);
+        assert_eq!(metadata.prompts.query("who"), "query: who");
+
+        let prompts = Prompts {
+            query: Some(String::from("Represent this question: {text}.")),
+            document: None,
+        };
+        let prompts = prompts.or(&metadata.prompts);
+        assert_eq!(prompts.query("who"), "Represent this question: who.");
+        assert_eq!(prompts.document("it"), "passage: it");
+        assert_eq!(Prompts::default().document("it"), "it");
+
+        let clip = r#"{
+            "letsearch_version": 3,
+            "variants": [{"variant": "f32", "path": "onnx/text_model.onnx"}],
+            "image": {"path": "onnx/vision_model.onnx", "size": 336}
+        }"#;
+        let metadata = ModelMetadata::parse(clip).unwrap();
+        assert_eq!(metadata.min_version(), 3);
+        let image = metadata.image.unwrap();
+        assert_eq!(image.size, 336);
+        assert_eq!(image.mean, CLIP_MEAN);
+
+        let invalid = [
+            r#"{"variants": []}"#,
+            r#"{"letsearch_version": 4, "variants": [{"variant": "f32", "path": "m.onnx"}]}"#,
+            r#"{"letsearch_version": 1, "variants": []}"#,
+            r#"{"letsearch_version": 1, "variants": [{"variant": "f32", "path": "../m.onnx"}]}"#,
+            r#"{"letsearch_version": 1, "variants": [{"variant": "f32", "path": "m.onnx"}], "max_len": 8}"#,
+            r#"{"letsearch_version": 1, "variants": [{"variant": "f32", "path": "m.onnx"}], "pooling": "max"}"#,
+            r#"{"letsearch_version": 2, "variants": [{"variant": "f32", "path": "m.onnx"}], "image": {"path": "v.onnx"}}"#,
+            r#"{"letsearch_version": 3, "variants": [{"variant": "f32", "path": "m.onnx"}], "image": {"path": "/v.onnx"}}"#,
+        ];
+        for json in invalid {
+            assert!(ModelMetadata::parse(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -1,3 +1,6 @@
 pub mod backends;
+pub mod batching;
+pub mod hardware;
+pub mod metadata;
 
 pub mod model_utils;
@@ -1,15 +1,40 @@
 use anyhow;
 use async_trait::async_trait;
 use half::f16;
-use ndarray::Array2;
+use ndarray::{s, Array2, Axis};
+use serde::Serialize;
 use std::sync::Arc;
+use tokenizers::Tokenizer;
 
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum Backend {
     ONNX,
     Gemini,
+    /// Any server implementing the OpenAI-compatible `/v1/embeddings` API.
+    Remote,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl Backend {
+    /// Backend that runs the model at `path`: `gemini://` models on the
+    /// Gemini API, `openai://` models and letsearch servers given by URL
+    /// remotely, and anything else, e.g. `hf://` paths, with ONNX Runtime.
+    pub fn of_model(path: &str) -> Self {
+        if path.starts_with("gemini://") {
+            Backend::Gemini
+        } else if ["openai://", "http://", "https://"]
+            .iter()
+            .any(|scheme| path.starts_with(scheme))
+        {
+            Backend::Remote
+        } else {
+            Backend::ONNX
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum ModelOutputDType {
     F32,
     F16,
@@ -17,17 +42,107 @@ pub enum ModelOutputDType {
     Int8,
 }
 
+#[derive(Clone)]
 pub enum Embeddings {
     F16(Arc<Array2<f16>>),
     F32(Arc<Array2<f32>>),
 }
 
+impl Embeddings {
+    /// Convert to one `f32` vector per input text, e.g. to send over the wire.
+    pub fn to_f32_rows(&self) -> Vec<Vec<f32>> {
+        match self {
+            Embeddings::F16(array) => array
+                .rows()
+                .into_iter()
+                .map(|row| row.iter().map(|v| v.to_f32()).collect())
+                .collect(),
+            Embeddings::F32(array) => array.rows().into_iter().map(|row| row.to_vec()).collect(),
+        }
+    }
+
+    /// Split into consecutive blocks of `sizes[i]` rows each, e.g. to hand the
+    /// result of a batched prediction back to the individual callers. Fails
+    /// when the sizes do not add up to the number of rows.
+    pub fn split_rows(&self, sizes: &[usize]) -> anyhow::Result<Vec<Embeddings>> {
+        let rows = match self {
+            Embeddings::F16(array) => array.nrows(),
+            Embeddings::F32(array) => array.nrows(),
+        };
+        let expected: usize = sizes.iter().sum();
+        if rows != expected {
+            anyhow::bail!("Expected {} embeddings but got {}", expected, rows);
+        }
+        let mut start = 0;
+        Ok(sizes
+            .iter()
+            .map(|&size| {
+                let rows = start..start + size;
+                start += size;
+                match self {
+                    Embeddings::F16(array) => {
+                        Embeddings::F16(Arc::new(array.slice(s![rows, ..]).to_owned()))
+                    }
+                    Embeddings::F32(array) => {
+                        Embeddings::F32(Arc::new(array.slice(s![rows, ..]).to_owned()))
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// Keep the first `dim` dimensions of each embedding and scale it back
+    /// to unit length, as models trained with Matryoshka representation
+    /// learning expect. Fails when the embeddings have fewer dimensions.
+    pub fn truncate(&self, dim: usize) -> anyhow::Result<Embeddings> {
+        let len = match self {
+            Embeddings::F16(array) => array.ncols(),
+            Embeddings::F32(array) => array.ncols(),
+        };
+        if dim == 0 || dim > len {
+            anyhow::bail!(
+                "Cannot truncate embeddings of {} dimensions to {}",
+                len,
+                dim
+            );
+        }
+        let mut truncated = match self {
+            Embeddings::F16(array) => array.slice(s![.., ..dim]).mapv(f16::to_f32),
+            Embeddings::F32(array) => array.slice(s![.., ..dim]).to_owned(),
+        };
+        for mut row in truncated.axis_iter_mut(Axis(0)) {
+            let norm = row.dot(&row).sqrt();
+            if norm > 0.0 {
+                row /= norm;
+            }
+        }
+        Ok(match self {
+            Embeddings::F16(_) => Embeddings::F16(Arc::new(truncated.mapv(f16::from_f32))),
+            Embeddings::F32(_) => Embeddings::F32(Arc::new(truncated)),
+        })
+    }
+}
+
 /// General async embedding trait implemented by all model backends.
 #[async_trait]
 pub trait Embedder: Send + Sync {
     fn output_dim(&self) -> anyhow::Result<i64>;
     fn output_dtype(&self) -> anyhow::Result<ModelOutputDType>;
+    fn backend(&self) -> Backend;
+    /// Tokenizer of the model, for models tokenized locally.
+    fn tokenizer(&self) -> Option<Arc<Tokenizer>> {
+        None
+    }
     async fn embed(&self, texts: Vec<String>) -> anyhow::Result<Embeddings>;
+    /// Whether the model embeds images too, see [`Embedder::predict_image`].
+    fn supports_images(&self) -> bool {
+        false
+    }
+    /// Embed encoded images, e.g. the bytes of PNG or JPEG files, into the
+    /// space of the texts, for text-to-image search with CLIP-style models.
+    async fn predict_image(&self, _images: Vec<Vec<u8>>) -> anyhow::Result<Embeddings> {
+        Err(anyhow::anyhow!("The model cannot embed images"))
+    }
 }
 
 pub trait ModelTrait {
@@ -50,3 +165,38 @@ pub trait ONNXModelTrait: ModelTrait {
 
 pub trait ONNXModel: ModelTrait + ONNXModelTrait + Send + Sync {}
 impl<T> ONNXModel for T where T: ModelTrait + ONNXModelTrait + Send + Sync {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        let embeddings = Embeddings::F32(Arc::new(
+            Array2::from_shape_vec((2, 3), vec![3.0, 4.0, 1.0, 0.0, 0.0, 1.0]).unwrap(),
+        ));
+        let truncated = embeddings.truncate(2).unwrap();
+        assert_eq!(
+            truncated.to_f32_rows(),
+            vec![vec![0.6, 0.8], vec![0.0, 0.0]]
+        );
+
+        let half = Embeddings::F16(Arc::new(Array2::from_elem((1, 4), f16::from_f32(1.0))));
+        assert_eq!(half.truncate(1).unwrap().to_f32_rows(), vec![vec![1.0]]);
+        assert!(half.truncate(5).is_err());
+        assert!(half.truncate(0).is_err());
+    }
+
+    #[test]
+    fn test_split_rows() {
+        let embeddings = Embeddings::F32(Arc::new(
+            Array2::from_shape_vec((3, 1), vec![1.0, 2.0, 3.0]).unwrap(),
+        ));
+        let parts = embeddings.split_rows(&[1, 2]).unwrap();
+        assert_eq!(parts[0].to_f32_rows(), vec![vec![1.0]]);
+        assert_eq!(parts[1].to_f32_rows(), vec![vec![2.0], vec![3.0]]);
+        // A model returning fewer or more embeddings than texts.
+        assert!(embeddings.split_rows(&[1, 3]).is_err());
+        assert!(embeddings.split_rows(&[1, 1]).is_err());
+    }
+}
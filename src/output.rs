@@ -0,0 +1,146 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How a command prints its results.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Aligned columns for people.
+    #[default]
+    Table,
+    /// Pretty-printed JSON, shaped like the HTTP API's responses, for scripts.
+    Json,
+    /// Comma-separated values with a header row, e.g. for spreadsheets.
+    Csv,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(anyhow::anyhow!(
+                "Unknown output format '{}', expected one of table, json, csv",
+                s
+            )),
+        }
+    }
+}
+
+/// `text` on one line, cut to `max_chars` characters with an ellipsis, to
+/// fit a table cell.
+pub fn cell(text: &str, max_chars: usize) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() <= max_chars {
+        return line;
+    }
+    let mut cut: String = line.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Lay out `rows` under `headers` in columns padded to their widest cell.
+/// The last column is not padded.
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: Vec<&str>| {
+        let last = values.len().saturating_sub(1);
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                if i == last {
+                    value.to_string()
+                } else {
+                    format!("{:<width$}", value, width = widths[i])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    let mut table = line(headers.to_vec());
+    for row in rows {
+        table.push('\n');
+        table.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    table
+}
+
+/// `rows` under `headers` as CSV, quoting the values that need it.
+pub fn format_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let quote = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut csv = headers
+        .iter()
+        .map(|h| quote(h))
+        .collect::<Vec<_>>()
+        .join(",");
+    for row in rows {
+        csv.push('\n');
+        csv.push_str(&row.iter().map(|v| quote(v)).collect::<Vec<_>>().join(","));
+    }
+    csv
+}
+
+/// `rows` under `headers` as a table or CSV. JSON output is left to each
+/// command, which serializes its own results.
+pub fn format_rows(format: OutputFormat, headers: &[&str], rows: &[Vec<String>]) -> String {
+    match format {
+        OutputFormat::Csv => format_csv(headers, rows),
+        OutputFormat::Table | OutputFormat::Json => format_table(headers, rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        assert_eq!("JSON".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert!("xml".parse::<OutputFormat>().is_err());
+        assert_eq!(cell("a\n  b", 10), "a b");
+        assert_eq!(cell("abcdef", 4), "abc…");
+
+        let table = format_table(
+            &["#", "score", "content"],
+            &[
+                vec!["1".into(), "0.9100".into(), "first".into()],
+                vec!["10".into(), "0.5".into(), "second".into()],
+            ],
+        );
+        assert_eq!(
+            table,
+            "#   score   content\n1   0.9100  first\n10  0.5     second"
+        );
+
+        let csv = format_csv(
+            &["key", "content"],
+            &[vec!["1".into(), "say \"hi\", then\nleave".into()]],
+        );
+        assert_eq!(csv, "key,content\n1,\"say \"\"hi\"\", then\nleave\"");
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Most clients tracked at once. Beyond it, clients whose buckets have
+/// refilled are forgotten, as they are no different from new clients.
+const MAX_CLIENTS: usize = 10_000;
+
+/// Requests a single client may make.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Sustained rate.
+    pub per_minute: u32,
+    /// Requests that may be made at once after being idle.
+    pub burst: u32,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket rate limiter keyed by client, e.g. API key or IP address.
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request of `client`, or return how long it has to wait
+    /// before its next request is accepted.
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let rate = self.limit.per_minute.max(1) as f64 / 60.0;
+        let burst = self.limit.burst.max(1) as f64;
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated);
+            (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            buckets.retain(|_, bucket| refill(bucket) < burst);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(RateLimit {
+            per_minute: 60,
+            burst: 2,
+        });
+        let start = Instant::now();
+        assert!(limiter.check_at("a", start).is_ok());
+        assert!(limiter.check_at("a", start).is_ok());
+        let wait = limiter.check_at("a", start).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6);
+
+        // Other clients have their own bucket.
+        assert!(limiter.check_at("b", start).is_ok());
+
+        // One request per second refills.
+        let later = start + Duration::from_millis(1500);
+        assert!(limiter.check_at("a", later).is_ok());
+        let wait = limiter.check_at("a", later).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.5).abs() < 1e-6);
+
+        // Idle clients get their burst back, not more.
+        let idle = start + Duration::from_secs(60);
+        assert!(limiter.check_at("a", idle).is_ok());
+        assert!(limiter.check_at("a", idle).is_ok());
+        assert!(limiter.check_at("a", idle).is_err());
+    }
+}
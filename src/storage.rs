@@ -0,0 +1,303 @@
+use anyhow::anyhow;
+use futures::StreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use reqwest::Url;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Whether `path` is on S3 or Google Cloud Storage.
+pub fn is_object_store_url(path: &str) -> bool {
+    ["s3://", "gs://", "gcs://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+}
+
+/// Credentials DuckDB reads `s3://` and `gs://` files with.
+///
+/// S3 keys are read from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+/// `AWS_SESSION_TOKEN`, falling back to the AWS credential chain, e.g. an
+/// instance profile, when they are not set. Google Cloud Storage is read
+/// through its S3-compatible API with the HMAC keys in `GCS_HMAC_KEY_ID` and
+/// `GCS_HMAC_SECRET`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectStoreCredentials {
+    pub s3_key_id: Option<String>,
+    pub s3_secret: Option<String>,
+    pub s3_session_token: Option<String>,
+    pub s3_region: Option<String>,
+    /// S3-compatible endpoint, e.g. `http://localhost:9000` for MinIO.
+    pub s3_endpoint: Option<String>,
+    pub gcs_key_id: Option<String>,
+    pub gcs_secret: Option<String>,
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|value| !value.is_empty())
+}
+
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+impl ObjectStoreCredentials {
+    /// Credentials from the environment, with the region and endpoint
+    /// overridden when given.
+    pub fn from_env(s3_region: Option<String>, s3_endpoint: Option<String>) -> Self {
+        Self {
+            s3_key_id: env_var(&["AWS_ACCESS_KEY_ID"]),
+            s3_secret: env_var(&["AWS_SECRET_ACCESS_KEY"]),
+            s3_session_token: env_var(&["AWS_SESSION_TOKEN"]),
+            s3_region: s3_region.or_else(|| env_var(&["AWS_REGION", "AWS_DEFAULT_REGION"])),
+            s3_endpoint: s3_endpoint.or_else(|| env_var(&["AWS_ENDPOINT_URL", "AWS_ENDPOINT"])),
+            gcs_key_id: env_var(&["GCS_HMAC_KEY_ID"]),
+            gcs_secret: env_var(&["GCS_HMAC_SECRET"]),
+        }
+    }
+
+    /// Statements creating the DuckDB secrets for these credentials. They
+    /// are temporary, so that keys are not persisted with the database.
+    pub fn duckdb_secrets(&self) -> Vec<String> {
+        let mut s3 = vec![String::from("TYPE S3")];
+        match (&self.s3_key_id, &self.s3_secret) {
+            (Some(key_id), Some(secret)) => {
+                s3.push(format!("KEY_ID {}", sql_string(key_id)));
+                s3.push(format!("SECRET {}", sql_string(secret)));
+                if let Some(token) = &self.s3_session_token {
+                    s3.push(format!("SESSION_TOKEN {}", sql_string(token)));
+                }
+            }
+            _ => s3.push(String::from("PROVIDER CREDENTIAL_CHAIN")),
+        }
+        if let Some(region) = &self.s3_region {
+            s3.push(format!("REGION {}", sql_string(region)));
+        }
+        if let Some(endpoint) = &self.s3_endpoint {
+            // DuckDB takes the host only, and most S3-compatible servers
+            // expect path-style URLs.
+            let host = endpoint
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/');
+            s3.push(format!("ENDPOINT {}", sql_string(host)));
+            s3.push(String::from("URL_STYLE 'path'"));
+            if endpoint.starts_with("http://") {
+                s3.push(String::from("USE_SSL false"));
+            }
+        }
+
+        let mut secrets = vec![format!(
+            "CREATE OR REPLACE TEMPORARY SECRET letsearch_s3 ({});",
+            s3.join(", ")
+        )];
+        if let (Some(key_id), Some(secret)) = (&self.gcs_key_id, &self.gcs_secret) {
+            secrets.push(format!(
+                "CREATE OR REPLACE TEMPORARY SECRET letsearch_gcs (TYPE GCS, KEY_ID {}, SECRET {});",
+                sql_string(key_id),
+                sql_string(secret)
+            ));
+        }
+        secrets
+    }
+}
+
+/// Open the store `url` is in, and the path of `url` within it. S3 and Google
+/// Cloud Storage are configured from the standard environment variables,
+/// e.g. `AWS_ACCESS_KEY_ID` or `GOOGLE_APPLICATION_CREDENTIALS`.
+fn open_store(
+    url: &str,
+    credentials: &ObjectStoreCredentials,
+) -> anyhow::Result<(Box<dyn ObjectStore>, ObjectPath)> {
+    let parsed = Url::parse(url).map_err(|e| anyhow!("Invalid URL '{}': {}", url, e))?;
+    let store: Box<dyn ObjectStore> = match parsed.scheme() {
+        "s3" => {
+            let mut builder = AmazonS3Builder::from_env().with_url(url);
+            if let Some(region) = &credentials.s3_region {
+                builder = builder.with_region(region);
+            }
+            if let Some(endpoint) = &credentials.s3_endpoint {
+                builder = builder
+                    .with_endpoint(endpoint)
+                    .with_allow_http(endpoint.starts_with("http://"));
+            }
+            Box::new(builder.build()?)
+        }
+        "gs" | "gcs" => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url)
+                .build()?,
+        ),
+        "file" => object_store::parse_url(&parsed)?.0,
+        scheme => {
+            return Err(anyhow!(
+                "Unsupported storage '{}://', expected s3://, gs:// or file://",
+                scheme
+            ))
+        }
+    };
+    let prefix = ObjectPath::from_url_path(parsed.path())?;
+    Ok((store, prefix))
+}
+
+/// Parts uploaded concurrently per file.
+const UPLOAD_CONCURRENCY: usize = 8;
+
+/// Upload every file under `dir` to `url`, keeping their relative paths, and
+/// return the number of files uploaded. Large files are uploaded in parts.
+pub async fn push_dir(
+    dir: &Path,
+    url: &str,
+    credentials: &ObjectStoreCredentials,
+) -> anyhow::Result<usize> {
+    let (store, prefix) = open_store(url, credentials)?;
+
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    for file in &files {
+        let relative = file.strip_prefix(dir)?;
+        let mut location = prefix.clone();
+        for part in relative.iter() {
+            location = location.child(part.to_string_lossy().as_ref());
+        }
+
+        let mut upload = WriteMultipart::new(store.put_multipart(&location).await?);
+        let mut reader = tokio::fs::File::open(file).await?;
+        let mut buf = vec![0u8; 8 * 1024 * 1024];
+        loop {
+            let read = reader.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            upload.wait_for_capacity(UPLOAD_CONCURRENCY).await?;
+            upload.write(&buf[..read]);
+        }
+        upload.finish().await?;
+        log::debug!("Uploaded '{}' to '{}'", file.display(), location);
+    }
+    Ok(files.len())
+}
+
+/// Download every object under `url` into `dir`, keeping their relative
+/// paths, and return the number of files downloaded. Each file is written
+/// next to its destination first, so that an interrupted download does not
+/// leave a truncated file behind.
+pub async fn pull_dir(
+    url: &str,
+    dir: &Path,
+    credentials: &ObjectStoreCredentials,
+) -> anyhow::Result<usize> {
+    let (store, prefix) = open_store(url, credentials)?;
+
+    let mut objects = store.list(Some(&prefix));
+    let mut count = 0;
+    while let Some(meta) = objects.next().await {
+        let meta = meta?;
+        let Some(parts) = meta.location.prefix_match(&prefix) else {
+            continue;
+        };
+        let mut target = dir.to_path_buf();
+        for part in parts {
+            target.push(part.as_ref());
+        }
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut partial = target.clone().into_os_string();
+        partial.push(".partial");
+        let mut writer = tokio::fs::File::create(&partial).await?;
+        let mut stream = store.get(&meta.location).await?.into_stream();
+        while let Some(chunk) = stream.next().await {
+            writer.write_all(&chunk?).await?;
+        }
+        writer.flush().await?;
+        tokio::fs::rename(&partial, &target).await?;
+        log::debug!("Downloaded '{}' to '{}'", meta.location, target.display());
+        count += 1;
+    }
+
+    if count == 0 {
+        return Err(anyhow!("Nothing found at '{}'", url));
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_push_and_pull_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let source = root.join("source");
+        std::fs::create_dir_all(source.join("index")).unwrap();
+        std::fs::write(source.join("config.json"), "{}").unwrap();
+        std::fs::write(source.join("index").join("text.usearch"), [1u8, 2, 3]).unwrap();
+
+        let credentials = ObjectStoreCredentials::default();
+        let url = format!("file://{}", root.join("bucket").join("docs").display());
+        std::fs::create_dir_all(root.join("bucket")).unwrap();
+        assert_eq!(push_dir(&source, &url, &credentials).await.unwrap(), 2);
+
+        let target = root.join("target");
+        assert_eq!(pull_dir(&url, &target, &credentials).await.unwrap(), 2);
+        assert_eq!(
+            std::fs::read(target.join("index").join("text.usearch")).unwrap(),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            std::fs::read_to_string(target.join("config.json")).unwrap(),
+            "{}"
+        );
+
+        let missing = format!("file://{}", root.join("bucket").join("other").display());
+        assert!(pull_dir(&missing, &target, &credentials).await.is_err());
+    }
+
+    #[test]
+    fn test_duckdb_secrets() {
+        assert!(is_object_store_url("s3://bucket/docs/*.parquet"));
+        assert!(!is_object_store_url("hf://datasets/a/b"));
+
+        let chain = ObjectStoreCredentials {
+            s3_region: Some(String::from("eu-west-1")),
+            ..ObjectStoreCredentials::default()
+        };
+        assert_eq!(
+            chain.duckdb_secrets(),
+            vec!["CREATE OR REPLACE TEMPORARY SECRET letsearch_s3 (TYPE S3, PROVIDER CREDENTIAL_CHAIN, REGION 'eu-west-1');"]
+        );
+
+        let minio = ObjectStoreCredentials {
+            s3_key_id: Some(String::from("key")),
+            s3_secret: Some(String::from("it's")),
+            s3_endpoint: Some(String::from("http://localhost:9000/")),
+            gcs_key_id: Some(String::from("hmac")),
+            gcs_secret: Some(String::from("secret")),
+            ..ObjectStoreCredentials::default()
+        };
+        let secrets = minio.duckdb_secrets();
+        assert_eq!(
+            secrets[0],
+            "CREATE OR REPLACE TEMPORARY SECRET letsearch_s3 (TYPE S3, KEY_ID 'key', SECRET 'it''s', ENDPOINT 'localhost:9000', URL_STYLE 'path', USE_SSL false);"
+        );
+        assert!(secrets[1].contains("TYPE GCS, KEY_ID 'hmac'"));
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::anyhow;
+use std::process::Command;
+
+/// Placeholder of the audio file's path in a transcribe command.
+pub const PATH_PLACEHOLDER: &str = "{path}";
+
+/// Transcribes audio files by running a command, e.g. whisper.cpp's
+/// `whisper-cli -m ggml-small.bin -nt -np -f {path}`, and reading the
+/// transcript from its standard output. The command is split into arguments
+/// like a shell would, but run without one: the file's path replaces `{path}`
+/// inside its argument, or is appended as the last one when it has none.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcriber {
+    args: Vec<String>,
+}
+
+impl Transcriber {
+    pub fn new(command: &str) -> anyhow::Result<Self> {
+        let args = shell_words::split(command)
+            .map_err(|e| anyhow!("Invalid transcribe command '{}': {}", command, e))?;
+        if args.is_empty() {
+            return Err(anyhow!("The transcribe command is empty"));
+        }
+        Ok(Self { args })
+    }
+
+    /// Program and arguments transcribing the file at `path`. The path is
+    /// never parsed by a shell, so it is passed on as it is.
+    fn args_for(&self, path: &str) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(PATH_PLACEHOLDER, path))
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(PATH_PLACEHOLDER)) {
+            args.push(path.to_string());
+        }
+        args
+    }
+
+    /// Transcript of the audio file at `path`.
+    pub fn transcribe(&self, path: &str) -> anyhow::Result<String> {
+        // The command would take such a path for one of its options.
+        if path.starts_with('-') {
+            return Err(anyhow!(
+                "Cannot transcribe '{}': paths starting with '-' are not passed to the transcribe command",
+                path
+            ));
+        }
+        let args = self.args_for(path);
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .output()
+            .map_err(|e| anyhow!("Failed to run the transcribe command: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to transcribe '{}' ({}): {}",
+                path,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_for() {
+        let transcriber = Transcriber::new("whisper-cli -f {path} -nt").unwrap();
+        assert_eq!(
+            transcriber.args_for("/podcasts/it's $HOME.mp3"),
+            ["whisper-cli", "-f", "/podcasts/it's $HOME.mp3", "-nt"]
+        );
+        // A placeholder the user quoted stays one argument holding the path.
+        let transcriber = Transcriber::new(r#"whisper "{path}" --out='{path}.txt'"#).unwrap();
+        assert_eq!(
+            transcriber.args_for("/podcasts/$(touch pwned).mp3"),
+            [
+                "whisper",
+                "/podcasts/$(touch pwned).mp3",
+                "--out=/podcasts/$(touch pwned).mp3.txt"
+            ]
+        );
+        let transcriber = Transcriber::new("transcribe").unwrap();
+        assert_eq!(transcriber.args_for("a b.wav"), ["transcribe", "a b.wav"]);
+        assert!(Transcriber::new("  ").is_err());
+        assert!(Transcriber::new("whisper \"{path}").is_err());
+    }
+
+    #[test]
+    fn test_transcribe() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("it's.wav");
+        std::fs::write(&path, "hello from the meeting\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let transcript = Transcriber::new("cat").unwrap().transcribe(path).unwrap();
+        assert_eq!(transcript, "hello from the meeting");
+        let failed = Transcriber::new(r#"sh -c 'cat "$1"; exit 3' sh {path}"#)
+            .unwrap()
+            .transcribe(path);
+        assert!(failed.unwrap_err().to_string().contains("exit status: 3"));
+        let option = Transcriber::new("cat").unwrap().transcribe("--version");
+        assert!(option
+            .unwrap_err()
+            .to_string()
+            .contains("starting with '-'"));
+
+        // A command substitution in a file name is never run, even when the
+        // user quoted the placeholder.
+        let marker = format!("letsearch_pwned_{}", std::process::id());
+        let path = tmp.path().join(format!("$(touch {}).wav", marker));
+        std::fs::write(&path, "not run\n").unwrap();
+        let path = path.to_str().unwrap();
+        let transcript = Transcriber::new(r#"cat "{path}""#)
+            .unwrap()
+            .transcribe(path)
+            .unwrap();
+        assert_eq!(transcript, "not run");
+        assert!(!std::path::Path::new(&marker).exists());
+    }
+}
@@ -6,7 +6,7 @@ fn main() {
     let rt = Runtime::new().unwrap();
     rt.block_on(async {
         // try to spawn an actor
-        let addr = ModelManagerActor::new().start();
+        let _addr = ModelManagerActor::new().start();
         println!("Actor started");
     });
 }
@@ -0,0 +1,407 @@
+use actix::Actor;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use arrow::array::{Float64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use letsearch::actors::collection_actor::{AppendArrow, EmbedColumn, ImportArrow};
+use letsearch::actors::collection_manager_actor::{
+    CollectionManagerActor, CreateCollection, GetModelIdForCollection, SearchCollection,
+    ShutdownCollections,
+};
+use letsearch::actors::model_actor::ModelManagerActor;
+use letsearch::collection::collection_utils::{CollectionConfig, GeoColumns};
+use letsearch::collection::filter::{Filter, GeoFilter};
+use letsearch::collection::vector_index::{ScoreThreshold, ScoreType};
+use letsearch::error::ProjectError;
+use letsearch::rate_limit::RateLimit;
+use letsearch::serve::{start_server, ApiKey, CorsConfig, ServeConfig, SqlConfig};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// OpenAI-compatible embeddings endpoint embedding each text as its
+/// letter counts, so that texts sharing letters are similar.
+async fn fake_embeddings(body: web::Json<Value>) -> HttpResponse {
+    let inputs = body["input"].as_array().cloned().unwrap_or_default();
+    let data: Vec<Value> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            let mut embedding = vec![0.1f32; 26];
+            for c in text.as_str().unwrap_or_default().to_lowercase().chars() {
+                if c.is_ascii_lowercase() {
+                    embedding[(c as u8 - b'a') as usize] += 1.0;
+                }
+            }
+            json!({"embedding": embedding, "index": index})
+        })
+        .collect();
+    HttpResponse::Ok().json(json!({"data": data, "model": "fake"}))
+}
+
+fn namespace_key(key: &str) -> ApiKey {
+    ApiKey {
+        namespace: Some(String::from("acme")),
+        ..ApiKey::new(key.to_string())
+    }
+}
+
+#[actix_web::test]
+async fn test_serve_on_port_zero() {
+    let tmp = tempfile::tempdir().unwrap();
+    let home = tmp.path();
+    std::env::set_var("LETSEARCH_HOME", home);
+
+    let embeddings =
+        HttpServer::new(|| App::new().route("/v1/embeddings", web::post().to(fake_embeddings)))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+    let base_url = format!("http://{}/v1", embeddings.addrs()[0]);
+    let embeddings = embeddings.run();
+    let embeddings_handle = embeddings.handle();
+    actix_web::rt::spawn(embeddings);
+
+    // Index a few documents, then close the collection for the server.
+    let data_dir = home.join("docs");
+    let manager =
+        CollectionManagerActor::new(None, ModelManagerActor::new().start(), None, None).start();
+    let collection = manager
+        .send(CreateCollection {
+            config: CollectionConfig {
+                name: String::from("docs"),
+                model_name: String::from("openai://fake"),
+                openai_base_url: Some(base_url),
+                data_dir: Some(data_dir.to_string_lossy().to_string()),
+                filterable_columns: vec![String::from("lang")],
+                geo_columns: Some(GeoColumns {
+                    lat: String::from("lat"),
+                    lon: String::from("lon"),
+                }),
+                ..CollectionConfig::default()
+            },
+            overwrite: true,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let arrow_path = home.join("docs.arrow");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("text", DataType::Utf8, false),
+        Field::new("lang", DataType::Utf8, false),
+        Field::new("lat", DataType::Float64, false),
+        Field::new("lon", DataType::Float64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from(vec![
+                "apples and bananas",
+                "zebra crossing",
+                "quick fox",
+            ])),
+            Arc::new(StringArray::from(vec!["en", "de", "en"])),
+            Arc::new(Float64Array::from(vec![52.52, 48.14, 52.50])),
+            Arc::new(Float64Array::from(vec![13.40, 11.58, 13.41])),
+        ],
+    )
+    .unwrap();
+    let mut writer =
+        FileWriter::try_new(std::fs::File::create(&arrow_path).unwrap(), &schema).unwrap();
+    writer.write(&batch).unwrap();
+    writer.finish().unwrap();
+
+    collection
+        .send(ImportArrow {
+            path: arrow_path.to_string_lossy().to_string(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let model_id = manager
+        .send(GetModelIdForCollection {
+            name: String::from("docs"),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    collection
+        .send(EmbedColumn {
+            name: String::from("text"),
+            batch_size: 2,
+            model_id,
+            skip_errors: false,
+            concurrency: 1,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let filtered_search = |filter: &str, geo_filter: Option<GeoFilter>| SearchCollection {
+        collection_name: String::from("docs"),
+        columns: Vec::new(),
+        query: String::from("banana"),
+        vector: None,
+        limit: 3,
+        offset: 0,
+        score_type: ScoreType::Similarity,
+        groups: None,
+        filter: Some(serde_json::from_str::<Filter>(filter).unwrap()),
+        geo_filter,
+        group_by: None,
+        timeout: None,
+        threshold: ScoreThreshold::default(),
+        skip_content: false,
+        span: tracing::Span::current(),
+    };
+    let results = manager
+        .send(filtered_search(
+            r#"{"field": "lang", "op": "eq", "value": "de"}"#,
+            None,
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(results.results.len(), 1);
+    assert_eq!(results.results[0].content, "zebra crossing");
+    let unindexed = manager
+        .send(filtered_search(
+            r#"{"field": "text", "op": "eq", "value": "quick fox"}"#,
+            None,
+        ))
+        .await
+        .unwrap();
+    assert!(matches!(unindexed, Err(ProjectError::NotFilterable(..))));
+    let nearby = manager
+        .send(filtered_search(
+            r#"{"field": "lang", "op": "eq", "value": "en"}"#,
+            Some(GeoFilter {
+                lat: 52.52,
+                lon: 13.405,
+                radius_m: 5000.0,
+            }),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let mut contents: Vec<_> = nearby.results.iter().map(|r| r.content.as_str()).collect();
+    contents.sort();
+    assert_eq!(contents, vec!["apples and bananas", "quick fox"]);
+
+    // New columns can still be added next to the filter index.
+    let more_schema = Arc::new(Schema::new(vec![
+        Field::new("text", DataType::Utf8, false),
+        Field::new("lang", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+    ]));
+    let more = RecordBatch::try_new(
+        more_schema.clone(),
+        vec![
+            Arc::new(StringArray::from(vec!["slow snail"])),
+            Arc::new(StringArray::from(vec!["en"])),
+            Arc::new(StringArray::from(vec!["garden"])),
+        ],
+    )
+    .unwrap();
+    let more_path = home.join("more.arrow");
+    let mut writer =
+        FileWriter::try_new(std::fs::File::create(&more_path).unwrap(), &more_schema).unwrap();
+    writer.write(&more).unwrap();
+    writer.finish().unwrap();
+    collection
+        .send(AppendArrow {
+            path: more_path.to_string_lossy().to_string(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    manager.send(ShutdownCollections).await.unwrap().unwrap();
+
+    let server = start_server(ServeConfig {
+        host: String::from("127.0.0.1"),
+        port: 0,
+        collection_name: String::from("docs"),
+        data_dir: Some(data_dir.to_string_lossy().to_string()),
+        rate_limit: Some(RateLimit {
+            per_minute: 1,
+            burst: 5,
+        }),
+        cors: Some(CorsConfig {
+            origins: vec![String::from("https://app.example.com")],
+            ..CorsConfig::default()
+        }),
+        sql: Some(SqlConfig::default()),
+        api_keys: vec![
+            ApiKey::new(String::from("secret")),
+            // Each key is rate limited on its own.
+            namespace_key("acme-key"),
+            namespace_key("acme-key-2"),
+        ],
+        ..ServeConfig::default()
+    })
+    .await
+    .unwrap();
+    assert_ne!(server.addr().port(), 0);
+
+    let client = reqwest::Client::builder()
+        .default_headers(
+            [(
+                reqwest::header::AUTHORIZATION,
+                reqwest::header::HeaderValue::from_static("Bearer secret"),
+            )]
+            .into_iter()
+            .collect(),
+        )
+        .build()
+        .unwrap();
+    let url = format!("http://{}", server.addr());
+    let health: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(health["data"]["collections"][0]["documents"], 4);
+    // Other namespaces do not see the collections of the default one.
+    let other: Value = client
+        .get(&url)
+        .header("X-Namespace", "acme")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(other["data"]["collections"], json!([]));
+
+    let ready = client
+        .get(format!("{}/readyz", url))
+        .header("X-Request-Id", "probe-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(ready.status(), 200);
+    assert_eq!(ready.headers()["x-request-id"], "probe-1");
+    let ready: Value = ready.json().await.unwrap();
+    let docs = &ready["data"]["collections"][0];
+    assert_eq!(docs["models"][0]["detail"], "loaded");
+    assert_eq!(docs["indexes"][0]["detail"], "3 vectors");
+
+    // Keys of a namespace can neither use the models nor the served
+    // collection of the default one.
+    let embed = |key: &str, body: Value| {
+        client
+            .post(format!("{}/embed", url))
+            .bearer_auth(key)
+            .json(&body)
+            .send()
+    };
+    let foreign = embed(
+        "acme-key",
+        json!({"texts": ["banana"], "model": "openai://fake"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(foreign.status(), 404);
+    let served = embed("acme-key-2", json!({"texts": ["banana"]}))
+        .await
+        .unwrap();
+    assert_eq!(served.status(), 404);
+
+    let ui = client.get(format!("{}/ui", url)).send().await.unwrap();
+    assert_eq!(ui.status(), 200);
+    assert!(ui.text().await.unwrap().contains("/collections"));
+
+    // Preflight requests carry no API key and are answered by the CORS layer.
+    let preflight = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/collections/docs/search", url),
+        )
+        .header("Origin", "https://app.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .header(
+            "Access-Control-Request-Headers",
+            "authorization, content-type",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(preflight.status(), 200);
+    assert_eq!(
+        preflight.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+
+    let response: Value = client
+        .post(format!("{}/collections/docs/search", url))
+        .json(&json!({"column_name": "text", "query": "banana", "limit": 2, "snippet": {}}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let results = response["data"]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["content"], "apples and bananas");
+    assert_eq!(results[0]["snippet"], "apples and <mark>bananas</mark>");
+
+    let aggregated: Value = client
+        .post(format!("{}/collections/docs/aggregate", url))
+        .json(&json!({"group_by": ["lang"], "metrics": [{"op": "count"}, {"op": "avg", "field": "lat"}]}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let english = &aggregated["data"]["groups"][0];
+    assert_eq!(
+        (&english["lang"], &english["count"]),
+        (&json!("en"), &json!(3))
+    );
+    // The appended document has no coordinates and is left out of the mean.
+    assert!((english["avg_lat"].as_f64().unwrap() - 52.51).abs() < 1e-9);
+    assert_eq!(aggregated["data"]["groups"][1]["count"], 1);
+    let invalid = client
+        .post(format!("{}/collections/docs/aggregate", url))
+        .json(&json!({"metrics": [{"op": "sum"}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), 400);
+
+    let sql: Value = client
+        .post(format!("{}/collections/docs/sql", url))
+        .json(
+            &json!({"query": "SELECT lang, count(*) AS n FROM docs GROUP BY lang ORDER BY n DESC"}),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sql["data"]["columns"], json!(["lang", "n"]));
+    assert_eq!(sql["data"]["rows"], json!([["en", 3], ["de", 1]]));
+    let denied = client
+        .post(format!("{}/collections/docs/sql", url))
+        .json(&json!({"query": "DELETE FROM docs"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(denied.status(), 400);
+
+    // The search, aggregations and SQL queries above used up the key's burst.
+    let limited = client
+        .post(format!("{}/collections/docs/search", url))
+        .json(&json!({"column_name": "text", "query": "banana", "limit": 2}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(limited.status(), 429);
+    assert!(limited.headers().contains_key("retry-after"));
+
+    // Idle keep-alive connections would hold up the graceful stop.
+    drop(client);
+    server.handle().stop(true).await;
+    server.wait().await.unwrap();
+    embeddings_handle.stop(false).await;
+}